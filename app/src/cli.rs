@@ -0,0 +1,247 @@
+//! Command-line interface: the subcommands and the flags they share.
+
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use telegram_llm_core::telegram::ExportFormat;
+use time::{format_description, Date, Time};
+
+use crate::config::ConfigSource;
+use crate::prompt::AuthMethod;
+use crate::secrets::Secret;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Telegram in the terminal, with LLM helpers")]
+pub struct Cli {
+    /// Config file to read instead of `APP_CONFIG_PATH` or `app.toml` in the
+    /// user config directory.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Uses `[profiles.<NAME>]` from the config file, and keeps the session,
+    /// cache, logs and downloads in a `profiles/<NAME>/` subdirectory unless
+    /// that section sets their paths.
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    pub fn config_source(&self) -> ConfigSource {
+        ConfigSource {
+            path: self.config.clone(),
+            profile: self.profile.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Open the chat TUI, logging in first if needed (the default).
+    #[default]
+    Run,
+    /// Log in to Telegram and save the session.
+    Login(LoginArgs),
+    /// Log out of Telegram and delete the saved session.
+    Logout,
+    /// Write the cached transcript of a chat as Markdown or JSON.
+    Export(ExportArgs),
+    /// Send a text message and wait until Telegram accepts it.
+    Send(SendArgs),
+    /// Keep the cache in sync with Telegram without the TUI, reporting
+    /// readiness and watchdog pings to systemd; stops on SIGTERM.
+    Daemon,
+    /// Check the configuration, file permissions, the connection to Telegram
+    /// and the LLM provider, and suggest fixes.
+    Doctor,
+    /// Inspect the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Manage secrets kept in the OS keyring.
+    #[command(subcommand)]
+    Secrets(SecretsCommand),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct LoginArgs {
+    /// How to log in; `[auth] method` from the config file when omitted.
+    #[arg(long, value_enum)]
+    pub method: Option<LoginMethod>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LoginMethod {
+    Phone,
+    Qr,
+}
+
+impl From<LoginMethod> for AuthMethod {
+    fn from(method: LoginMethod) -> Self {
+        match method {
+            LoginMethod::Phone => AuthMethod::Phone,
+            LoginMethod::Qr => AuthMethod::Qr,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct ExportArgs {
+    /// Chat id, or its title as shown in the chat list.
+    pub chat: String,
+    #[arg(long, value_enum, default_value_t = TranscriptFormat::Markdown)]
+    pub format: TranscriptFormat,
+    /// Only messages from this day on (YYYY-MM-DD, UTC).
+    #[arg(long, value_name = "DAY", value_parser = parse_day_start)]
+    pub since: Option<i64>,
+    /// Only messages up to the end of this day (YYYY-MM-DD, UTC).
+    #[arg(long, value_name = "DAY", value_parser = parse_day_end)]
+    pub until: Option<i64>,
+    /// File to write the transcript to; stdout when omitted.
+    #[arg(long, short, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TranscriptFormat {
+    Markdown,
+    Json,
+}
+
+impl From<TranscriptFormat> for ExportFormat {
+    fn from(format: TranscriptFormat) -> Self {
+        match format {
+            TranscriptFormat::Markdown => ExportFormat::Markdown,
+            TranscriptFormat::Json => ExportFormat::Json,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct SendArgs {
+    /// Chat id, or its title as shown in the chat list.
+    pub chat: String,
+    /// Message text; read from stdin when omitted.
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum ConfigCommand {
+    /// Validate the config file and environment and print the settings in
+    /// effect.
+    Check,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum SecretsCommand {
+    /// Prompt for a secret and store it in the OS keyring, for the active
+    /// profile.
+    Set(SecretArgs),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct SecretArgs {
+    #[arg(value_enum)]
+    pub name: SecretName,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SecretName {
+    /// TELEGRAM_API_HASH.
+    ApiHash,
+    /// TELEGRAM_2FA_PASSWORD, tried before prompting at login.
+    #[value(name = "2fa-password")]
+    TwoFactorPassword,
+    /// TELEGRAM_CACHE_PASSPHRASE.
+    CachePassphrase,
+    /// LLM_API_KEY.
+    LlmApiKey,
+}
+
+impl From<SecretName> for Secret {
+    fn from(name: SecretName) -> Self {
+        match name {
+            SecretName::ApiHash => Secret::ApiHash,
+            SecretName::TwoFactorPassword => Secret::TwoFactorPassword,
+            SecretName::CachePassphrase => Secret::CachePassphrase,
+            SecretName::LlmApiKey => Secret::LlmApiKey,
+        }
+    }
+}
+
+fn parse_day(raw: &str) -> Result<Date, String> {
+    let format = format_description::parse("[year]-[month]-[day]").expect("valid day format");
+    Date::parse(raw.trim(), &format).map_err(|_| format!("expected YYYY-MM-DD, got `{raw}`"))
+}
+
+fn parse_day_start(raw: &str) -> Result<i64, String> {
+    Ok(parse_day(raw)?.midnight().assume_utc().unix_timestamp())
+}
+
+fn parse_day_end(raw: &str) -> Result<i64, String> {
+    let end = Time::from_hms(23, 59, 59).expect("valid time");
+    Ok(parse_day(raw)?.with_time(end).assume_utc().unix_timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("app").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn runs_the_tui_without_a_subcommand() {
+        let cli = parse(&["--profile", "work"]);
+        assert_eq!(cli.command.unwrap_or_default(), Command::Run);
+        assert_eq!(cli.profile.as_deref(), Some("work"));
+    }
+
+    #[test]
+    fn global_flags_follow_the_subcommand() {
+        let cli = parse(&["config", "check", "--config", "/tmp/app.toml"]);
+        assert_eq!(cli.command, Some(Command::Config(ConfigCommand::Check)));
+        assert_eq!(
+            cli.config_source(),
+            ConfigSource {
+                path: Some(PathBuf::from("/tmp/app.toml")),
+                profile: None,
+            }
+        );
+    }
+
+    #[test]
+    fn secrets_set_names_the_secret() {
+        let cli = parse(&["secrets", "set", "2fa-password"]);
+        assert_eq!(
+            cli.command,
+            Some(Command::Secrets(SecretsCommand::Set(SecretArgs {
+                name: SecretName::TwoFactorPassword,
+            })))
+        );
+    }
+
+    #[test]
+    fn export_reads_days_as_utc_bounds() {
+        let cli = parse(&[
+            "export",
+            "Ada",
+            "--format",
+            "json",
+            "--since",
+            "2024-03-01",
+            "--until",
+            "2024-03-01",
+        ]);
+        let Some(Command::Export(args)) = cli.command else {
+            panic!("expected export");
+        };
+        assert_eq!(args.chat, "Ada");
+        assert_eq!(args.format, TranscriptFormat::Json);
+        assert_eq!(args.since, Some(1_709_251_200));
+        assert_eq!(args.until, Some(1_709_251_200 + 86_399));
+
+        let err = Cli::try_parse_from(["app", "export", "Ada", "--since", "March"]).unwrap_err();
+        assert!(err.to_string().contains("expected YYYY-MM-DD"));
+    }
+}
@@ -0,0 +1,370 @@
+//! The subcommands besides `run`: each loads the same [`AppConfig`] and
+//! drives the core services without the TUI.
+
+use std::io::{self, Read};
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use telegram_llm_core::telegram::auth::GrammersAuthClient;
+use telegram_llm_core::telegram::{
+    AuthFlow, AuthResult, CacheManager, ChatId, ChatSummary, ExportRange, QrLoginResult,
+    SendRequest, SendResult, SendStatus, TelegramBootstrap,
+};
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use crate::cli::{ExportArgs, SendArgs};
+use crate::config::{config_path, parse_profile, AppConfig, CacheBackend, ConfigSource};
+use crate::open_cache_store;
+use crate::prompt::{prompt_line, prompt_secret, AuthMethod};
+use crate::secrets::{self, Secret};
+
+type CommandResult = Result<(), Box<dyn std::error::Error>>;
+
+/// How long `send` waits for Telegram to accept the message.
+const SEND_TIMEOUT: Duration = Duration::from_secs(60);
+/// Messages pulled back from the store per round while exporting.
+const EXPORT_HYDRATE_BATCH: usize = 1000;
+
+/// Logs in with `method` unless the session is already authorized.
+pub async fn authorize(
+    auth_flow: &AuthFlow<GrammersAuthClient>,
+    config: &AppConfig,
+    method: AuthMethod,
+) -> CommandResult {
+    if auth_flow.is_authorized().await? {
+        info!("already authorized");
+        return Ok(());
+    }
+    info!(method = ?method, "authentication required");
+    match method {
+        AuthMethod::Phone => {
+            run_phone_login(
+                auth_flow,
+                config.phone_number.as_deref(),
+                config.two_factor_password.clone(),
+            )
+            .await
+        }
+        AuthMethod::Qr => run_qr_login(auth_flow).await,
+    }
+}
+
+pub async fn login(config: &AppConfig, method: AuthMethod) -> CommandResult {
+    let bootstrap = TelegramBootstrap::connect(config.telegram_config()).await?;
+    let result = authorize(&bootstrap.auth_flow(), config, method).await;
+    bootstrap.shutdown().await;
+    result?;
+    println!(
+        "Logged in; session saved to {}",
+        config.session_path.display()
+    );
+    Ok(())
+}
+
+pub async fn logout(config: &AppConfig) -> CommandResult {
+    if !config.session_path.exists() {
+        println!("Not logged in");
+        return Ok(());
+    }
+    let bootstrap = TelegramBootstrap::connect(config.telegram_config()).await?;
+    let auth_flow = bootstrap.auth_flow();
+    let result = match auth_flow.is_authorized().await {
+        Ok(true) => auth_flow.sign_out().await,
+        Ok(false) => Ok(()),
+        Err(err) => Err(err),
+    };
+    bootstrap.shutdown().await;
+    result?;
+    std::fs::remove_file(&config.session_path)?;
+    info!("logged out");
+    println!("Logged out; removed {}", config.session_path.display());
+    Ok(())
+}
+
+pub async fn export(config: &AppConfig, args: ExportArgs) -> CommandResult {
+    let cache = CacheManager::spawn(open_cache_store(config)?, config.cache_config()).await?;
+    let result = export_transcript(&cache, &args).await;
+    cache.shutdown().await;
+    let transcript = result?;
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, transcript)?;
+            eprintln!("Wrote {}", path.display());
+        }
+        None => println!("{transcript}"),
+    }
+    Ok(())
+}
+
+async fn export_transcript(
+    cache: &CacheManager,
+    args: &ExportArgs,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let chat_id = resolve_chat(&cache.chat_summaries(), &args.chat)?;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    while cache
+        .hydrate_history(chat_id, EXPORT_HYDRATE_BATCH, now)
+        .await?
+        > 0
+    {}
+    let range = ExportRange {
+        since: args.since,
+        until: args.until,
+    };
+    Ok(cache.export(chat_id, args.format.into(), range)?)
+}
+
+pub async fn send(config: &AppConfig, args: SendArgs) -> CommandResult {
+    let text = match args.text {
+        Some(text) => text,
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+    let text = text.trim_end().to_string();
+    if text.is_empty() {
+        return Err("nothing to send".into());
+    }
+
+    let cache = CacheManager::spawn(open_cache_store(config)?, config.cache_config()).await?;
+    let chat_id = resolve_chat(&cache.chat_summaries(), &args.chat);
+    cache.shutdown().await;
+    let chat_id = chat_id?;
+
+    let bootstrap = TelegramBootstrap::connect(config.telegram_config()).await?;
+    let result = send_text(&bootstrap, chat_id, text).await;
+    bootstrap.shutdown().await;
+    result
+}
+
+async fn send_text(bootstrap: &TelegramBootstrap, chat_id: ChatId, text: String) -> CommandResult {
+    if !bootstrap.auth_flow().is_authorized().await? {
+        return Err("not logged in; run the `login` subcommand first".into());
+    }
+    let peer = bootstrap.peer_ref(chat_id).ok_or_else(|| {
+        format!(
+            "chat {} is not known to this session; open it in the TUI first",
+            chat_id.0
+        )
+    })?;
+    let pipeline = bootstrap.spawn_send_pipeline();
+    let mut ticket = pipeline.enqueue(SendRequest::SendText {
+        peer,
+        text,
+        reply_to: None,
+        schedule_date: None,
+    })?;
+    let status = ticket.wait_final(SEND_TIMEOUT).await;
+    pipeline.stop().await;
+    match status {
+        Ok(SendStatus::Sent(SendResult::MessageSent { message_id })) => {
+            println!("Sent message {}", message_id.0);
+            Ok(())
+        }
+        Ok(SendStatus::Sent(_)) => Ok(()),
+        Ok(SendStatus::Failed(failure)) => Err(failure.error.into()),
+        Ok(_) => Err("sending was cancelled".into()),
+        Err(_) => Err(format!("no reply from Telegram within {}s", SEND_TIMEOUT.as_secs()).into()),
+    }
+}
+
+/// Loads the configuration like every other subcommand would and prints
+/// what it resolved to.
+pub fn check_config(source: &ConfigSource) -> CommandResult {
+    let path = config_path(source)?;
+    let config = AppConfig::load(source)?;
+    if path.exists() {
+        println!("config file   {}", path.display());
+    } else {
+        println!("config file   {} (missing, using defaults)", path.display());
+    }
+    if let Some(profile) = &config.profile {
+        println!("profile       {profile}");
+    }
+    println!("session       {}", config.session_path.display());
+    match config.cache_backend {
+        CacheBackend::Sqlite => println!("cache         {}", config.cache_db_path.display()),
+        CacheBackend::Postgres => println!("cache         postgres"),
+    }
+    println!("log file      {}", config.log_file_path.display());
+    println!("error log     {}", config.error_log_path.display());
+    println!("crash reports {}", config.crash_report_dir.display());
+    println!("downloads     {}", config.media_download_dir.display());
+    println!("auth method   {:?}", config.auth_method);
+    let llm_api_key = if config.llm_api_key.is_some() {
+        "set"
+    } else {
+        "not set"
+    };
+    println!("llm api key   {llm_api_key}");
+    println!("Configuration is valid");
+    Ok(())
+}
+
+/// Stores a secret for the profile in `source`; it does not need the rest
+/// of the configuration, so it works before anything else is set up.
+pub fn set_secret(source: &ConfigSource, secret: Secret) -> CommandResult {
+    let profile = source.profile.clone().map(parse_profile).transpose()?;
+    let value = prompt_secret(&format!("{}: ", secret.label()))?;
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(format!("no {} given", secret.label()).into());
+    }
+    secrets::store(secret, profile.as_deref(), value)?;
+    println!(
+        "Stored the {} in the OS keyring; {} still takes precedence when set",
+        secret.label(),
+        secret.env_var()
+    );
+    Ok(())
+}
+
+/// Finds a cached chat by id or, ignoring case, by its exact title.
+fn resolve_chat(chats: &[ChatSummary], query: &str) -> Result<ChatId, String> {
+    let query = query.trim();
+    if let Ok(id) = query.parse::<i64>() {
+        return Ok(ChatId(id));
+    }
+    let mut matches = chats
+        .iter()
+        .filter(|chat| chat.title.trim().eq_ignore_ascii_case(query));
+    match (matches.next(), matches.next()) {
+        (Some(chat), None) => Ok(chat.chat_id),
+        (Some(_), Some(_)) => Err(format!(
+            "several chats are titled `{query}`; pass the chat id instead"
+        )),
+        (None, _) => Err(format!("no cached chat is titled `{query}`")),
+    }
+}
+
+async fn run_phone_login(
+    auth_flow: &AuthFlow<GrammersAuthClient>,
+    default_phone: Option<&str>,
+    mut stored_password: Option<String>,
+) -> CommandResult {
+    let phone = match default_phone {
+        Some(phone) => phone.to_string(),
+        None => prompt_line("Phone number: ")?,
+    };
+    info!("requesting login code");
+    let login = auth_flow.begin_phone_login(phone.trim()).await?;
+
+    loop {
+        let code = prompt_line("Login code: ")?;
+        match auth_flow.submit_phone_code(&login, code.trim()).await? {
+            AuthResult::Authorized => {
+                info!("phone login authorized");
+                break;
+            }
+            AuthResult::PasswordRequired(token) => {
+                info!("2fa password required");
+                let password = match stored_password.take() {
+                    Some(password) => password,
+                    None => prompt_secret("2fa password: ")?,
+                };
+                match auth_flow.submit_password(token, password.trim()).await? {
+                    AuthResult::Authorized => {
+                        info!("2fa authorized");
+                        break;
+                    }
+                    AuthResult::InvalidPassword => {
+                        warn!("invalid password, retry");
+                    }
+                    AuthResult::SignUpRequired => {
+                        warn!("sign up required, use official client");
+                        break;
+                    }
+                    AuthResult::InvalidCode | AuthResult::PasswordRequired(_) => {}
+                }
+            }
+            AuthResult::InvalidCode => {
+                warn!("invalid code, retry");
+            }
+            AuthResult::SignUpRequired => {
+                warn!("sign up required, use official client");
+                break;
+            }
+            AuthResult::InvalidPassword => {
+                warn!("invalid password, retry");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_qr_login(auth_flow: &AuthFlow<GrammersAuthClient>) -> CommandResult {
+    info!("requesting qr login token");
+    let mut pending = match auth_flow.begin_qr_login().await? {
+        QrLoginResult::Authorized => {
+            info!("qr login already authorized");
+            return Ok(());
+        }
+        QrLoginResult::Pending(login) => login,
+    };
+
+    loop {
+        let url = format!(
+            "tg://login?token={}",
+            URL_SAFE_NO_PAD.encode(&pending.token)
+        );
+        println!("Scan QR code from this URL: {url}");
+        info!("waiting for qr approval");
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            match auth_flow.poll_qr_login(&pending).await? {
+                QrLoginResult::Authorized => {
+                    info!("qr login authorized");
+                    return Ok(());
+                }
+                QrLoginResult::Pending(login) => {
+                    if login.token != pending.token || login.dc_id != pending.dc_id {
+                        pending = login;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use telegram_llm_core::telegram::{ChatFlags, ChatPeerKind};
+
+    fn chat(chat_id: i64, title: &str) -> ChatSummary {
+        ChatSummary {
+            chat_id: ChatId(chat_id),
+            title: title.to_string(),
+            peer_kind: ChatPeerKind::User,
+            last_message_id: None,
+            last_message_at: None,
+            unread_count: None,
+            last_read_message_id: None,
+            last_outbox_read_message_id: None,
+            last_accessed_at: None,
+            pinned_message_ids: Vec::new(),
+            flags: ChatFlags::default(),
+        }
+    }
+
+    #[test]
+    fn resolves_chats_by_id_or_title() {
+        let chats = [chat(1, "Ada"), chat(2, "Team"), chat(3, "team")];
+
+        assert_eq!(resolve_chat(&chats, "-100123"), Ok(ChatId(-100123)));
+        assert_eq!(resolve_chat(&chats, " ada "), Ok(ChatId(1)));
+        assert!(resolve_chat(&chats, "Team")
+            .unwrap_err()
+            .contains("pass the chat id"));
+        assert!(resolve_chat(&chats, "Bob")
+            .unwrap_err()
+            .contains("no cached chat"));
+    }
+}
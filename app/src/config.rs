@@ -1,33 +1,67 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use ratatui::style::Color;
 use serde::Deserialize;
-use telegram_llm_core::telegram::{CacheConfig, CacheLimits, SendPipelineConfig};
+use telegram_llm_core::telegram::{
+    BatchConfig, CacheConfig, CacheLimits, SendPipelineConfig, TelegramConfig,
+};
 use thiserror::Error;
 use tracing_subscriber::filter::LevelFilter;
+use ui::interaction::KeymapStyle;
+use ui::theme::Theme;
+use ui::view::LayoutConfig;
 
+use crate::dirs::AppDirs;
 use crate::prompt::AuthMethod;
+use crate::secrets::{self, Secret};
+use crate::telemetry::OtlpEndpoint;
 
-const DEFAULT_SESSION_PATH: &str = "data/telegram.session";
+const SESSION_FILE_NAME: &str = "telegram.session";
+const LEGACY_SESSION_PATH: &str = "data/telegram.session";
 const DEFAULT_UPDATE_BUFFER: usize = 1024;
 const DEFAULT_AUTH_METHOD: AuthMethod = AuthMethod::Phone;
-const DEFAULT_CONFIG_PATH: &str = "app/config/app.toml";
-const DEFAULT_LOG_FILE_PATH: &str = "data/logs/app.log";
-const DEFAULT_ERROR_LOG_PATH: &str = "data/logs/app-error.log";
+const CONFIG_FILE_NAME: &str = "app.toml";
+const LEGACY_CONFIG_PATH: &str = "app/config/app.toml";
+const LEGACY_DATA_DIR: &str = "data";
+const LOG_FILE_NAME: &str = "app.log";
+const LEGACY_LOG_FILE_PATH: &str = "data/logs/app.log";
+const ERROR_LOG_FILE_NAME: &str = "app-error.log";
+const LEGACY_ERROR_LOG_PATH: &str = "data/logs/app-error.log";
+const CRASH_REPORT_DIR_NAME: &str = "crashes";
+const LEGACY_CRASH_REPORT_DIR: &str = "data/crashes";
 const DEFAULT_SEND_QUEUE_LIMIT: usize = 256;
 const DEFAULT_SEND_RETRY_BASE_DELAY_MS: u64 = 500;
 const DEFAULT_SEND_RETRY_MAX_DELAY_MS: u64 = 30_000;
-const DEFAULT_CACHE_DB_PATH: &str = "data/cache.sqlite";
+const DEFAULT_SEND_DEAD_LETTER_LIMIT: usize = 32;
+const DEFAULT_EVENT_BATCH_WINDOW_MS: u64 = 0;
+const CACHE_DB_FILE_NAME: &str = "cache.sqlite";
+const LEGACY_CACHE_DB_PATH: &str = "data/cache.sqlite";
+const MEDIA_DOWNLOAD_DIR_NAME: &str = "downloads";
+const LEGACY_MEDIA_DOWNLOAD_DIR: &str = "data/downloads";
 const DEFAULT_CACHE_MAX_CHATS: usize = 0;
 const DEFAULT_CACHE_MAX_MESSAGES_PER_CHAT: usize = 5000;
 const DEFAULT_CACHE_MAX_BYTES: u64 = 0;
+const DEFAULT_CACHE_MAX_BYTES_PER_CHAT: u64 = 0;
 const DEFAULT_CACHE_FLUSH_DEBOUNCE_MS: u64 = 500;
+const DEFAULT_CACHE_ENCRYPT: bool = false;
+const DEFAULT_CACHE_KEEP_TOMBSTONES: bool = false;
+const DEFAULT_CACHE_BACKEND: CacheBackend = CacheBackend::Sqlite;
 const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::INFO;
 const DEFAULT_LOG_FORMAT: LogFormat = LogFormat::Plain;
 const DEFAULT_LOG_ROTATION: LogRotation = LogRotation::Size;
 const DEFAULT_ROTATION_MAX_SIZE_MB: u64 = 1;
 const DEFAULT_ROTATION_MAX_FILES: usize = 20;
 const DEFAULT_LOG_CONTENT: bool = true;
+const DEFAULT_COMPOSER_MAX_LINES: u16 = 5;
+const DEFAULT_CHAT_WIDTH: u16 = 24;
+const DEFAULT_COMPOSER_MIN_LINES: u16 = 1;
+const DEFAULT_KEYMAP: KeymapStyle = KeymapStyle::Vscode;
+const DEFAULT_TELEMETRY_LISTEN: &str = "127.0.0.1:9464";
+const DEFAULT_TELEMETRY_ENDPOINT: &str = "http://127.0.0.1:4318/v1/metrics";
+const DEFAULT_TELEMETRY_INTERVAL_SECS: u64 = 15;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AppConfig {
@@ -39,35 +73,93 @@ pub struct AppConfig {
     pub send_retry_max_attempts: Option<u32>,
     pub send_retry_base_delay_ms: u64,
     pub send_retry_max_delay_ms: u64,
+    pub send_dead_letter_limit: usize,
+    pub event_batch_window_ms: u64,
     pub phone_number: Option<String>,
+    /// Tried before prompting when Telegram asks for the 2fa password.
+    pub two_factor_password: Option<String>,
     pub auth_method: AuthMethod,
+    pub cache_backend: CacheBackend,
     pub cache_db_path: PathBuf,
+    pub cache_postgres_url: Option<String>,
     pub cache_max_chats: usize,
     pub cache_max_messages_per_chat: usize,
     pub cache_max_bytes: u64,
+    pub cache_max_bytes_per_chat: u64,
     pub cache_flush_debounce_ms: u64,
+    pub cache_keep_tombstones: bool,
+    pub cache_startup_messages_per_chat: Option<usize>,
+    pub cache_passphrase: Option<String>,
     pub log_file_path: PathBuf,
     pub error_log_path: PathBuf,
+    /// Where the panic hook saves crash reports.
+    pub crash_report_dir: PathBuf,
     pub log_level: LevelFilter,
     pub log_format: LogFormat,
     pub log_rotation: LogRotation,
     pub rotation_max_size_bytes: u64,
     pub rotation_max_files: usize,
     pub log_content: bool,
+    pub composer_max_lines: u16,
+    /// Starting width of the chat list; `Alt+Left`/`Alt+Right` adjust it.
+    pub chat_width: u16,
+    /// Starting composer height in text lines; `Alt+Up`/`Alt+Down` adjust it.
+    pub composer_min_lines: u16,
+    pub keymap: KeymapStyle,
+    pub theme: Theme,
+    /// Where `s` saves attachments from the message pane.
+    pub media_download_dir: PathBuf,
+    pub llm_api_key: Option<String>,
+    /// Command `o` plays attachments with; the desktop's opener when unset.
+    pub media_player: Option<String>,
+    /// Metrics export from `[telemetry]`; `None` unless enabled.
+    pub telemetry: Option<TelemetryConfig>,
+    /// Profile chosen with `--profile`; its session, cache, logs and
+    /// downloads default to `data/profiles/<name>/` instead of `data/`.
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TelemetryConfig {
+    pub exporter: MetricsExporter,
+    /// How often metrics are sampled, and pushed when exporting over OTLP.
+    pub interval: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricsExporter {
+    /// Serves the Prometheus text format on `GET /metrics`.
+    Prometheus { listen: SocketAddr },
+    /// Pushes OTLP/HTTP JSON to a collector.
+    Otlp { endpoint: OtlpEndpoint },
+}
+
+/// Where [`AppConfig::load`] reads from, as given on the command line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigSource {
+    /// Config file; `APP_CONFIG_PATH`, then `app/config/app.toml`, when unset.
+    pub path: Option<PathBuf>,
+    pub profile: Option<String>,
 }
 
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum ConfigError {
     #[error("missing environment variable: {0}")]
     Missing(&'static str),
+    #[error("missing {0}: set it in the environment or store it with `secrets set`")]
+    MissingSecret(&'static str),
     #[error("invalid api id: {0}")]
     InvalidApiId(String),
     #[error("invalid update buffer: {0}")]
     InvalidUpdateBuffer(String),
     #[error("invalid auth method: {0}")]
     InvalidAuthMethod(String),
+    #[error("invalid session path: {0}")]
+    InvalidSessionPath(String),
     #[error("invalid cache db path: {0}")]
     InvalidCachePath(String),
+    #[error("invalid cache backend: {0}")]
+    InvalidCacheBackend(String),
     #[error("invalid log file path: {0}")]
     InvalidLogPath(String),
     #[error("invalid log level: {0}")]
@@ -80,6 +172,16 @@ pub enum ConfigError {
     InvalidLogRotationSize(String),
     #[error("invalid log rotation files: {0}")]
     InvalidLogRotationFiles(String),
+    #[error("invalid keymap: {0}")]
+    InvalidKeymap(String),
+    #[error("invalid theme color: {0}")]
+    InvalidThemeColor(String),
+    #[error("invalid media download dir: {0}")]
+    InvalidDownloadDir(String),
+    #[error("invalid telemetry setting: {0}")]
+    InvalidTelemetry(String),
+    #[error("invalid profile name: {0}")]
+    InvalidProfile(String),
     #[error("failed to read config file: {0}")]
     ConfigRead(String),
     #[error("failed to resolve current directory: {0}")]
@@ -91,6 +193,23 @@ struct FileConfig {
     auth: Option<AuthSection>,
     logging: Option<LoggingSection>,
     telegram: Option<TelegramSection>,
+    ui: Option<UiSection>,
+    theme: Option<ThemeSection>,
+    media: Option<MediaSection>,
+    telemetry: Option<TelemetrySection>,
+    profiles: Option<HashMap<String, ProfileSection>>,
+}
+
+/// `[profiles.<name>]`, read when `--profile <name>` is active. Each setting
+/// takes the place of its counterpart elsewhere in the file.
+#[derive(Debug, Deserialize)]
+struct ProfileSection {
+    session_path: Option<String>,
+    phone_number: Option<String>,
+    db_path: Option<String>,
+    log_file: Option<String>,
+    error_log_file: Option<String>,
+    download_dir: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -105,16 +224,24 @@ struct TelegramSection {
     send_retry_max_attempts: Option<u32>,
     send_retry_base_delay_ms: Option<u64>,
     send_retry_max_delay_ms: Option<u64>,
+    send_dead_letter_limit: Option<usize>,
+    event_batch_window_ms: Option<u64>,
     cache: Option<CacheSection>,
 }
 
 #[derive(Debug, Deserialize)]
 struct CacheSection {
+    backend: Option<String>,
     db_path: Option<String>,
+    postgres_url: Option<String>,
     max_chats: Option<usize>,
     max_messages_per_chat: Option<usize>,
     max_bytes: Option<u64>,
+    max_bytes_per_chat: Option<u64>,
     flush_debounce_ms: Option<u64>,
+    keep_tombstones: Option<bool>,
+    startup_messages_per_chat: Option<usize>,
+    encrypt: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -129,11 +256,46 @@ struct LoggingSection {
     log_content: Option<bool>,
 }
 
+#[derive(Debug, Deserialize)]
+struct UiSection {
+    composer_max_lines: Option<u16>,
+    chat_width: Option<u16>,
+    composer_min_lines: Option<u16>,
+    keymap: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThemeSection {
+    focused_border: Option<String>,
+    unfocused_border: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MediaSection {
+    download_dir: Option<String>,
+    player: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelemetrySection {
+    enabled: Option<bool>,
+    exporter: Option<String>,
+    listen: Option<String>,
+    endpoint: Option<String>,
+    interval_secs: Option<u64>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogFormat {
     Plain,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheBackend {
+    Sqlite,
+    Postgres,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogRotation {
     Size,
@@ -141,24 +303,39 @@ pub enum LogRotation {
 }
 
 impl AppConfig {
-    pub fn from_env() -> Result<Self, ConfigError> {
-        let file_config = load_file_config()?;
+    /// Reads the config file named by `source` and the environment.
+    pub fn load(source: &ConfigSource) -> Result<Self, ConfigError> {
+        let profile = source.profile.clone().map(parse_profile).transpose()?;
+        let profile = profile.as_deref();
+        let file_config = load_file_config(&config_path(source)?)?;
+        let dirs = AppDirs::from_env();
+        let dir = |pick: fn(&AppDirs) -> &Path| dirs.as_ref().map(pick);
+        let profile_section = profile.and_then(|name| {
+            file_config
+                .as_ref()
+                .and_then(|config| config.profiles.as_ref())
+                .and_then(|profiles| profiles.get(name))
+        });
         let api_id_raw = std::env::var("TELEGRAM_API_ID")
             .map_err(|_| ConfigError::Missing("TELEGRAM_API_ID"))?;
         let api_id = api_id_raw
             .parse::<i32>()
             .map_err(|_| ConfigError::InvalidApiId(api_id_raw))?;
 
-        let api_hash = std::env::var("TELEGRAM_API_HASH")
-            .map_err(|_| ConfigError::Missing("TELEGRAM_API_HASH"))?;
+        let api_hash = secrets::lookup(Secret::ApiHash, profile)
+            .ok_or(ConfigError::MissingSecret("TELEGRAM_API_HASH"))?;
 
         let session_path = match std::env::var("TELEGRAM_SESSION_PATH") {
             Ok(path) => PathBuf::from(path),
-            Err(_) => {
-                let base = std::env::current_dir()
-                    .map_err(|err| ConfigError::CurrentDir(err.to_string()))?;
-                base.join(DEFAULT_SESSION_PATH)
-            }
+            Err(_) => match profile_section.and_then(|section| section.session_path.clone()) {
+                Some(raw) => parse_session_path(raw)?,
+                None => default_path(
+                    dir(|dirs| &dirs.state),
+                    profile,
+                    SESSION_FILE_NAME,
+                    LEGACY_SESSION_PATH,
+                )?,
+            },
         };
 
         let update_buffer = match std::env::var("TELEGRAM_UPDATE_BUFFER") {
@@ -198,17 +375,38 @@ impl AppConfig {
             .unwrap_or(DEFAULT_SEND_RETRY_MAX_DELAY_MS);
         let send_retry_max_delay_ms = send_retry_max_delay_ms.max(send_retry_base_delay_ms);
 
-        let cache_db_path = file_config
+        let send_dead_letter_limit = file_config
             .as_ref()
             .and_then(|config| config.telegram.as_ref())
-            .and_then(|telegram| telegram.cache.as_ref())
-            .and_then(|cache| cache.db_path.as_ref())
+            .and_then(|telegram| telegram.send_dead_letter_limit)
+            .unwrap_or(DEFAULT_SEND_DEAD_LETTER_LIMIT);
+
+        let event_batch_window_ms = file_config
+            .as_ref()
+            .and_then(|config| config.telegram.as_ref())
+            .and_then(|telegram| telegram.event_batch_window_ms)
+            .unwrap_or(DEFAULT_EVENT_BATCH_WINDOW_MS);
+
+        let cache_db_path = profile_section
+            .and_then(|section| section.db_path.as_ref())
+            .or_else(|| {
+                file_config
+                    .as_ref()
+                    .and_then(|config| config.telegram.as_ref())
+                    .and_then(|telegram| telegram.cache.as_ref())
+                    .and_then(|cache| cache.db_path.as_ref())
+            })
             .map(|raw| parse_cache_path(raw.to_string()))
             .transpose()?;
 
         let cache_db_path = match cache_db_path {
             Some(path) => path,
-            None => resolve_path(DEFAULT_CACHE_DB_PATH)?,
+            None => default_path(
+                dir(|dirs| &dirs.cache),
+                profile,
+                CACHE_DB_FILE_NAME,
+                LEGACY_CACHE_DB_PATH,
+            )?,
         };
 
         let cache_max_chats = file_config
@@ -234,6 +432,13 @@ impl AppConfig {
             .and_then(|cache| cache.max_bytes)
             .unwrap_or(DEFAULT_CACHE_MAX_BYTES);
 
+        let cache_max_bytes_per_chat = file_config
+            .as_ref()
+            .and_then(|config| config.telegram.as_ref())
+            .and_then(|telegram| telegram.cache.as_ref())
+            .and_then(|cache| cache.max_bytes_per_chat)
+            .unwrap_or(DEFAULT_CACHE_MAX_BYTES_PER_CHAT);
+
         let cache_flush_debounce_ms = file_config
             .as_ref()
             .and_then(|config| config.telegram.as_ref())
@@ -242,9 +447,71 @@ impl AppConfig {
             .unwrap_or(DEFAULT_CACHE_FLUSH_DEBOUNCE_MS);
         let cache_flush_debounce_ms = normalize_cache_flush_debounce_ms(cache_flush_debounce_ms);
 
+        let cache_keep_tombstones = file_config
+            .as_ref()
+            .and_then(|config| config.telegram.as_ref())
+            .and_then(|telegram| telegram.cache.as_ref())
+            .and_then(|cache| cache.keep_tombstones)
+            .unwrap_or(DEFAULT_CACHE_KEEP_TOMBSTONES);
+
+        let cache_startup_messages_per_chat = file_config
+            .as_ref()
+            .and_then(|config| config.telegram.as_ref())
+            .and_then(|telegram| telegram.cache.as_ref())
+            .and_then(|cache| cache.startup_messages_per_chat)
+            .filter(|per_chat| *per_chat > 0);
+
+        let cache_backend = file_config
+            .as_ref()
+            .and_then(|config| config.telegram.as_ref())
+            .and_then(|telegram| telegram.cache.as_ref())
+            .and_then(|cache| cache.backend.as_ref())
+            .map(|raw| parse_cache_backend(raw.to_string()))
+            .transpose()?
+            .unwrap_or(DEFAULT_CACHE_BACKEND);
+
+        let cache_postgres_url = match cache_backend {
+            CacheBackend::Sqlite => None,
+            CacheBackend::Postgres => {
+                let url = std::env::var("TELEGRAM_CACHE_POSTGRES_URL")
+                    .ok()
+                    .or_else(|| {
+                        file_config
+                            .as_ref()
+                            .and_then(|config| config.telegram.as_ref())
+                            .and_then(|telegram| telegram.cache.as_ref())
+                            .and_then(|cache| cache.postgres_url.clone())
+                    })
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+                    .ok_or(ConfigError::Missing("TELEGRAM_CACHE_POSTGRES_URL"))?;
+                Some(url)
+            }
+        };
+
+        let cache_encrypt = file_config
+            .as_ref()
+            .and_then(|config| config.telegram.as_ref())
+            .and_then(|telegram| telegram.cache.as_ref())
+            .and_then(|cache| cache.encrypt)
+            .unwrap_or(DEFAULT_CACHE_ENCRYPT);
+        if cache_encrypt && cache_backend != CacheBackend::Sqlite {
+            return Err(ConfigError::InvalidCacheBackend(
+                "encryption is only supported by the sqlite backend".to_string(),
+            ));
+        }
+        let cache_passphrase = if cache_encrypt {
+            let passphrase = secrets::lookup(Secret::CachePassphrase, profile)
+                .ok_or(ConfigError::MissingSecret("TELEGRAM_CACHE_PASSPHRASE"))?;
+            Some(passphrase)
+        } else {
+            None
+        };
+
         let phone_number = std::env::var("TELEGRAM_PHONE_NUMBER")
             .ok()
             .or_else(|| std::env::var("PHONE_NUMBER").ok())
+            .or_else(|| profile_section.and_then(|section| section.phone_number.clone()))
             .and_then(|value| {
                 let trimmed = value.trim().to_string();
                 if trimmed.is_empty() {
@@ -253,6 +520,7 @@ impl AppConfig {
                     Some(trimmed)
                 }
             });
+        let two_factor_password = secrets::lookup(Secret::TwoFactorPassword, profile);
 
         let auth_method = file_config
             .as_ref()
@@ -262,28 +530,46 @@ impl AppConfig {
             .transpose()?
             .unwrap_or(DEFAULT_AUTH_METHOD);
 
-        let log_file_path = file_config
-            .as_ref()
-            .and_then(|config| config.logging.as_ref())
-            .and_then(|logging| logging.log_file.as_ref())
+        let log_file_path = profile_section
+            .and_then(|section| section.log_file.as_ref())
+            .or_else(|| {
+                file_config
+                    .as_ref()
+                    .and_then(|config| config.logging.as_ref())
+                    .and_then(|logging| logging.log_file.as_ref())
+            })
             .map(|raw| parse_log_path(raw.to_string()))
             .transpose()?;
 
         let log_file_path = match log_file_path {
             Some(path) => path,
-            None => resolve_path(DEFAULT_LOG_FILE_PATH)?,
+            None => default_path(
+                dir(|dirs| &dirs.logs),
+                profile,
+                LOG_FILE_NAME,
+                LEGACY_LOG_FILE_PATH,
+            )?,
         };
 
-        let error_log_path = file_config
-            .as_ref()
-            .and_then(|config| config.logging.as_ref())
-            .and_then(|logging| logging.error_log_file.as_ref())
+        let error_log_path = profile_section
+            .and_then(|section| section.error_log_file.as_ref())
+            .or_else(|| {
+                file_config
+                    .as_ref()
+                    .and_then(|config| config.logging.as_ref())
+                    .and_then(|logging| logging.error_log_file.as_ref())
+            })
             .map(|raw| parse_log_path(raw.to_string()))
             .transpose()?;
 
         let error_log_path = match error_log_path {
             Some(path) => path,
-            None => resolve_path(DEFAULT_ERROR_LOG_PATH)?,
+            None => default_path(
+                dir(|dirs| &dirs.logs),
+                profile,
+                ERROR_LOG_FILE_NAME,
+                LEGACY_ERROR_LOG_PATH,
+            )?,
         };
 
         let log_level = file_config
@@ -332,6 +618,74 @@ impl AppConfig {
             .and_then(|logging| logging.log_content)
             .unwrap_or(DEFAULT_LOG_CONTENT);
 
+        let composer_max_lines = file_config
+            .as_ref()
+            .and_then(|config| config.ui.as_ref())
+            .and_then(|ui| ui.composer_max_lines)
+            .unwrap_or(DEFAULT_COMPOSER_MAX_LINES)
+            .max(1);
+
+        let chat_width = file_config
+            .as_ref()
+            .and_then(|config| config.ui.as_ref())
+            .and_then(|ui| ui.chat_width)
+            .unwrap_or(DEFAULT_CHAT_WIDTH)
+            .clamp(LayoutConfig::MIN_CHAT_WIDTH, LayoutConfig::MAX_CHAT_WIDTH);
+
+        let composer_min_lines = file_config
+            .as_ref()
+            .and_then(|config| config.ui.as_ref())
+            .and_then(|ui| ui.composer_min_lines)
+            .unwrap_or(DEFAULT_COMPOSER_MIN_LINES)
+            .clamp(1, LayoutConfig::MAX_COMPOSER_LINES);
+
+        let keymap = file_config
+            .as_ref()
+            .and_then(|config| config.ui.as_ref())
+            .and_then(|ui| ui.keymap.clone())
+            .map(parse_keymap)
+            .transpose()?
+            .unwrap_or(DEFAULT_KEYMAP);
+
+        let theme_section = file_config
+            .as_ref()
+            .and_then(|config| config.theme.as_ref());
+        let mut theme = Theme::default();
+        if let Some(raw) = theme_section.and_then(|theme| theme.focused_border.clone()) {
+            theme.focused_border = parse_theme_color(raw)?;
+        }
+        if let Some(raw) = theme_section.and_then(|theme| theme.unfocused_border.clone()) {
+            theme.unfocused_border = parse_theme_color(raw)?;
+        }
+
+        let media_section = file_config
+            .as_ref()
+            .and_then(|config| config.media.as_ref());
+        let download_dir = profile_section
+            .and_then(|section| section.download_dir.clone())
+            .or_else(|| media_section.and_then(|media| media.download_dir.clone()));
+        let media_download_dir = match download_dir {
+            Some(raw) => parse_download_dir(raw)?,
+            None => default_path(
+                dir(|dirs| &dirs.cache),
+                profile,
+                MEDIA_DOWNLOAD_DIR_NAME,
+                LEGACY_MEDIA_DOWNLOAD_DIR,
+            )?,
+        };
+        let media_player = media_section
+            .and_then(|media| media.player.as_deref())
+            .map(str::trim)
+            .filter(|player| !player.is_empty())
+            .map(str::to_string);
+
+        let telemetry = file_config
+            .as_ref()
+            .and_then(|config| config.telemetry.as_ref())
+            .filter(|telemetry| telemetry.enabled.unwrap_or(false))
+            .map(parse_telemetry)
+            .transpose()?;
+
         Ok(Self {
             api_id,
             api_hash,
@@ -341,21 +695,46 @@ impl AppConfig {
             send_retry_max_attempts,
             send_retry_base_delay_ms,
             send_retry_max_delay_ms,
+            send_dead_letter_limit,
+            event_batch_window_ms,
             phone_number,
+            two_factor_password,
             auth_method,
+            cache_backend,
             cache_db_path,
+            cache_postgres_url,
             cache_max_chats,
             cache_max_messages_per_chat,
             cache_max_bytes,
+            cache_max_bytes_per_chat,
             cache_flush_debounce_ms,
+            cache_keep_tombstones,
+            cache_startup_messages_per_chat,
+            cache_passphrase,
             log_file_path,
             error_log_path,
+            crash_report_dir: default_path(
+                dir(|dirs| &dirs.state),
+                profile,
+                CRASH_REPORT_DIR_NAME,
+                LEGACY_CRASH_REPORT_DIR,
+            )?,
             log_level,
             log_format,
             log_rotation,
             rotation_max_size_bytes,
             rotation_max_files,
             log_content,
+            composer_max_lines,
+            chat_width,
+            composer_min_lines,
+            keymap,
+            theme,
+            media_download_dir,
+            media_player,
+            telemetry,
+            llm_api_key: secrets::lookup(Secret::LlmApiKey, profile),
+            profile: profile.map(str::to_string),
         })
     }
 
@@ -365,9 +744,29 @@ impl AppConfig {
             max_retry_attempts: self.send_retry_max_attempts,
             retry_base_delay: Duration::from_millis(self.send_retry_base_delay_ms),
             retry_max_delay: Duration::from_millis(self.send_retry_max_delay_ms),
+            dead_letter_limit: self.send_dead_letter_limit,
         }
     }
 
+    pub fn telegram_config(&self) -> TelegramConfig {
+        let mut config = TelegramConfig::new(
+            self.api_id,
+            self.api_hash.clone(),
+            self.session_path.clone(),
+        );
+        config.send_pipeline = self.send_pipeline_config();
+        config.event_stream.log_content = self.log_content;
+        config
+    }
+
+    /// Event coalescing for the cache, or `None` when the window is 0.
+    pub fn event_batch(&self) -> Option<BatchConfig> {
+        (self.event_batch_window_ms > 0).then(|| BatchConfig {
+            window: Duration::from_millis(self.event_batch_window_ms),
+            ..BatchConfig::default()
+        })
+    }
+
     pub fn cache_config(&self) -> CacheConfig {
         CacheConfig {
             db_path: self.cache_db_path.clone(),
@@ -375,12 +774,23 @@ impl AppConfig {
                 max_chats: self.cache_max_chats,
                 max_messages_per_chat: self.cache_max_messages_per_chat,
                 max_bytes: self.cache_max_bytes as usize,
+                max_bytes_per_chat: self.cache_max_bytes_per_chat as usize,
             },
             flush_debounce: Duration::from_millis(self.cache_flush_debounce_ms),
+            keep_tombstones: self.cache_keep_tombstones,
+            startup_messages_per_chat: self.cache_startup_messages_per_chat,
         }
     }
 }
 
+fn parse_keymap(raw: String) -> Result<KeymapStyle, ConfigError> {
+    match raw.trim().to_lowercase().as_str() {
+        "vscode" => Ok(KeymapStyle::Vscode),
+        "vim" => Ok(KeymapStyle::Vim),
+        other => Err(ConfigError::InvalidKeymap(other.to_string())),
+    }
+}
+
 fn parse_auth_method(raw: String) -> Result<AuthMethod, ConfigError> {
     match raw.trim().to_lowercase().as_str() {
         "phone" => Ok(AuthMethod::Phone),
@@ -389,14 +799,31 @@ fn parse_auth_method(raw: String) -> Result<AuthMethod, ConfigError> {
     }
 }
 
-fn load_file_config() -> Result<Option<FileConfig>, ConfigError> {
-    let path = std::env::var("APP_CONFIG_PATH").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
-    let resolved = resolve_path(&path)?;
-    if !resolved.exists() {
+/// The config file `source` points at, resolved against the working
+/// directory; without one, `app.toml` in the platform config directory. It
+/// does not have to exist.
+pub fn config_path(source: &ConfigSource) -> Result<PathBuf, ConfigError> {
+    if let Some(path) = &source.path {
+        return resolve_path(&path.to_string_lossy());
+    }
+    if let Ok(path) = std::env::var("APP_CONFIG_PATH") {
+        return resolve_path(&path);
+    }
+    let dirs = AppDirs::from_env();
+    default_path(
+        dirs.as_ref().map(|dirs| dirs.config.as_path()),
+        None,
+        CONFIG_FILE_NAME,
+        LEGACY_CONFIG_PATH,
+    )
+}
+
+fn load_file_config(path: &Path) -> Result<Option<FileConfig>, ConfigError> {
+    if !path.exists() {
         return Ok(None);
     }
-    let contents = std::fs::read_to_string(&resolved)
-        .map_err(|err| ConfigError::ConfigRead(err.to_string()))?;
+    let contents =
+        std::fs::read_to_string(path).map_err(|err| ConfigError::ConfigRead(err.to_string()))?;
     let config: FileConfig =
         toml::from_str(&contents).map_err(|err| ConfigError::ConfigRead(err.to_string()))?;
     Ok(Some(config))
@@ -411,6 +838,89 @@ fn resolve_path(raw: &str) -> Result<PathBuf, ConfigError> {
     Ok(base.join(path))
 }
 
+/// `name` in the platform directory `base`, under `profiles/<profile>/`
+/// when a profile is active. Falls back to the older path under the working
+/// directory when that already exists or there is no platform directory.
+fn default_path(
+    base: Option<&Path>,
+    profile: Option<&str>,
+    name: &str,
+    legacy: &str,
+) -> Result<PathBuf, ConfigError> {
+    let legacy = data_path(profile, legacy)?;
+    match base {
+        Some(base) if !legacy.exists() => Ok(match profile {
+            Some(profile) => base.join("profiles").join(profile).join(name),
+            None => base.join(name),
+        }),
+        _ => Ok(legacy),
+    }
+}
+
+/// `default` (a path under `data/`), moved under `data/profiles/<profile>/`
+/// when a profile is active.
+fn data_path(profile: Option<&str>, default: &str) -> Result<PathBuf, ConfigError> {
+    match (profile, default.strip_prefix(LEGACY_DATA_DIR)) {
+        (Some(profile), Some(rest)) => {
+            resolve_path(&format!("{LEGACY_DATA_DIR}/profiles/{profile}{rest}"))
+        }
+        _ => resolve_path(default),
+    }
+}
+
+pub fn parse_profile(raw: String) -> Result<String, ConfigError> {
+    let name = raw.trim();
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(ConfigError::InvalidProfile(raw));
+    }
+    Ok(name.to_string())
+}
+
+fn parse_telemetry(section: &TelemetrySection) -> Result<TelemetryConfig, ConfigError> {
+    let exporter = match section
+        .exporter
+        .as_deref()
+        .unwrap_or("prometheus")
+        .trim()
+        .to_lowercase()
+        .as_str()
+    {
+        "prometheus" => {
+            let raw = section
+                .listen
+                .as_deref()
+                .unwrap_or(DEFAULT_TELEMETRY_LISTEN);
+            let listen = raw
+                .trim()
+                .parse()
+                .map_err(|_| ConfigError::InvalidTelemetry(format!("listen = {raw}")))?;
+            MetricsExporter::Prometheus { listen }
+        }
+        "otlp" => {
+            let raw = section
+                .endpoint
+                .as_deref()
+                .unwrap_or(DEFAULT_TELEMETRY_ENDPOINT);
+            let endpoint = OtlpEndpoint::parse(raw)
+                .ok_or_else(|| ConfigError::InvalidTelemetry(format!("endpoint = {raw}")))?;
+            MetricsExporter::Otlp { endpoint }
+        }
+        other => return Err(ConfigError::InvalidTelemetry(format!("exporter = {other}"))),
+    };
+    let interval_secs = section
+        .interval_secs
+        .unwrap_or(DEFAULT_TELEMETRY_INTERVAL_SECS)
+        .max(1);
+    Ok(TelemetryConfig {
+        exporter,
+        interval: Duration::from_secs(interval_secs),
+    })
+}
+
 fn parse_log_path(raw: String) -> Result<PathBuf, ConfigError> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
@@ -419,6 +929,14 @@ fn parse_log_path(raw: String) -> Result<PathBuf, ConfigError> {
     resolve_path(trimmed)
 }
 
+fn parse_session_path(raw: String) -> Result<PathBuf, ConfigError> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Err(ConfigError::InvalidSessionPath(raw));
+    }
+    resolve_path(trimmed)
+}
+
 fn parse_cache_path(raw: String) -> Result<PathBuf, ConfigError> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
@@ -427,6 +945,14 @@ fn parse_cache_path(raw: String) -> Result<PathBuf, ConfigError> {
     resolve_path(trimmed)
 }
 
+fn parse_download_dir(raw: String) -> Result<PathBuf, ConfigError> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Err(ConfigError::InvalidDownloadDir(raw));
+    }
+    resolve_path(trimmed)
+}
+
 fn parse_log_level(raw: String) -> Result<LevelFilter, ConfigError> {
     raw.trim()
         .parse::<LevelFilter>()
@@ -448,6 +974,22 @@ fn parse_log_rotation(raw: String) -> Result<LogRotation, ConfigError> {
     }
 }
 
+/// Color names such as `cyan` or `lightblue`, `#rrggbb` or a 256-color
+/// index.
+fn parse_theme_color(raw: String) -> Result<Color, ConfigError> {
+    raw.trim()
+        .parse::<Color>()
+        .map_err(|_| ConfigError::InvalidThemeColor(raw))
+}
+
+fn parse_cache_backend(raw: String) -> Result<CacheBackend, ConfigError> {
+    match raw.trim().to_lowercase().as_str() {
+        "sqlite" => Ok(CacheBackend::Sqlite),
+        "postgres" => Ok(CacheBackend::Postgres),
+        other => Err(ConfigError::InvalidCacheBackend(other.to_string())),
+    }
+}
+
 fn normalize_send_queue_limit(value: usize) -> usize {
     if value == 0 {
         DEFAULT_SEND_QUEUE_LIMIT
@@ -503,16 +1045,18 @@ fn parse_rotation_files(raw: String) -> Result<usize, ConfigError> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use std::sync::{Mutex, OnceLock};
 
-    fn env_lock() -> &'static Mutex<()> {
+    /// Held by every test that touches the environment, here and in the
+    /// modules that load an [`AppConfig`].
+    pub(crate) fn env_lock() -> &'static Mutex<()> {
         static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
         LOCK.get_or_init(|| Mutex::new(()))
     }
 
-    struct EnvGuard {
+    pub(crate) struct EnvGuard {
         key: &'static str,
         original: Option<String>,
     }
@@ -541,7 +1085,7 @@ mod tests {
         }
     }
 
-    fn set_required_env() -> (EnvGuard, EnvGuard) {
+    pub(crate) fn set_required_env() -> (EnvGuard, EnvGuard) {
         (
             EnvGuard::set("TELEGRAM_API_ID", "123"),
             EnvGuard::set("TELEGRAM_API_HASH", "hash"),
@@ -554,7 +1098,7 @@ mod tests {
         let _hash = EnvGuard::set("TELEGRAM_API_HASH", "hash");
         let _id = EnvGuard::unset("TELEGRAM_API_ID");
 
-        let err = AppConfig::from_env().unwrap_err();
+        let err = AppConfig::load(&ConfigSource::default()).unwrap_err();
         assert_eq!(err, ConfigError::Missing("TELEGRAM_API_ID"));
     }
 
@@ -564,8 +1108,24 @@ mod tests {
         let _id = EnvGuard::set("TELEGRAM_API_ID", "123");
         let _hash = EnvGuard::unset("TELEGRAM_API_HASH");
 
-        let err = AppConfig::from_env().unwrap_err();
-        assert_eq!(err, ConfigError::Missing("TELEGRAM_API_HASH"));
+        let err = AppConfig::load(&ConfigSource::default()).unwrap_err();
+        assert_eq!(err, ConfigError::MissingSecret("TELEGRAM_API_HASH"));
+    }
+
+    #[test]
+    fn optional_secrets_read_from_environment() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+        let _llm = EnvGuard::unset("LLM_API_KEY");
+
+        let missing = AppConfig::load(&ConfigSource::default()).unwrap();
+        let _password = EnvGuard::set("TELEGRAM_2FA_PASSWORD", "hunter2");
+        let _llm = EnvGuard::set("LLM_API_KEY", "sk-test");
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
+
+        assert_eq!(missing.llm_api_key, None);
+        assert_eq!(config.two_factor_password.as_deref(), Some("hunter2"));
+        assert_eq!(config.llm_api_key.as_deref(), Some("sk-test"));
     }
 
     #[test]
@@ -574,7 +1134,7 @@ mod tests {
         let _hash = EnvGuard::set("TELEGRAM_API_HASH", "hash");
         let _id = EnvGuard::set("TELEGRAM_API_ID", "nope");
 
-        let err = AppConfig::from_env().unwrap_err();
+        let err = AppConfig::load(&ConfigSource::default()).unwrap_err();
         assert_eq!(err, ConfigError::InvalidApiId("nope".to_string()));
     }
 
@@ -584,9 +1144,9 @@ mod tests {
         let (_id, _hash) = set_required_env();
         let _session = EnvGuard::unset("TELEGRAM_SESSION_PATH");
 
-        let config = AppConfig::from_env().unwrap();
-        let path = config.session_path.to_string_lossy();
-        assert!(path.ends_with(DEFAULT_SESSION_PATH));
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
+        let dirs = AppDirs::from_env().unwrap();
+        assert_eq!(config.session_path, dirs.state.join(SESSION_FILE_NAME));
     }
 
     #[test]
@@ -595,7 +1155,7 @@ mod tests {
         let (_id, _hash) = set_required_env();
         let _session = EnvGuard::set("TELEGRAM_SESSION_PATH", "/tmp/tg.session");
 
-        let config = AppConfig::from_env().unwrap();
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
         assert_eq!(config.session_path, PathBuf::from("/tmp/tg.session"));
     }
 
@@ -607,7 +1167,7 @@ mod tests {
         let temp_path = std::env::temp_dir().join("telegram-llm-tui-missing-update-config.toml");
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
 
-        let config = AppConfig::from_env().unwrap();
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
         assert_eq!(config.update_buffer, DEFAULT_UPDATE_BUFFER);
     }
 
@@ -617,7 +1177,7 @@ mod tests {
         let (_id, _hash) = set_required_env();
         let _buffer = EnvGuard::set("TELEGRAM_UPDATE_BUFFER", "42");
 
-        let config = AppConfig::from_env().unwrap();
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
         assert_eq!(config.update_buffer, 42);
     }
 
@@ -630,11 +1190,277 @@ mod tests {
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
         std::fs::write(&temp_path, "[telegram]\nupdate_buffer = 256\n").unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
         assert_eq!(config.update_buffer, 256);
+        assert_eq!(config.event_batch(), None);
+    }
+
+    #[test]
+    fn event_batch_window_reads_from_config_file() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-batch-config.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
+        std::fs::write(&temp_path, "[telegram]\nevent_batch_window_ms = 8\n").unwrap();
+
+        let result = AppConfig::load(&ConfigSource::default());
+        let _ = std::fs::remove_file(&temp_path);
+
+        let batch = result.unwrap().event_batch().expect("batching enabled");
+        assert_eq!(batch.window, Duration::from_millis(8));
+    }
+
+    #[test]
+    fn composer_max_lines_reads_from_config_file() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-ui-config.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
+        std::fs::write(&temp_path, "[ui]\ncomposer_max_lines = 8\n").unwrap();
+
+        let result = AppConfig::load(&ConfigSource::default());
+        let _ = std::fs::remove_file(&temp_path);
+
+        assert_eq!(result.unwrap().composer_max_lines, 8);
+    }
+
+    #[test]
+    fn pane_sizes_read_from_config_file_within_bounds() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-pane-config.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
+        std::fs::write(
+            &temp_path,
+            "[ui]\nchat_width = 4\ncomposer_min_lines = 3\nkeymap = \"Vim\"\n",
+        )
+        .unwrap();
+
+        let result = AppConfig::load(&ConfigSource::default());
+        let _ = std::fs::remove_file(&temp_path);
+
+        let config = result.unwrap();
+        assert_eq!(config.chat_width, LayoutConfig::MIN_CHAT_WIDTH);
+        assert_eq!(config.composer_min_lines, 3);
+        assert_eq!(config.keymap, KeymapStyle::Vim);
+    }
+
+    #[test]
+    fn theme_colors_read_from_config_file() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-theme-config.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
+        std::fs::write(&temp_path, "[theme]\nfocused_border = \"#ffaa00\"\n").unwrap();
+        let result = AppConfig::load(&ConfigSource::default());
+        std::fs::write(&temp_path, "[theme]\nunfocused_border = \"mauve\"\n").unwrap();
+        let invalid = AppConfig::load(&ConfigSource::default());
+        let _ = std::fs::remove_file(&temp_path);
+
+        let theme = result.unwrap().theme;
+        assert_eq!(theme.focused_border, Color::Rgb(0xff, 0xaa, 0x00));
+        assert_eq!(theme.unfocused_border, Theme::default().unfocused_border);
+        assert_eq!(
+            invalid.unwrap_err(),
+            ConfigError::InvalidThemeColor("mauve".to_string())
+        );
+    }
+
+    #[test]
+    fn media_settings_read_from_config_file() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-media-config.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
+        std::fs::write(&temp_path, "[media]\nplayer = \"  \"\n").unwrap();
+        let defaults = AppConfig::load(&ConfigSource::default());
+        std::fs::write(
+            &temp_path,
+            "[media]\ndownload_dir = \"data/test-downloads\"\nplayer = \"mpv --no-video\"\n",
+        )
+        .unwrap();
+        let result = AppConfig::load(&ConfigSource::default());
+        let _ = std::fs::remove_file(&temp_path);
+
+        let defaults = defaults.unwrap();
+        assert_eq!(
+            defaults.media_download_dir,
+            AppDirs::from_env()
+                .unwrap()
+                .cache
+                .join(MEDIA_DOWNLOAD_DIR_NAME)
+        );
+        assert_eq!(defaults.media_player, None);
+        let config = result.unwrap();
+        assert!(config.media_download_dir.ends_with("data/test-downloads"));
+        assert_eq!(config.media_player.as_deref(), Some("mpv --no-video"));
+    }
+
+    #[test]
+    fn config_flag_takes_precedence_over_env_path() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+        let _buffer = EnvGuard::unset("TELEGRAM_UPDATE_BUFFER");
+
+        let env_path = std::env::temp_dir().join("telegram-llm-tui-env-config.toml");
+        let flag_path = std::env::temp_dir().join("telegram-llm-tui-flag-config.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", env_path.to_string_lossy().as_ref());
+        std::fs::write(&env_path, "[telegram]\nupdate_buffer = 64\n").unwrap();
+        std::fs::write(&flag_path, "[telegram]\nupdate_buffer = 32\n").unwrap();
+        let result = AppConfig::load(&ConfigSource {
+            path: Some(flag_path.clone()),
+            profile: None,
+        });
+        let _ = std::fs::remove_file(&env_path);
+        let _ = std::fs::remove_file(&flag_path);
+
+        assert_eq!(result.unwrap().update_buffer, 32);
+    }
+
+    #[test]
+    fn profile_moves_default_data_paths() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+        let _session = EnvGuard::unset("TELEGRAM_SESSION_PATH");
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-missing-config.toml");
+        let source = |profile: &str| ConfigSource {
+            path: Some(temp_path.clone()),
+            profile: Some(profile.to_string()),
+        };
+
+        let config = AppConfig::load(&source("work")).unwrap();
+        let dirs = AppDirs::from_env().unwrap();
+        assert_eq!(config.profile.as_deref(), Some("work"));
+        assert_eq!(
+            config.session_path,
+            dirs.state.join("profiles/work/telegram.session")
+        );
+        assert_eq!(
+            config.cache_db_path,
+            dirs.cache.join("profiles/work/cache.sqlite")
+        );
+        assert_eq!(
+            config.log_file_path,
+            dirs.logs.join("profiles/work/app.log")
+        );
+        assert_eq!(
+            AppConfig::load(&source("../work")).unwrap_err(),
+            ConfigError::InvalidProfile("../work".to_string())
+        );
+    }
+
+    #[test]
+    fn profile_sections_override_shared_settings() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+        let _session = EnvGuard::unset("TELEGRAM_SESSION_PATH");
+        let _phone = EnvGuard::unset("TELEGRAM_PHONE_NUMBER");
+        let _legacy_phone = EnvGuard::unset("PHONE_NUMBER");
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-profiles-config.toml");
+        std::fs::write(
+            &temp_path,
+            "[telegram.cache]\ndb_path = \"/tmp/shared.sqlite\"\n\n\
+             [profiles.work]\nsession_path = \"/tmp/work.session\"\n\
+             phone_number = \"+15550100\"\ndb_path = \"/tmp/work.sqlite\"\n",
+        )
+        .unwrap();
+        let load = |profile: &str| {
+            AppConfig::load(&ConfigSource {
+                path: Some(temp_path.clone()),
+                profile: Some(profile.to_string()),
+            })
+        };
+        let work = load("work");
+        let home = load("home");
+        let _ = std::fs::remove_file(&temp_path);
+
+        let work = work.unwrap();
+        assert_eq!(work.session_path, PathBuf::from("/tmp/work.session"));
+        assert_eq!(work.phone_number.as_deref(), Some("+15550100"));
+        assert_eq!(work.cache_db_path, PathBuf::from("/tmp/work.sqlite"));
+        let home = home.unwrap();
+        assert_eq!(home.phone_number, None);
+        assert_eq!(home.cache_db_path, PathBuf::from("/tmp/shared.sqlite"));
+        assert_eq!(
+            home.session_path,
+            AppDirs::from_env()
+                .unwrap()
+                .state
+                .join("profiles/home/telegram.session")
+        );
+    }
+
+    #[test]
+    fn existing_legacy_paths_are_kept() {
+        let legacy = std::env::temp_dir().join("telegram-llm-tui-legacy.session");
+        let legacy_raw = legacy.to_string_lossy().to_string();
+        let base = Path::new("/state");
+        std::fs::write(&legacy, "").unwrap();
+        let kept = default_path(Some(base), None, SESSION_FILE_NAME, &legacy_raw);
+        let _ = std::fs::remove_file(&legacy);
+
+        assert_eq!(kept.unwrap(), legacy);
+        assert_eq!(
+            default_path(Some(base), None, SESSION_FILE_NAME, &legacy_raw).unwrap(),
+            base.join(SESSION_FILE_NAME)
+        );
+        assert_eq!(
+            default_path(None, None, SESSION_FILE_NAME, &legacy_raw).unwrap(),
+            legacy
+        );
+    }
+
+    #[test]
+    fn telemetry_reads_from_config_file() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-telemetry-config.toml");
+        let load = |contents: &str| {
+            std::fs::write(&temp_path, contents).unwrap();
+            AppConfig::load(&ConfigSource {
+                path: Some(temp_path.clone()),
+                profile: None,
+            })
+        };
+        let disabled = load("[telemetry]\nexporter = \"otlp\"\n");
+        let prometheus = load("[telemetry]\nenabled = true\n");
+        let otlp = load(
+            "[telemetry]\nenabled = true\nexporter = \"otlp\"\nendpoint = \"http://collector:4318/v1/metrics\"\ninterval_secs = 30\n",
+        );
+        let invalid = load(
+            "[telemetry]\nenabled = true\nendpoint = \"https://collector\"\nexporter = \"otlp\"\n",
+        );
+        let _ = std::fs::remove_file(&temp_path);
+
+        assert_eq!(disabled.unwrap().telemetry, None);
+        assert_eq!(
+            prometheus.unwrap().telemetry,
+            Some(TelemetryConfig {
+                exporter: MetricsExporter::Prometheus {
+                    listen: DEFAULT_TELEMETRY_LISTEN.parse().unwrap(),
+                },
+                interval: Duration::from_secs(DEFAULT_TELEMETRY_INTERVAL_SECS),
+            })
+        );
+        let otlp = otlp.unwrap().telemetry.unwrap();
+        assert_eq!(otlp.interval, Duration::from_secs(30));
+        assert!(matches!(
+            otlp.exporter,
+            MetricsExporter::Otlp { endpoint } if endpoint.host == "collector"
+        ));
+        assert_eq!(
+            invalid.unwrap_err(),
+            ConfigError::InvalidTelemetry("endpoint = https://collector".to_string())
+        );
     }
 
     #[test]
@@ -647,7 +1473,7 @@ mod tests {
         let _buffer = EnvGuard::set("TELEGRAM_UPDATE_BUFFER", "42");
         std::fs::write(&temp_path, "[telegram]\nupdate_buffer = 256\n").unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -661,7 +1487,7 @@ mod tests {
         let _phone = EnvGuard::set("TELEGRAM_PHONE_NUMBER", "+123");
         let _legacy = EnvGuard::unset("PHONE_NUMBER");
 
-        let config = AppConfig::from_env().unwrap();
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
         assert_eq!(config.phone_number, Some("+123".to_string()));
     }
 
@@ -672,7 +1498,7 @@ mod tests {
         let _phone = EnvGuard::unset("TELEGRAM_PHONE_NUMBER");
         let _legacy = EnvGuard::set("PHONE_NUMBER", "+456");
 
-        let config = AppConfig::from_env().unwrap();
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
         assert_eq!(config.phone_number, Some("+456".to_string()));
     }
 
@@ -683,7 +1509,7 @@ mod tests {
         let temp_path = std::env::temp_dir().join("telegram-llm-tui-missing-config.toml");
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
 
-        let config = AppConfig::from_env().unwrap();
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
         assert_eq!(config.auth_method, AuthMethod::Phone);
     }
 
@@ -696,7 +1522,7 @@ mod tests {
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
         std::fs::write(&temp_path, "[auth]\ndefault_method = \"qr\"\n").unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -716,7 +1542,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -733,7 +1559,7 @@ mod tests {
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
         std::fs::write(&temp_path, "[logging]\nlevel = \"debug\"\n").unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -746,9 +1572,10 @@ mod tests {
         let (_id, _hash) = set_required_env();
         let _config = EnvGuard::unset("APP_CONFIG_PATH");
 
-        let config = AppConfig::from_env().unwrap();
-        let path = config.log_file_path.to_string_lossy();
-        assert!(path.ends_with(DEFAULT_LOG_FILE_PATH));
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
+        let dirs = AppDirs::from_env().unwrap();
+        assert_eq!(config.log_file_path, dirs.logs.join(LOG_FILE_NAME));
+        assert_eq!(config.error_log_path, dirs.logs.join(ERROR_LOG_FILE_NAME));
     }
 
     #[test]
@@ -760,7 +1587,7 @@ mod tests {
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
         std::fs::write(&temp_path, "[logging]\nlog_file = \"logs/test.log\"\n").unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -777,7 +1604,7 @@ mod tests {
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
         std::fs::write(&temp_path, "[logging]\nformat = \"plain\"\n").unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -793,7 +1620,7 @@ mod tests {
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
         std::fs::write(&temp_path, "[logging]\nrotation = \"daily\"\n").unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -813,7 +1640,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -830,7 +1657,7 @@ mod tests {
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
         std::fs::write(&temp_path, "[logging]\nlog_content = false\n").unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -844,19 +1671,30 @@ mod tests {
         let temp_path = std::env::temp_dir().join("telegram-llm-tui-cache-defaults.toml");
         let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
 
-        let config = AppConfig::from_env().unwrap();
-        let path = config.cache_db_path.to_string_lossy();
-        assert!(path.ends_with(DEFAULT_CACHE_DB_PATH));
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
+        assert_eq!(
+            config.cache_db_path,
+            AppDirs::from_env().unwrap().cache.join(CACHE_DB_FILE_NAME)
+        );
         assert_eq!(config.cache_max_chats, DEFAULT_CACHE_MAX_CHATS);
         assert_eq!(
             config.cache_max_messages_per_chat,
             DEFAULT_CACHE_MAX_MESSAGES_PER_CHAT
         );
         assert_eq!(config.cache_max_bytes, DEFAULT_CACHE_MAX_BYTES);
+        assert_eq!(
+            config.cache_max_bytes_per_chat,
+            DEFAULT_CACHE_MAX_BYTES_PER_CHAT
+        );
         assert_eq!(
             config.cache_flush_debounce_ms,
             DEFAULT_CACHE_FLUSH_DEBOUNCE_MS
         );
+        assert_eq!(config.cache_passphrase, None);
+        assert_eq!(config.cache_keep_tombstones, DEFAULT_CACHE_KEEP_TOMBSTONES);
+        assert_eq!(config.cache_startup_messages_per_chat, None);
+        assert_eq!(config.cache_backend, DEFAULT_CACHE_BACKEND);
+        assert_eq!(config.cache_postgres_url, None);
     }
 
     #[test]
@@ -873,11 +1711,14 @@ db_path = \"data/cache/test.sqlite\"\n\
 max_chats = 99\n\
 max_messages_per_chat = 1234\n\
 max_bytes = 1024\n\
-flush_debounce_ms = 250\n",
+max_bytes_per_chat = 512\n\
+flush_debounce_ms = 250\n\
+keep_tombstones = true\n\
+startup_messages_per_chat = 200\n",
         )
         .unwrap();
 
-        let result = AppConfig::from_env();
+        let result = AppConfig::load(&ConfigSource::default());
         let _ = std::fs::remove_file(&temp_path);
 
         let config = result.unwrap();
@@ -886,6 +1727,83 @@ flush_debounce_ms = 250\n",
         assert_eq!(config.cache_max_chats, 99);
         assert_eq!(config.cache_max_messages_per_chat, 1234);
         assert_eq!(config.cache_max_bytes, 1024);
+        assert_eq!(config.cache_max_bytes_per_chat, 512);
         assert_eq!(config.cache_flush_debounce_ms, 250);
+        assert!(config.cache_keep_tombstones);
+        assert_eq!(config.cache_startup_messages_per_chat, Some(200));
+    }
+
+    #[test]
+    fn cache_encryption_requires_passphrase() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-cache-encrypt.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
+        std::fs::write(&temp_path, "[telegram.cache]\nencrypt = true\n").unwrap();
+
+        let missing = {
+            let _passphrase = EnvGuard::unset("TELEGRAM_CACHE_PASSPHRASE");
+            AppConfig::load(&ConfigSource::default())
+        };
+        let present = {
+            let _passphrase = EnvGuard::set("TELEGRAM_CACHE_PASSPHRASE", "hunter2");
+            AppConfig::load(&ConfigSource::default())
+        };
+        let _ = std::fs::remove_file(&temp_path);
+
+        assert_eq!(
+            missing.unwrap_err(),
+            ConfigError::MissingSecret("TELEGRAM_CACHE_PASSPHRASE")
+        );
+        assert_eq!(
+            present.unwrap().cache_passphrase,
+            Some("hunter2".to_string())
+        );
+    }
+
+    #[test]
+    fn cache_postgres_backend_reads_url() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+        let _url = EnvGuard::unset("TELEGRAM_CACHE_POSTGRES_URL");
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-cache-postgres.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
+        std::fs::write(
+            &temp_path,
+            "[telegram.cache]\n\
+backend = \"postgres\"\n\
+postgres_url = \"postgres://tg@localhost/cache\"\n",
+        )
+        .unwrap();
+
+        let result = AppConfig::load(&ConfigSource::default());
+        let _ = std::fs::remove_file(&temp_path);
+
+        let config = result.unwrap();
+        assert_eq!(config.cache_backend, CacheBackend::Postgres);
+        assert_eq!(
+            config.cache_postgres_url.as_deref(),
+            Some("postgres://tg@localhost/cache")
+        );
+    }
+
+    #[test]
+    fn invalid_cache_backend_returns_error() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+
+        let temp_path = std::env::temp_dir().join("telegram-llm-tui-cache-backend.toml");
+        let _config = EnvGuard::set("APP_CONFIG_PATH", temp_path.to_string_lossy().as_ref());
+        std::fs::write(&temp_path, "[telegram.cache]\nbackend = \"redis\"\n").unwrap();
+
+        let result = AppConfig::load(&ConfigSource::default());
+        let _ = std::fs::remove_file(&temp_path);
+
+        assert_eq!(
+            result.unwrap_err(),
+            ConfigError::InvalidCacheBackend("redis".to_string())
+        );
     }
 }
@@ -0,0 +1,132 @@
+//! Panic hook: gives the terminal back to the shell and saves a crash report
+//! with the backtrace and the end of the log, so a panic in the TUI leaves
+//! neither a broken terminal nor nothing to go on.
+
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crossterm::event::DisableBracketedPaste;
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, is_raw_mode_enabled, LeaveAlternateScreen};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// How much of the log goes into a crash report.
+const LOG_TAIL_LINES: usize = 200;
+
+/// Installs the hook; the default one still prints the panic message,
+/// after the terminal was restored.
+pub fn install_panic_hook(report_dir: PathBuf, log_file: PathBuf) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+        let backtrace = Backtrace::force_capture();
+        match write_report(&report_dir, &log_file, &info.to_string(), &backtrace) {
+            Ok(path) => eprintln!("A crash report was saved to {}", path.display()),
+            Err(err) => eprintln!(
+                "Failed to save a crash report in {}: {err}",
+                report_dir.display()
+            ),
+        }
+    }));
+}
+
+fn restore_terminal() {
+    if is_raw_mode_enabled().unwrap_or(false) {
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            io::stdout(),
+            DisableBracketedPaste,
+            LeaveAlternateScreen,
+            crossterm::cursor::Show
+        );
+    }
+}
+
+fn write_report(
+    dir: &Path,
+    log_file: &Path,
+    panic: &str,
+    backtrace: &Backtrace,
+) -> io::Result<PathBuf> {
+    let now = OffsetDateTime::now_utc();
+    let thread = std::thread::current();
+    let report = format_report(
+        now,
+        &format!("{panic} (thread {})", thread.name().unwrap_or("unnamed")),
+        &backtrace.to_string(),
+        &log_tail(log_file, LOG_TAIL_LINES),
+    );
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("crash-{}.txt", now.unix_timestamp()));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+fn format_report(now: OffsetDateTime, panic: &str, backtrace: &str, log_tail: &[String]) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "telegram-llm-tui {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        report,
+        "time: {}",
+        now.format(&Rfc3339)
+            .unwrap_or_else(|_| now.unix_timestamp().to_string())
+    );
+    let _ = writeln!(
+        report,
+        "os: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report, "\n{panic}\n\nbacktrace:\n{backtrace}");
+    let _ = writeln!(report, "last {} log lines:", log_tail.len());
+    for line in log_tail {
+        let _ = writeln!(report, "{line}");
+    }
+    report
+}
+
+/// The last `count` lines of the log; none when it cannot be read.
+fn log_tail(path: &Path, count: usize) -> Vec<String> {
+    let Ok(log) = std::fs::read(path) else {
+        return Vec::new();
+    };
+    let log = String::from_utf8_lossy(&log);
+    let lines: Vec<&str> = log.lines().collect();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_ends_with_the_tail_of_the_log() {
+        let path = std::env::temp_dir().join("telegram-llm-tui-crash.log");
+        let log: String = (0..300).map(|line| format!("line {line}\n")).collect();
+        std::fs::write(&path, log).unwrap();
+        let tail = log_tail(&path, LOG_TAIL_LINES);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(tail.len(), LOG_TAIL_LINES);
+        assert_eq!(tail[0], "line 100");
+
+        let report = format_report(
+            OffsetDateTime::UNIX_EPOCH,
+            "panicked at src/tui.rs:1:1: boom",
+            "0: main",
+            &tail,
+        );
+        assert!(report.starts_with(concat!("telegram-llm-tui ", env!("CARGO_PKG_VERSION"))));
+        assert!(report.contains("time: 1970-01-01T00:00:00Z"));
+        assert!(report.contains("panicked at src/tui.rs:1:1: boom\n\nbacktrace:\n0: main"));
+        assert!(report.contains("last 200 log lines:\nline 100\n"));
+        assert!(report.ends_with("line 299\n"));
+        assert!(log_tail(&path, LOG_TAIL_LINES).is_empty());
+    }
+}
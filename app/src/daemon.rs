@@ -0,0 +1,126 @@
+//! `daemon`: keeps the cache in sync with Telegram without the TUI, for
+//! running as a systemd service with `Type=notify`. Readiness is reported
+//! once the update pump runs, and each connection heartbeat from the pump
+//! pings the watchdog, so systemd restarts a daemon whose pump stalled.
+
+use telegram_llm_core::telegram::{CacheManager, ConnectionStatus, DomainEvent, TelegramBootstrap};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::config::AppConfig;
+use crate::shutdown::{
+    Shutdown, CACHE_FLUSH_TIMEOUT, CLIENT_TIMEOUT, EVENT_STREAM_TIMEOUT, TELEMETRY_TIMEOUT,
+};
+use crate::systemd::Notifier;
+use crate::telemetry::{self, Telemetry};
+use crate::{open_cache_store, sync_chat_flags};
+
+pub async fn run(config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let notifier = Notifier::from_env();
+    let cache_manager =
+        CacheManager::spawn(open_cache_store(config)?, config.cache_config()).await?;
+
+    let mut telegram_config = config.telegram_config();
+    if let Some(interval) = notifier.watchdog_interval() {
+        telegram_config.event_stream.heartbeat_interval = Some(interval);
+    }
+    let mut bootstrap = TelegramBootstrap::connect(telegram_config).await?;
+    // Nobody is there to answer a login prompt.
+    if !bootstrap.auth_flow().is_authorized().await? {
+        cache_manager.shutdown().await;
+        bootstrap.shutdown().await;
+        return Err("not logged in; run the `login` subcommand first".into());
+    }
+    sync_chat_flags(&bootstrap, &cache_manager).await;
+
+    info!("starting domain event stream");
+    let event_stream = bootstrap.spawn_event_stream(config.update_buffer)?;
+    let mut events = event_stream.subscribe();
+    notifier.ready();
+    notifier.status("connected");
+
+    // There is no send pipeline here, so only cache and event metrics go out.
+    let telemetry = Telemetry::start_configured(config.telemetry.as_ref()).await;
+    // Scoped so the collector lets go of the services before they stop.
+    let result = {
+        let mut collect_metrics = std::pin::pin!(async {
+            match &telemetry {
+                Some(telemetry) => telemetry.collect(&cache_manager, &event_stream, None).await,
+                None => std::future::pending().await,
+            }
+        });
+
+        let mut shutdown = std::pin::pin!(shutdown_requested());
+        loop {
+            tokio::select! {
+                () = &mut shutdown => break Ok(()),
+                () = &mut collect_metrics => break Ok(()),
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        let batch = match config.event_batch() {
+                            Some(batch) => events.collect_batch(event, batch).await.events,
+                            None => vec![event],
+                        };
+                        if events.take_gap() {
+                            warn!("domain events were dropped; cached chats may be stale");
+                        }
+                        for event in &batch {
+                            if let DomainEvent::ConnectionState(state) = event {
+                                notifier.watchdog();
+                                notifier.status(status_text(state.status));
+                            }
+                        }
+                        cache_manager.apply_events(&batch);
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break Err("the event stream closed".into()),
+                },
+            }
+        }
+    };
+
+    notifier.stopping();
+    // The cache flush writes out whatever the debounced flush has not saved
+    // yet.
+    Shutdown::default()
+        .stage(
+            "metrics exporter",
+            TELEMETRY_TIMEOUT,
+            telemetry::stop(telemetry),
+        )
+        .stage("event stream", EVENT_STREAM_TIMEOUT, event_stream.stop())
+        .stage("cache flush", CACHE_FLUSH_TIMEOUT, cache_manager.shutdown())
+        .stage("telegram client", CLIENT_TIMEOUT, bootstrap.shutdown())
+        .run()
+        .await;
+    result
+}
+
+/// SIGTERM, which is how systemd stops the service, or Ctrl+C.
+async fn shutdown_requested() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => info!("SIGTERM received; shutting down"),
+                    _ = tokio::signal::ctrl_c() => info!("shutdown requested"),
+                }
+                return;
+            }
+            Err(err) => warn!(error = %err, "cannot listen for SIGTERM"),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+    info!("shutdown requested");
+}
+
+fn status_text(status: ConnectionStatus) -> &'static str {
+    match status {
+        ConnectionStatus::Connected => "connected",
+        ConnectionStatus::Reconnecting { .. } => "reconnecting to Telegram",
+        ConnectionStatus::Offline { .. } => "offline; Telegram is unreachable",
+    }
+}
@@ -0,0 +1,150 @@
+//! Platform directories the default paths live in: the XDG base directories
+//! on Linux and other unixes, and their usual equivalents on macOS and
+//! Windows.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Subdirectory of each base directory that holds this app's files.
+const APP_DIR_NAME: &str = "telegram-llm-tui";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppDirs {
+    /// `app.toml`.
+    pub config: PathBuf,
+    /// The Telegram session.
+    pub state: PathBuf,
+    /// The cache database and downloads.
+    pub cache: PathBuf,
+    pub logs: PathBuf,
+}
+
+impl AppDirs {
+    /// Directories for this platform, or `None` when the home directory
+    /// cannot be told from the environment.
+    pub fn from_env() -> Option<Self> {
+        let env = |key: &str| std::env::var_os(key);
+        if cfg!(target_os = "macos") {
+            macos(env)
+        } else if cfg!(windows) {
+            windows(env)
+        } else {
+            xdg(env)
+        }
+    }
+}
+
+/// `$XDG_*_HOME` when set to an absolute path, else the spec's defaults
+/// under `$HOME`. Logs are state, as the spec suggests.
+fn xdg(env: impl Fn(&str) -> Option<OsString>) -> Option<AppDirs> {
+    let home = env("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from);
+    let base = |key: &str, fallback: &str| {
+        env(key)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| home.as_ref().map(|home| home.join(fallback)))
+            .map(|base| base.join(APP_DIR_NAME))
+    };
+    let state = base("XDG_STATE_HOME", ".local/state")?;
+    Some(AppDirs {
+        config: base("XDG_CONFIG_HOME", ".config")?,
+        cache: base("XDG_CACHE_HOME", ".cache")?,
+        logs: state.join("logs"),
+        state,
+    })
+}
+
+fn macos(env: impl Fn(&str) -> Option<OsString>) -> Option<AppDirs> {
+    let library = PathBuf::from(env("HOME").filter(|home| !home.is_empty())?).join("Library");
+    let support = library.join("Application Support").join(APP_DIR_NAME);
+    Some(AppDirs {
+        config: support.clone(),
+        state: support,
+        cache: library.join("Caches").join(APP_DIR_NAME),
+        logs: library.join("Logs").join(APP_DIR_NAME),
+    })
+}
+
+/// Roaming `%APPDATA%` for the config, machine-local `%LOCALAPPDATA%` for
+/// the rest.
+fn windows(env: impl Fn(&str) -> Option<OsString>) -> Option<AppDirs> {
+    let roaming = PathBuf::from(env("APPDATA").filter(|dir| !dir.is_empty())?);
+    let local =
+        PathBuf::from(env("LOCALAPPDATA").filter(|dir| !dir.is_empty())?).join(APP_DIR_NAME);
+    Some(AppDirs {
+        config: roaming.join(APP_DIR_NAME),
+        cache: local.join("cache"),
+        logs: local.join("logs"),
+        state: local,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<OsString> + 'a {
+        move |key| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| OsString::from(value))
+        }
+    }
+
+    #[test]
+    fn xdg_directories_fall_back_to_home() {
+        let dirs = xdg(env(&[
+            ("HOME", "/home/ada"),
+            ("XDG_CONFIG_HOME", "/etc/ada"),
+            ("XDG_CACHE_HOME", "relative/cache"),
+        ]))
+        .unwrap();
+
+        assert_eq!(dirs.config, PathBuf::from("/etc/ada/telegram-llm-tui"));
+        assert_eq!(
+            dirs.state,
+            PathBuf::from("/home/ada/.local/state/telegram-llm-tui")
+        );
+        assert_eq!(
+            dirs.cache,
+            PathBuf::from("/home/ada/.cache/telegram-llm-tui")
+        );
+        assert_eq!(dirs.logs, dirs.state.join("logs"));
+        assert_eq!(xdg(env(&[("XDG_CONFIG_HOME", "/etc/ada")])), None);
+    }
+
+    #[test]
+    fn macos_and_windows_use_their_library_and_app_data_folders() {
+        let mac = macos(env(&[("HOME", "/Users/ada")])).unwrap();
+        assert_eq!(
+            mac.config,
+            PathBuf::from("/Users/ada/Library/Application Support/telegram-llm-tui")
+        );
+        assert_eq!(
+            mac.cache,
+            PathBuf::from("/Users/ada/Library/Caches/telegram-llm-tui")
+        );
+        assert_eq!(
+            mac.logs,
+            PathBuf::from("/Users/ada/Library/Logs/telegram-llm-tui")
+        );
+
+        let win = windows(env(&[
+            ("APPDATA", "/Users/ada/AppData/Roaming"),
+            ("LOCALAPPDATA", "/Users/ada/AppData/Local"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            win.config,
+            PathBuf::from("/Users/ada/AppData/Roaming/telegram-llm-tui")
+        );
+        assert_eq!(
+            win.state,
+            PathBuf::from("/Users/ada/AppData/Local/telegram-llm-tui")
+        );
+        assert_eq!(win.cache, win.state.join("cache"));
+        assert_eq!(windows(env(&[("APPDATA", "C:/x")])), None);
+    }
+}
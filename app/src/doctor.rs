@@ -0,0 +1,294 @@
+//! `doctor`: checks the configuration, the files it points at and the
+//! services the app talks to, and says how to fix whatever is wrong.
+
+use std::fmt::Display;
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use telegram_llm_core::telegram::TelegramBootstrap;
+
+use crate::config::{config_path, AppConfig, CacheBackend, ConfigError, ConfigSource};
+
+/// How long each step of the Telegram probe may take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Skip,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Skip => "skip",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+        }
+    }
+}
+
+/// Findings, printed as they come in.
+#[derive(Debug, Default)]
+struct Report {
+    findings: Vec<Status>,
+}
+
+impl Report {
+    fn ok(&mut self, what: impl Display) {
+        self.record(Status::Ok, what, None::<&str>);
+    }
+
+    fn skip(&mut self, what: impl Display) {
+        self.record(Status::Skip, what, None::<&str>);
+    }
+
+    fn warn(&mut self, what: impl Display, fix: impl Display) {
+        self.record(Status::Warn, what, Some(fix));
+    }
+
+    fn fail(&mut self, what: impl Display, fix: impl Display) {
+        self.record(Status::Fail, what, Some(fix));
+    }
+
+    fn record(&mut self, status: Status, what: impl Display, fix: Option<impl Display>) {
+        println!("{:<6}{what}", status.label());
+        if let Some(fix) = fix {
+            println!("      fix: {fix}");
+        }
+        self.findings.push(status);
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.findings
+            .iter()
+            .filter(|found| **found == status)
+            .count()
+    }
+
+    fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        match (self.count(Status::Fail), self.count(Status::Warn)) {
+            (0, 0) => println!("Everything looks fine"),
+            (0, warnings) => println!("{warnings} warning(s), nothing failed"),
+            (failures, _) => return Err(format!("{failures} check(s) failed").into()),
+        }
+        Ok(())
+    }
+}
+
+pub async fn run(source: &ConfigSource) -> Result<(), Box<dyn std::error::Error>> {
+    let mut report = Report::default();
+    if let Some(config) = check_config(&mut report, source) {
+        check_file(&mut report, "session", &config.session_path);
+        match config.cache_backend {
+            CacheBackend::Sqlite => {
+                check_file(&mut report, "cache database", &config.cache_db_path);
+            }
+            CacheBackend::Postgres => report.skip("cache database is on postgres"),
+        }
+        check_telegram(&mut report, &config).await;
+    }
+    report.finish()
+}
+
+/// Loads the configuration the way every other subcommand does.
+fn check_config(report: &mut Report, source: &ConfigSource) -> Option<AppConfig> {
+    let path = match config_path(source) {
+        Ok(path) => path,
+        Err(err) => {
+            report.fail(
+                format!("cannot find the config file: {err}"),
+                "run from a directory that exists",
+            );
+            return None;
+        }
+    };
+    if path.exists() {
+        report.ok(format!("config file {}", path.display()));
+    } else {
+        report.warn(
+            format!("no config file at {}; using defaults", path.display()),
+            "copy app/config/app.toml there to change settings",
+        );
+    }
+    match AppConfig::load(source) {
+        Ok(config) => {
+            report.ok("configuration is valid");
+            Some(config)
+        }
+        Err(err) => {
+            report.fail(
+                format!("invalid configuration: {err}"),
+                config_fix(&err, &path),
+            );
+            None
+        }
+    }
+}
+
+fn config_fix(err: &ConfigError, path: &Path) -> String {
+    match err {
+        ConfigError::Missing(var) => format!("set {var} in the environment or in .env"),
+        ConfigError::MissingSecret(var) => {
+            format!("set {var} in the environment, or store it with `secrets set`")
+        }
+        ConfigError::InvalidProfile(_) => {
+            "use only letters, digits, `-` and `_` in profile names".to_string()
+        }
+        ConfigError::CurrentDir(_) => "run from a directory that exists".to_string(),
+        _ => format!(
+            "correct the setting in {}; app/config/app.toml lists the supported values",
+            path.display()
+        ),
+    }
+}
+
+/// Whether the app can create or update `path`, and that no other user can
+/// read it: the session grants full access to the account, and the cache
+/// holds message history.
+fn check_file(report: &mut Report, what: &str, path: &Path) {
+    let shown = path.display();
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().readonly() => report.fail(
+            format!("{what} {shown} is read-only"),
+            format!("chmod u+w {shown}"),
+        ),
+        Ok(metadata) => match shared_mode(&metadata) {
+            Some(mode) => report.warn(
+                format!("{what} {shown} is readable by other users (mode {mode:o})"),
+                format!("chmod 600 {shown}"),
+            ),
+            None => report.ok(format!("{what} {shown}")),
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            match path.ancestors().skip(1).find(|dir| dir.exists()) {
+                Some(dir) if !dir.is_dir() => report.fail(
+                    format!("cannot create {what} {shown}: {} is a file", dir.display()),
+                    "point the setting at a path inside a directory",
+                ),
+                Some(dir) if is_read_only(dir) => report.fail(
+                    format!(
+                        "cannot create {what} {shown}: {} is read-only",
+                        dir.display()
+                    ),
+                    "point the setting at a writable directory",
+                ),
+                _ => report.ok(format!("{what} {shown} will be created")),
+            }
+        }
+        Err(err) => report.fail(
+            format!("cannot read {what} {shown}: {err}"),
+            "check the permissions of the file and the directories above it",
+        ),
+    }
+}
+
+fn is_read_only(dir: &Path) -> bool {
+    std::fs::metadata(dir).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+/// Permission bits of a file that its group or others can access.
+#[cfg(unix)]
+fn shared_mode(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode() & 0o777;
+    (mode & 0o077 != 0).then_some(mode)
+}
+
+#[cfg(not(unix))]
+fn shared_mode(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+async fn check_telegram(report: &mut Report, config: &AppConfig) {
+    let started = Instant::now();
+    let connect = TelegramBootstrap::connect(config.telegram_config());
+    let bootstrap = match tokio::time::timeout(PROBE_TIMEOUT, connect).await {
+        Ok(Ok(bootstrap)) => bootstrap,
+        Ok(Err(err)) => {
+            report.fail(
+                format!("cannot connect to Telegram: {err}"),
+                "check the network, TELEGRAM_API_ID and TELEGRAM_API_HASH",
+            );
+            return;
+        }
+        Err(_) => {
+            report.fail(
+                format!(
+                    "no answer from Telegram within {}s",
+                    PROBE_TIMEOUT.as_secs()
+                ),
+                "check the network and any firewall or proxy in the way",
+            );
+            return;
+        }
+    };
+    report.ok(format!(
+        "connected to Telegram in {} ms",
+        started.elapsed().as_millis()
+    ));
+
+    let authorized =
+        tokio::time::timeout(PROBE_TIMEOUT, bootstrap.auth_flow().is_authorized()).await;
+    bootstrap.shutdown().await;
+    match authorized {
+        Ok(Ok(true)) => report.ok("session is logged in"),
+        Ok(Ok(false)) => report.warn("session is not logged in", "run `login`"),
+        Ok(Err(err)) => report.fail(
+            format!("cannot check the session: {err}"),
+            "run `logout`, then `login`, if the session was ended elsewhere",
+        ),
+        Err(_) => report.fail(
+            "no answer from Telegram while checking the session",
+            "try again, and check the network",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_files_are_fine_when_their_directory_is_writable() {
+        let dir = std::env::temp_dir().join("telegram-llm-tui-doctor-missing");
+        let mut report = Report::default();
+
+        check_file(&mut report, "session", &dir.join("nested/telegram.session"));
+        assert_eq!(report.findings, [Status::Ok]);
+        assert!(report.finish().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn warns_about_files_other_users_can_read() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join("telegram-llm-tui-doctor.session");
+        std::fs::write(&path, "").unwrap();
+        let mut report = Report::default();
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        check_file(&mut report, "session", &path);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        check_file(&mut report, "session", &path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(report.findings, [Status::Warn, Status::Ok]);
+        assert!(report.finish().is_ok());
+    }
+
+    #[test]
+    fn failures_fail_the_command() {
+        let mut report = Report::default();
+        report.fail("cannot connect to Telegram", "check the network");
+
+        assert!(report.finish().is_err());
+    }
+}
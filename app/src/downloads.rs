@@ -0,0 +1,99 @@
+//! Where downloaded attachments land and how they are handed to an external
+//! player.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use telegram_llm_core::telegram::{ChatId, DocumentFile};
+
+/// Opens files with the desktop's default application when no player is
+/// configured.
+#[cfg(target_os = "macos")]
+const SYSTEM_OPENER: &str = "open";
+#[cfg(not(target_os = "macos"))]
+const SYSTEM_OPENER: &str = "xdg-open";
+
+/// `dir/<chat>-<message>.<ext>`, with the extension taken from the MIME type.
+pub fn download_path(
+    dir: &Path,
+    chat_id: ChatId,
+    message_id: i64,
+    document: &DocumentFile,
+) -> PathBuf {
+    dir.join(format!(
+        "{}-{}.{}",
+        chat_id.0,
+        message_id,
+        extension(&document.mime_type)
+    ))
+}
+
+fn extension(mime_type: &str) -> &str {
+    let subtype = mime_type
+        .split(';')
+        .next()
+        .and_then(|essence| essence.split('/').nth(1))
+        .unwrap_or_default()
+        .trim();
+    match subtype {
+        "ogg" => "ogg",
+        "mpeg" => "mp3",
+        "mp4" => "mp4",
+        "quicktime" => "mov",
+        "x-matroska" => "mkv",
+        "webm" => "webm",
+        "pdf" => "pdf",
+        subtype if !subtype.is_empty() && subtype.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            subtype
+        }
+        _ => "bin",
+    }
+}
+
+/// Starts `player` (a command with optional arguments, such as
+/// `mpv --no-video`) on `path` without waiting for it, detached from the
+/// terminal the TUI draws on.
+pub fn open_in_player(player: Option<&str>, path: &Path) -> io::Result<()> {
+    let mut words = player.unwrap_or(SYSTEM_OPENER).split_whitespace();
+    let program = words.next().unwrap_or(SYSTEM_OPENER);
+    Command::new(program)
+        .args(words)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(mime_type: &str) -> DocumentFile {
+        DocumentFile {
+            id: 1,
+            access_hash: 2,
+            file_reference: Vec::new(),
+            dc_id: 2,
+            mime_type: mime_type.to_string(),
+            file_name: None,
+        }
+    }
+
+    #[test]
+    fn names_downloads_after_the_message_and_mime_type() {
+        let dir = Path::new("/tmp/downloads");
+        let path = |mime_type: &str| download_path(dir, ChatId(-100), 7, &document(mime_type));
+
+        assert_eq!(path("audio/ogg"), dir.join("-100-7.ogg"));
+        assert_eq!(path("audio/mpeg"), dir.join("-100-7.mp3"));
+        assert_eq!(
+            path("application/pdf; charset=binary"),
+            dir.join("-100-7.pdf")
+        );
+        assert_eq!(path("application/x-tar+gzip"), dir.join("-100-7.bin"));
+        assert_eq!(path(""), dir.join("-100-7.bin"));
+    }
+}
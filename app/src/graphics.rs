@@ -0,0 +1,303 @@
+//! Inline images through terminal graphics protocols: detecting which one the
+//! terminal speaks and encoding a preview for a cell area.
+
+use std::fmt::Write as _;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::RgbaImage;
+use ratatui::layout::Rect;
+
+/// Cell size assumed when the terminal does not report its pixel size.
+const DEFAULT_CELL_PX: (u32, u32) = (10, 20);
+/// Kitty takes base64 payloads in chunks of at most this many bytes.
+const KITTY_CHUNK: usize = 4096;
+/// Channel levels per color in the sixel palette, a 6x6x6 cube.
+const SIXEL_LEVELS: u32 = 6;
+/// Deletes every kitty image placement on screen. Kitty keeps placements
+/// until told otherwise, unlike the other protocols, whose pixels are
+/// covered by the next full redraw.
+pub const KITTY_CLEAR: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+}
+
+impl GraphicsProtocol {
+    /// Protocol of the current terminal, or `None` to fall back to text
+    /// placeholders.
+    pub fn detect() -> Option<Self> {
+        Self::detect_from(|name| std::env::var(name).ok())
+    }
+
+    fn detect_from(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        // Multiplexers swallow graphics escapes unless passthrough is set up.
+        if var("TMUX").is_some() || var("STY").is_some() {
+            return None;
+        }
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        if var("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || program == "ghostty" {
+            return Some(Self::Kitty);
+        }
+        if matches!(program.as_str(), "iTerm.app" | "WezTerm") {
+            return Some(Self::Iterm2);
+        }
+        if ["foot", "mlterm", "sixel"]
+            .iter()
+            .any(|name| term.contains(name))
+        {
+            return Some(Self::Sixel);
+        }
+        None
+    }
+}
+
+/// A decoded thumbnail, kept with its original bytes for protocols that
+/// take encoded files.
+#[derive(Debug, Clone)]
+pub struct Preview {
+    source: Vec<u8>,
+    image: RgbaImage,
+}
+
+impl Preview {
+    pub fn decode(source: Vec<u8>) -> image::ImageResult<Self> {
+        let image = image::load_from_memory(&source)?.to_rgba8();
+        Ok(Self { source, image })
+    }
+}
+
+/// Pixel size of one terminal cell, from the reported window size.
+pub fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            u32::from(size.width / size.columns),
+            u32::from(size.height / size.rows),
+        ),
+        _ => DEFAULT_CELL_PX,
+    }
+}
+
+/// Escape sequence drawing `preview` at the top left of `area`, scaled down
+/// to fit it with its aspect ratio kept. The cursor is restored afterwards.
+pub fn encode(
+    protocol: GraphicsProtocol,
+    preview: &Preview,
+    area: Rect,
+    cell_px: (u32, u32),
+) -> String {
+    let (cols, rows) = fit(
+        (preview.image.width(), preview.image.height()),
+        (u32::from(area.width), u32::from(area.height)),
+        cell_px,
+    );
+    let mut out = format!("\x1b7\x1b[{};{}H", area.y + 1, area.x + 1);
+    match protocol {
+        GraphicsProtocol::Kitty => {
+            let scaled = scale(&preview.image, cols, rows, cell_px);
+            out.push_str(&kitty(&scaled, cols, rows));
+        }
+        GraphicsProtocol::Iterm2 => out.push_str(&iterm2(&preview.source, cols, rows)),
+        GraphicsProtocol::Sixel => {
+            let scaled = scale(&preview.image, cols, rows, cell_px);
+            out.push_str(&sixel(&scaled));
+        }
+    }
+    out.push_str("\x1b8");
+    out
+}
+
+/// Columns and rows an image of `image_px` takes when shrunk to fit
+/// `max_cells`. Images are never enlarged.
+fn fit(image_px: (u32, u32), max_cells: (u32, u32), cell_px: (u32, u32)) -> (u32, u32) {
+    let (width, height) = (image_px.0.max(1) as f64, image_px.1.max(1) as f64);
+    let max_width = (max_cells.0 * cell_px.0) as f64;
+    let max_height = (max_cells.1 * cell_px.1) as f64;
+    let ratio = (max_width / width).min(max_height / height).min(1.0);
+    let cols = ((width * ratio) / cell_px.0 as f64).round() as u32;
+    let rows = ((height * ratio) / cell_px.1 as f64).round() as u32;
+    (
+        cols.clamp(1, max_cells.0.max(1)),
+        rows.clamp(1, max_cells.1.max(1)),
+    )
+}
+
+fn scale(image: &RgbaImage, cols: u32, rows: u32, cell_px: (u32, u32)) -> RgbaImage {
+    image::imageops::resize(
+        image,
+        cols * cell_px.0,
+        rows * cell_px.1,
+        FilterType::Triangle,
+    )
+}
+
+fn kitty(image: &RgbaImage, cols: u32, rows: u32) -> String {
+    let payload = STANDARD.encode(image.as_raw());
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(index + 1 < chunks.len());
+        // Chunks are base64, so always ASCII.
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if index == 0 {
+            let _ = write!(
+                out,
+                "\x1b_Ga=T,f=32,s={},v={},c={cols},r={rows},C=1,q=2,m={more};{chunk}\x1b\\",
+                image.width(),
+                image.height()
+            );
+        } else {
+            let _ = write!(out, "\x1b_Gm={more};{chunk}\x1b\\");
+        }
+    }
+    out
+}
+
+fn iterm2(source: &[u8], cols: u32, rows: u32) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={cols};height={rows};preserveAspectRatio=1:{}\x07",
+        source.len(),
+        STANDARD.encode(source)
+    )
+}
+
+/// Sixel data on a fixed color cube, which keeps encoding cheap at the cost
+/// of some banding.
+fn sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let mut out = format!("\x1bPq\"1;1;{width};{height}");
+    let colors = SIXEL_LEVELS.pow(3);
+    for color in 0..colors {
+        let level = |value: u32| value * 100 / (SIXEL_LEVELS - 1);
+        let (red, green, blue) = (
+            color / (SIXEL_LEVELS * SIXEL_LEVELS),
+            color / SIXEL_LEVELS % SIXEL_LEVELS,
+            color % SIXEL_LEVELS,
+        );
+        let _ = write!(
+            out,
+            "#{color};2;{};{};{}",
+            level(red),
+            level(green),
+            level(blue)
+        );
+    }
+    for band_top in (0..height).step_by(6) {
+        // Sixel bits of each column, per palette color used in the band.
+        let mut bands: Vec<(u32, Vec<u8>)> = Vec::new();
+        for y in band_top..(band_top + 6).min(height) {
+            for x in 0..width {
+                let pixel = image.get_pixel(x, y).0;
+                if pixel[3] < 128 {
+                    continue;
+                }
+                let color = palette_index(pixel);
+                let position = match bands.iter().position(|(used, _)| *used == color) {
+                    Some(position) => position,
+                    None => {
+                        bands.push((color, vec![0; width as usize]));
+                        bands.len() - 1
+                    }
+                };
+                bands[position].1[x as usize] |= 1 << (y - band_top);
+            }
+        }
+        for (index, (color, bits)) in bands.iter().enumerate() {
+            if index > 0 {
+                out.push('$');
+            }
+            let _ = write!(out, "#{color}");
+            push_sixel_runs(&mut out, bits);
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+fn palette_index(pixel: [u8; 4]) -> u32 {
+    let level = |value: u8| (u32::from(value) * (SIXEL_LEVELS - 1) + 127) / 255;
+    level(pixel[0]) * SIXEL_LEVELS * SIXEL_LEVELS + level(pixel[1]) * SIXEL_LEVELS + level(pixel[2])
+}
+
+/// Appends sixel characters, run-length encoding repeats.
+fn push_sixel_runs(out: &mut String, bits: &[u8]) {
+    let mut rest = bits;
+    while let Some(&first) = rest.first() {
+        let run = rest.iter().take_while(|bits| **bits == first).count();
+        let symbol = char::from(63 + first);
+        if run > 3 {
+            let _ = write!(out, "!{run}{symbol}");
+        } else {
+            out.extend(std::iter::repeat_n(symbol, run));
+        }
+        rest = &rest[run..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn detects_protocols_from_the_environment() {
+        let detect = |vars: &[(&str, &str)]| GraphicsProtocol::detect_from(env(vars));
+
+        assert_eq!(
+            detect(&[("TERM", "xterm-kitty")]),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            detect(&[("TERM_PROGRAM", "iTerm.app")]),
+            Some(GraphicsProtocol::Iterm2)
+        );
+        assert_eq!(detect(&[("TERM", "foot")]), Some(GraphicsProtocol::Sixel));
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), None);
+        assert_eq!(
+            detect(&[
+                ("TERM", "xterm-kitty"),
+                ("TMUX", "/tmp/tmux-1000/default,1,0")
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn fits_images_without_enlarging_them() {
+        assert_eq!(fit((320, 240), (40, 8), (10, 20)), (21, 8));
+        assert_eq!(fit((50, 40), (40, 8), (10, 20)), (5, 2));
+        assert_eq!(fit((4000, 10), (40, 8), (10, 20)), (40, 1));
+    }
+
+    #[test]
+    fn encodes_sixel_bands_with_run_lengths() {
+        let image = RgbaImage::from_pixel(8, 6, image::Rgba([255, 0, 0, 255]));
+        let encoded = sixel(&image);
+
+        assert!(encoded.starts_with("\x1bPq\"1;1;8;6"));
+        // Pure red is color 5 * 36 = 180; all six bits set in every column.
+        assert!(encoded.ends_with("#180!8~-\x1b\\"));
+    }
+
+    #[test]
+    fn kitty_payloads_are_chunked() {
+        let image = RgbaImage::from_pixel(40, 40, image::Rgba([0, 0, 0, 255]));
+        let encoded = kitty(&image, 4, 2);
+
+        assert!(encoded.starts_with("\x1b_Ga=T,f=32,s=40,v=40,c=4,r=2,C=1,q=2,m=1;"));
+        assert!(encoded.contains("\x1b_Gm=0;"));
+    }
+}
@@ -1,105 +1,232 @@
+mod cli;
+mod clipboard;
+mod commands;
 mod config;
+mod crash;
+mod daemon;
+mod dirs;
+mod doctor;
+mod downloads;
+mod graphics;
 mod prompt;
+mod reload;
+mod secrets;
+mod shutdown;
+mod systemd;
+mod telemetry;
+mod tui;
 mod ui_state;
 
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 
-use base64::engine::general_purpose::URL_SAFE_NO_PAD;
-use base64::Engine;
-use telegram_llm_core::telegram::{
-    AuthResult, CacheManager, QrLoginResult, SqliteCacheStore, TelegramBootstrap, TelegramConfig,
-};
-use time::{format_description, UtcOffset};
-use tokio::sync::broadcast::error::RecvError;
+use clap::Parser;
+use telegram_llm_core::telegram::{CacheManager, CacheStore, SqliteCacheStore, TelegramBootstrap};
+use time::{format_description, OffsetDateTime, UtcOffset};
 use tracing::{info, warn};
 use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload as filter_reload;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::Layer;
 
-use crate::config::{AppConfig, LogFormat, LogRotation};
-use crate::prompt::{prompt_line, prompt_secret, AuthMethod};
+use crate::cli::{Cli, Command, ConfigCommand, SecretsCommand};
+use crate::config::{AppConfig, CacheBackend, ConfigSource, LogFormat, LogRotation};
+use crate::graphics::GraphicsProtocol;
+use crate::reload::ConfigWatcher;
+use crate::shutdown::{
+    Shutdown, CACHE_FLUSH_TIMEOUT, CLIENT_TIMEOUT, EVENT_STREAM_TIMEOUT, SEND_PIPELINE_TIMEOUT,
+    TELEMETRY_TIMEOUT,
+};
+use crate::telemetry::Telemetry;
+use crate::tui::ChatClient;
 use crate::ui_state::UiCacheBridge;
 
+/// How long leftover blocking tasks may run once `async_main` returned.
+const RUNTIME_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .thread_stack_size(4 * 1024 * 1024)
         .build()?;
-    runtime.block_on(async_main())
+    let result = runtime.block_on(async_main());
+    // A blocking task that never returns would otherwise hold up the exit.
+    runtime.shutdown_timeout(RUNTIME_SHUTDOWN_TIMEOUT);
+    result
 }
 
 async fn async_main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
-    let config = AppConfig::from_env()?;
-    init_tracing(&config)?;
-    info!("loaded configuration");
+    let cli = Cli::parse();
+    let source = cli.config_source();
+    let command = cli.command.unwrap_or_default();
+    // Storing a secret and the doctor must work before the config is
+    // complete, and checking it must not create log files as a side effect.
+    match &command {
+        Command::Config(ConfigCommand::Check) => return commands::check_config(&source),
+        Command::Doctor => return doctor::run(&source).await,
+        Command::Secrets(SecretsCommand::Set(args)) => {
+            return commands::set_secret(&source, args.name.into())
+        }
+        _ => {}
+    }
 
-    let cache_store = Arc::new(SqliteCacheStore::new(config.cache_db_path.clone()));
+    let config = AppConfig::load(&source)?;
+    let logging = init_tracing(&config)?;
+    crash::install_panic_hook(
+        config.crash_report_dir.clone(),
+        config.log_file_path.clone(),
+    );
+    let console = &logging.console;
+    info!(profile = ?config.profile, "loaded configuration");
+
+    match command {
+        Command::Run => run(&config, &source, &logging).await,
+        Command::Login(args) => {
+            let method = args.method.map(Into::into).unwrap_or(config.auth_method);
+            commands::login(&config, method).await
+        }
+        Command::Logout => commands::logout(&config).await,
+        Command::Export(args) => {
+            console.set_enabled(false);
+            commands::export(&config, args).await
+        }
+        Command::Send(args) => {
+            console.set_enabled(false);
+            commands::send(&config, args).await
+        }
+        Command::Daemon => daemon::run(&config).await,
+        Command::Config(_) | Command::Doctor | Command::Secrets(_) => {
+            unreachable!("handled before logging starts")
+        }
+    }
+}
+
+/// The TUI: logs in if needed, then runs until the user quits.
+async fn run(
+    config: &AppConfig,
+    source: &ConfigSource,
+    logging: &Logging,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let console = &logging.console;
+    let cache_store = open_cache_store(config)?;
     let cache_manager = CacheManager::spawn(cache_store, config.cache_config()).await?;
     let mut ui_bridge = UiCacheBridge::new(None);
+    ui_bridge.state.layout.composer_max_lines = config.composer_max_lines;
+    ui_bridge.state.layout.chat_width = config.chat_width;
+    ui_bridge.state.layout.composer_min_lines = config.composer_min_lines;
+    ui_bridge.state.theme = config.theme;
     ui_bridge.refresh(&cache_manager);
 
-    let mut telegram_config = TelegramConfig::new(
-        config.api_id,
-        config.api_hash.clone(),
-        config.session_path.clone(),
-    );
-    telegram_config.send_pipeline = config.send_pipeline_config();
-
-    let mut bootstrap = TelegramBootstrap::connect(telegram_config).await?;
-    let auth_flow = bootstrap.auth_flow();
-
-    if !auth_flow.is_authorized().await? {
-        info!("authentication required");
-        let method = config.auth_method;
-        info!(method = ?method, "using default auth method");
-        match method {
-            AuthMethod::Phone => {
-                run_phone_login(&auth_flow, config.phone_number.as_deref()).await?
-            }
-            AuthMethod::Qr => run_qr_login(&auth_flow).await?,
-        }
-    } else {
-        info!("already authorized");
-    }
+    let mut bootstrap = TelegramBootstrap::connect(config.telegram_config()).await?;
+    commands::authorize(&bootstrap.auth_flow(), config, config.auth_method).await?;
+    sync_chat_flags(&bootstrap, &cache_manager).await;
+    ui_bridge.refresh(&cache_manager);
 
     info!("starting domain event stream");
     let event_stream = bootstrap.spawn_event_stream(config.update_buffer)?;
-    let mut event_rx = event_stream.subscribe();
-
-    tokio::select! {
-        _ = async {
-            loop {
-                match event_rx.recv().await {
-                    Ok(event) => {
-                        cache_manager.apply_event(&event);
-                        ui_bridge.refresh(&cache_manager);
-                        info!(?event, "received domain event");
-                    }
-                    Err(RecvError::Lagged(_)) => {
-                        continue;
-                    }
-                    Err(RecvError::Closed) => break,
-                }
+    let event_rx = event_stream.subscribe();
+    let send_pipeline = bootstrap
+        .spawn_send_pipeline()
+        .with_status_events(event_stream.publisher());
+    send_pipeline.follow_connectivity(event_stream.connectivity());
+
+    let telemetry = Telemetry::start_configured(config.telemetry.as_ref()).await;
+    let collect_metrics = async {
+        match &telemetry {
+            Some(telemetry) => {
+                telemetry
+                    .collect(&cache_manager, &event_stream, Some(&send_pipeline))
+                    .await
             }
-        } => {}
+            None => std::future::pending().await,
+        }
+    };
+
+    info!("starting terminal ui");
+    console.set_enabled(false);
+    let client = ChatClient {
+        cache: &cache_manager,
+        bridge: &mut ui_bridge,
+        telegram: &bootstrap,
+        send_pipeline: &send_pipeline,
+        keymap: config.keymap,
+        event_batch: config.event_batch(),
+        media: bootstrap.media_downloader(),
+        graphics: GraphicsProtocol::detect(),
+        download_dir: config.media_download_dir.clone(),
+        player: config.media_player.clone(),
+        sends: Default::default(),
+        config: ConfigWatcher::new(source.clone(), config.clone())?,
+        log_level: &logging.level,
+        log_content: config.log_content,
+    };
+    let result = tokio::select! {
+        result = tui::run(client, event_rx) => result,
+        () = collect_metrics => Ok(()),
         _ = tokio::signal::ctrl_c() => {
             info!("shutdown requested");
+            Ok(())
         }
+    };
+    console.set_enabled(true);
+    if let Err(err) = &result {
+        warn!(error = %err, "terminal ui failed");
     }
 
-    event_stream.stop().await;
-    cache_manager.shutdown().await;
-    bootstrap.shutdown().await;
-    info!("shutdown complete");
-    Ok(())
+    ui_bridge.save_draft(&cache_manager, OffsetDateTime::now_utc().unix_timestamp());
+    Shutdown::default()
+        .stage(
+            "metrics exporter",
+            TELEMETRY_TIMEOUT,
+            telemetry::stop(telemetry),
+        )
+        .stage("event stream", EVENT_STREAM_TIMEOUT, event_stream.stop())
+        .stage("send pipeline", SEND_PIPELINE_TIMEOUT, send_pipeline.stop())
+        .stage("cache flush", CACHE_FLUSH_TIMEOUT, cache_manager.shutdown())
+        .stage("telegram client", CLIENT_TIMEOUT, bootstrap.shutdown())
+        .run()
+        .await;
+    result.map_err(Into::into)
 }
 
-fn init_tracing(config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+/// Brings the pinned, muted and archived state of cached chats up to date;
+/// a failure leaves the cached flags for updates to correct.
+async fn sync_chat_flags(bootstrap: &TelegramBootstrap, cache_manager: &CacheManager) {
+    match bootstrap.sync_dialog_flags().await {
+        Ok(flags) => cache_manager.apply_event(&flags),
+        Err(err) => warn!(error = %err, "failed to sync chat flags"),
+    }
+}
+
+fn open_cache_store(config: &AppConfig) -> Result<Arc<dyn CacheStore>, Box<dyn std::error::Error>> {
+    match config.cache_backend {
+        CacheBackend::Sqlite => Ok(match &config.cache_passphrase {
+            Some(passphrase) => Arc::new(SqliteCacheStore::encrypted(
+                config.cache_db_path.clone(),
+                passphrase.clone(),
+            )),
+            None => Arc::new(SqliteCacheStore::new(config.cache_db_path.clone())),
+        }),
+        #[cfg(feature = "postgres")]
+        CacheBackend::Postgres => {
+            let url = config.cache_postgres_url.clone().unwrap_or_default();
+            Ok(Arc::new(
+                telegram_llm_core::telegram::PostgresCacheStore::new(url),
+            ))
+        }
+        #[cfg(not(feature = "postgres"))]
+        CacheBackend::Postgres => {
+            Err("the postgres cache backend requires building with `--features postgres`".into())
+        }
+    }
+}
+
+fn init_tracing(config: &AppConfig) -> Result<Logging, Box<dyn std::error::Error>> {
     ensure_parent_dir(&config.log_file_path)?;
     ensure_parent_dir(&config.error_log_path)?;
 
@@ -116,8 +243,23 @@ fn init_tracing(config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
         config.rotation_max_files,
     )?;
 
-    let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new(level_filter_directive(config.log_level)));
+    let console = ConsoleWriter::default();
+    let mut level = LogLevel::default();
+    let env_filter = EnvFilter::try_from_default_env().ok();
+    // `RUST_LOG` wins over the config file, also when that is reloaded.
+    let follows_config = env_filter.is_none();
+    let filter =
+        env_filter.unwrap_or_else(|| EnvFilter::new(level_filter_directive(config.log_level)));
+    let (stdout_filter, stdout_level) = filter_reload::Layer::new(filter.clone());
+    let (file_filter, file_level) = filter_reload::Layer::new(filter);
+    if follows_config {
+        level.reloads.push(Box::new(move |filter: EnvFilter| {
+            stdout_level.reload(filter)
+        }));
+        level
+            .reloads
+            .push(Box::new(move |filter: EnvFilter| file_level.reload(filter)));
+    }
     match config.log_format {
         LogFormat::Plain => {
             let stdout_timer = build_timer();
@@ -125,15 +267,16 @@ fn init_tracing(config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
             let error_timer = build_timer();
             let stdout_layer = tracing_subscriber::fmt::layer()
                 .compact()
+                .with_writer(console.clone())
                 .with_ansi(true)
                 .with_timer(stdout_timer)
-                .with_filter(filter.clone());
+                .with_filter(stdout_filter);
             let file_layer = tracing_subscriber::fmt::layer()
                 .compact()
                 .with_writer(log_writer)
                 .with_ansi(false)
                 .with_timer(file_timer)
-                .with_filter(filter);
+                .with_filter(file_filter);
             let error_layer = tracing_subscriber::fmt::layer()
                 .compact()
                 .with_writer(error_writer)
@@ -148,7 +291,7 @@ fn init_tracing(config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
                 .init();
         }
     }
-    Ok(())
+    Ok(Logging { console, level })
 }
 
 fn level_filter_directive(level: tracing_subscriber::filter::LevelFilter) -> &'static str {
@@ -201,6 +344,62 @@ fn build_log_writer(
     Ok(SharedWriter::new(writer))
 }
 
+struct Logging {
+    console: ConsoleWriter,
+    level: LogLevel,
+}
+
+type FilterReload = Box<dyn Fn(EnvFilter) -> Result<(), filter_reload::Error> + Send + Sync>;
+
+/// Level of the console and the log file, which a reloaded config can
+/// change. Does nothing when `RUST_LOG` set the filter.
+#[derive(Default)]
+pub struct LogLevel {
+    reloads: Vec<FilterReload>,
+}
+
+impl LogLevel {
+    pub fn set(&self, level: tracing_subscriber::filter::LevelFilter) {
+        for reload in &self.reloads {
+            if let Err(err) = reload(EnvFilter::new(level_filter_directive(level))) {
+                warn!(error = %err, "failed to change the log level");
+            }
+        }
+    }
+}
+
+/// Stdout log sink that goes quiet while the terminal ui owns the screen.
+#[derive(Clone)]
+struct ConsoleWriter {
+    enabled: Arc<AtomicBool>,
+}
+
+impl Default for ConsoleWriter {
+    fn default() -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
+
+impl ConsoleWriter {
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for ConsoleWriter {
+    type Writer = Box<dyn Write + 'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        if self.enabled.load(Ordering::Relaxed) {
+            Box::new(io::stdout())
+        } else {
+            Box::new(io::sink())
+        }
+    }
+}
+
 struct SharedWriter {
     inner: Arc<Mutex<Box<dyn Write + Send>>>,
 }
@@ -311,97 +510,3 @@ impl Write for RotatingFileWriter {
         self.file.flush()
     }
 }
-
-async fn run_phone_login(
-    auth_flow: &telegram_llm_core::telegram::AuthFlow<
-        telegram_llm_core::telegram::auth::GrammersAuthClient,
-    >,
-    default_phone: Option<&str>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let phone = match default_phone {
-        Some(phone) => phone.to_string(),
-        None => prompt_line("Phone number: ")?,
-    };
-    info!("requesting login code");
-    let login = auth_flow.begin_phone_login(phone.trim()).await?;
-
-    loop {
-        let code = prompt_line("Login code: ")?;
-        match auth_flow.submit_phone_code(&login, code.trim()).await? {
-            AuthResult::Authorized => {
-                info!("phone login authorized");
-                break;
-            }
-            AuthResult::PasswordRequired(token) => {
-                info!("2fa password required");
-                let password = prompt_secret("2fa password: ")?;
-                match auth_flow.submit_password(token, password.trim()).await? {
-                    AuthResult::Authorized => {
-                        info!("2fa authorized");
-                        break;
-                    }
-                    AuthResult::InvalidPassword => {
-                        warn!("invalid password, retry");
-                    }
-                    AuthResult::SignUpRequired => {
-                        warn!("sign up required, use official client");
-                        break;
-                    }
-                    AuthResult::InvalidCode | AuthResult::PasswordRequired(_) => {}
-                }
-            }
-            AuthResult::InvalidCode => {
-                warn!("invalid code, retry");
-            }
-            AuthResult::SignUpRequired => {
-                warn!("sign up required, use official client");
-                break;
-            }
-            AuthResult::InvalidPassword => {
-                warn!("invalid password, retry");
-            }
-        }
-    }
-
-    Ok(())
-}
-
-async fn run_qr_login(
-    auth_flow: &telegram_llm_core::telegram::AuthFlow<
-        telegram_llm_core::telegram::auth::GrammersAuthClient,
-    >,
-) -> Result<(), Box<dyn std::error::Error>> {
-    info!("requesting qr login token");
-    let mut pending = match auth_flow.begin_qr_login().await? {
-        QrLoginResult::Authorized => {
-            info!("qr login already authorized");
-            return Ok(());
-        }
-        QrLoginResult::Pending(login) => login,
-    };
-
-    loop {
-        let url = format!(
-            "tg://login?token={}",
-            URL_SAFE_NO_PAD.encode(&pending.token)
-        );
-        println!("Scan QR code from this URL: {url}");
-        info!("waiting for qr approval");
-
-        loop {
-            tokio::time::sleep(Duration::from_secs(2)).await;
-            match auth_flow.poll_qr_login(&pending).await? {
-                QrLoginResult::Authorized => {
-                    info!("qr login authorized");
-                    return Ok(());
-                }
-                QrLoginResult::Pending(login) => {
-                    if login.token != pending.token || login.dc_id != pending.dc_id {
-                        pending = login;
-                        break;
-                    }
-                }
-            }
-        }
-    }
-}
@@ -0,0 +1,150 @@
+//! Hot reload of the config file while the TUI runs. The log level, theme,
+//! keymap and send retry tuning apply right away; anything else that changed
+//! waits for a restart, so the Telegram connection is never touched.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::{config_path, AppConfig, ConfigError, ConfigSource};
+
+/// Polls the modification time of the config file.
+pub struct ConfigWatcher {
+    source: ConfigSource,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    config: AppConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChanges {
+    pub config: AppConfig,
+    /// The settings that changed and apply right away.
+    pub applied: Vec<&'static str>,
+    /// Whether anything else changed.
+    pub needs_restart: bool,
+}
+
+impl ConfigWatcher {
+    /// Watches the config file `source` points at; `config` is what was
+    /// loaded from it.
+    pub fn new(source: ConfigSource, config: AppConfig) -> Result<Self, ConfigError> {
+        let path = config_path(&source)?;
+        Ok(Self {
+            modified: modified_at(&path),
+            source,
+            path,
+            config,
+        })
+    }
+
+    /// Reloads the config if the file changed since the last call.
+    pub fn poll(&mut self) -> Option<Result<ConfigChanges, ConfigError>> {
+        let modified = modified_at(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let config = match AppConfig::load(&self.source) {
+            Ok(config) => config,
+            Err(err) => return Some(Err(err)),
+        };
+        let changes = compare(&self.config, config);
+        self.config = changes.config.clone();
+        Some(Ok(changes))
+    }
+}
+
+impl ConfigChanges {
+    /// Status bar text, or `None` when nothing changed.
+    pub fn summary(&self) -> Option<String> {
+        let restart = "changes to other settings apply after a restart";
+        match (self.applied.is_empty(), self.needs_restart) {
+            (true, false) => None,
+            (true, true) => Some(format!("Config file changed; {restart}")),
+            (false, false) => Some(format!("Reloaded {}", self.applied.join(", "))),
+            (false, true) => Some(format!("Reloaded {}; {restart}", self.applied.join(", "))),
+        }
+    }
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn compare(old: &AppConfig, new: AppConfig) -> ConfigChanges {
+    let mut applied = Vec::new();
+    if new.log_level != old.log_level {
+        applied.push("log level");
+    }
+    if new.theme != old.theme {
+        applied.push("theme");
+    }
+    if new.keymap != old.keymap {
+        applied.push("keymap");
+    }
+    if (
+        new.send_retry_max_attempts,
+        new.send_retry_base_delay_ms,
+        new.send_retry_max_delay_ms,
+        new.send_dead_letter_limit,
+    ) != (
+        old.send_retry_max_attempts,
+        old.send_retry_base_delay_ms,
+        old.send_retry_max_delay_ms,
+        old.send_dead_letter_limit,
+    ) {
+        applied.push("send retries");
+    }
+    let rest = AppConfig {
+        log_level: old.log_level,
+        theme: old.theme,
+        keymap: old.keymap,
+        send_retry_max_attempts: old.send_retry_max_attempts,
+        send_retry_base_delay_ms: old.send_retry_base_delay_ms,
+        send_retry_max_delay_ms: old.send_retry_max_delay_ms,
+        send_dead_letter_limit: old.send_dead_letter_limit,
+        ..new.clone()
+    };
+    ConfigChanges {
+        needs_restart: rest != *old,
+        config: new,
+        applied,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::{env_lock, set_required_env};
+    use ratatui::style::Color;
+    use tracing_subscriber::filter::LevelFilter;
+
+    #[test]
+    fn sorts_changes_into_reloaded_and_restart_only() {
+        let _lock = env_lock().lock().unwrap();
+        let (_id, _hash) = set_required_env();
+        let config = AppConfig::load(&ConfigSource::default()).unwrap();
+
+        let mut tuned = config.clone();
+        tuned.log_level = LevelFilter::TRACE;
+        tuned.theme.focused_border = Color::Magenta;
+        tuned.send_retry_max_attempts = Some(3);
+        let changes = compare(&config, tuned);
+        assert_eq!(changes.applied, ["log level", "theme", "send retries"]);
+        assert!(!changes.needs_restart);
+        assert_eq!(
+            changes.summary().as_deref(),
+            Some("Reloaded log level, theme, send retries")
+        );
+
+        let mut moved = config.clone();
+        moved.update_buffer += 1;
+        let changes = compare(&config, moved);
+        assert!(changes.applied.is_empty());
+        assert!(changes.needs_restart);
+
+        assert_eq!(compare(&config, config.clone()).summary(), None);
+    }
+}
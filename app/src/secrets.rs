@@ -0,0 +1,120 @@
+//! Secrets kept in the OS keyring (Keychain, Credential Manager or the
+//! Secret Service) so they need not live in `.env`. A non-empty environment
+//! variable still takes precedence over the stored value.
+//!
+//! Without the `keyring` feature only the environment is read.
+
+#[cfg(feature = "keyring")]
+use keyring::Entry;
+
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "telegram-llm-tui";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret {
+    ApiHash,
+    TwoFactorPassword,
+    CachePassphrase,
+    LlmApiKey,
+}
+
+impl Secret {
+    /// The environment variable that overrides the stored value.
+    pub fn env_var(self) -> &'static str {
+        match self {
+            Secret::ApiHash => "TELEGRAM_API_HASH",
+            Secret::TwoFactorPassword => "TELEGRAM_2FA_PASSWORD",
+            Secret::CachePassphrase => "TELEGRAM_CACHE_PASSPHRASE",
+            Secret::LlmApiKey => "LLM_API_KEY",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Secret::ApiHash => "Telegram API hash",
+            Secret::TwoFactorPassword => "2fa password",
+            Secret::CachePassphrase => "cache passphrase",
+            Secret::LlmApiKey => "LLM API key",
+        }
+    }
+
+    /// Keyring account name; profiles get their own entries.
+    #[cfg_attr(not(feature = "keyring"), allow(dead_code))]
+    fn account(self, profile: Option<&str>) -> String {
+        let name = match self {
+            Secret::ApiHash => "api-hash",
+            Secret::TwoFactorPassword => "2fa-password",
+            Secret::CachePassphrase => "cache-passphrase",
+            Secret::LlmApiKey => "llm-api-key",
+        };
+        match profile {
+            Some(profile) => format!("{profile}/{name}"),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// The secret from the environment, else from the keyring. An unreadable
+/// keyring counts as having no entry.
+pub fn lookup(secret: Secret, profile: Option<&str>) -> Option<String> {
+    std::env::var(secret.env_var())
+        .ok()
+        .filter(|value| !value.is_empty())
+        .or_else(|| stored(secret, profile))
+}
+
+#[cfg(feature = "keyring")]
+pub fn store(
+    secret: Secret,
+    profile: Option<&str>,
+    value: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(entry(secret, profile)?.set_password(value)?)
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn store(
+    secret: Secret,
+    _profile: Option<&str>,
+    _value: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err(format!(
+        "storing secrets requires building with the `keyring` feature; set {} instead",
+        secret.env_var()
+    )
+    .into())
+}
+
+#[cfg(feature = "keyring")]
+fn entry(secret: Secret, profile: Option<&str>) -> keyring::Result<Entry> {
+    Entry::new(KEYRING_SERVICE, &secret.account(profile))
+}
+
+#[cfg(all(feature = "keyring", not(test)))]
+fn stored(secret: Secret, profile: Option<&str>) -> Option<String> {
+    entry(secret, profile)
+        .and_then(|entry| entry.get_password())
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+/// Tests never read the user's keyring, and builds without the feature
+/// have none.
+#[cfg(any(test, not(feature = "keyring")))]
+fn stored(_secret: Secret, _profile: Option<&str>) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_get_their_own_keyring_entries() {
+        assert_eq!(Secret::ApiHash.account(None), "api-hash");
+        assert_eq!(
+            Secret::TwoFactorPassword.account(Some("work")),
+            "work/2fa-password"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Stops the running components one after another, each within its own
+//! timeout, so one that hangs cannot keep the rest from shutting down.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use tracing::{error, info, warn};
+
+pub const TELEMETRY_TIMEOUT: Duration = Duration::from_secs(2);
+pub const EVENT_STREAM_TIMEOUT: Duration = Duration::from_secs(3);
+pub const SEND_PIPELINE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Writing out the cache can take a while on a slow disk.
+pub const CACHE_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Added to the sum of the stage timeouts before the process is killed; a
+/// stage that blocks its thread is never interrupted by its timeout.
+const FORCED_EXIT_GRACE: Duration = Duration::from_secs(5);
+
+struct Stage<'a> {
+    name: &'static str,
+    timeout: Duration,
+    stop: Pin<Box<dyn Future<Output = ()> + 'a>>,
+}
+
+/// Stages run in the order they were added.
+#[derive(Default)]
+pub struct Shutdown<'a> {
+    stages: Vec<Stage<'a>>,
+}
+
+impl<'a> Shutdown<'a> {
+    pub fn stage(
+        mut self,
+        name: &'static str,
+        timeout: Duration,
+        stop: impl Future<Output = ()> + 'a,
+    ) -> Self {
+        self.stages.push(Stage {
+            name,
+            timeout,
+            stop: Box::pin(stop),
+        });
+        self
+    }
+
+    /// Runs every stage, giving up on those that time out, and returns the
+    /// names of those. Exits the process if the whole shutdown hangs.
+    pub async fn run(self) -> Vec<&'static str> {
+        let deadline = self
+            .stages
+            .iter()
+            .map(|stage| stage.timeout)
+            .sum::<Duration>()
+            + FORCED_EXIT_GRACE;
+        let (done, finished) = mpsc::channel::<()>();
+        std::thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(deadline) {
+                error!(after_secs = deadline.as_secs(), "shutdown hung; exiting");
+                eprintln!("Shutdown hung for {}s; exiting", deadline.as_secs());
+                std::process::exit(1);
+            }
+        });
+
+        let mut timed_out = Vec::new();
+        for stage in self.stages {
+            let started = Instant::now();
+            match tokio::time::timeout(stage.timeout, stage.stop).await {
+                Ok(()) => info!(
+                    stage = stage.name,
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    "stopped"
+                ),
+                Err(_) => {
+                    warn!(
+                        stage = stage.name,
+                        timeout_secs = stage.timeout.as_secs(),
+                        "timed out while stopping"
+                    );
+                    timed_out.push(stage.name);
+                }
+            }
+        }
+        drop(done);
+
+        if timed_out.is_empty() {
+            info!("shutdown complete");
+        } else {
+            warn!(?timed_out, "shutdown complete; some stages timed out");
+        }
+        timed_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[tokio::test]
+    async fn later_stages_still_run_after_one_times_out() {
+        let stopped = RefCell::new(Vec::new());
+        let timed_out = Shutdown::default()
+            .stage("event stream", Duration::from_millis(10), async {
+                stopped.borrow_mut().push("event stream");
+            })
+            .stage(
+                "send pipeline",
+                Duration::from_millis(10),
+                std::future::pending(),
+            )
+            .stage("cache flush", Duration::from_millis(10), async {
+                stopped.borrow_mut().push("cache flush");
+            })
+            .run()
+            .await;
+
+        assert_eq!(timed_out, ["send pipeline"]);
+        assert_eq!(*stopped.borrow(), ["event stream", "cache flush"]);
+    }
+}
@@ -0,0 +1,136 @@
+//! The systemd notify protocol: readiness, watchdog pings and status text,
+//! sent as datagrams to `$NOTIFY_SOCKET`. Does nothing when not started by
+//! systemd with `Type=notify`.
+
+use std::ffi::OsString;
+use std::time::Duration;
+
+use tracing::warn;
+
+#[derive(Debug, Default)]
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: Option<std::os::unix::net::UnixDatagram>,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    pub fn from_env() -> Self {
+        from_vars(|key| std::env::var_os(key), std::process::id())
+    }
+
+    /// How often to ping the watchdog: half of `WatchdogSec=`, as systemd
+    /// recommends. `None` when the watchdog is off.
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog.map(|timeout| timeout / 2)
+    }
+
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    pub fn watchdog(&self) {
+        if self.watchdog.is_some() {
+            self.send("WATCHDOG=1");
+        }
+    }
+
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    /// One line shown by `systemctl status`.
+    pub fn status(&self, text: &str) {
+        self.send(&format!("STATUS={text}"));
+    }
+
+    #[cfg(unix)]
+    fn send(&self, state: &str) {
+        if let Some(socket) = &self.socket {
+            if let Err(err) = socket.send(state.as_bytes()) {
+                warn!(error = %err, state, "failed to notify systemd");
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self, _state: &str) {}
+}
+
+/// `WATCHDOG_USEC` only counts when `WATCHDOG_PID` is unset or names `pid`.
+fn from_vars(env: impl Fn(&str) -> Option<OsString>, pid: u32) -> Notifier {
+    let watchdog = env("WATCHDOG_USEC")
+        .and_then(|usec| usec.to_str()?.parse::<u64>().ok())
+        .filter(|usec| *usec > 0)
+        .filter(|_| match env("WATCHDOG_PID") {
+            Some(owner) => owner.to_str() == Some(pid.to_string().as_str()),
+            None => true,
+        })
+        .map(Duration::from_micros);
+    Notifier {
+        #[cfg(unix)]
+        socket: env("NOTIFY_SOCKET")
+            .filter(|path| !path.is_empty())
+            .and_then(|path| match connect(&path) {
+                Ok(socket) => Some(socket),
+                Err(err) => {
+                    warn!(error = %err, "cannot reach the systemd notify socket");
+                    None
+                }
+            }),
+        watchdog,
+    }
+}
+
+/// A leading `@` names a socket in the abstract namespace.
+#[cfg(unix)]
+fn connect(path: &std::ffi::OsStr) -> std::io::Result<std::os::unix::net::UnixDatagram> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    match path.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.connect_addr(&addr)?;
+        }
+        _ => socket.connect(path)?,
+    }
+    Ok(socket)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn sends_states_to_the_notify_socket() {
+        let path = std::env::temp_dir().join("telegram-llm-tui-notify.sock");
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        let vars = |key: &str| match key {
+            "NOTIFY_SOCKET" => Some(path.clone().into_os_string()),
+            "WATCHDOG_USEC" => Some("30000000".into()),
+            "WATCHDOG_PID" => Some("42".into()),
+            _ => None,
+        };
+
+        let notifier = from_vars(vars, 42);
+        assert_eq!(notifier.watchdog_interval(), Some(Duration::from_secs(15)));
+        notifier.ready();
+        notifier.watchdog();
+        let mut received = Vec::new();
+        let mut buf = [0; 64];
+        for _ in 0..2 {
+            let len = systemd.recv(&mut buf).unwrap();
+            received.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(received, ["READY=1", "WATCHDOG=1"]);
+
+        assert_eq!(from_vars(vars, 7).watchdog_interval(), None);
+    }
+}
@@ -0,0 +1,608 @@
+//! Optional metrics export: samples the cache, event stream and send
+//! pipeline on an interval, then serves the samples to Prometheus or pushes
+//! them to an OTLP collector.
+
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use telegram_llm_core::telegram::{
+    CacheManager, CacheMetrics, EventStream, EventStreamMetrics, SendPipeline, SendPipelineMetrics,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::{MetricsExporter, TelemetryConfig};
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+const OTLP_DEFAULT_PORT: u16 = 4318;
+const OTLP_DEFAULT_PATH: &str = "/v1/metrics";
+/// Longest a push to the collector may take before it is abandoned.
+const OTLP_TIMEOUT: Duration = Duration::from_secs(10);
+const SERVICE_NAME: &str = "telegram-llm-tui";
+
+/// A plain `http://host[:port][/path]` OTLP/HTTP endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtlpEndpoint {
+    /// IPv6 addresses are kept without their brackets.
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl OtlpEndpoint {
+    /// `None` unless `raw` is an `http://` URL with a host; the port
+    /// defaults to 4318 and the path to `/v1/metrics`.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let rest = raw.trim().strip_prefix("http://")?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, OTLP_DEFAULT_PATH),
+        };
+        let (host, port) = match authority.strip_prefix('[') {
+            // An IPv6 address, whose colons are not the port separator.
+            Some(bracketed) => match bracketed.split_once(']')? {
+                (host, "") => (host, None),
+                (host, rest) => (host, Some(rest.strip_prefix(':')?)),
+            },
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port.parse().ok()?,
+            None => OTLP_DEFAULT_PORT,
+        };
+        if host.is_empty() {
+            return None;
+        }
+        Some(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// `host:port` for the `Host` header, with IPv6 addresses in brackets.
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// A running exporter, fed by [`Telemetry::collect`].
+pub struct Telemetry {
+    samples: watch::Sender<Vec<Sample>>,
+    interval: Duration,
+    exporter: JoinHandle<()>,
+}
+
+impl Telemetry {
+    /// Binds the Prometheus listener or starts pushing to the collector.
+    pub async fn start(config: &TelemetryConfig) -> io::Result<Self> {
+        let (samples, receiver) = watch::channel(Vec::new());
+        let exporter = match &config.exporter {
+            MetricsExporter::Prometheus { listen } => {
+                let listener = TcpListener::bind(listen).await?;
+                info!(listen = %listen, "serving prometheus metrics");
+                tokio::spawn(serve_prometheus(listener, receiver))
+            }
+            MetricsExporter::Otlp { endpoint } => {
+                info!(
+                    host = %endpoint.host,
+                    port = endpoint.port,
+                    "pushing otlp metrics"
+                );
+                tokio::spawn(push_otlp(endpoint.clone(), receiver, SystemTime::now()))
+            }
+        };
+        Ok(Self {
+            samples,
+            interval: config.interval,
+            exporter,
+        })
+    }
+
+    /// Starts the configured exporter, if any. A failure is logged and leaves
+    /// metrics off rather than keeping the client from starting.
+    pub async fn start_configured(config: Option<&TelemetryConfig>) -> Option<Self> {
+        match Self::start(config?).await {
+            Ok(telemetry) => Some(telemetry),
+            Err(err) => {
+                warn!(error = %err, "metrics exporter failed to start");
+                None
+            }
+        }
+    }
+
+    /// Samples the services every interval; never returns, so it is meant to
+    /// be raced against the TUI or the daemon loop. The daemon has no send
+    /// pipeline, so its send metrics are left out.
+    pub async fn collect(
+        &self,
+        cache: &CacheManager,
+        events: &EventStream,
+        sends: Option<&SendPipeline>,
+    ) {
+        let mut ticks = tokio::time::interval(self.interval);
+        loop {
+            ticks.tick().await;
+            self.samples.send_replace(samples(
+                cache.metrics(),
+                &events.metrics(),
+                sends.map(SendPipeline::metrics),
+            ));
+        }
+    }
+
+    pub async fn stop(self) {
+        self.exporter.abort();
+        let _ = self.exporter.await;
+    }
+}
+
+/// The shutdown stage for an exporter that may not be running.
+pub async fn stop(telemetry: Option<Telemetry>) {
+    if let Some(telemetry) = telemetry {
+        telemetry.stop().await;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricKind {
+    Gauge,
+    Counter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Sample {
+    name: &'static str,
+    help: &'static str,
+    kind: MetricKind,
+    label: Option<(&'static str, String)>,
+    value: f64,
+}
+
+impl Sample {
+    fn gauge(name: &'static str, help: &'static str, value: f64) -> Self {
+        Self {
+            name,
+            help,
+            kind: MetricKind::Gauge,
+            label: None,
+            value,
+        }
+    }
+
+    fn counter(name: &'static str, help: &'static str, value: u64) -> Self {
+        Self {
+            name,
+            help,
+            kind: MetricKind::Counter,
+            label: None,
+            value: value as f64,
+        }
+    }
+}
+
+/// One sample per metric, except the subscriber backlog, which has one per
+/// subscriber. Samples of a metric are adjacent.
+fn samples(
+    cache: CacheMetrics,
+    events: &EventStreamMetrics,
+    sends: Option<SendPipelineMetrics>,
+) -> Vec<Sample> {
+    let mut samples = vec![
+        Sample::gauge(
+            "tgtui_cache_chats",
+            "Chats resident in the cache.",
+            cache.chats as f64,
+        ),
+        Sample::gauge(
+            "tgtui_cache_messages",
+            "Messages resident in the cache.",
+            cache.messages as f64,
+        ),
+        Sample::gauge(
+            "tgtui_cache_bytes",
+            "Estimated size of the resident cache in bytes.",
+            cache.bytes as f64,
+        ),
+        Sample::gauge(
+            "tgtui_cache_pending_sends",
+            "Local echoes waiting for a send result.",
+            cache.pending_sends as f64,
+        ),
+        Sample::counter(
+            "tgtui_cache_flushes_total",
+            "Cache flushes written to the store.",
+            cache.flushes,
+        ),
+        Sample::counter(
+            "tgtui_cache_flush_failures_total",
+            "Cache flushes that failed and were requeued.",
+            cache.flush_failures,
+        ),
+        Sample::counter(
+            "tgtui_updates_received_total",
+            "Telegram updates received by the event pump.",
+            events.updates_received,
+        ),
+        Sample::counter(
+            "tgtui_events_mapped_total",
+            "Domain events mapped from Telegram updates.",
+            events.events_mapped,
+        ),
+        Sample::counter(
+            "tgtui_updates_unsupported_total",
+            "Telegram updates without a domain event.",
+            events.updates_unsupported,
+        ),
+        Sample::gauge(
+            "tgtui_update_mapping_seconds_avg",
+            "Average time to map an update.",
+            events.mapping_latency_avg.as_secs_f64(),
+        ),
+        Sample::gauge(
+            "tgtui_update_mapping_seconds_max",
+            "Longest time to map an update.",
+            events.mapping_latency_max.as_secs_f64(),
+        ),
+    ];
+    samples.extend(
+        events
+            .subscriber_backlog
+            .iter()
+            .enumerate()
+            .map(|(index, backlog)| Sample {
+                label: Some(("subscriber", index.to_string())),
+                ..Sample::gauge(
+                    "tgtui_event_subscriber_backlog",
+                    "Events waiting for each subscriber.",
+                    *backlog as f64,
+                )
+            }),
+    );
+    let Some(sends) = sends else {
+        return samples;
+    };
+    samples.extend([
+        Sample::gauge(
+            "tgtui_send_queued",
+            "Send requests queued or in flight.",
+            sends.queued as f64,
+        ),
+        Sample::gauge(
+            "tgtui_send_dead_letters",
+            "Failed sends kept for retry.",
+            sends.dead_letters as f64,
+        ),
+        Sample::counter(
+            "tgtui_send_attempts_total",
+            "Send attempts, retries included.",
+            sends.attempts,
+        ),
+        Sample::counter(
+            "tgtui_sends_sent_total",
+            "Send requests Telegram accepted.",
+            sends.sent,
+        ),
+        Sample::counter(
+            "tgtui_sends_failed_total",
+            "Send requests that failed for good.",
+            sends.failed,
+        ),
+    ]);
+    samples
+}
+
+/// The Prometheus text exposition format.
+fn render_prometheus(samples: &[Sample]) -> String {
+    let mut out = String::new();
+    for metric in samples.chunk_by(|a, b| a.name == b.name) {
+        let first = &metric[0];
+        let kind = match first.kind {
+            MetricKind::Gauge => "gauge",
+            MetricKind::Counter => "counter",
+        };
+        out.push_str(&format!("# HELP {} {}\n", first.name, first.help));
+        out.push_str(&format!("# TYPE {} {kind}\n", first.name));
+        for sample in metric {
+            match &sample.label {
+                Some((key, value)) => out.push_str(&format!(
+                    "{}{{{key}=\"{value}\"}} {}\n",
+                    sample.name, sample.value
+                )),
+                None => out.push_str(&format!("{} {}\n", sample.name, sample.value)),
+            }
+        }
+    }
+    out
+}
+
+/// An OTLP/HTTP JSON `ExportMetricsServiceRequest`; counters are cumulative
+/// sums since `start`.
+fn render_otlp(samples: &[Sample], start: SystemTime, now: SystemTime) -> Value {
+    let start = unix_nanos(start);
+    let now = unix_nanos(now);
+    let metrics = samples
+        .chunk_by(|a, b| a.name == b.name)
+        .map(|metric| {
+            let first = &metric[0];
+            let points = metric
+                .iter()
+                .map(|sample| {
+                    let attributes = sample
+                        .label
+                        .iter()
+                        .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+                        .collect::<Vec<_>>();
+                    json!({
+                        "asDouble": sample.value,
+                        "startTimeUnixNano": start,
+                        "timeUnixNano": now,
+                        "attributes": attributes,
+                    })
+                })
+                .collect::<Vec<_>>();
+            match first.kind {
+                MetricKind::Gauge => json!({
+                    "name": first.name,
+                    "description": first.help,
+                    "gauge": {"dataPoints": points},
+                }),
+                MetricKind::Counter => json!({
+                    "name": first.name,
+                    "description": first.help,
+                    "sum": {
+                        "dataPoints": points,
+                        // AGGREGATION_TEMPORALITY_CUMULATIVE
+                        "aggregationTemporality": 2,
+                        "isMonotonic": true,
+                    },
+                }),
+            }
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "resourceMetrics": [{
+            "resource": {
+                "attributes": [{"key": "service.name", "value": {"stringValue": SERVICE_NAME}}],
+            },
+            "scopeMetrics": [{
+                "scope": {"name": SERVICE_NAME},
+                "metrics": metrics,
+            }],
+        }],
+    })
+}
+
+/// OTLP encodes 64-bit integers as JSON strings.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+async fn serve_prometheus(listener: TcpListener, samples: watch::Receiver<Vec<Sample>>) {
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                warn!(error = %err, "metrics listener failed to accept");
+                continue;
+            }
+        };
+        let samples = samples.clone();
+        tokio::spawn(async move {
+            if let Err(err) = answer_scrape(&mut stream, &samples).await {
+                warn!(peer = %peer, error = %err, "metrics scrape failed");
+            }
+        });
+    }
+}
+
+async fn answer_scrape(
+    stream: &mut TcpStream,
+    samples: &watch::Receiver<Vec<Sample>>,
+) -> io::Result<()> {
+    let mut request = [0u8; 1024];
+    let read = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..read]);
+    let mut request_line = request.split_whitespace();
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = render_prometheus(&samples.borrow());
+            http_response("200 OK", PROMETHEUS_CONTENT_TYPE, &body)
+        }
+        _ => http_response("404 Not Found", "text/plain", "not found\n"),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+async fn push_otlp(
+    endpoint: OtlpEndpoint,
+    mut samples: watch::Receiver<Vec<Sample>>,
+    start: SystemTime,
+) {
+    while samples.changed().await.is_ok() {
+        let body = render_otlp(&samples.borrow_and_update(), start, SystemTime::now()).to_string();
+        match tokio::time::timeout(OTLP_TIMEOUT, post_json(&endpoint, &body)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => warn!(error = %err, "otlp metrics push failed"),
+            Err(_) => warn!("otlp metrics push timed out"),
+        }
+    }
+}
+
+async fn post_json(endpoint: &OtlpEndpoint, body: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect((endpoint.host.as_str(), endpoint.port)).await?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        endpoint.path,
+        endpoint.authority(),
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "collector answered `{status_line}`"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_metrics() -> Vec<Sample> {
+        samples(
+            CacheMetrics {
+                chats: 3,
+                messages: 42,
+                flushes: 7,
+                ..CacheMetrics::default()
+            },
+            &EventStreamMetrics {
+                updates_received: 10,
+                subscriber_backlog: vec![0, 5],
+                ..EventStreamMetrics::default()
+            },
+            Some(SendPipelineMetrics {
+                sent: 2,
+                ..SendPipelineMetrics::default()
+            }),
+        )
+    }
+
+    #[test]
+    fn renders_the_prometheus_text_format() {
+        let text = render_prometheus(&sample_metrics());
+
+        assert!(text.contains(
+            "# HELP tgtui_cache_messages Messages resident in the cache.\n\
+             # TYPE tgtui_cache_messages gauge\n\
+             tgtui_cache_messages 42\n"
+        ));
+        assert!(text
+            .contains("# TYPE tgtui_cache_flushes_total counter\ntgtui_cache_flushes_total 7\n"));
+        assert!(text.contains(
+            "tgtui_event_subscriber_backlog{subscriber=\"0\"} 0\n\
+             tgtui_event_subscriber_backlog{subscriber=\"1\"} 5\n"
+        ));
+        assert_eq!(
+            text.matches("# TYPE tgtui_event_subscriber_backlog")
+                .count(),
+            1
+        );
+        assert!(text.contains("tgtui_sends_sent_total 2\n"));
+    }
+
+    #[test]
+    fn leaves_out_send_metrics_without_a_pipeline() {
+        let samples = samples(
+            CacheMetrics::default(),
+            &EventStreamMetrics::default(),
+            None,
+        );
+
+        assert!(samples
+            .iter()
+            .any(|sample| sample.name == "tgtui_cache_chats"));
+        assert!(!samples
+            .iter()
+            .any(|sample| sample.name.starts_with("tgtui_send")));
+    }
+
+    #[test]
+    fn renders_counters_as_cumulative_otlp_sums() {
+        let start = UNIX_EPOCH + Duration::from_secs(1);
+        let now = UNIX_EPOCH + Duration::from_secs(2);
+        let request = render_otlp(&sample_metrics(), start, now);
+        let metrics = &request["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        let metric = |name: &str| {
+            metrics
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|metric| metric["name"] == name)
+                .unwrap()
+                .clone()
+        };
+
+        let updates = metric("tgtui_updates_received_total");
+        assert_eq!(updates["sum"]["isMonotonic"], true);
+        assert_eq!(updates["sum"]["dataPoints"][0]["asDouble"], 10.0);
+        assert_eq!(
+            updates["sum"]["dataPoints"][0]["startTimeUnixNano"],
+            "1000000000"
+        );
+        let backlog = metric("tgtui_event_subscriber_backlog");
+        let points = backlog["gauge"]["dataPoints"].as_array().unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[1]["attributes"][0]["value"]["stringValue"], "1");
+    }
+
+    #[test]
+    fn parses_plain_http_otlp_endpoints() {
+        assert_eq!(
+            OtlpEndpoint::parse("http://collector:4318/v1/metrics"),
+            Some(OtlpEndpoint {
+                host: "collector".to_string(),
+                port: 4318,
+                path: "/v1/metrics".to_string(),
+            })
+        );
+        assert_eq!(
+            OtlpEndpoint::parse("http://localhost"),
+            Some(OtlpEndpoint {
+                host: "localhost".to_string(),
+                port: OTLP_DEFAULT_PORT,
+                path: OTLP_DEFAULT_PATH.to_string(),
+            })
+        );
+        assert_eq!(OtlpEndpoint::parse("https://collector/v1/metrics"), None);
+        assert_eq!(OtlpEndpoint::parse("http://:4318"), None);
+    }
+
+    #[test]
+    fn parses_bracketed_ipv6_otlp_endpoints() {
+        let endpoint = OtlpEndpoint::parse("http://[::1]:4318/v1/metrics").expect("endpoint");
+        assert_eq!(
+            endpoint,
+            OtlpEndpoint {
+                host: "::1".to_string(),
+                port: 4318,
+                path: "/v1/metrics".to_string(),
+            }
+        );
+        assert_eq!(endpoint.authority(), "[::1]:4318");
+        assert_eq!(
+            OtlpEndpoint::parse("http://[fe80::1]").map(|endpoint| endpoint.port),
+            Some(OTLP_DEFAULT_PORT)
+        );
+        assert_eq!(OtlpEndpoint::parse("http://::1:4318"), None);
+        assert_eq!(OtlpEndpoint::parse("http://[::1]4318"), None);
+    }
+}
@@ -0,0 +1,751 @@
+//! Terminal runtime: raw mode, the draw loop and key polling, wired to the
+//! cache, the domain event stream and the send pipeline.
+
+use std::collections::HashMap;
+use std::io::{self, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers,
+};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::Terminal;
+use telegram_llm_core::telegram::{
+    BatchConfig, CacheManager, ChatFlagChange, ChatFlagsChanged, ChatId, DomainEvent,
+    EventReceiver, MediaDownloader, MediaKind, MessageId, MessageMediaKind, SendId, SendPipeline,
+    SendRequest, SendStatus, SendTicket, TelegramBootstrap,
+};
+use time::OffsetDateTime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use ui::input::InputState;
+use ui::interaction::{handle_ui_key, handle_ui_paste, KeymapStyle};
+use ui::view::{draw, fit_message_view, preview_placements, PreviewPlacement, UiAction};
+
+use crate::clipboard;
+use crate::downloads;
+use crate::graphics::{self, GraphicsProtocol, Preview};
+use crate::reload::ConfigWatcher;
+use crate::ui_state::UiCacheBridge;
+use crate::LogLevel;
+
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const TTL_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Everything the chat client reads from or drives while the TUI runs.
+pub struct ChatClient<'a> {
+    pub cache: &'a CacheManager,
+    pub bridge: &'a mut UiCacheBridge,
+    pub telegram: &'a TelegramBootstrap,
+    pub send_pipeline: &'a SendPipeline,
+    pub keymap: KeymapStyle,
+    pub event_batch: Option<BatchConfig>,
+    pub media: MediaDownloader,
+    /// Draws photo previews inline when set; otherwise photos show as text
+    /// placeholders.
+    pub graphics: Option<GraphicsProtocol>,
+    /// Where `s` saves attachments.
+    pub download_dir: PathBuf,
+    /// Command `o` plays attachments with; the desktop's opener when unset.
+    pub player: Option<String>,
+    pub sends: SendWatch,
+    /// Reloads the config file when it changes.
+    pub config: ConfigWatcher,
+    pub log_level: &'a LogLevel,
+    /// `[logging] log_content`: whether logged events keep message text.
+    pub log_content: bool,
+}
+
+/// Status changes of sends from the composer, forwarded from their tickets
+/// so local echoes go from queued to sending to sent as it happens.
+pub struct SendWatch {
+    sender: mpsc::UnboundedSender<(SendId, SendStatus)>,
+    receiver: mpsc::UnboundedReceiver<(SendId, SendStatus)>,
+}
+
+impl Default for SendWatch {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self { sender, receiver }
+    }
+}
+
+impl SendWatch {
+    /// Forwards every status `ticket` takes until the pipeline drops it;
+    /// a failed send it keeps for retrying can still move on.
+    fn follow(&self, mut ticket: SendTicket) {
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            loop {
+                let status = ticket.status.borrow_and_update().clone();
+                if sender.send((ticket.id, status)).is_err() {
+                    return;
+                }
+                if ticket.status.changed().await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+/// Decoded thumbnails and the placements drawn in the last frame.
+struct InlinePreviews {
+    images: HashMap<(ChatId, i64), Preview>,
+    shown: Vec<PreviewPlacement>,
+    sender: mpsc::UnboundedSender<(ChatId, i64, Preview)>,
+    receiver: mpsc::UnboundedReceiver<(ChatId, i64, Preview)>,
+}
+
+impl InlinePreviews {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            images: HashMap::new(),
+            shown: Vec::new(),
+            sender,
+            receiver,
+        }
+    }
+}
+
+/// Runs the TUI until the user quits or the event stream closes.
+pub async fn run(mut client: ChatClient<'_>, mut events: EventReceiver) -> io::Result<()> {
+    let mut session = TerminalSession::enter()?;
+    let mut input = spawn_input_reader();
+    let mut ttl_sweep = tokio::time::interval(TTL_SWEEP_INTERVAL);
+    let mut config_poll = tokio::time::interval(CONFIG_POLL_INTERVAL);
+    let mut previews = InlinePreviews::new();
+    if client.graphics.is_some() {
+        client.bridge.enable_inline_previews();
+        client.bridge.refresh(client.cache);
+    }
+
+    loop {
+        if let Err(err) = client.bridge.run_requests(client.cache).await {
+            warn!(error = %err, "failed to load messages");
+        }
+        client.request_previews(&previews.sender);
+        let area = session.terminal.size()?;
+        fit_message_view(area, &mut client.bridge.state);
+        let placements = preview_placements(area, &client.bridge.state);
+        let moved = placements != previews.shown;
+        if let Some(protocol) = client.graphics.filter(|_| moved) {
+            session.clear_images(protocol)?;
+        }
+        session
+            .terminal
+            .draw(|frame| draw(frame, &client.bridge.state))?;
+        if let Some(protocol) = client.graphics.filter(|_| moved) {
+            let chat_id = client.bridge.selected_chat();
+            session.draw_images(protocol, &placements, |message_id| {
+                chat_id.and_then(|chat_id| previews.images.get(&(chat_id, message_id)))
+            })?;
+        }
+        previews.shown = placements;
+        tokio::select! {
+            terminal_input = input.receiver.recv() => match terminal_input {
+                Some(TerminalInput::Key(key)) => {
+                    // Ctrl+C copies in the message pane and quits elsewhere.
+                    if is_quit_key(key) || (!client.handle_key(key) && is_interrupt_key(key)) {
+                        info!("quit requested");
+                        break;
+                    }
+                }
+                Some(TerminalInput::Paste(text)) => client.handle_paste(&text),
+                // The next draw picks up the new size.
+                Some(TerminalInput::Resize) => {}
+                None => break,
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    let batch = match client.event_batch {
+                        Some(batch) => events.collect_batch(event, batch).await.events,
+                        None => vec![event],
+                    };
+                    if events.take_gap() {
+                        warn!("domain events were dropped; cached chats may be stale");
+                    }
+                    client.cache.apply_events(&batch);
+                    let now = OffsetDateTime::now_utc().unix_timestamp();
+                    client.bridge.observe_events(client.cache, &batch, now);
+                    client.bridge.refresh(client.cache);
+                    for event in &batch {
+                        info!(event = ?event.for_log(client.log_content), "received domain event");
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            Some((send_id, status)) = client.sends.receiver.recv() => {
+                if let SendStatus::Failed(failure) = &status {
+                    client.bridge.send_failed(send_id, failure.error.clone());
+                }
+                client.cache.apply_send_status(send_id, &status);
+                client.bridge.refresh(client.cache);
+            }
+            Some((chat_id, message_id, preview)) = previews.receiver.recv() => {
+                previews.images.insert((chat_id, message_id), preview);
+                client.bridge.preview_ready(chat_id, message_id);
+                client.bridge.refresh(client.cache);
+            }
+            _ = ttl_sweep.tick() => {
+                let now = OffsetDateTime::now_utc().unix_timestamp();
+                client.cache.evict_expired(now);
+                client.bridge.refresh(client.cache);
+            }
+            _ = config_poll.tick() => client.reload_config(),
+        }
+    }
+    Ok(())
+}
+
+impl ChatClient<'_> {
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let handled = handle_ui_key(&mut self.bridge.state, key, self.keymap);
+        self.bridge.sync_selection();
+        let actions = std::mem::take(&mut self.bridge.state.actions);
+        for action in actions {
+            self.run_action(action);
+        }
+        self.bridge.refresh(self.cache);
+        handled
+    }
+
+    /// Applies the settings that can change without reconnecting once the
+    /// config file changed, and says so in the status bar.
+    fn reload_config(&mut self) {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        match self.config.poll() {
+            None => {}
+            Some(Ok(changes)) => {
+                let config = &changes.config;
+                self.log_level.set(config.log_level);
+                self.bridge.state.theme = config.theme;
+                self.keymap = config.keymap;
+                self.send_pipeline.retune(config.send_pipeline_config());
+                if let Some(text) = changes.summary() {
+                    info!(
+                        applied = ?changes.applied,
+                        needs_restart = changes.needs_restart,
+                        "reloaded config file"
+                    );
+                    self.bridge.notify(text, false, now);
+                }
+            }
+            Some(Err(err)) => {
+                warn!(error = %err, "config file not reloaded");
+                self.bridge
+                    .notify(format!("Config file not reloaded: {err}"), true, now);
+            }
+        }
+    }
+
+    fn handle_paste(&mut self, text: &str) {
+        if handle_ui_paste(&mut self.bridge.state, text) {
+            self.bridge.refresh(self.cache);
+        }
+    }
+
+    fn run_action(&mut self, action: UiAction) {
+        match action {
+            UiAction::SendComposer => self.send_composer(),
+            UiAction::OpenMessageDetail(id) => self.bridge.open_message_detail(self.cache, id),
+            UiAction::LoadOlderMessages => self.bridge.request_older_messages(),
+            UiAction::SearchAllChats(query) => self.bridge.request_search(query),
+            UiAction::JumpToMessage {
+                chat_id,
+                message_id,
+            } => self.bridge.request_jump(ChatId(chat_id), message_id),
+            UiAction::CopyText(text) => match clipboard::copy(&mut io::stdout(), &text) {
+                Ok(()) => info!(lines = text.lines().count(), "copied messages"),
+                Err(err) => warn!(error = %err, "failed to copy messages"),
+            },
+            UiAction::RetrySend(id) => match self.send_pipeline.retry_dead_letter(SendId(id)) {
+                Ok(()) => self.bridge.forget_send_failure(SendId(id)),
+                Err(err) => {
+                    warn!(error = %err, send_id = id, "failed to retry send");
+                    let now = OffsetDateTime::now_utc().unix_timestamp();
+                    self.bridge
+                        .notify(format!("Send not retried: {err}"), true, now);
+                }
+            },
+            UiAction::DiscardSend(id) => {
+                self.bridge.forget_send_failure(SendId(id));
+                self.send_pipeline.discard_dead_letter(SendId(id));
+                self.cache.discard_failed_send(SendId(id));
+            }
+            UiAction::ForwardMessages {
+                from_chat_id,
+                to_chat_id,
+                message_ids,
+            } => self.forward_messages(ChatId(from_chat_id), ChatId(to_chat_id), &message_ids),
+            UiAction::DeleteMessages(message_ids) => self.delete_messages(&message_ids),
+            UiAction::ToggleMute { chat_id, muted } => {
+                self.change_chat_flag(ChatId(chat_id), ChatFlagChange::Muted(muted))
+            }
+            UiAction::TogglePin { chat_id, pinned } => {
+                self.change_chat_flag(ChatId(chat_id), ChatFlagChange::Pinned(pinned))
+            }
+            UiAction::SendAttachment(path) => self.send_attachment(path),
+            UiAction::SaveMedia(message_id) => self.download_media(message_id, false),
+            UiAction::OpenMedia(message_id) => self.download_media(message_id, true),
+            UiAction::TranscribeVoice(message_id) => self.transcribe_voice(message_id),
+        }
+    }
+
+    /// Sends `change` to Telegram and shows it at once; Telegram echoes it
+    /// back as an update once the request lands.
+    fn change_chat_flag(&mut self, chat_id: ChatId, change: ChatFlagChange) {
+        let Some(peer) = self.telegram.peer_ref(chat_id) else {
+            warn!(chat_id = chat_id.0, "chat peer unknown; chat not changed");
+            return;
+        };
+        let request = match change {
+            ChatFlagChange::Muted(muted) => SendRequest::SetChatMuted { peer, muted },
+            ChatFlagChange::Pinned(pinned) => SendRequest::SetChatPinned { peer, pinned },
+            // Chats are not moved between folders from the TUI.
+            ChatFlagChange::Folder(_) => return,
+        };
+        if let Err(err) = self.send_pipeline.enqueue(request) {
+            warn!(error = %err, chat_id = chat_id.0, ?change, "failed to queue chat change");
+            return;
+        }
+        self.cache
+            .apply_event(&DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+                changes: vec![(chat_id, change)],
+            }));
+    }
+
+    fn forward_messages(&mut self, from_chat_id: ChatId, to_chat_id: ChatId, message_ids: &[i64]) {
+        let message_ids = sent_message_ids(message_ids);
+        if message_ids.is_empty() {
+            return;
+        }
+        let (Some(from_peer), Some(peer)) = (
+            self.telegram.peer_ref(from_chat_id),
+            self.telegram.peer_ref(to_chat_id),
+        ) else {
+            warn!(
+                chat_id = to_chat_id.0,
+                "chat peer unknown; messages not forwarded"
+            );
+            return;
+        };
+        let count = message_ids.len();
+        let request = SendRequest::Forward {
+            peer,
+            from_peer,
+            message_ids,
+        };
+        if let Err(err) = self.send_pipeline.enqueue(request) {
+            warn!(error = %err, chat_id = to_chat_id.0, count, "failed to queue forward");
+        }
+    }
+
+    fn delete_messages(&mut self, message_ids: &[i64]) {
+        let Some(chat_id) = self.bridge.selected_chat() else {
+            return;
+        };
+        let message_ids = sent_message_ids(message_ids);
+        if message_ids.is_empty() {
+            return;
+        }
+        let Some(peer) = self.telegram.peer_ref(chat_id) else {
+            warn!(
+                chat_id = chat_id.0,
+                "chat peer unknown; messages not deleted"
+            );
+            return;
+        };
+        let count = message_ids.len();
+        let request = SendRequest::DeleteMessage { peer, message_ids };
+        if let Err(err) = self.send_pipeline.enqueue(request) {
+            warn!(error = %err, chat_id = chat_id.0, count, "failed to queue delete");
+        }
+    }
+
+    /// Downloads thumbnails of newly shown photos in the background.
+    fn request_previews(&mut self, sender: &mpsc::UnboundedSender<(ChatId, i64, Preview)>) {
+        for (chat_id, message_id, thumbnail) in self.bridge.take_wanted_previews() {
+            let media = self.media.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                let bytes = match media.download_thumbnail(&thumbnail).await {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        warn!(error = %err, message_id, "failed to download thumbnail");
+                        return;
+                    }
+                };
+                match Preview::decode(bytes) {
+                    Ok(preview) => {
+                        let _ = sender.send((chat_id, message_id, preview));
+                    }
+                    Err(err) => warn!(error = %err, message_id, "failed to decode thumbnail"),
+                }
+            });
+        }
+    }
+
+    fn send_composer(&mut self) {
+        let Some(chat_id) = self.bridge.selected_chat() else {
+            return;
+        };
+        let text = self.bridge.state.input.text.trim().to_string();
+        let Some(peer) = self.telegram.peer_ref(chat_id) else {
+            warn!(chat_id = chat_id.0, "chat peer unknown; message not sent");
+            return;
+        };
+        let reply_to = self
+            .bridge
+            .state
+            .reply_to
+            .as_ref()
+            .map(|reply| MessageId(reply.message_id));
+        let request = SendRequest::SendText {
+            peer,
+            text: text.clone(),
+            reply_to,
+            schedule_date: None,
+        };
+        match self.send_pipeline.enqueue(request) {
+            Ok(ticket) => {
+                let now = OffsetDateTime::now_utc().unix_timestamp();
+                self.cache.insert_pending(chat_id, ticket.id, &text, now);
+                self.sends.follow(ticket);
+                self.cache.set_draft(chat_id, "", now);
+                self.bridge.state.input = InputState::default();
+                self.bridge.state.reply_to = None;
+            }
+            Err(err) => warn!(error = %err, chat_id = chat_id.0, "failed to queue message"),
+        }
+    }
+
+    /// Sends `path` to the selected chat with the composer text as its
+    /// caption.
+    fn send_attachment(&mut self, path: PathBuf) {
+        let Some(chat_id) = self.bridge.selected_chat() else {
+            return;
+        };
+        let Some(peer) = self.telegram.peer_ref(chat_id) else {
+            warn!(
+                chat_id = chat_id.0,
+                "chat peer unknown; attachment not sent"
+            );
+            return;
+        };
+        let reply_to = self
+            .bridge
+            .state
+            .reply_to
+            .as_ref()
+            .map(|reply| MessageId(reply.message_id));
+        let caption = self.bridge.state.input.text.trim().to_string();
+        let kind = media_kind(&path);
+        let echo = attachment_echo(kind, &path, &caption);
+        let request = SendRequest::SendMedia {
+            kind,
+            peer,
+            path,
+            caption,
+            reply_to,
+        };
+        match self.send_pipeline.enqueue(request) {
+            Ok(ticket) => {
+                let now = OffsetDateTime::now_utc().unix_timestamp();
+                self.cache.insert_pending(chat_id, ticket.id, &echo, now);
+                self.sends.follow(ticket);
+                self.cache.set_draft(chat_id, "", now);
+                self.bridge.state.input = InputState::default();
+                self.bridge.state.reply_to = None;
+            }
+            Err(err) => warn!(error = %err, chat_id = chat_id.0, "failed to queue attachment"),
+        }
+    }
+
+    /// Downloads the attachment of `message_id` into the download directory,
+    /// or into a temporary one and then into the player when `open` is set.
+    fn download_media(&mut self, message_id: i64, open: bool) {
+        let Some((chat_id, media)) = self.bridge.message_media(self.cache, message_id) else {
+            return;
+        };
+        let Some(document) = media.document else {
+            warn!(
+                message_id,
+                kind = media.kind.label(),
+                "attachment has no file to download"
+            );
+            return;
+        };
+        let dir = if open {
+            std::env::temp_dir().join("telegram-llm-tui")
+        } else {
+            self.download_dir.clone()
+        };
+        let path = downloads::download_path(&dir, chat_id, message_id, &document);
+        let player = open.then(|| self.player.clone());
+        let media = self.media.clone();
+        tokio::spawn(async move {
+            if let Err(err) = tokio::fs::create_dir_all(&dir).await {
+                warn!(error = %err, dir = %dir.display(), "failed to create download dir");
+                return;
+            }
+            if let Err(err) = media.download_document(&document, &path).await {
+                warn!(error = %err, message_id, "failed to download attachment");
+                return;
+            }
+            match player {
+                Some(player) => match downloads::open_in_player(player.as_deref(), &path) {
+                    Ok(()) => info!(path = %path.display(), "opened attachment"),
+                    Err(err) => {
+                        warn!(error = %err, path = %path.display(), "failed to open attachment")
+                    }
+                },
+                None => info!(path = %path.display(), "saved attachment"),
+            }
+        });
+    }
+
+    /// Asks Telegram for the text of a voice message; the transcription
+    /// arrives on the send pipeline and as updates while it is worked on.
+    fn transcribe_voice(&mut self, message_id: i64) {
+        let Some((chat_id, media)) = self.bridge.message_media(self.cache, message_id) else {
+            return;
+        };
+        if media.kind != MessageMediaKind::Voice {
+            warn!(
+                message_id,
+                kind = media.kind.label(),
+                "only voice messages can be transcribed"
+            );
+            return;
+        }
+        let Some(peer) = self.telegram.peer_ref(chat_id) else {
+            warn!(
+                chat_id = chat_id.0,
+                "chat peer unknown; voice message not transcribed"
+            );
+            return;
+        };
+        let request = SendRequest::TranscribeAudio {
+            peer,
+            message_id: MessageId(message_id),
+        };
+        if let Err(err) = self.send_pipeline.enqueue(request) {
+            warn!(error = %err, message_id, "failed to queue transcription");
+        }
+    }
+}
+
+/// Images Telegram can show inline go as photos, anything else as a file.
+fn media_kind(path: &Path) -> MediaKind {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("jpg" | "jpeg" | "png" | "webp") => MediaKind::Photo,
+        _ => MediaKind::Document,
+    }
+}
+
+/// The local echo of an attachment until Telegram has it: a placeholder
+/// naming the file, then the caption.
+fn attachment_echo(kind: MediaKind, path: &Path, caption: &str) -> String {
+    let label = match kind {
+        MediaKind::Photo => "photo",
+        MediaKind::Document => "document",
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let mut echo = format!("[{label}: {name}]");
+    if !caption.is_empty() {
+        echo.push('\n');
+        echo.push_str(caption);
+    }
+    echo
+}
+
+/// Ids Telegram knows; local echoes of unsent messages have none yet.
+fn sent_message_ids(message_ids: &[i64]) -> Vec<MessageId> {
+    message_ids
+        .iter()
+        .filter(|id| **id > 0)
+        .map(|id| MessageId(*id))
+        .collect()
+}
+
+fn is_quit_key(key: KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('q')
+}
+
+/// Ctrl+C, which quits unless the focused pane uses it.
+fn is_interrupt_key(key: KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c')
+}
+
+/// Raw mode, the alternate screen and bracketed paste, restored on drop so an
+/// error or a panic still leaves the shell usable.
+struct TerminalSession {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl TerminalSession {
+    fn enter() -> io::Result<Self> {
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        if let Err(err) = execute!(stdout, EnterAlternateScreen, EnableBracketedPaste) {
+            let _ = disable_raw_mode();
+            return Err(err);
+        }
+        match Terminal::new(CrosstermBackend::new(stdout)) {
+            Ok(terminal) => Ok(Self { terminal }),
+            Err(err) => {
+                let _ = execute!(io::stdout(), DisableBracketedPaste, LeaveAlternateScreen);
+                let _ = disable_raw_mode();
+                Err(err)
+            }
+        }
+    }
+}
+
+impl TerminalSession {
+    /// Removes images from earlier frames. Protocols without a delete
+    /// command get a full redraw over them instead.
+    fn clear_images(&mut self, protocol: GraphicsProtocol) -> io::Result<()> {
+        match protocol {
+            GraphicsProtocol::Kitty => {
+                let backend = self.terminal.backend_mut();
+                backend.write_all(graphics::KITTY_CLEAR.as_bytes())?;
+                backend.flush()
+            }
+            GraphicsProtocol::Iterm2 | GraphicsProtocol::Sixel => self.terminal.clear(),
+        }
+    }
+
+    /// Draws previews over the rows the last frame reserved for them.
+    fn draw_images<'p>(
+        &mut self,
+        protocol: GraphicsProtocol,
+        placements: &[PreviewPlacement],
+        preview: impl Fn(i64) -> Option<&'p Preview>,
+    ) -> io::Result<()> {
+        let cell_px = graphics::cell_size();
+        let backend = self.terminal.backend_mut();
+        for placement in placements {
+            if let Some(preview) = preview(placement.message_id) {
+                let encoded = graphics::encode(protocol, preview, placement.area, cell_px);
+                backend.write_all(encoded.as_bytes())?;
+            }
+        }
+        backend.flush()
+    }
+}
+
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            self.terminal.backend_mut(),
+            DisableBracketedPaste,
+            LeaveAlternateScreen
+        );
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+enum TerminalInput {
+    Key(KeyEvent),
+    Paste(String),
+    Resize,
+}
+
+/// Terminal input read on a dedicated thread, since crossterm reads block.
+struct InputReader {
+    receiver: mpsc::UnboundedReceiver<TerminalInput>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for InputReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn spawn_input_reader() -> InputReader {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_thread = Arc::clone(&stop);
+    std::thread::spawn(move || {
+        while !stop_thread.load(Ordering::Relaxed) {
+            match event::poll(INPUT_POLL_INTERVAL) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    warn!(error = %err, "terminal input failed");
+                    break;
+                }
+            }
+            let input = match event::read() {
+                Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => TerminalInput::Key(key),
+                Ok(Event::Paste(text)) => TerminalInput::Paste(text),
+                Ok(Event::Resize(_, _)) => TerminalInput::Resize,
+                Ok(_) => continue,
+                Err(err) => {
+                    warn!(error = %err, "terminal input failed");
+                    break;
+                }
+            };
+            if sender.send(input).is_err() {
+                break;
+            }
+        }
+    });
+    InputReader { receiver, stop }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ctrl_c_and_ctrl_q_quit() {
+        assert!(is_interrupt_key(KeyEvent::new(
+            KeyCode::Char('c'),
+            KeyModifiers::CONTROL
+        )));
+        assert!(is_quit_key(KeyEvent::new(
+            KeyCode::Char('q'),
+            KeyModifiers::CONTROL
+        )));
+        assert!(!is_quit_key(KeyEvent::new(
+            KeyCode::Char('q'),
+            KeyModifiers::NONE
+        )));
+    }
+
+    #[test]
+    fn attachment_echo_names_the_file_before_the_caption() {
+        assert_eq!(
+            attachment_echo(MediaKind::Photo, Path::new("/tmp/cat.png"), ""),
+            "[photo: cat.png]"
+        );
+        assert_eq!(
+            attachment_echo(MediaKind::Document, Path::new("/tmp/report.pdf"), "minutes"),
+            "[document: report.pdf]\nminutes"
+        );
+    }
+}
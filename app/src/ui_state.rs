@@ -1,14 +1,61 @@
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 
-use telegram_llm_core::telegram::{CacheManager, CachedMessage, ChatId, ChatSummary};
+use telegram_llm_core::telegram::{
+    CacheError, CacheManager, CachedMessage, ChatId, ChatPeerKind, ChatSummary, DeliveryState,
+    DomainEvent, MessageMedia, MessageReaction, PhotoThumbnail, Presence, SendId, UserId,
+};
 use time::{format_description, OffsetDateTime};
-use ui::view::{ChatListItem, MessageItem, UiState};
+use ui::attach::format_size;
+use ui::view::{
+    ChatListItem, ChatSort, DeliveryMark, FailedSend, GlobalSearchResult, MediaItem, MessageDetail,
+    MessageItem, MessageViewMemory, Notice, ReplyTarget, Toast, UiState,
+};
+
+const DELETED_MESSAGE_BODY: &str = "message deleted";
+/// Seconds a typing indicator stays up without a fresh typing update, in
+/// case the stream never reports that it stopped.
+const TYPING_DISPLAY_SECS: i64 = 6;
+/// Seconds a new-message toast stays up.
+const TOAST_DISPLAY_SECS: i64 = 5;
+/// Seconds a status bar notice stays up.
+const NOTICE_DISPLAY_SECS: i64 = 8;
+/// Toasts stacked at once; older ones make way.
+const MAX_TOASTS: usize = 4;
+/// Older messages loaded each time the cursor pushes past the top.
+const HISTORY_PAGE: usize = 50;
+/// Results listed by the search across all chats.
+const GLOBAL_SEARCH_LIMIT: usize = 50;
+/// History pages loaded at most while looking for a message to jump to.
+const MAX_JUMP_PAGES: usize = 20;
 
 #[derive(Debug, Clone)]
 pub struct UiCacheBridge {
     pub state: UiState,
     selected_chat: Option<ChatId>,
+    /// Chat whose draft currently fills the composer and whose messages are
+    /// listed.
+    draft_chat: Option<ChatId>,
+    /// Message view positions of chats switched away from.
+    view_memory: HashMap<ChatId, MessageViewMemory>,
     message_limit: Option<usize>,
+    /// Whether photo thumbnails are fetched to draw inline.
+    inline_previews: bool,
+    /// Thumbnails downloaded and decoded, by chat and message id.
+    ready_previews: HashSet<(ChatId, i64)>,
+    requested_previews: HashSet<(ChatId, i64)>,
+    wanted_previews: Vec<(ChatId, i64, PhotoThumbnail)>,
+    /// When each typing indicator lapses, by chat and user.
+    typing: HashMap<ChatId, HashMap<UserId, i64>>,
+    /// Older messages were asked for and
+    /// [`Self::load_requested_history`] has not run since.
+    older_requested: bool,
+    /// Chats whose whole stored history is loaded.
+    history_exhausted: HashSet<ChatId>,
+    search_request: Option<String>,
+    jump_request: Option<(ChatId, i64)>,
+    /// Why sends failed, by the placeholder id of their local echo.
+    send_errors: HashMap<i64, String>,
 }
 
 impl UiCacheBridge {
@@ -16,46 +63,423 @@ impl UiCacheBridge {
         Self {
             state: UiState::default(),
             selected_chat: None,
+            draft_chat: None,
+            view_memory: HashMap::new(),
             message_limit,
+            inline_previews: false,
+            ready_previews: HashSet::new(),
+            requested_previews: HashSet::new(),
+            wanted_previews: Vec::new(),
+            typing: HashMap::new(),
+            older_requested: false,
+            history_exhausted: HashSet::new(),
+            search_request: None,
+            jump_request: None,
+            send_errors: HashMap::new(),
+        }
+    }
+
+    /// Asks [`Self::run_requests`] to search all chats for `query`.
+    pub fn request_search(&mut self, query: String) {
+        self.search_request = Some(query);
+    }
+
+    /// Asks [`Self::run_requests`] to show a message in its chat.
+    pub fn request_jump(&mut self, chat_id: ChatId, message_id: i64) {
+        self.jump_request = Some((chat_id, message_id));
+    }
+
+    /// Carries out the history loads, searches and jumps key handling asked
+    /// for, which need the store.
+    pub async fn run_requests(&mut self, cache: &CacheManager) -> Result<(), CacheError> {
+        self.load_requested_history(cache).await?;
+        self.run_requested_search(cache).await?;
+        self.jump_to_requested_message(cache).await
+    }
+
+    /// Remembers why `send_id` failed, for the banner offering to retry it.
+    pub fn send_failed(&mut self, send_id: SendId, error: String) {
+        self.send_errors.insert(echo_message_id(send_id), error);
+    }
+
+    /// Forgets the failure of a send that was retried or discarded.
+    pub fn forget_send_failure(&mut self, send_id: SendId) {
+        self.send_errors.remove(&echo_message_id(send_id));
+    }
+
+    /// Shows `text` in the status bar for a few seconds.
+    pub fn notify(&mut self, text: String, is_error: bool, now: i64) {
+        self.state.notice = Some(Notice {
+            text,
+            is_error,
+            expires_at: now + NOTICE_DISPLAY_SECS,
+        });
+    }
+
+    /// Asks [`Self::load_requested_history`] for a page of older messages.
+    pub fn request_older_messages(&mut self) {
+        self.older_requested = true;
+    }
+
+    /// Extends the selected chat with a page of older messages when one was
+    /// requested: first from the resident messages hidden by the message
+    /// limit, then from the store. Returns whether any were added.
+    pub async fn load_requested_history(
+        &mut self,
+        cache: &CacheManager,
+    ) -> Result<bool, CacheError> {
+        if !std::mem::take(&mut self.older_requested) {
+            return Ok(false);
+        }
+        let Some(chat_id) = self.selected_chat else {
+            return Ok(false);
+        };
+        if let Some(limit) = self.message_limit {
+            if cache.messages_for_chat(chat_id, None).len() > limit {
+                self.message_limit = Some(limit + HISTORY_PAGE);
+                self.refresh(cache);
+                return Ok(true);
+            }
+        }
+        if self.history_exhausted.contains(&chat_id) {
+            return Ok(false);
+        }
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        match cache.hydrate_history(chat_id, HISTORY_PAGE, now).await? {
+            0 => {
+                self.history_exhausted.insert(chat_id);
+                Ok(false)
+            }
+            added => {
+                self.message_limit = self.message_limit.map(|limit| limit + added);
+                self.refresh(cache);
+                Ok(true)
+            }
+        }
+    }
+
+    async fn run_requested_search(&mut self, cache: &CacheManager) -> Result<(), CacheError> {
+        let Some(query) = self.search_request.take() else {
+            return Ok(());
+        };
+        if query.is_empty() || !self.state.global_search.is_open {
+            self.state.global_search.results.clear();
+            return Ok(());
+        }
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let hits = cache.search(&query, None, GLOBAL_SEARCH_LIMIT, now).await?;
+        let summaries = cache.chat_summaries();
+        self.state.global_search.results = hits
+            .into_iter()
+            .map(|hit| {
+                let chat_id = hit.message.chat_id;
+                GlobalSearchResult {
+                    chat_id: chat_id.0,
+                    message_id: hit.message.message_id.0,
+                    chat_title: summaries
+                        .iter()
+                        .find(|chat| chat.chat_id == chat_id)
+                        .map(chat_title)
+                        .unwrap_or_else(|| format!("Chat {}", chat_id.0)),
+                    author: message_author_label(&hit.message),
+                    snippet: hit.snippet,
+                }
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Selects the chat of the requested message and puts the cursor on it,
+    /// paging in older history until it turns up.
+    async fn jump_to_requested_message(&mut self, cache: &CacheManager) -> Result<(), CacheError> {
+        let Some((chat_id, message_id)) = self.jump_request.take() else {
+            return Ok(());
+        };
+        self.selected_chat = Some(chat_id);
+        self.refresh(cache);
+        for _ in 0..MAX_JUMP_PAGES {
+            let found = self
+                .state
+                .messages
+                .iter()
+                .position(|message| message.id == message_id);
+            if let Some(index) = found {
+                self.state.message_view.cursor = Some(index);
+                self.state.message_view.scroll_to(index);
+                return Ok(());
+            }
+            self.older_requested = true;
+            if !self.load_requested_history(cache).await? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tracks typing indicators from a batch of domain events, and raises
+    /// toasts for new messages in other chats that are not muted.
+    pub fn observe_events(&mut self, cache: &CacheManager, events: &[DomainEvent], now: i64) {
+        let summaries = cache.chat_summaries();
+        for event in events {
+            match event {
+                DomainEvent::Typing(typing) => {
+                    self.typing
+                        .entry(typing.chat_id)
+                        .or_default()
+                        .insert(typing.user_id, now + TYPING_DISPLAY_SECS);
+                }
+                DomainEvent::TypingStopped(typing) => {
+                    self.stop_typing(typing.chat_id, typing.user_id)
+                }
+                DomainEvent::MessageNew(message) => {
+                    self.stop_typing(message.chat_id, message.author_id);
+                    if message.outgoing || Some(message.chat_id) == self.selected_chat {
+                        continue;
+                    }
+                    let chat = summaries
+                        .iter()
+                        .find(|chat| chat.chat_id == message.chat_id);
+                    if chat.is_some_and(|chat| chat.flags.muted) {
+                        continue;
+                    }
+                    let preview = match message.text.lines().next() {
+                        Some(line) if !line.trim().is_empty() => line.to_string(),
+                        _ => message.media.as_ref().map(media_label).unwrap_or_default(),
+                    };
+                    self.state.toasts.push(Toast {
+                        chat_id: message.chat_id.0,
+                        chat_title: chat
+                            .map(chat_title)
+                            .unwrap_or_else(|| format!("Chat {}", message.chat_id.0)),
+                        text: format!("{}: {}", user_label(message.author_id), preview),
+                        expires_at: now + TOAST_DISPLAY_SECS,
+                    });
+                }
+                _ => {}
+            }
+        }
+        let overflow = self.state.toasts.len().saturating_sub(MAX_TOASTS);
+        self.state.toasts.drain(..overflow);
+    }
+
+    fn stop_typing(&mut self, chat_id: ChatId, user_id: UserId) {
+        if let Some(users) = self.typing.get_mut(&chat_id) {
+            users.remove(&user_id);
+            if users.is_empty() {
+                self.typing.remove(&chat_id);
+            }
         }
     }
 
+    /// Starts collecting thumbnails of the shown photos for
+    /// [`Self::take_wanted_previews`].
+    pub fn enable_inline_previews(&mut self) {
+        self.inline_previews = true;
+    }
+
+    /// Thumbnails of shown messages not requested before.
+    pub fn take_wanted_previews(&mut self) -> Vec<(ChatId, i64, PhotoThumbnail)> {
+        std::mem::take(&mut self.wanted_previews)
+    }
+
+    /// Reserves room for the preview of a message from the next refresh.
+    pub fn preview_ready(&mut self, chat_id: ChatId, message_id: i64) {
+        self.ready_previews.insert((chat_id, message_id));
+    }
+
     #[cfg(test)]
     pub fn set_selected_chat(&mut self, chat_id: Option<ChatId>) {
         self.selected_chat = chat_id;
     }
 
+    pub fn selected_chat(&self) -> Option<ChatId> {
+        self.selected_chat
+    }
+
+    /// Adopts the chat highlighted by key handling, for the next refresh.
+    pub fn sync_selection(&mut self) {
+        if let Some(chat) = self.state.chats.iter().find(|chat| chat.is_selected) {
+            self.selected_chat = Some(ChatId(chat.id));
+        }
+    }
+
     pub fn refresh(&mut self, cache: &CacheManager) -> Option<ChatId> {
         let summaries = cache.chat_summaries();
-        let (chat_items, selected_chat) = map_chat_summaries(&summaries, self.selected_chat);
+        let (chat_items, selected_chat) =
+            map_chat_summaries(&summaries, self.selected_chat, self.state.chat_sort);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if let Some(chat_id) = selected_chat.filter(|_| selected_chat != self.selected_chat) {
+            cache.mark_accessed(chat_id, now);
+        }
+        let switched = selected_chat != self.draft_chat;
+        if switched {
+            if let Some(chat_id) = self.draft_chat {
+                let memory = self.state.message_view.memory(&self.state.messages);
+                self.view_memory.insert(chat_id, memory);
+            }
+            self.save_draft(cache, now);
+            let draft = selected_chat
+                .and_then(|chat_id| cache.draft(chat_id))
+                .unwrap_or_default();
+            self.state.input.replace(draft);
+            self.state.reply_to = None;
+            self.draft_chat = selected_chat;
+        }
         self.selected_chat = selected_chat;
+        self.typing.retain(|_, users| {
+            users.retain(|_, lapses_at| *lapses_at > now);
+            !users.is_empty()
+        });
         self.state.chats = chat_items;
+        for chat in &mut self.state.chats {
+            chat.typing = self.typing.contains_key(&ChatId(chat.id));
+        }
+        let mut typing: Vec<UserId> = selected_chat
+            .and_then(|chat_id| self.typing.get(&chat_id))
+            .map(|users| users.keys().copied().collect())
+            .unwrap_or_default();
+        typing.sort_by_key(|user_id| user_id.0);
+        self.state.typing = typing.into_iter().map(user_label).collect();
+        self.state
+            .toasts
+            .retain(|toast| toast.expires_at > now && Some(ChatId(toast.chat_id)) != selected_chat);
+        self.state.notice = self
+            .state
+            .notice
+            .take()
+            .filter(|notice| notice.expires_at > now);
+        self.state.chat_status = selected_chat
+            .and_then(|chat_id| summaries.iter().find(|chat| chat.chat_id == chat_id))
+            .and_then(|chat| chat_status(cache, chat, now));
 
-        self.state.messages = match selected_chat {
+        let messages = match selected_chat {
             Some(chat_id) => {
                 let messages = cache.messages_for_chat(chat_id, self.message_limit);
-                map_messages(messages)
+                if self.inline_previews {
+                    self.want_previews(chat_id, &messages);
+                }
+                let mut items = map_messages(messages, now);
+                for item in &mut items {
+                    if let Some(media) = &mut item.media {
+                        media.has_preview = self.ready_previews.contains(&(chat_id, item.id));
+                    }
+                }
+                items
             }
             None => Vec::new(),
         };
-        self.state.message_view.reconcile(&self.state.messages);
+        let previous = std::mem::replace(&mut self.state.messages, messages);
+        self.state.failed_sends = self
+            .state
+            .messages
+            .iter()
+            .filter(|message| message.delivery == Some(DeliveryMark::Failed))
+            .map(|message| FailedSend {
+                message_id: message.id,
+                error: self.send_errors.get(&message.id).cloned(),
+            })
+            .collect();
+        if switched {
+            let memory = selected_chat.and_then(|chat_id| self.view_memory.remove(&chat_id));
+            self.state
+                .message_view
+                .restore(memory, &self.state.messages);
+        } else {
+            self.state
+                .message_view
+                .follow(&previous, &self.state.messages);
+        }
+
+        if let Some(detail) = &self.state.message_detail {
+            let (id, scroll) = (detail.id, detail.scroll);
+            self.state.message_detail = self
+                .message_detail(cache, id)
+                .map(|detail| MessageDetail { scroll, ..detail });
+        }
 
         selected_chat
     }
+
+    fn want_previews(&mut self, chat_id: ChatId, messages: &[CachedMessage]) {
+        for message in messages {
+            let Some(thumbnail) = message
+                .media
+                .as_ref()
+                .and_then(|media| media.thumbnail.as_ref())
+            else {
+                continue;
+            };
+            if self
+                .requested_previews
+                .insert((chat_id, message.message_id.0))
+            {
+                self.wanted_previews
+                    .push((chat_id, message.message_id.0, thumbnail.clone()));
+            }
+        }
+    }
+
+    /// Opens the detail view for a message of the selected chat.
+    pub fn open_message_detail(&mut self, cache: &CacheManager, message_id: i64) {
+        self.state.message_detail = self.message_detail(cache, message_id);
+    }
+
+    /// Attachment of a message in the selected chat, with the chat it is in.
+    pub fn message_media(
+        &self,
+        cache: &CacheManager,
+        message_id: i64,
+    ) -> Option<(ChatId, MessageMedia)> {
+        let chat_id = self.selected_chat?;
+        let media = cache
+            .messages_for_chat(chat_id, self.message_limit)
+            .into_iter()
+            .find(|message| message.message_id.0 == message_id)?
+            .media?;
+        Some((chat_id, media))
+    }
+
+    fn message_detail(&self, cache: &CacheManager, message_id: i64) -> Option<MessageDetail> {
+        let chat_id = self.selected_chat?;
+        let message = cache
+            .messages_for_chat(chat_id, self.message_limit)
+            .into_iter()
+            .find(|message| message.message_id.0 == message_id)?;
+        Some(map_message_detail(&message))
+    }
+
+    /// Stores the composer text as the draft of the chat it was typed in.
+    pub fn save_draft(&self, cache: &CacheManager, now: i64) {
+        if let Some(chat_id) = self.draft_chat {
+            cache.set_draft(chat_id, &self.state.input.text, now);
+        }
+    }
 }
 
+/// Sorts chats into sections, pinned chats on top, each section ordered by
+/// `sort`.
 fn map_chat_summaries(
     summaries: &[ChatSummary],
     selected_chat: Option<ChatId>,
+    sort: ChatSort,
 ) -> (Vec<ChatListItem>, Option<ChatId>) {
     let mut sorted = summaries.to_vec();
     sorted.sort_by(|left, right| {
-        let left_ts = left.last_message_at.unwrap_or(0);
-        let right_ts = right.last_message_at.unwrap_or(0);
-        match right_ts.cmp(&left_ts) {
-            Ordering::Equal => left.title.cmp(&right.title),
-            ordering => ordering,
-        }
+        section_rank(left, sort)
+            .cmp(&section_rank(right, sort))
+            .then_with(|| match sort {
+                ChatSort::Recent | ChatSort::Folders => by_recency(left, right),
+                ChatSort::UnreadFirst => {
+                    let unread = |chat: &ChatSummary| chat.unread_count.unwrap_or(0) > 0;
+                    unread(right)
+                        .cmp(&unread(left))
+                        .then_with(|| by_recency(left, right))
+                }
+                ChatSort::Alphabetical => chat_title(left)
+                    .to_lowercase()
+                    .cmp(&chat_title(right).to_lowercase())
+                    .then_with(|| by_recency(left, right)),
+            })
     });
 
     let resolved_selection = selected_chat
@@ -69,12 +493,89 @@ fn map_chat_summaries(
             title: chat_title(chat),
             unread: chat.unread_count.unwrap_or(0),
             is_selected: resolved_selection == Some(chat.chat_id),
+            section: section_title(chat, sort),
+            typing: false,
+            muted: chat.flags.muted,
+            pinned: chat.flags.pinned,
         })
         .collect();
 
     (items, resolved_selection)
 }
 
+/// Latest message first, ties by title.
+fn by_recency(left: &ChatSummary, right: &ChatSummary) -> Ordering {
+    let left_ts = left.last_message_at.unwrap_or(0);
+    let right_ts = right.last_message_at.unwrap_or(0);
+    match right_ts.cmp(&left_ts) {
+        Ordering::Equal => left.title.cmp(&right.title),
+        ordering => ordering,
+    }
+}
+
+/// Order of the section a chat is listed in: pinned chats, then the main
+/// list, then (sorting by folder) each folder and the archive.
+fn section_rank(chat: &ChatSummary, sort: ChatSort) -> (u8, i32) {
+    if chat.flags.pinned && !chat.flags.archived {
+        return (0, 0);
+    }
+    if sort != ChatSort::Folders {
+        return (1, 0);
+    }
+    match chat.flags.folder_id {
+        _ if chat.flags.archived => (3, 0),
+        Some(folder_id) => (2, folder_id),
+        None => (1, 0),
+    }
+}
+
+fn section_title(chat: &ChatSummary, sort: ChatSort) -> String {
+    match section_rank(chat, sort) {
+        (0, _) => "Pinned".to_string(),
+        (2, folder_id) => format!("Folder {folder_id}"),
+        (3, _) => "Archived".to_string(),
+        _ => "Chats".to_string(),
+    }
+}
+
+/// Presence of a private chat's peer, or the known member count of a group.
+fn chat_status(cache: &CacheManager, chat: &ChatSummary, now: i64) -> Option<String> {
+    match chat.peer_kind {
+        ChatPeerKind::User => presence_label(cache.presence(UserId(chat.chat_id.0))?, now),
+        ChatPeerKind::Group | ChatPeerKind::Channel => {
+            match cache.chat_members(chat.chat_id).len() {
+                0 => None,
+                1 => Some("1 member".to_string()),
+                count => Some(format!("{count} members")),
+            }
+        }
+        ChatPeerKind::Unknown => None,
+    }
+}
+
+fn presence_label(presence: Presence, now: i64) -> Option<String> {
+    let label = match presence {
+        Presence::Online { expires } if expires > now => "online".to_string(),
+        Presence::Online { expires: last_seen } | Presence::Offline { last_seen } => {
+            format!("last seen {}", time_ago(now - last_seen))
+        }
+        Presence::Recently => "last seen recently".to_string(),
+        Presence::LastWeek => "last seen within a week".to_string(),
+        Presence::LastMonth => "last seen within a month".to_string(),
+        Presence::Unknown => return None,
+    };
+    Some(label)
+}
+
+fn time_ago(seconds: i64) -> String {
+    match seconds.max(0) {
+        seconds if seconds < 60 => "just now".to_string(),
+        seconds if seconds < 3600 => format!("{}m ago", seconds / 60),
+        seconds if seconds < 86_400 => format!("{}h ago", seconds / 3600),
+        seconds => format!("{}d ago", seconds / 86_400),
+    }
+}
+
 fn chat_title(chat: &ChatSummary) -> String {
     if chat.title.trim().is_empty() {
         format!("Chat {}", chat.chat_id.0)
@@ -83,24 +584,169 @@ fn chat_title(chat: &ChatSummary) -> String {
     }
 }
 
-fn map_messages(mut messages: Vec<CachedMessage>) -> Vec<MessageItem> {
+fn map_messages(mut messages: Vec<CachedMessage>, now: i64) -> Vec<MessageItem> {
     messages.sort_by_key(|message| message.timestamp);
-    messages
+    let replied: Vec<Option<i64>> = messages
+        .iter()
+        .map(|message| message.reply_to.map(|message_id| message_id.0))
+        .collect();
+    let mut items: Vec<MessageItem> = messages
         .into_iter()
         .map(|message| MessageItem {
             id: message.message_id.0,
             author: message_author_label(&message),
             timestamp: format_timestamp(message.timestamp),
-            body: message.text,
+            sent_at: message.timestamp,
+            expires_in: message
+                .expires_at
+                .map(|expires_at| expires_at.saturating_sub(now)),
+            media: message.media.as_ref().map(|media| MediaItem {
+                label: media_label(media),
+                has_preview: false,
+                transcript: media
+                    .transcription
+                    .as_ref()
+                    .filter(|transcription| !transcription.text.is_empty())
+                    .map(|transcription| transcription.text.clone()),
+            }),
+            body: if message.deleted {
+                DELETED_MESSAGE_BODY.to_string()
+            } else {
+                message.text
+            },
+            reply_to: None,
+            delivery: message.delivery.map(delivery_mark),
+        })
+        .collect();
+    // Quotes of originals older than the loaded page only carry their id.
+    let quotes: Vec<Option<ReplyTarget>> = replied
+        .into_iter()
+        .map(|reply_to| {
+            let message_id = reply_to?;
+            let original = items.iter().find(|item| item.id == message_id);
+            Some(original.map_or_else(
+                || ReplyTarget {
+                    message_id,
+                    author: String::new(),
+                    snippet: String::new(),
+                },
+                ReplyTarget::for_message,
+            ))
         })
-        .collect()
+        .collect();
+    for (item, quote) in items.iter_mut().zip(quotes) {
+        item.reply_to = quote;
+    }
+    items
+}
+
+fn map_message_detail(message: &CachedMessage) -> MessageDetail {
+    MessageDetail {
+        id: message.message_id.0,
+        author: message_author_label(message),
+        body: if message.deleted {
+            DELETED_MESSAGE_BODY.to_string()
+        } else {
+            message.text.clone()
+        },
+        sent_at: format_exact_timestamp(message.timestamp),
+        edited_at: message.edit_timestamp.map(format_exact_timestamp),
+        expires_at: message.expires_at.map(format_exact_timestamp),
+        status: message
+            .delivery
+            .map(|delivery| delivery_label(delivery).to_string()),
+        reply_to: message
+            .reply_to
+            .map(|message_id| format!("message {}", message_id.0)),
+        reactions: message.reactions.iter().map(reaction_label).collect(),
+        attachment: message.media.as_ref().map(media_label),
+        scroll: 0,
+    }
+}
+
+/// Placeholder for an attachment, like `photo 1.2MB` or
+/// `voice 0:42 48KB · transcribed`.
+fn media_label(media: &MessageMedia) -> String {
+    let mut label = media.kind.label().to_string();
+    if let Some(duration) = media.duration {
+        label.push(' ');
+        label.push_str(&format_duration(duration));
+    }
+    if let Some(size) = media.size {
+        label.push(' ');
+        label.push_str(&format_size(size));
+    }
+    match &media.transcription {
+        Some(transcription) if transcription.pending => label.push_str(" · transcribing…"),
+        Some(_) => label.push_str(" · transcribed"),
+        None => {}
+    }
+    label
+}
+
+/// Play length as `m:ss`, or `h:mm:ss` from an hour up.
+fn format_duration(seconds: u32) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// Placeholder id the cache gives the local echo of `send_id`.
+fn echo_message_id(send_id: SendId) -> i64 {
+    -(send_id.0.min(i64::MAX as u64) as i64)
+}
+
+fn delivery_mark(delivery: DeliveryState) -> DeliveryMark {
+    match delivery {
+        DeliveryState::Pending => DeliveryMark::Queued,
+        DeliveryState::Sending => DeliveryMark::Sending,
+        DeliveryState::Sent => DeliveryMark::Sent,
+        DeliveryState::Read => DeliveryMark::Read,
+        DeliveryState::Failed => DeliveryMark::Failed,
+    }
+}
+
+fn delivery_label(delivery: DeliveryState) -> &'static str {
+    match delivery {
+        DeliveryState::Pending => "queued",
+        DeliveryState::Sending => "sending",
+        DeliveryState::Sent => "sent",
+        DeliveryState::Read => "read",
+        DeliveryState::Failed => "failed",
+    }
+}
+
+fn reaction_label(reaction: &MessageReaction) -> String {
+    let mine = if reaction.mine { " (you)" } else { "" };
+    format!("{} {}{}", reaction.emoji, reaction.count, mine)
 }
 
 fn message_author_label(message: &CachedMessage) -> String {
     if message.outgoing {
         "You".to_string()
     } else {
-        format!("User {}", message.author_id.0)
+        user_label(message.author_id)
+    }
+}
+
+fn user_label(user_id: UserId) -> String {
+    format!("User {}", user_id.0)
+}
+
+fn format_exact_timestamp(timestamp: i64) -> String {
+    let format =
+        match format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second] UTC") {
+            Ok(format) => format,
+            Err(_) => return timestamp.to_string(),
+        };
+    match OffsetDateTime::from_unix_timestamp(timestamp) {
+        Ok(date_time) => date_time
+            .format(&format)
+            .unwrap_or_else(|_| timestamp.to_string()),
+        Err(_) => timestamp.to_string(),
     }
 }
 
@@ -122,29 +768,14 @@ fn format_timestamp(timestamp: i64) -> String {
 mod tests {
     use super::*;
     use std::path::PathBuf;
-    use std::sync::{Arc, Mutex};
+    use std::sync::Arc;
     use std::time::Duration;
     use telegram_llm_core::telegram::{
-        CacheConfig, CacheError, CacheLimits, CacheSnapshot, CacheStore, ChatPeerKind, ChatSummary,
-        DomainEvent, MessageId, MessageNew, UserId,
+        CacheConfig, CacheLimits, CacheStore, ChatFlags, ChatMemberUpdate, ChatSummary,
+        MemberChange, MemoryCacheStore, MessageId, MessageMediaKind, MessageNew, OnlineStatus,
+        SendFailure, SendStatus, Transcription, Typing, TypingStopped,
     };
 
-    #[derive(Default)]
-    struct InMemoryStore {
-        snapshot: Mutex<CacheSnapshot>,
-    }
-
-    impl CacheStore for InMemoryStore {
-        fn load(&self) -> Result<CacheSnapshot, CacheError> {
-            Ok(self.snapshot.lock().unwrap().clone())
-        }
-
-        fn save(&self, snapshot: &CacheSnapshot) -> Result<(), CacheError> {
-            *self.snapshot.lock().unwrap() = snapshot.clone();
-            Ok(())
-        }
-    }
-
     fn cache_config() -> CacheConfig {
         CacheConfig {
             db_path: PathBuf::from(":memory:"),
@@ -152,8 +783,11 @@ mod tests {
                 max_chats: 10,
                 max_messages_per_chat: 50,
                 max_bytes: 0,
+                max_bytes_per_chat: 0,
             },
             flush_debounce: Duration::from_millis(5),
+            keep_tombstones: false,
+            startup_messages_per_chat: None,
         }
     }
 
@@ -165,6 +799,11 @@ mod tests {
             last_message_id: Some(MessageId(last_message_at)),
             last_message_at: Some(last_message_at),
             unread_count: Some(1),
+            last_read_message_id: None,
+            last_outbox_read_message_id: None,
+            last_accessed_at: None,
+            pinned_message_ids: Vec::new(),
+            flags: ChatFlags::default(),
         }
     }
 
@@ -176,12 +815,15 @@ mod tests {
             timestamp,
             text: format!("message-{}", message_id),
             outgoing,
+            ttl_period: None,
+            media: None,
+            reply_to: None,
         }
     }
 
     #[tokio::test]
     async fn selects_most_recent_chat_when_none_selected() {
-        let store: Arc<dyn CacheStore> = Arc::new(InMemoryStore::default());
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
         let manager = CacheManager::spawn(store, cache_config())
             .await
             .expect("spawn cache manager");
@@ -203,16 +845,22 @@ mod tests {
 
     #[tokio::test]
     async fn maps_messages_for_selected_chat() {
-        let store: Arc<dyn CacheStore> = Arc::new(InMemoryStore::default());
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
         let manager = CacheManager::spawn(store, cache_config())
             .await
             .expect("spawn cache manager");
 
         manager.upsert_chat(chat_summary(1, "General", 100));
         manager.upsert_chat(chat_summary(2, "Product", 200));
-        manager.apply_event(&DomainEvent::MessageNew(message_new(1, 1, 0, false)));
-        manager.apply_event(&DomainEvent::MessageNew(message_new(2, 1, 60, true)));
-        manager.apply_event(&DomainEvent::MessageNew(message_new(2, 2, 120, false)));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            1, 1, 0, false,
+        ))));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            2, 1, 60, true,
+        ))));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            2, 2, 120, false,
+        ))));
 
         let mut bridge = UiCacheBridge::new(None);
         bridge.set_selected_chat(Some(ChatId(2)));
@@ -228,4 +876,554 @@ mod tests {
 
         manager.shutdown().await;
     }
+
+    #[tokio::test]
+    async fn restores_draft_when_chat_regains_focus() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.upsert_chat(chat_summary(2, "Product", 200));
+
+        let mut bridge = UiCacheBridge::new(None);
+        assert_eq!(bridge.refresh(&manager), Some(ChatId(2)));
+        bridge.state.input.text = "ship it".to_string();
+
+        bridge.set_selected_chat(Some(ChatId(1)));
+        bridge.refresh(&manager);
+        assert!(bridge.state.input.text.is_empty());
+        assert_eq!(manager.draft(ChatId(2)).as_deref(), Some("ship it"));
+        bridge.state.input.text = "later".to_string();
+
+        bridge.set_selected_chat(Some(ChatId(2)));
+        bridge.refresh(&manager);
+        assert_eq!(bridge.state.input.text, "ship it");
+        assert_eq!(bridge.state.input.cursor, "ship it".len());
+        assert_eq!(manager.draft(ChatId(1)).as_deref(), Some("later"));
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn returns_to_where_each_chat_was_left() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.upsert_chat(chat_summary(2, "Product", 200));
+        for message_id in 1..=5 {
+            manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+                1,
+                message_id,
+                message_id * 10,
+                false,
+            ))));
+        }
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            2, 9, 90, false,
+        ))));
+
+        let mut bridge = UiCacheBridge::new(None);
+        bridge.set_selected_chat(Some(ChatId(1)));
+        bridge.refresh(&manager);
+        assert_eq!(bridge.state.message_view.cursor, Some(4));
+        bridge.state.message_view.cursor = Some(1);
+        bridge.state.message_view.scroll_offset = 1;
+        bridge.state.message_view.toggle_selection(3);
+
+        bridge.set_selected_chat(Some(ChatId(2)));
+        bridge.refresh(&manager);
+        assert_eq!(bridge.state.message_view.cursor, Some(0));
+        assert_eq!(bridge.state.message_view.scroll_offset, 0);
+        assert!(bridge.state.message_view.selected_ids.is_empty());
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            1, 6, 60, false,
+        ))));
+
+        bridge.set_selected_chat(Some(ChatId(1)));
+        bridge.refresh(&manager);
+        let view = &bridge.state.message_view;
+        assert_eq!(view.cursor_message_id(&bridge.state.messages), Some(2));
+        assert_eq!(view.scroll_offset, 1);
+        assert_eq!(view.selected_ids.iter().copied().collect::<Vec<_>>(), [3]);
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn older_messages_load_above_the_cursor() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        for message_id in 1..=5 {
+            manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+                1,
+                message_id,
+                message_id * 10,
+                false,
+            ))));
+        }
+
+        let mut bridge = UiCacheBridge::new(Some(2));
+        bridge.refresh(&manager);
+        bridge.state.message_view.cursor = Some(0);
+        assert_eq!(bridge.state.messages[0].id, 4);
+
+        bridge
+            .load_requested_history(&manager)
+            .await
+            .expect("nothing requested");
+        assert_eq!(bridge.state.messages.len(), 2);
+
+        bridge.request_older_messages();
+        bridge
+            .load_requested_history(&manager)
+            .await
+            .expect("load older messages");
+        assert_eq!(bridge.state.messages.len(), 5);
+        let view = &bridge.state.message_view;
+        assert_eq!(view.cursor_message_id(&bridge.state.messages), Some(4));
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn jumps_to_a_message_in_older_history() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.upsert_chat(chat_summary(2, "Product", 200));
+        // Older than chat 2's last message, so chat 2 stays on top.
+        for message_id in 1..=40 {
+            manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+                1, message_id, message_id, false,
+            ))));
+        }
+
+        let mut bridge = UiCacheBridge::new(Some(5));
+        assert_eq!(bridge.refresh(&manager), Some(ChatId(2)));
+        bridge.request_jump(ChatId(1), 3);
+        bridge
+            .run_requests(&manager)
+            .await
+            .expect("jump to message");
+
+        assert_eq!(bridge.selected_chat(), Some(ChatId(1)));
+        let view = &bridge.state.message_view;
+        assert_eq!(view.cursor_message_id(&bridge.state.messages), Some(3));
+        assert_eq!(view.scroll_offset, view.cursor.unwrap_or_default());
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn opens_detail_for_a_cached_message() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            1, 7, 3_723, false,
+        ))));
+
+        let mut bridge = UiCacheBridge::new(None);
+        bridge.refresh(&manager);
+        bridge.open_message_detail(&manager, 7);
+
+        let detail = bridge.state.message_detail.clone().expect("detail open");
+        assert_eq!(detail.author, "User 42");
+        assert_eq!(detail.body, "message-7");
+        assert_eq!(detail.sent_at, "1970-01-01 01:02:03 UTC");
+        assert_eq!(detail.status, None);
+
+        bridge.open_message_detail(&manager, 99);
+        assert!(bridge.state.message_detail.is_none());
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn photos_show_placeholders_until_their_preview_is_ready() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        let mut photo = message_new(1, 7, 100, false);
+        photo.media = Some(MessageMedia {
+            kind: MessageMediaKind::Photo,
+            size: Some(1_258_291),
+            thumbnail: Some(PhotoThumbnail {
+                photo_id: 5,
+                access_hash: 6,
+                file_reference: vec![1, 2],
+                dc_id: 2,
+                size_type: "m".to_string(),
+            }),
+            duration: None,
+            document: None,
+            transcription: None,
+        });
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(photo)));
+
+        let mut bridge = UiCacheBridge::new(None);
+        bridge.refresh(&manager);
+        let media = bridge.state.messages[0].media.clone().expect("media item");
+        assert_eq!(media.label, "photo 1.2MB");
+        assert!(!media.has_preview);
+        assert!(bridge.take_wanted_previews().is_empty());
+
+        bridge.enable_inline_previews();
+        bridge.refresh(&manager);
+        bridge.refresh(&manager);
+        let wanted = bridge.take_wanted_previews();
+        assert_eq!(wanted.len(), 1);
+        assert_eq!((wanted[0].0, wanted[0].1), (ChatId(1), 7));
+
+        bridge.preview_ready(ChatId(1), 7);
+        bridge.refresh(&manager);
+        assert!(bridge.state.messages[0]
+            .media
+            .as_ref()
+            .is_some_and(|media| media.has_preview));
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(40 * 1024), "40KB");
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn outgoing_messages_carry_delivery_marks() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            1, 7, 100, false,
+        ))));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            1, 8, 101, true,
+        ))));
+        manager.insert_pending(ChatId(1), SendId(3), "queued", 102);
+
+        let mut bridge = UiCacheBridge::new(None);
+        bridge.refresh(&manager);
+        let marks: Vec<_> = bridge
+            .state
+            .messages
+            .iter()
+            .map(|message| message.delivery)
+            .collect();
+        assert_eq!(
+            marks,
+            [None, Some(DeliveryMark::Sent), Some(DeliveryMark::Queued)]
+        );
+
+        manager.apply_send_status(SendId(3), &SendStatus::Sending { attempt: 1 });
+        bridge.refresh(&manager);
+        assert_eq!(
+            bridge.state.messages[2].delivery,
+            Some(DeliveryMark::Sending)
+        );
+        assert!(bridge.state.failed_sends.is_empty());
+
+        manager.apply_send_status(
+            SendId(3),
+            &SendStatus::Failed(SendFailure {
+                error: "FLOOD_WAIT".to_string(),
+                attempts: 3,
+                retryable: true,
+            }),
+        );
+        bridge.send_failed(SendId(3), "FLOOD_WAIT".to_string());
+        bridge.refresh(&manager);
+        assert_eq!(
+            bridge.state.failed_sends,
+            [FailedSend {
+                message_id: -3,
+                error: Some("FLOOD_WAIT".to_string()),
+            }]
+        );
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn replies_quote_the_message_they_answer() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(message_new(
+            1, 7, 100, false,
+        ))));
+        let mut reply = message_new(1, 8, 101, true);
+        reply.reply_to = Some(MessageId(7));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(reply)));
+        let mut late_reply = message_new(1, 9, 102, false);
+        late_reply.reply_to = Some(MessageId(3));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(late_reply)));
+
+        let mut bridge = UiCacheBridge::new(None);
+        bridge.refresh(&manager);
+        let quotes: Vec<_> = bridge
+            .state
+            .messages
+            .iter()
+            .map(|message| message.reply_to.clone())
+            .collect();
+        assert_eq!(quotes[0], None);
+        let quote = quotes[1].clone().expect("quote of a loaded message");
+        assert_eq!((quote.message_id, quote.snippet.as_str()), (7, "message-7"));
+        let quote = quotes[2].clone().expect("quote of an unloaded message");
+        assert_eq!((quote.message_id, quote.author.as_str()), (3, ""));
+
+        manager.shutdown().await;
+    }
+
+    #[test]
+    fn voice_labels_show_duration_and_transcription_status() {
+        let mut voice = MessageMedia {
+            kind: MessageMediaKind::Voice,
+            size: Some(48 * 1024),
+            thumbnail: None,
+            duration: Some(42),
+            document: None,
+            transcription: None,
+        };
+        assert_eq!(media_label(&voice), "voice 0:42 48KB");
+
+        voice.transcription = Some(Transcription {
+            text: String::new(),
+            pending: true,
+        });
+        assert_eq!(media_label(&voice), "voice 0:42 48KB · transcribing…");
+
+        voice.transcription = Some(Transcription {
+            text: "see you at five".to_string(),
+            pending: false,
+        });
+        voice.duration = Some(3_725);
+        assert_eq!(media_label(&voice), "voice 1:02:05 48KB · transcribed");
+    }
+
+    #[tokio::test]
+    async fn shows_typing_until_it_stops_or_lapses() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.upsert_chat(chat_summary(2, "Product", 200));
+        let typing = |chat_id: i64, user_id: i64| {
+            DomainEvent::Typing(Typing {
+                chat_id: ChatId(chat_id),
+                user_id: UserId(user_id),
+                timestamp: 0,
+            })
+        };
+
+        let mut bridge = UiCacheBridge::new(None);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        bridge.observe_events(&manager, &[typing(2, 42), typing(2, 7), typing(1, 9)], now);
+        bridge.refresh(&manager);
+        assert_eq!(bridge.state.typing, ["User 7", "User 42"]);
+        assert!(bridge.state.chats.iter().all(|chat| chat.typing));
+
+        bridge.observe_events(
+            &manager,
+            &[DomainEvent::TypingStopped(TypingStopped {
+                chat_id: ChatId(2),
+                user_id: UserId(7),
+                timestamp: 0,
+            })],
+            now,
+        );
+        bridge.refresh(&manager);
+        assert_eq!(bridge.state.typing, ["User 42"]);
+
+        bridge.observe_events(&manager, &[typing(2, 42)], now - TYPING_DISPLAY_SECS);
+        bridge.refresh(&manager);
+        assert!(bridge.state.typing.is_empty());
+        assert!(!bridge.state.chats[0].typing);
+        assert!(bridge.state.chats[1].typing);
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn toasts_new_messages_in_other_unmuted_chats() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.upsert_chat(chat_summary(2, "Product", 200));
+        let mut muted = chat_summary(3, "Noise", 50);
+        muted.flags.muted = true;
+        manager.upsert_chat(muted);
+
+        let mut bridge = UiCacheBridge::new(None);
+        bridge.refresh(&manager);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let events = [
+            message_new(1, 5, now, false),
+            message_new(1, 6, now, true),
+            message_new(2, 7, now, false),
+            message_new(3, 8, now, false),
+        ]
+        .map(|message| DomainEvent::MessageNew(Box::new(message)));
+        manager.apply_events(&events);
+        bridge.observe_events(&manager, &events, now);
+        bridge.refresh(&manager);
+
+        assert_eq!(
+            bridge.state.toasts,
+            vec![Toast {
+                chat_id: 1,
+                chat_title: "General".to_string(),
+                text: "User 42: message-5".to_string(),
+                expires_at: now + TOAST_DISPLAY_SECS,
+            }]
+        );
+
+        bridge.set_selected_chat(Some(ChatId(1)));
+        bridge.refresh(&manager);
+        assert!(bridge.state.toasts.is_empty());
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn titles_show_presence_or_member_count() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(5, "Ada", 100));
+        let mut group = chat_summary(-9, "Team", 200);
+        group.peer_kind = ChatPeerKind::Group;
+        manager.upsert_chat(group);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        manager.apply_event(&DomainEvent::OnlineStatus(OnlineStatus {
+            user_id: UserId(5),
+            presence: Presence::Offline {
+                last_seen: now - 2 * 3600,
+            },
+            timestamp: now,
+        }));
+        for user_id in [5, 6] {
+            manager.apply_event(&DomainEvent::ChatMemberUpdate(ChatMemberUpdate {
+                chat_id: ChatId(-9),
+                user_id: UserId(user_id),
+                change: MemberChange::Joined { inviter_id: None },
+                timestamp: now,
+            }));
+        }
+
+        let mut bridge = UiCacheBridge::new(None);
+        bridge.refresh(&manager);
+        assert_eq!(bridge.state.chat_status.as_deref(), Some("2 members"));
+
+        bridge.set_selected_chat(Some(ChatId(5)));
+        bridge.refresh(&manager);
+        assert_eq!(
+            bridge.state.chat_status.as_deref(),
+            Some("last seen 2h ago")
+        );
+
+        assert_eq!(
+            presence_label(Presence::Online { expires: now + 30 }, now).as_deref(),
+            Some("online")
+        );
+        assert_eq!(
+            presence_label(Presence::Online { expires: now - 30 }, now).as_deref(),
+            Some("last seen just now")
+        );
+        assert_eq!(presence_label(Presence::Unknown, now), None);
+
+        manager.shutdown().await;
+    }
+
+    #[test]
+    fn pinned_chats_lead_and_the_rest_follow_the_chosen_sort() {
+        let mut pinned = chat_summary(1, "Zulu", 100);
+        pinned.flags.pinned = true;
+        let mut unread = chat_summary(2, "alpha", 200);
+        unread.unread_count = Some(4);
+        let mut work = chat_summary(3, "Bravo", 300);
+        work.unread_count = None;
+        work.flags.folder_id = Some(7);
+        let mut archived = chat_summary(4, "Charlie", 400);
+        archived.unread_count = None;
+        archived.flags.archived = true;
+        let summaries = [pinned, unread, work, archived];
+        let listed = |sort| {
+            map_chat_summaries(&summaries, None, sort)
+                .0
+                .into_iter()
+                .map(|chat| (chat.id, chat.section))
+                .collect::<Vec<_>>()
+        };
+        let chats = |ids: [i64; 3]| ids.map(|id| (id, "Chats".to_string()));
+
+        let pinned_first = (1, "Pinned".to_string());
+        let recent = listed(ChatSort::Recent);
+        assert_eq!(recent[0], pinned_first);
+        assert_eq!(recent[1..], chats([4, 3, 2]));
+        assert_eq!(listed(ChatSort::UnreadFirst)[1..], chats([2, 4, 3]));
+        assert_eq!(listed(ChatSort::Alphabetical)[1..], chats([2, 3, 4]));
+        assert_eq!(
+            listed(ChatSort::Folders),
+            vec![
+                pinned_first,
+                (2, "Chats".to_string()),
+                (3, "Folder 7".to_string()),
+                (4, "Archived".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn follows_selection_made_in_the_chat_list() {
+        let store: Arc<dyn CacheStore> = Arc::new(MemoryCacheStore::new());
+        let manager = CacheManager::spawn(store, cache_config())
+            .await
+            .expect("spawn cache manager");
+
+        manager.upsert_chat(chat_summary(1, "General", 100));
+        manager.upsert_chat(chat_summary(2, "Product", 200));
+
+        let mut bridge = UiCacheBridge::new(None);
+        assert_eq!(bridge.refresh(&manager), Some(ChatId(2)));
+        for chat in bridge.state.chats.iter_mut() {
+            chat.is_selected = chat.id == 1;
+        }
+        bridge.sync_selection();
+
+        assert_eq!(bridge.refresh(&manager), Some(ChatId(1)));
+        assert_eq!(bridge.selected_chat(), Some(ChatId(1)));
+
+        manager.shutdown().await;
+    }
 }
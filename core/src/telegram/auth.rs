@@ -62,6 +62,8 @@ pub trait AuthClient: Send + Sync {
         except_ids: &[i64],
     ) -> Result<QrLoginResult>;
     async fn import_login_token(&self, token: &[u8], dc_id: Option<i32>) -> Result<QrLoginResult>;
+    /// Ends the session on Telegram's side, invalidating its auth key.
+    async fn sign_out(&self) -> Result<()>;
 }
 
 pub struct AuthFlow<C: AuthClient> {
@@ -120,6 +122,10 @@ impl<C: AuthClient> AuthFlow<C> {
             .import_login_token(&login.token, login.dc_id)
             .await
     }
+
+    pub async fn sign_out(&self) -> Result<()> {
+        self.client.sign_out().await
+    }
 }
 
 pub struct GrammersAuthClient {
@@ -241,6 +247,11 @@ impl AuthClient for GrammersAuthClient {
         };
         Ok(Self::map_login_token_result(result))
     }
+
+    async fn sign_out(&self) -> Result<()> {
+        self.client.sign_out().await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -336,6 +347,11 @@ mod tests {
                 .pop_front()
                 .unwrap_or(QrLoginResult::Authorized))
         }
+
+        async fn sign_out(&self) -> Result<()> {
+            self.state.lock().unwrap().authorized = false;
+            Ok(())
+        }
     }
 
     #[tokio::test]
@@ -378,4 +394,14 @@ mod tests {
         let poll = flow.poll_qr_login(&login).await.unwrap();
         assert_eq!(poll, QrLoginResult::Authorized);
     }
+
+    #[tokio::test]
+    async fn sign_out_deauthorizes_the_session() {
+        let client = MockAuthClient::new();
+        client.state.lock().unwrap().authorized = true;
+        let flow = AuthFlow::new(client, 1, "hash", vec![]);
+
+        flow.sign_out().await.unwrap();
+        assert!(!flow.is_authorized().await.unwrap());
+    }
 }
@@ -1,34 +1,61 @@
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use grammers_client::{Client, ClientConfiguration, UpdatesConfiguration};
 use grammers_mtsender::{ConnectionParams, SenderPool, SenderPoolHandle};
+use grammers_session::defs::{PeerId, PeerRef};
 use grammers_session::storages::SqliteSession;
 use grammers_session::updates::UpdatesLike;
+use grammers_session::Session;
+use grammers_tl_types as tl;
+use time::OffsetDateTime;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
 use crate::telegram::auth::{AuthFlow, GrammersAuthClient};
 use crate::telegram::error::Result;
-use crate::telegram::events::{spawn_domain_event_pump, EventStream};
+use crate::telegram::events::{
+    spawn_domain_event_pump, ChatId, DomainEvent, EventDropPolicy, EventMapper, EventStream,
+    ARCHIVE_FOLDER_ID,
+};
+use crate::telegram::journal::EventJournal;
+use crate::telegram::media::MediaDownloader;
 use crate::telegram::send::{spawn_grammers_send_pipeline, SendPipeline, SendPipelineConfig};
-use crate::telegram::updates::{spawn_telegram_update_pump, take_updates, UpdatePump};
+use crate::telegram::updates::{
+    spawn_telegram_update_pump, take_updates, ReconnectPolicy, UpdatePump,
+};
+
+/// Dialogs read per folder by [`TelegramBootstrap::sync_dialog_flags`];
+/// pinned chats come first.
+const DIALOG_SYNC_LIMIT: i32 = 100;
 
 #[derive(Debug, Clone)]
 pub struct UpdatesConfig {
     pub catch_up: bool,
     pub update_queue_limit: Option<usize>,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum EventDropPolicy {
-    DropOldest,
+    pub reconnect: ReconnectPolicy,
 }
 
 #[derive(Debug, Clone)]
 pub struct EventStreamConfig {
     pub buffer_size: usize,
     pub drop_policy: EventDropPolicy,
+    /// When set, raw updates are also forwarded to
+    /// [`EventStream::take_raw_updates`] through a buffer of this size.
+    pub raw_update_buffer: Option<usize>,
+    /// Records every mapped event before it is published.
+    pub journal: Option<Arc<dyn EventJournal>>,
+    /// How often [`EventStream::metrics`] are logged; `None` disables it.
+    pub metrics_log_interval: Option<Duration>,
+    /// How often the current [`ConnectionState`] is repeated; `None`
+    /// publishes changes only.
+    ///
+    /// [`ConnectionState`]: crate::telegram::events::ConnectionState
+    pub heartbeat_interval: Option<Duration>,
+    /// Whether logs may show message content, such as the raw update behind
+    /// an "unsupported telegram update" warning.
+    pub log_content: bool,
 }
 
 impl Default for EventStreamConfig {
@@ -36,6 +63,11 @@ impl Default for EventStreamConfig {
         Self {
             buffer_size: 1024,
             drop_policy: EventDropPolicy::DropOldest,
+            raw_update_buffer: None,
+            journal: None,
+            metrics_log_interval: Some(Duration::from_secs(60)),
+            heartbeat_interval: Some(Duration::from_secs(30)),
+            log_content: true,
         }
     }
 }
@@ -45,6 +77,7 @@ impl Default for UpdatesConfig {
         Self {
             catch_up: false,
             update_queue_limit: Some(100),
+            reconnect: ReconnectPolicy::default(),
         }
     }
 }
@@ -79,6 +112,7 @@ impl TelegramConfig {
 
 pub struct TelegramBootstrap {
     client: Client,
+    session: Arc<SqliteSession>,
     sender_handle: SenderPoolHandle,
     runner: JoinHandle<()>,
     updates: Option<mpsc::UnboundedReceiver<UpdatesLike>>,
@@ -123,6 +157,7 @@ impl TelegramBootstrap {
 
         Ok(Self {
             client,
+            session,
             sender_handle,
             runner,
             updates: Some(updates),
@@ -139,6 +174,21 @@ impl TelegramBootstrap {
         &self.client
     }
 
+    /// Peer to address requests about a cached chat to, using the access hash
+    /// the session stored when the chat was last seen.
+    pub fn peer_ref(&self, chat_id: ChatId) -> Option<PeerRef> {
+        let id = peer_id_from_chat(chat_id);
+        let info = self.session.peer(id)?;
+        Some(PeerRef {
+            id,
+            auth: info.auth(),
+        })
+    }
+
+    pub fn media_downloader(&self) -> MediaDownloader {
+        MediaDownloader::new(self.client.clone())
+    }
+
     pub fn auth_flow(&self) -> AuthFlow<GrammersAuthClient> {
         AuthFlow::new(
             GrammersAuthClient::new(self.client.clone()),
@@ -158,12 +208,38 @@ impl TelegramBootstrap {
             updates,
             self.updates_config.clone().into(),
             buffer,
+            self.updates_config.reconnect.clone(),
         ))
     }
 
     pub fn spawn_event_stream(&mut self, update_buffer: usize) -> Result<EventStream> {
         let update_pump = self.spawn_update_pump(update_buffer)?;
-        spawn_domain_event_pump(update_pump, self.event_stream_config.buffer_size)
+        spawn_domain_event_pump(update_pump, &self.event_stream_config)
+    }
+
+    /// Reads whether chats are pinned, muted or archived from the first page
+    /// of the main chat list and of the archive, which updates only report
+    /// once the state changes.
+    pub async fn sync_dialog_flags(&self) -> Result<DomainEvent> {
+        let mut dialogs = Vec::new();
+        for folder_id in [None, Some(ARCHIVE_FOLDER_ID)] {
+            let request = tl::functions::messages::GetDialogs {
+                exclude_pinned: false,
+                folder_id,
+                offset_date: 0,
+                offset_id: 0,
+                offset_peer: tl::enums::InputPeer::Empty,
+                limit: DIALOG_SYNC_LIMIT,
+                hash: 0,
+            };
+            match self.client.invoke(&request).await? {
+                tl::enums::messages::Dialogs::Dialogs(page) => dialogs.extend(page.dialogs),
+                tl::enums::messages::Dialogs::Slice(page) => dialogs.extend(page.dialogs),
+                tl::enums::messages::Dialogs::NotModified(_) => {}
+            }
+        }
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        Ok(EventMapper::new().map_dialogs(&dialogs, now))
     }
 
     pub fn spawn_send_pipeline(&self) -> SendPipeline {
@@ -176,6 +252,16 @@ impl TelegramBootstrap {
     }
 }
 
+/// Inverse of the Bot API dialog ids used for [`ChatId`].
+fn peer_id_from_chat(chat_id: ChatId) -> PeerId {
+    const CHANNEL_OFFSET: i64 = 1_000_000_000_000;
+    match chat_id.0 {
+        id if id > 0 => PeerId::user(id),
+        id if id > -CHANNEL_OFFSET => PeerId::chat(-id),
+        id => PeerId::channel(-id - CHANNEL_OFFSET),
+    }
+}
+
 impl From<UpdatesConfig> for UpdatesConfiguration {
     fn from(config: UpdatesConfig) -> Self {
         UpdatesConfiguration {
@@ -184,3 +270,16 @@ impl From<UpdatesConfig> for UpdatesConfiguration {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chat_ids_map_back_to_their_peers() {
+        for peer in [PeerId::user(42), PeerId::chat(77), PeerId::channel(3003)] {
+            let chat_id = ChatId(peer.bot_api_dialog_id());
+            assert_eq!(peer_id_from_chat(chat_id), peer);
+        }
+    }
+}
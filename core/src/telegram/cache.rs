@@ -1,15 +1,24 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use sqlite::{Connection, State, Value};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tokio::time::Instant;
 use tracing::{info, warn};
 
-use crate::telegram::events::{ChatId, DomainEvent, MessageId, UserId};
+use crate::telegram::cache_crypto::{CacheCipher, KEY_CHECK_PLAINTEXT};
+use crate::telegram::cache_export::{render_transcript, ExportFormat, ExportRange};
+use crate::telegram::events::{
+    AudioTranscribed, ChatFlags, ChatId, DocumentFile, DomainEvent, MemberChange, MessageId,
+    MessageMedia, MessageMediaKind, MessageReaction, PhotoThumbnail, Presence, UserId,
+};
+use crate::telegram::send::{SendId, SendResult, SendStatus};
 
 const SCHEMA: &str = r#"
 CREATE TABLE IF NOT EXISTS chats (
@@ -19,6 +28,14 @@ CREATE TABLE IF NOT EXISTS chats (
     last_message_id INTEGER,
     last_message_at INTEGER,
     unread_count INTEGER,
+    last_read_message_id INTEGER,
+    last_outbox_read_message_id INTEGER,
+    last_accessed_at INTEGER,
+    pinned_message_ids TEXT,
+    folder_id INTEGER,
+    muted INTEGER NOT NULL DEFAULT 0,
+    archived INTEGER NOT NULL DEFAULT 0,
+    pinned INTEGER NOT NULL DEFAULT 0,
     updated_at INTEGER NOT NULL
 );
 CREATE TABLE IF NOT EXISTS messages (
@@ -29,13 +46,88 @@ CREATE TABLE IF NOT EXISTS messages (
     edit_timestamp INTEGER,
     text TEXT NOT NULL,
     outgoing INTEGER NOT NULL,
+    expires_at INTEGER,
+    reactions TEXT,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    delivery TEXT,
+    media TEXT,
+    reply_to INTEGER,
     PRIMARY KEY (chat_id, message_id)
 );
 CREATE INDEX IF NOT EXISTS idx_messages_chat_id ON messages(chat_id);
 CREATE INDEX IF NOT EXISTS idx_messages_chat_timestamp ON messages(chat_id, timestamp);
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+    text,
+    content='messages',
+    content_rowid='rowid'
+);
+CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts(rowid, text) VALUES (new.rowid, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts(messages_fts, rowid, text) VALUES ('delete', old.rowid, old.text);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE ON messages BEGIN
+    INSERT INTO messages_fts(messages_fts, rowid, text) VALUES ('delete', old.rowid, old.text);
+    INSERT INTO messages_fts(rowid, text) VALUES (new.rowid, new.text);
+END;
+CREATE TABLE IF NOT EXISTS drafts (
+    chat_id INTEGER PRIMARY KEY,
+    text TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS message_embeddings (
+    chat_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    model TEXT NOT NULL,
+    vector BLOB NOT NULL,
+    PRIMARY KEY (chat_id, message_id, model)
+);
+CREATE TABLE IF NOT EXISTS cache_meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+"#;
+
+/// Statements that detach the FTS index when text columns are encrypted, so
+/// ciphertext is never indexed and no plaintext lingers in the index.
+const DISABLE_FTS_SYNC: &str = r#"
+DROP TRIGGER IF EXISTS messages_fts_insert;
+DROP TRIGGER IF EXISTS messages_fts_delete;
+DROP TRIGGER IF EXISTS messages_fts_update;
+INSERT INTO messages_fts(messages_fts) VALUES ('delete-all');
+PRAGMA secure_delete = ON;
 "#;
 
+/// Removes vectors whose message is no longer cached.
+const PRUNE_EMBEDDINGS: &str = "DELETE FROM message_embeddings WHERE NOT EXISTS (\
+     SELECT 1 FROM messages m \
+     WHERE m.chat_id = message_embeddings.chat_id AND m.message_id = message_embeddings.message_id)";
+
+const MESSAGE_COLUMNS: &str =
+    "chat_id, message_id, author_id, timestamp, edit_timestamp, text, outgoing, expires_at, reactions, deleted, delivery, media, reply_to";
+
+/// Columns added after the initial schema, applied to existing databases on open.
+const COLUMN_MIGRATIONS: &[(&str, &str, &str)] = &[
+    ("messages", "expires_at", "INTEGER"),
+    ("chats", "last_read_message_id", "INTEGER"),
+    ("chats", "last_accessed_at", "INTEGER"),
+    ("messages", "reactions", "TEXT"),
+    ("messages", "deleted", "INTEGER NOT NULL DEFAULT 0"),
+    ("chats", "pinned_message_ids", "TEXT"),
+    ("chats", "folder_id", "INTEGER"),
+    ("chats", "muted", "INTEGER NOT NULL DEFAULT 0"),
+    ("chats", "archived", "INTEGER NOT NULL DEFAULT 0"),
+    ("chats", "pinned", "INTEGER NOT NULL DEFAULT 0"),
+    ("messages", "delivery", "TEXT"),
+    ("messages", "media", "TEXT"),
+    ("messages", "reply_to", "INTEGER"),
+    ("chats", "last_outbox_read_message_id", "INTEGER"),
+];
+
+const SQLITE_BUSY_TIMEOUT_MS: u64 = 5_000;
 const MESSAGE_OVERHEAD_BYTES: usize = 64;
+const REACTION_OVERHEAD_BYTES: usize = 16;
 const CHAT_OVERHEAD_BYTES: usize = 64;
 
 #[derive(Debug, thiserror::Error)]
@@ -46,6 +138,15 @@ pub enum CacheError {
     Io(#[from] std::io::Error),
     #[error("cache task failed: {0}")]
     Task(String),
+    #[error("cache encryption error: {0}")]
+    Crypto(String),
+    #[error("export failed: {0}")]
+    Export(String),
+    #[error("{0} is not supported by this cache store")]
+    Unsupported(&'static str),
+    #[cfg(feature = "postgres")]
+    #[error("postgres error: {0}")]
+    Postgres(#[from] postgres::Error),
 }
 
 pub type Result<T> = std::result::Result<T, CacheError>;
@@ -59,7 +160,7 @@ pub enum ChatPeerKind {
 }
 
 impl ChatPeerKind {
-    fn as_str(self) -> &'static str {
+    pub(crate) fn as_str(self) -> &'static str {
         match self {
             ChatPeerKind::User => "user",
             ChatPeerKind::Group => "group",
@@ -68,7 +169,7 @@ impl ChatPeerKind {
         }
     }
 
-    fn from_str(raw: &str) -> Self {
+    pub(crate) fn from_str(raw: &str) -> Self {
         match raw {
             "user" => ChatPeerKind::User,
             "group" => ChatPeerKind::Group,
@@ -86,6 +187,58 @@ pub struct ChatSummary {
     pub last_message_id: Option<MessageId>,
     pub last_message_at: Option<i64>,
     pub unread_count: Option<u32>,
+    /// Highest message id we have read; later incoming messages are unread.
+    pub last_read_message_id: Option<MessageId>,
+    /// Highest message id the other side has read; our messages up to it
+    /// show as read.
+    pub last_outbox_read_message_id: Option<MessageId>,
+    /// When the chat was last opened in the UI; viewed chats are evicted last.
+    pub last_accessed_at: Option<i64>,
+    /// Pinned messages, most recently pinned first; the banner shows the first.
+    pub pinned_message_ids: Vec<MessageId>,
+    pub flags: ChatFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChatMember {
+    pub user_id: UserId,
+    pub admin: bool,
+}
+
+/// Progress of an outgoing message, for sent/read ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryState {
+    /// Enqueued locally; the message still carries its placeholder id.
+    Pending,
+    /// Picked up by the send pipeline and on its way to Telegram.
+    Sending,
+    Sent,
+    /// Covered by the peer's outbox read receipt.
+    Read,
+    Failed,
+}
+
+impl DeliveryState {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            DeliveryState::Pending => "pending",
+            DeliveryState::Sending => "sending",
+            DeliveryState::Sent => "sent",
+            DeliveryState::Read => "read",
+            DeliveryState::Failed => "failed",
+        }
+    }
+
+    pub(crate) fn from_str(raw: &str) -> Option<Self> {
+        match raw {
+            "pending" => Some(DeliveryState::Pending),
+            "sending" => Some(DeliveryState::Sending),
+            "sent" => Some(DeliveryState::Sent),
+            "read" => Some(DeliveryState::Read),
+            "failed" => Some(DeliveryState::Failed),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -97,6 +250,36 @@ pub struct CachedMessage {
     pub edit_timestamp: Option<i64>,
     pub text: String,
     pub outgoing: bool,
+    pub expires_at: Option<i64>,
+    pub reactions: Vec<MessageReaction>,
+    /// Tombstone left behind when a deleted message is kept for display.
+    pub deleted: bool,
+    /// Set for outgoing messages only.
+    pub delivery: Option<DeliveryState>,
+    pub media: Option<MessageMedia>,
+    pub reply_to: Option<MessageId>,
+}
+
+impl CachedMessage {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSearchHit {
+    pub message: CachedMessage,
+    /// Excerpt around the match with hits wrapped in `[` and `]`.
+    pub snippet: String,
+}
+
+/// Semantic-search vector for one message under one embedding model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageEmbedding {
+    pub chat_id: ChatId,
+    pub message_id: MessageId,
+    pub model: String,
+    pub vector: Vec<f32>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -104,6 +287,9 @@ pub struct CacheLimits {
     pub max_chats: usize,
     pub max_messages_per_chat: usize,
     pub max_bytes: usize,
+    /// Trims a chat's oldest messages once they exceed this many bytes; the
+    /// newest message is always kept. Zero disables the limit.
+    pub max_bytes_per_chat: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -111,18 +297,125 @@ pub struct CacheConfig {
     pub db_path: PathBuf,
     pub limits: CacheLimits,
     pub flush_debounce: Duration,
+    /// Keep deleted messages as tombstones without content instead of dropping them.
+    pub keep_tombstones: bool,
+    /// Load only the newest N messages per chat at startup and hydrate older
+    /// history from the store on demand. `None` loads everything.
+    pub startup_messages_per_chat: Option<usize>,
+}
+
+/// Unsent composer text for a chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatDraft {
+    pub chat_id: ChatId,
+    pub text: String,
+    pub updated_at: i64,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct CacheSnapshot {
     pub chats: Vec<ChatSummary>,
     pub messages: Vec<CachedMessage>,
+    pub drafts: Vec<ChatDraft>,
+}
+
+/// Chats changed since the last flush. Each listed chat carries its complete
+/// resident message set, replacing whatever the store held for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheDelta {
+    pub chats: Vec<ChatSummary>,
+    pub messages: Vec<CachedMessage>,
+    pub removed_chats: Vec<ChatId>,
+    /// Drafts changed since the last flush; an empty text clears the stored draft.
+    pub drafts: Vec<ChatDraft>,
+    /// Chats only partly resident: stored rows older than the floor are kept.
+    pub history_floors: Vec<(ChatId, MessageId)>,
+    /// Deleted messages below a chat's floor, which the rewrite cannot see.
+    pub deleted_history: Vec<(ChatId, MessageId)>,
+}
+
+impl CacheDelta {
+    pub fn is_empty(&self) -> bool {
+        self.chats.is_empty()
+            && self.removed_chats.is_empty()
+            && self.drafts.is_empty()
+            && self.deleted_history.is_empty()
+    }
+
+    fn touched_chats(&self) -> HashSet<ChatId> {
+        self.chats
+            .iter()
+            .map(|chat| chat.chat_id)
+            .chain(self.removed_chats.iter().copied())
+            .collect()
+    }
 }
 
 pub trait CacheStore: Send + Sync {
     fn load(&self) -> Result<CacheSnapshot>;
     fn save(&self, snapshot: &CacheSnapshot) -> Result<()>;
 
+    /// Loads every chat and draft but only the newest `per_chat` messages of
+    /// each chat. Placeholder echoes are always included.
+    fn load_recent(&self, per_chat: usize) -> Result<CacheSnapshot> {
+        let mut snapshot = self.load()?;
+        let mut messages = std::mem::take(&mut snapshot.messages);
+        messages
+            .sort_by_key(|message| (message.chat_id.0, std::cmp::Reverse(message.message_id.0)));
+        let mut kept: HashMap<ChatId, usize> = HashMap::new();
+        messages.retain(|message| {
+            let count = kept.entry(message.chat_id).or_default();
+            *count += 1;
+            *count <= per_chat || message.message_id.0 <= 0
+        });
+        messages.sort_by_key(|message| (message.chat_id.0, message.timestamp));
+        snapshot.messages = messages;
+        Ok(snapshot)
+    }
+
+    /// Persists only the chats in `delta`. Stores that cannot write partially
+    /// fall back to rewriting a merged snapshot.
+    fn save_delta(&self, delta: &CacheDelta) -> Result<()> {
+        let touched = delta.touched_chats();
+        let floors = delta
+            .history_floors
+            .iter()
+            .copied()
+            .collect::<HashMap<_, _>>();
+        let mut snapshot = self.load()?;
+        snapshot
+            .chats
+            .retain(|chat| !touched.contains(&chat.chat_id));
+        snapshot.messages.retain(|message| {
+            if delta
+                .deleted_history
+                .contains(&(message.chat_id, message.message_id))
+            {
+                return false;
+            }
+            !touched.contains(&message.chat_id)
+                || floors
+                    .get(&message.chat_id)
+                    .is_some_and(|floor| below_floor(message.message_id, *floor))
+        });
+        snapshot.chats.extend(delta.chats.iter().cloned());
+        snapshot.messages.extend(delta.messages.iter().cloned());
+        snapshot.drafts.retain(|draft| {
+            !delta
+                .drafts
+                .iter()
+                .any(|changed| changed.chat_id == draft.chat_id)
+        });
+        snapshot.drafts.extend(
+            delta
+                .drafts
+                .iter()
+                .filter(|draft| !draft.text.is_empty())
+                .cloned(),
+        );
+        self.save(&snapshot)
+    }
+
     fn upsert_chat(&self, summary: &ChatSummary) -> Result<()> {
         let mut snapshot = self.load()?;
         if let Some(existing) = snapshot
@@ -160,356 +453,2267 @@ pub trait CacheStore: Send + Sync {
     fn clear(&self) -> Result<()> {
         self.save(&CacheSnapshot::default())
     }
+
+    /// Returns up to `limit` messages older than `before`, oldest first.
+    /// Messages expired by `now` are left out.
+    fn messages_before(
+        &self,
+        chat_id: ChatId,
+        before: MessageId,
+        limit: usize,
+        now: i64,
+    ) -> Result<Vec<CachedMessage>> {
+        let mut messages = self
+            .load()?
+            .messages
+            .into_iter()
+            .filter(|message| message.chat_id == chat_id && message.message_id.0 < before.0)
+            .filter(|message| !message.is_expired(now))
+            .collect::<Vec<_>>();
+        messages.sort_by_key(|message| message.message_id.0);
+        let skip = messages.len().saturating_sub(limit);
+        Ok(messages.split_off(skip))
+    }
+
+    /// Messages expired by `now` are left out.
+    fn search(
+        &self,
+        query: &str,
+        chat_id: Option<ChatId>,
+        limit: usize,
+        now: i64,
+    ) -> Result<Vec<MessageSearchHit>> {
+        Ok(search_snapshot(self.load()?, query, chat_id, limit, now))
+    }
+
+    /// Inserts or replaces vectors keyed by `(chat_id, message_id, model)`.
+    fn upsert_embeddings(&self, _embeddings: &[MessageEmbedding]) -> Result<()> {
+        Err(CacheError::Unsupported("embeddings"))
+    }
+
+    fn load_embeddings(&self, _model: &str) -> Result<Vec<MessageEmbedding>> {
+        Err(CacheError::Unsupported("embeddings"))
+    }
+
+    /// Returns up to `limit` cached messages, newest first, that have no
+    /// vector for `model` yet, so the index can be built incrementally.
+    fn messages_without_embedding(&self, model: &str, limit: usize) -> Result<Vec<CachedMessage>> {
+        let embedded = self
+            .load_embeddings(model)?
+            .into_iter()
+            .map(|embedding| (embedding.chat_id, embedding.message_id))
+            .collect::<HashSet<_>>();
+        let mut messages = self
+            .load()?
+            .messages
+            .into_iter()
+            .filter(|message| {
+                !message.deleted
+                    && !message.text.is_empty()
+                    && !embedded.contains(&(message.chat_id, message.message_id))
+            })
+            .collect::<Vec<_>>();
+        messages.sort_by_key(|message| std::cmp::Reverse(message.timestamp));
+        messages.truncate(limit);
+        Ok(messages)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct SqliteCacheStore {
-    path: PathBuf,
+/// Placeholder echoes have non-positive ids and are always resident.
+fn below_floor(message_id: MessageId, floor: MessageId) -> bool {
+    message_id.0 > 0 && message_id.0 < floor.0
 }
 
-impl SqliteCacheStore {
-    pub fn new(path: PathBuf) -> Self {
-        Self { path }
+/// Case-insensitive substring search over a loaded snapshot, newest first.
+fn search_snapshot(
+    snapshot: CacheSnapshot,
+    query: &str,
+    chat_id: Option<ChatId>,
+    limit: usize,
+    now: i64,
+) -> Vec<MessageSearchHit> {
+    let needle = query.trim().to_lowercase();
+    if needle.is_empty() || limit == 0 {
+        return Vec::new();
     }
+    let mut messages = snapshot
+        .messages
+        .into_iter()
+        .filter(|message| chat_id.is_none_or(|chat_id| message.chat_id == chat_id))
+        .filter(|message| !message.is_expired(now))
+        .filter(|message| message.text.to_lowercase().contains(&needle))
+        .collect::<Vec<_>>();
+    messages.sort_by_key(|message| std::cmp::Reverse(message.timestamp));
+    messages
+        .into_iter()
+        .take(limit)
+        .map(|message| MessageSearchHit {
+            snippet: message.text.clone(),
+            message,
+        })
+        .collect()
+}
 
-    fn open_connection(&self) -> Result<Connection> {
-        if let Some(parent) = self.path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let connection = sqlite::open(&self.path)?;
-        connection.execute(SCHEMA)?;
-        Ok(connection)
-    }
+/// Process-local store for tests and demo mode; nothing survives a restart.
+#[derive(Debug, Default)]
+pub struct MemoryCacheStore {
+    snapshot: Mutex<CacheSnapshot>,
+    embeddings: Mutex<Vec<MessageEmbedding>>,
+    save_count: AtomicUsize,
 }
 
-impl CacheStore for SqliteCacheStore {
-    fn load(&self) -> Result<CacheSnapshot> {
-        let connection = self.open_connection()?;
-        let mut chats = Vec::new();
-        let mut messages = Vec::new();
+impl MemoryCacheStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        let mut chat_stmt = connection.prepare(
-            "SELECT chat_id, title, peer_kind, last_message_id, last_message_at, unread_count, updated_at FROM chats",
-        )?;
-        while let State::Row = chat_stmt.next()? {
-            let chat_id = ChatId(chat_stmt.read::<i64, _>(0)?);
-            let title = chat_stmt.read::<String, _>(1)?;
-            let peer_kind = ChatPeerKind::from_str(chat_stmt.read::<String, _>(2)?.as_str());
-            let last_message_id = chat_stmt.read::<Option<i64>, _>(3)?;
-            let last_message_at = chat_stmt.read::<Option<i64>, _>(4)?;
-            let unread_count = chat_stmt.read::<Option<i64>, _>(5)?;
-            let _updated_at = chat_stmt.read::<i64, _>(6)?;
-
-            chats.push(ChatSummary {
-                chat_id,
-                title,
-                peer_kind,
-                last_message_id: last_message_id.map(MessageId),
-                last_message_at,
-                unread_count: unread_count.map(|value| value as u32),
-            });
+    pub fn with_snapshot(snapshot: CacheSnapshot) -> Self {
+        Self {
+            snapshot: Mutex::new(snapshot),
+            embeddings: Mutex::new(Vec::new()),
+            save_count: AtomicUsize::new(0),
         }
+    }
 
-        let mut message_stmt = connection.prepare(
-            "SELECT chat_id, message_id, author_id, timestamp, edit_timestamp, text, outgoing FROM messages ORDER BY chat_id, timestamp",
-        )?;
-        while let State::Row = message_stmt.next()? {
-            let chat_id = ChatId(message_stmt.read::<i64, _>(0)?);
-            let message_id = MessageId(message_stmt.read::<i64, _>(1)?);
-            let author_id = UserId(message_stmt.read::<i64, _>(2)?);
-            let timestamp = message_stmt.read::<i64, _>(3)?;
-            let edit_timestamp = message_stmt.read::<Option<i64>, _>(4)?;
-            let text = message_stmt.read::<String, _>(5)?;
-            let outgoing = message_stmt.read::<i64, _>(6)? != 0;
-
-            messages.push(CachedMessage {
-                chat_id,
-                message_id,
-                author_id,
-                timestamp,
-                edit_timestamp,
-                text,
-                outgoing,
-            });
+    /// Current contents, as the next `load` would return them.
+    pub fn snapshot(&self) -> CacheSnapshot {
+        match self.snapshot.lock() {
+            Ok(snapshot) => snapshot.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
         }
+    }
+
+    /// Number of completed `save` calls, useful for asserting flush behavior.
+    pub fn save_count(&self) -> usize {
+        self.save_count.load(Ordering::SeqCst)
+    }
+}
 
-        Ok(CacheSnapshot { chats, messages })
+impl CacheStore for MemoryCacheStore {
+    fn load(&self) -> Result<CacheSnapshot> {
+        Ok(self.snapshot())
     }
 
     fn save(&self, snapshot: &CacheSnapshot) -> Result<()> {
-        let connection = self.open_connection()?;
-        connection.execute("BEGIN IMMEDIATE TRANSACTION")?;
-        connection.execute("DELETE FROM messages")?;
-        connection.execute("DELETE FROM chats")?;
+        let mut guard = match self.snapshot.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *guard = snapshot.clone();
+        self.save_count.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
 
-        {
-            let mut chat_stmt = connection.prepare(
-                "INSERT INTO chats (chat_id, title, peer_kind, last_message_id, last_message_at, unread_count, updated_at) VALUES (:chat_id, :title, :peer_kind, :last_message_id, :last_message_at, :unread_count, :updated_at)",
-            )?;
-            for chat in &snapshot.chats {
-                let updated_at = chat.last_message_at.unwrap_or(0);
-                chat_stmt.bind_iter::<_, (_, Value)>([
-                    (":chat_id", (chat.chat_id.0).into()),
-                    (":title", chat.title.clone().into()),
-                    (":peer_kind", chat.peer_kind.as_str().into()),
-                    (
-                        ":last_message_id",
-                        chat.last_message_id.map(|id| id.0).into(),
-                    ),
-                    (":last_message_at", chat.last_message_at.into()),
-                    (
-                        ":unread_count",
-                        chat.unread_count.map(|value| value as i64).into(),
-                    ),
-                    (":updated_at", updated_at.into()),
-                ])?;
-                let _ = chat_stmt.next()?;
-                chat_stmt.reset()?;
-            }
+    fn upsert_embeddings(&self, embeddings: &[MessageEmbedding]) -> Result<()> {
+        let mut guard = match self.embeddings.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        for embedding in embeddings {
+            guard.retain(|existing| {
+                !(existing.chat_id == embedding.chat_id
+                    && existing.message_id == embedding.message_id
+                    && existing.model == embedding.model)
+            });
+            guard.push(embedding.clone());
         }
-
-        {
-            let mut message_stmt = connection.prepare(
-                "INSERT INTO messages (chat_id, message_id, author_id, timestamp, edit_timestamp, text, outgoing) VALUES (:chat_id, :message_id, :author_id, :timestamp, :edit_timestamp, :text, :outgoing)",
-            )?;
-            for message in &snapshot.messages {
-                message_stmt.bind_iter::<_, (_, Value)>([
-                    (":chat_id", (message.chat_id.0).into()),
-                    (":message_id", (message.message_id.0).into()),
-                    (":author_id", (message.author_id.0).into()),
-                    (":timestamp", message.timestamp.into()),
-                    (":edit_timestamp", message.edit_timestamp.into()),
-                    (":text", message.text.clone().into()),
-                    (
-                        ":outgoing",
-                        if message.outgoing { 1i64 } else { 0i64 }.into(),
-                    ),
-                ])?;
-                let _ = message_stmt.next()?;
-                message_stmt.reset()?;
-            }
-        }
-
-        connection.execute("COMMIT")?;
         Ok(())
     }
+
+    fn load_embeddings(&self, model: &str) -> Result<Vec<MessageEmbedding>> {
+        let guard = match self.embeddings.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Ok(guard
+            .iter()
+            .filter(|embedding| embedding.model == model)
+            .cloned()
+            .collect())
+    }
 }
 
-#[derive(Debug)]
-pub struct CacheManager {
-    inner: Arc<RwLock<ChatCache>>,
-    flush_tx: mpsc::UnboundedSender<FlushCommand>,
-    join: JoinHandle<()>,
+/// Sqlite-backed store that keeps one long-lived connection in WAL mode.
+///
+/// The connection is opened lazily on first use so the schema and migrations
+/// run once per process instead of on every load/save. When built with
+/// [`SqliteCacheStore::encrypted`], message text and chat titles are sealed
+/// with a passphrase-derived key before they reach disk.
+pub struct SqliteCacheStore {
+    path: PathBuf,
+    passphrase: Option<String>,
+    connection: Mutex<Option<OpenStore>>,
 }
 
-impl CacheManager {
-    pub async fn spawn(store: Arc<dyn CacheStore>, config: CacheConfig) -> Result<Self> {
-        let snapshot = tokio::task::spawn_blocking({
-            let store = Arc::clone(&store);
-            move || store.load()
-        })
-        .await
-        .map_err(|err| CacheError::Task(err.to_string()))??;
+struct OpenStore {
+    connection: Connection,
+    cipher: Option<CacheCipher>,
+}
 
-        let cache = ChatCache::from_snapshot(snapshot, config.limits);
-        let inner = Arc::new(RwLock::new(cache));
-        let (flush_tx, flush_rx) = mpsc::unbounded_channel();
-        let join = spawn_flush_task(Arc::clone(&inner), store, flush_rx, config.flush_debounce);
+impl std::fmt::Debug for SqliteCacheStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SqliteCacheStore")
+            .field("path", &self.path)
+            .field("encrypted", &self.passphrase.is_some())
+            .finish_non_exhaustive()
+    }
+}
 
-        info!(
-            chats = inner.read().map(|cache| cache.chat_count()).unwrap_or(0),
-            "cache loaded"
-        );
+impl SqliteCacheStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            passphrase: None,
+            connection: Mutex::new(None),
+        }
+    }
 
-        Ok(Self {
-            inner,
-            flush_tx,
-            join,
-        })
+    /// Opens the cache with encryption at rest. Plaintext rows left by an
+    /// unencrypted cache are re-sealed on the next save.
+    pub fn encrypted(path: PathBuf, passphrase: impl Into<String>) -> Self {
+        Self {
+            path,
+            passphrase: Some(passphrase.into()),
+            connection: Mutex::new(None),
+        }
     }
 
-    pub fn apply_event(&self, event: &DomainEvent) {
-        let mut cache = match self.inner.write() {
-            Ok(cache) => cache,
-            Err(poisoned) => poisoned.into_inner(),
-        };
-        let stats = cache.apply_event(event);
-        if stats.any_evicted() {
-            info!(
-                chats = stats.chats_evicted,
-                messages = stats.messages_evicted,
-                "cache eviction applied"
-            );
+    fn open_store(&self) -> Result<OpenStore> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
-        let _ = self.flush_tx.send(FlushCommand::Dirty);
+        let connection = sqlite::open(&self.path)?;
+        connection.execute(format!(
+            "PRAGMA busy_timeout = {SQLITE_BUSY_TIMEOUT_MS}; PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;"
+        ))?;
+        let has_fts = table_exists(&connection, "messages_fts")?;
+        connection.execute(SCHEMA)?;
+        apply_column_migrations(&connection)?;
+        let cipher = unlock_cipher(&connection, self.passphrase.as_deref())?;
+        if cipher.is_some() {
+            connection.execute(DISABLE_FTS_SYNC)?;
+        } else if !has_fts {
+            // Index rows written before the FTS table existed.
+            connection.execute("INSERT INTO messages_fts(messages_fts) VALUES ('rebuild')")?;
+        }
+        Ok(OpenStore { connection, cipher })
     }
 
-    pub fn upsert_chat(&self, summary: ChatSummary) {
-        let mut cache = match self.inner.write() {
-            Ok(cache) => cache,
+    fn with_store<T>(&self, op: impl FnOnce(&OpenStore) -> Result<T>) -> Result<T> {
+        let mut guard = match self.connection.lock() {
+            Ok(guard) => guard,
             Err(poisoned) => poisoned.into_inner(),
         };
-        let stats = cache.upsert_chat(summary);
-        if stats.any_evicted() {
-            info!(
-                chats = stats.chats_evicted,
-                messages = stats.messages_evicted,
-                "cache eviction applied"
-            );
+        if guard.is_none() {
+            *guard = Some(self.open_store()?);
         }
-        let _ = self.flush_tx.send(FlushCommand::Dirty);
+        let store = guard.as_ref().expect("sqlite connection opened");
+        op(store)
     }
 
-    pub fn chat_summaries(&self) -> Vec<ChatSummary> {
-        let cache = self.inner.read().map(|cache| cache.chat_summaries());
-        cache.unwrap_or_default()
+    fn with_connection<T>(&self, op: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        self.with_store(|store| op(&store.connection))
     }
+}
 
-    pub fn messages_for_chat(&self, chat_id: ChatId, limit: Option<usize>) -> Vec<CachedMessage> {
-        let cache = self
-            .inner
-            .read()
-            .map(|cache| cache.messages_for_chat(chat_id, limit));
-        cache.unwrap_or_default()
+/// Derives the cache key and checks it against the stored key check. A new
+/// database gets a fresh salt; an encrypted one refuses to open without the
+/// right passphrase.
+fn unlock_cipher(connection: &Connection, passphrase: Option<&str>) -> Result<Option<CacheCipher>> {
+    let salt = read_meta(connection, "kdf_salt")?;
+    let key_check = read_meta(connection, "key_check")?;
+    let Some(passphrase) = passphrase else {
+        if key_check.is_some() {
+            return Err(CacheError::Crypto(
+                "cache is encrypted; a passphrase is required".to_string(),
+            ));
+        }
+        return Ok(None);
+    };
+
+    match (salt, key_check) {
+        (Some(salt), Some(key_check)) => {
+            let cipher = CacheCipher::derive(passphrase, &CacheCipher::decode_salt(&salt)?)?;
+            if cipher.decrypt(&key_check)? != KEY_CHECK_PLAINTEXT {
+                return Err(CacheError::Crypto(
+                    "wrong passphrase or corrupted cache".to_string(),
+                ));
+            }
+            Ok(Some(cipher))
+        }
+        _ => {
+            let salt = CacheCipher::generate_salt();
+            let cipher = CacheCipher::derive(passphrase, &salt)?;
+            write_meta(connection, "kdf_salt", &CacheCipher::encode_salt(&salt))?;
+            write_meta(
+                connection,
+                "key_check",
+                &cipher.encrypt(KEY_CHECK_PLAINTEXT)?,
+            )?;
+            // Existing vectors are plaintext; they are rebuilt sealed on demand.
+            connection.execute("DELETE FROM message_embeddings")?;
+            info!("cache encryption enabled");
+            Ok(Some(cipher))
+        }
     }
+}
 
-    pub async fn shutdown(self) {
-        let _ = self.flush_tx.send(FlushCommand::Shutdown);
-        let _ = self.join.await;
+fn read_meta(connection: &Connection, key: &str) -> Result<Option<String>> {
+    let mut stmt = connection.prepare("SELECT value FROM cache_meta WHERE key = :key")?;
+    stmt.bind((":key", key))?;
+    match stmt.next()? {
+        State::Row => Ok(Some(stmt.read::<String, _>(0)?)),
+        State::Done => Ok(None),
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct EvictionStats {
-    pub chats_evicted: usize,
-    pub messages_evicted: usize,
+fn write_meta(connection: &Connection, key: &str, value: &str) -> Result<()> {
+    let mut stmt = connection.prepare(
+        "INSERT INTO cache_meta (key, value) VALUES (:key, :value) \
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+    )?;
+    stmt.bind_iter::<_, (_, Value)>([(":key", key.into()), (":value", value.into())])?;
+    let _ = stmt.next()?;
+    Ok(())
 }
 
-impl EvictionStats {
-    fn any_evicted(self) -> bool {
-        self.chats_evicted > 0 || self.messages_evicted > 0
+fn apply_column_migrations(connection: &Connection) -> Result<()> {
+    for (table, column, definition) in COLUMN_MIGRATIONS {
+        if !table_has_column(connection, table, column)? {
+            connection.execute(format!(
+                "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+            ))?;
+        }
     }
+    Ok(())
 }
 
-#[derive(Debug)]
-struct ChatEntry {
-    summary: ChatSummary,
-    messages: VecDeque<CachedMessage>,
-    updated_at: i64,
-    message_bytes: usize,
-    summary_bytes: usize,
+fn table_exists(connection: &Connection, table: &str) -> Result<bool> {
+    let mut stmt = connection.prepare("SELECT 1 FROM sqlite_master WHERE name = :name")?;
+    stmt.bind((":name", table))?;
+    Ok(matches!(stmt.next()?, State::Row))
 }
 
-#[derive(Debug)]
-pub struct ChatCache {
-    chats: HashMap<ChatId, ChatEntry>,
-    limits: CacheLimits,
-    current_bytes: usize,
+fn table_has_column(connection: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = connection.prepare(format!("PRAGMA table_info({table})"))?;
+    while let State::Row = stmt.next()? {
+        if stmt.read::<String, _>(1)? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
 
-impl ChatCache {
-    pub fn new(limits: CacheLimits) -> Self {
-        Self {
-            chats: HashMap::new(),
-            limits,
-            current_bytes: 0,
-        }
+impl CacheStore for SqliteCacheStore {
+    fn load(&self) -> Result<CacheSnapshot> {
+        self.with_store(|store| load_snapshot(&store.connection, store.cipher.as_ref(), None))
+    }
+
+    fn load_recent(&self, per_chat: usize) -> Result<CacheSnapshot> {
+        self.with_store(|store| {
+            load_snapshot(&store.connection, store.cipher.as_ref(), Some(per_chat))
+        })
+    }
+
+    fn search(
+        &self,
+        query: &str,
+        chat_id: Option<ChatId>,
+        limit: usize,
+        now: i64,
+    ) -> Result<Vec<MessageSearchHit>> {
+        let Some(match_expr) = fts_match_expression(query) else {
+            return Ok(Vec::new());
+        };
+        self.with_store(|store| match &store.cipher {
+            // Ciphertext is not indexed, so search the decrypted snapshot instead.
+            Some(cipher) => Ok(search_snapshot(
+                load_snapshot(&store.connection, Some(cipher), None)?,
+                query,
+                chat_id,
+                limit,
+                now,
+            )),
+            None => search_messages(&store.connection, &match_expr, chat_id, limit, now),
+        })
+    }
+
+    fn messages_before(
+        &self,
+        chat_id: ChatId,
+        before: MessageId,
+        limit: usize,
+        now: i64,
+    ) -> Result<Vec<CachedMessage>> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        self.with_store(|store| {
+            page_messages(
+                &store.connection,
+                store.cipher.as_ref(),
+                chat_id,
+                before,
+                limit,
+                now,
+            )
+        })
+    }
+
+    fn delete_message(&self, chat_id: ChatId, message_id: MessageId) -> Result<()> {
+        self.with_connection(|connection| {
+            let mut stmt = connection.prepare(
+                "DELETE FROM messages WHERE chat_id = :chat_id AND message_id = :message_id",
+            )?;
+            stmt.bind_iter::<_, (_, Value)>([
+                (":chat_id", chat_id.0.into()),
+                (":message_id", message_id.0.into()),
+            ])?;
+            let _ = stmt.next()?;
+            Ok(())
+        })
+    }
+
+    fn save(&self, snapshot: &CacheSnapshot) -> Result<()> {
+        self.with_store(|store| {
+            in_transaction(&store.connection, |connection| {
+                write_snapshot(connection, store.cipher.as_ref(), snapshot)
+            })
+        })
+    }
+
+    fn save_delta(&self, delta: &CacheDelta) -> Result<()> {
+        if delta.is_empty() {
+            return Ok(());
+        }
+        self.with_store(|store| {
+            in_transaction(&store.connection, |connection| {
+                write_delta(connection, store.cipher.as_ref(), delta)
+            })
+        })
+    }
+
+    fn upsert_embeddings(&self, embeddings: &[MessageEmbedding]) -> Result<()> {
+        if embeddings.is_empty() {
+            return Ok(());
+        }
+        self.with_store(|store| {
+            in_transaction(&store.connection, |connection| {
+                let mut stmt = connection.prepare(
+                    "INSERT INTO message_embeddings (chat_id, message_id, model, vector) \
+                     VALUES (:chat_id, :message_id, :model, :vector) \
+                     ON CONFLICT(chat_id, message_id, model) DO UPDATE SET vector = excluded.vector",
+                )?;
+                for embedding in embeddings {
+                    let mut vector = encode_vector(&embedding.vector);
+                    if let Some(cipher) = &store.cipher {
+                        vector = cipher.seal(&vector)?;
+                    }
+                    stmt.bind_iter::<_, (_, Value)>([
+                        (":chat_id", embedding.chat_id.0.into()),
+                        (":message_id", embedding.message_id.0.into()),
+                        (":model", embedding.model.as_str().into()),
+                        (":vector", vector.into()),
+                    ])?;
+                    let _ = stmt.next()?;
+                    stmt.reset()?;
+                }
+                Ok(())
+            })
+        })
+    }
+
+    fn load_embeddings(&self, model: &str) -> Result<Vec<MessageEmbedding>> {
+        self.with_store(|store| {
+            let mut stmt = store.connection.prepare(
+                "SELECT chat_id, message_id, vector FROM message_embeddings WHERE model = :model",
+            )?;
+            stmt.bind((":model", model))?;
+            let mut embeddings = Vec::new();
+            while let State::Row = stmt.next()? {
+                let mut vector = stmt.read::<Vec<u8>, _>(2)?;
+                if let Some(cipher) = &store.cipher {
+                    vector = cipher.open(&vector)?;
+                }
+                embeddings.push(MessageEmbedding {
+                    chat_id: ChatId(stmt.read::<i64, _>(0)?),
+                    message_id: MessageId(stmt.read::<i64, _>(1)?),
+                    model: model.to_string(),
+                    vector: decode_vector(&vector),
+                });
+            }
+            Ok(embeddings)
+        })
+    }
+
+    fn messages_without_embedding(&self, model: &str, limit: usize) -> Result<Vec<CachedMessage>> {
+        let columns = MESSAGE_COLUMNS
+            .split(", ")
+            .map(|column| format!("m.{column}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.with_store(|store| {
+            let mut stmt = store.connection.prepare(format!(
+                "SELECT {columns} FROM messages m \
+                 WHERE m.deleted = 0 AND m.text <> '' AND NOT EXISTS (\
+                     SELECT 1 FROM message_embeddings e \
+                     WHERE e.chat_id = m.chat_id AND e.message_id = m.message_id AND e.model = :model) \
+                 ORDER BY m.timestamp DESC LIMIT :limit"
+            ))?;
+            stmt.bind_iter::<_, (_, Value)>([
+                (":model", model.into()),
+                (":limit", (limit.min(i64::MAX as usize) as i64).into()),
+            ])?;
+            let mut messages = Vec::new();
+            while let State::Row = stmt.next()? {
+                let mut message = read_message_row(&stmt)?;
+                open_message(store.cipher.as_ref(), &mut message)?;
+                messages.push(message);
+            }
+            Ok(messages)
+        })
+    }
+}
+
+fn in_transaction(
+    connection: &Connection,
+    op: impl FnOnce(&Connection) -> Result<()>,
+) -> Result<()> {
+    connection.execute("BEGIN IMMEDIATE TRANSACTION")?;
+    match op(connection) {
+        Ok(()) => {
+            connection.execute("COMMIT")?;
+            Ok(())
+        }
+        Err(err) => {
+            // The connection is reused, so never leave a transaction open.
+            let _ = connection.execute("ROLLBACK");
+            Err(err)
+        }
+    }
+}
+
+/// Loads the whole cache, or with `per_chat` only the newest messages of each
+/// chat (plus placeholder echoes).
+fn load_snapshot(
+    connection: &Connection,
+    cipher: Option<&CacheCipher>,
+    per_chat: Option<usize>,
+) -> Result<CacheSnapshot> {
+    let mut chats = Vec::new();
+    let mut messages = Vec::new();
+
+    let mut chat_stmt = connection.prepare(
+        "SELECT chat_id, title, peer_kind, last_message_id, last_message_at, unread_count, updated_at, last_read_message_id, last_accessed_at, pinned_message_ids, folder_id, muted, archived, pinned, last_outbox_read_message_id FROM chats",
+    )?;
+    while let State::Row = chat_stmt.next()? {
+        let chat_id = ChatId(chat_stmt.read::<i64, _>(0)?);
+        let title = open_text(cipher, chat_stmt.read::<String, _>(1)?)?;
+        let peer_kind = ChatPeerKind::from_str(chat_stmt.read::<String, _>(2)?.as_str());
+        let last_message_id = chat_stmt.read::<Option<i64>, _>(3)?;
+        let last_message_at = chat_stmt.read::<Option<i64>, _>(4)?;
+        let unread_count = chat_stmt.read::<Option<i64>, _>(5)?;
+        let _updated_at = chat_stmt.read::<i64, _>(6)?;
+        let last_read_message_id = chat_stmt.read::<Option<i64>, _>(7)?;
+        let last_accessed_at = chat_stmt.read::<Option<i64>, _>(8)?;
+        let pinned_message_ids = chat_stmt.read::<Option<String>, _>(9)?;
+        let flags = ChatFlags {
+            folder_id: chat_stmt.read::<Option<i64>, _>(10)?.map(|id| id as i32),
+            muted: chat_stmt.read::<i64, _>(11)? != 0,
+            archived: chat_stmt.read::<i64, _>(12)? != 0,
+            pinned: chat_stmt.read::<i64, _>(13)? != 0,
+        };
+        let last_outbox_read_message_id = chat_stmt.read::<Option<i64>, _>(14)?;
+
+        chats.push(ChatSummary {
+            chat_id,
+            title,
+            peer_kind,
+            last_message_id: last_message_id.map(MessageId),
+            last_message_at,
+            unread_count: unread_count.map(|value| value as u32),
+            last_read_message_id: last_read_message_id.map(MessageId),
+            last_outbox_read_message_id: last_outbox_read_message_id.map(MessageId),
+            last_accessed_at,
+            pinned_message_ids: pinned_message_ids
+                .map(|raw| decode_pins(&raw))
+                .unwrap_or_default(),
+            flags,
+        });
+    }
+
+    let mut message_stmt = match per_chat {
+        Some(per_chat) => {
+            let mut stmt = connection.prepare(format!(
+                "SELECT {MESSAGE_COLUMNS} FROM (\
+                     SELECT *, ROW_NUMBER() OVER (\
+                         PARTITION BY chat_id ORDER BY message_id DESC) AS recency \
+                     FROM messages) \
+                 WHERE recency <= :per_chat OR message_id <= 0 \
+                 ORDER BY chat_id, timestamp"
+            ))?;
+            stmt.bind((":per_chat", per_chat.min(i64::MAX as usize) as i64))?;
+            stmt
+        }
+        None => connection.prepare(format!(
+            "SELECT {MESSAGE_COLUMNS} FROM messages ORDER BY chat_id, timestamp"
+        ))?,
+    };
+    while let State::Row = message_stmt.next()? {
+        let mut message = read_message_row(&message_stmt)?;
+        open_message(cipher, &mut message)?;
+        messages.push(message);
+    }
+
+    let mut drafts = Vec::new();
+    let mut draft_stmt = connection.prepare("SELECT chat_id, text, updated_at FROM drafts")?;
+    while let State::Row = draft_stmt.next()? {
+        drafts.push(ChatDraft {
+            chat_id: ChatId(draft_stmt.read::<i64, _>(0)?),
+            text: open_text(cipher, draft_stmt.read::<String, _>(1)?)?,
+            updated_at: draft_stmt.read::<i64, _>(2)?,
+        });
+    }
+
+    Ok(CacheSnapshot {
+        chats,
+        messages,
+        drafts,
+    })
+}
+
+/// Walks the `(chat_id, message_id)` primary key backwards so a page costs
+/// `limit` rows regardless of how much history is cached.
+fn page_messages(
+    connection: &Connection,
+    cipher: Option<&CacheCipher>,
+    chat_id: ChatId,
+    before: MessageId,
+    limit: usize,
+    now: i64,
+) -> Result<Vec<CachedMessage>> {
+    let mut stmt = connection.prepare(format!(
+        "SELECT {MESSAGE_COLUMNS} FROM messages \
+         WHERE chat_id = :chat_id AND message_id < :before \
+             AND (expires_at IS NULL OR expires_at > :now) \
+         ORDER BY message_id DESC LIMIT :limit"
+    ))?;
+    stmt.bind_iter::<_, (_, Value)>([
+        (":chat_id", chat_id.0.into()),
+        (":before", before.0.into()),
+        (":now", now.into()),
+        (":limit", (limit.min(i64::MAX as usize) as i64).into()),
+    ])?;
+
+    let mut messages = Vec::new();
+    while let State::Row = stmt.next()? {
+        let mut message = read_message_row(&stmt)?;
+        open_message(cipher, &mut message)?;
+        messages.push(message);
+    }
+    messages.reverse();
+    Ok(messages)
+}
+
+/// Serializes reactions as `emoji\tcount\tmine` lines; `None` when empty.
+pub(crate) fn encode_reactions(reactions: &[MessageReaction]) -> Option<String> {
+    if reactions.is_empty() {
+        return None;
+    }
+    Some(
+        reactions
+            .iter()
+            .map(|reaction| {
+                format!(
+                    "{}\t{}\t{}",
+                    reaction.emoji,
+                    reaction.count,
+                    u8::from(reaction.mine)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+/// Serializes pinned ids as a comma-separated list in pin order; `None` when empty.
+fn encode_pins(pins: &[MessageId]) -> Option<String> {
+    if pins.is_empty() {
+        return None;
+    }
+    Some(
+        pins.iter()
+            .map(|id| id.0.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+fn decode_pins(raw: &str) -> Vec<MessageId> {
+    raw.split(',')
+        .filter_map(|id| id.trim().parse().ok())
+        .map(MessageId)
+        .collect()
+}
+
+/// Serializes media as one tab-separated line: kind, size, then the
+/// thumbnail's photo id, access hash, dc id, size type and base64 file
+/// reference, the duration, and the document's id, access hash, dc id, MIME
+/// type, base64 file reference and base64 file name. Unknown fields are left
+/// empty and trailing empty ones dropped. Transcriptions stay in memory,
+/// since they are message content the cache may be asked to encrypt; the
+/// file name is too, so encrypted stores write it through [`seal_media`].
+pub(crate) fn encode_media(media: Option<&MessageMedia>) -> Option<String> {
+    let media = media?;
+    let mut fields = vec![
+        media.kind.as_str().to_string(),
+        media.size.map(|size| size.to_string()).unwrap_or_default(),
+    ];
+    match &media.thumbnail {
+        Some(thumbnail) => fields.extend([
+            thumbnail.photo_id.to_string(),
+            thumbnail.access_hash.to_string(),
+            thumbnail.dc_id.to_string(),
+            thumbnail.size_type.clone(),
+            STANDARD.encode(&thumbnail.file_reference),
+        ]),
+        None => fields.extend(std::iter::repeat_n(String::new(), 5)),
+    }
+    fields.push(
+        media
+            .duration
+            .map(|duration| duration.to_string())
+            .unwrap_or_default(),
+    );
+    if let Some(document) = &media.document {
+        fields.extend([
+            document.id.to_string(),
+            document.access_hash.to_string(),
+            document.dc_id.to_string(),
+            document.mime_type.clone(),
+            STANDARD.encode(&document.file_reference),
+            document
+                .file_name
+                .as_ref()
+                .map(|name| STANDARD.encode(name))
+                .unwrap_or_default(),
+        ]);
+    }
+    while fields.last().is_some_and(String::is_empty) {
+        fields.pop();
+    }
+    Some(fields.join("\t"))
+}
+
+pub(crate) fn decode_media(raw: &str) -> Option<MessageMedia> {
+    let fields: Vec<&str> = raw.split('\t').collect();
+    let field = |index: usize| fields.get(index).copied().filter(|field| !field.is_empty());
+    let kind = MessageMediaKind::from_str(field(0)?)?;
+    let thumbnail = (|| {
+        Some(PhotoThumbnail {
+            photo_id: field(2)?.parse().ok()?,
+            access_hash: field(3)?.parse().ok()?,
+            dc_id: field(4)?.parse().ok()?,
+            size_type: field(5)?.to_string(),
+            file_reference: STANDARD.decode(field(6)?).ok()?,
+        })
+    })();
+    let document = (|| {
+        Some(DocumentFile {
+            id: field(8)?.parse().ok()?,
+            access_hash: field(9)?.parse().ok()?,
+            dc_id: field(10)?.parse().ok()?,
+            mime_type: field(11)?.to_string(),
+            file_reference: STANDARD.decode(field(12).unwrap_or_default()).ok()?,
+            file_name: field(13)
+                .and_then(|name| STANDARD.decode(name).ok())
+                .and_then(|name| String::from_utf8(name).ok()),
+        })
+    })();
+    Some(MessageMedia {
+        kind,
+        size: field(1).and_then(|size| size.parse().ok()),
+        thumbnail,
+        duration: field(7).and_then(|duration| duration.parse().ok()),
+        document,
+        transcription: None,
+    })
+}
+
+pub(crate) fn decode_reactions(raw: &str) -> Vec<MessageReaction> {
+    raw.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let emoji = fields.next()?.to_string();
+            let count = fields.next()?.parse().ok()?;
+            let mine = fields.next()? == "1";
+            Some(MessageReaction { emoji, count, mine })
+        })
+        .collect()
+}
+
+/// Packs a vector as little-endian `f32`s.
+pub(crate) fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+pub(crate) fn decode_vector(raw: &[u8]) -> Vec<f32> {
+    raw.chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+fn open_text(cipher: Option<&CacheCipher>, stored: String) -> Result<String> {
+    match cipher {
+        Some(cipher) => cipher.decrypt(&stored),
+        None => Ok(stored),
+    }
+}
+
+/// [`encode_media`] with the document's file name sealed like message text.
+fn seal_media(
+    cipher: Option<&CacheCipher>,
+    media: Option<&MessageMedia>,
+) -> Result<Option<String>> {
+    let (Some(cipher), Some(media)) = (cipher, media) else {
+        return Ok(encode_media(media));
+    };
+    let mut sealed = media.clone();
+    if let Some(name) = sealed
+        .document
+        .as_mut()
+        .and_then(|document| document.file_name.as_mut())
+    {
+        *name = cipher.encrypt(name)?;
+    }
+    Ok(encode_media(Some(&sealed)))
+}
+
+/// Opens the sealed text and file name of a message read from the store.
+fn open_message(cipher: Option<&CacheCipher>, message: &mut CachedMessage) -> Result<()> {
+    message.text = open_text(cipher, std::mem::take(&mut message.text))?;
+    if let Some(name) = message
+        .media
+        .as_mut()
+        .and_then(|media| media.document.as_mut())
+        .and_then(|document| document.file_name.as_mut())
+    {
+        *name = open_text(cipher, std::mem::take(name))?;
+    }
+    Ok(())
+}
+
+fn seal_text(cipher: Option<&CacheCipher>, text: &str) -> Result<String> {
+    match cipher {
+        Some(cipher) => cipher.encrypt(text),
+        None => Ok(text.to_string()),
+    }
+}
+
+/// Reads a row laid out as [`MESSAGE_COLUMNS`].
+fn read_message_row(stmt: &sqlite::Statement<'_>) -> Result<CachedMessage> {
+    Ok(CachedMessage {
+        chat_id: ChatId(stmt.read::<i64, _>(0)?),
+        message_id: MessageId(stmt.read::<i64, _>(1)?),
+        author_id: UserId(stmt.read::<i64, _>(2)?),
+        timestamp: stmt.read::<i64, _>(3)?,
+        edit_timestamp: stmt.read::<Option<i64>, _>(4)?,
+        text: stmt.read::<String, _>(5)?,
+        outgoing: stmt.read::<i64, _>(6)? != 0,
+        expires_at: stmt.read::<Option<i64>, _>(7)?,
+        reactions: stmt
+            .read::<Option<String>, _>(8)?
+            .map(|raw| decode_reactions(&raw))
+            .unwrap_or_default(),
+        deleted: stmt.read::<i64, _>(9)? != 0,
+        delivery: stmt
+            .read::<Option<String>, _>(10)?
+            .and_then(|raw| DeliveryState::from_str(&raw)),
+        media: stmt
+            .read::<Option<String>, _>(11)?
+            .and_then(|raw| decode_media(&raw)),
+        reply_to: stmt.read::<Option<i64>, _>(12)?.map(MessageId),
+    })
+}
+
+fn search_messages(
+    connection: &Connection,
+    match_expr: &str,
+    chat_id: Option<ChatId>,
+    limit: usize,
+    now: i64,
+) -> Result<Vec<MessageSearchHit>> {
+    let columns = MESSAGE_COLUMNS
+        .split(", ")
+        .map(|column| format!("m.{column}"))
+        .collect::<Vec<_>>();
+    // The snippet follows the message columns.
+    let snippet_index = columns.len();
+    let columns = columns.join(", ");
+    let mut stmt = connection.prepare(format!(
+        "SELECT {columns}, snippet(messages_fts, 0, '[', ']', '…', 12) \
+         FROM messages_fts JOIN messages m ON m.rowid = messages_fts.rowid \
+         WHERE messages_fts MATCH :query AND (:chat_id IS NULL OR m.chat_id = :chat_id) \
+             AND (m.expires_at IS NULL OR m.expires_at > :now) \
+         ORDER BY m.timestamp DESC LIMIT :limit"
+    ))?;
+    stmt.bind_iter::<_, (_, Value)>([
+        (":query", match_expr.to_string().into()),
+        (":chat_id", chat_id.map(|id| id.0).into()),
+        (":now", now.into()),
+        (":limit", (limit.min(i64::MAX as usize) as i64).into()),
+    ])?;
+
+    let mut hits = Vec::new();
+    while let State::Row = stmt.next()? {
+        let message = read_message_row(&stmt)?;
+        let snippet = stmt.read::<String, _>(snippet_index)?;
+        hits.push(MessageSearchHit { message, snippet });
+    }
+    Ok(hits)
+}
+
+/// Turns free-form user input into an FTS5 expression of quoted prefix terms,
+/// so punctuation in the query can never produce a syntax error.
+fn fts_match_expression(query: &str) -> Option<String> {
+    let terms = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+fn write_snapshot(
+    connection: &Connection,
+    cipher: Option<&CacheCipher>,
+    snapshot: &CacheSnapshot,
+) -> Result<()> {
+    connection.execute("DELETE FROM messages")?;
+    connection.execute("DELETE FROM chats")?;
+    connection.execute("DELETE FROM drafts")?;
+    insert_rows(connection, cipher, &snapshot.chats, &snapshot.messages)?;
+    insert_drafts(connection, cipher, &snapshot.drafts)?;
+    connection.execute(PRUNE_EMBEDDINGS)?;
+    Ok(())
+}
+
+fn write_delta(
+    connection: &Connection,
+    cipher: Option<&CacheCipher>,
+    delta: &CacheDelta,
+) -> Result<()> {
+    {
+        let floors = delta
+            .history_floors
+            .iter()
+            .copied()
+            .collect::<HashMap<_, _>>();
+        let mut message_stmt =
+            connection.prepare("DELETE FROM messages WHERE chat_id = :chat_id")?;
+        let mut resident_stmt = connection.prepare(
+            "DELETE FROM messages WHERE chat_id = :chat_id \
+             AND (message_id >= :floor OR message_id <= 0)",
+        )?;
+        let mut chat_stmt = connection.prepare("DELETE FROM chats WHERE chat_id = :chat_id")?;
+        for chat_id in delta.touched_chats() {
+            match floors.get(&chat_id) {
+                Some(floor) => {
+                    resident_stmt.bind_iter::<_, (_, Value)>([
+                        (":chat_id", chat_id.0.into()),
+                        (":floor", floor.0.into()),
+                    ])?;
+                    let _ = resident_stmt.next()?;
+                    resident_stmt.reset()?;
+                }
+                None => {
+                    message_stmt.bind((":chat_id", chat_id.0))?;
+                    let _ = message_stmt.next()?;
+                    message_stmt.reset()?;
+                }
+            }
+            chat_stmt.bind((":chat_id", chat_id.0))?;
+            let _ = chat_stmt.next()?;
+            chat_stmt.reset()?;
+        }
+        let mut history_stmt = connection.prepare(
+            "DELETE FROM messages WHERE chat_id = :chat_id AND message_id = :message_id",
+        )?;
+        for (chat_id, message_id) in &delta.deleted_history {
+            history_stmt.bind_iter::<_, (_, Value)>([
+                (":chat_id", chat_id.0.into()),
+                (":message_id", message_id.0.into()),
+            ])?;
+            let _ = history_stmt.next()?;
+            history_stmt.reset()?;
+        }
+        let mut draft_stmt = connection.prepare("DELETE FROM drafts WHERE chat_id = :chat_id")?;
+        for draft in &delta.drafts {
+            draft_stmt.bind((":chat_id", draft.chat_id.0))?;
+            let _ = draft_stmt.next()?;
+            draft_stmt.reset()?;
+        }
+    }
+    insert_rows(connection, cipher, &delta.chats, &delta.messages)?;
+    insert_drafts(connection, cipher, &delta.drafts)?;
+    if !delta.chats.is_empty()
+        || !delta.removed_chats.is_empty()
+        || !delta.deleted_history.is_empty()
+    {
+        connection.execute(PRUNE_EMBEDDINGS)?;
+    }
+    Ok(())
+}
+
+/// Inserts non-empty drafts; empty ones only mark a cleared draft.
+fn insert_drafts(
+    connection: &Connection,
+    cipher: Option<&CacheCipher>,
+    drafts: &[ChatDraft],
+) -> Result<()> {
+    let mut stmt = connection.prepare(
+        "INSERT INTO drafts (chat_id, text, updated_at) VALUES (:chat_id, :text, :updated_at)",
+    )?;
+    for draft in drafts.iter().filter(|draft| !draft.text.is_empty()) {
+        stmt.bind_iter::<_, (_, Value)>([
+            (":chat_id", draft.chat_id.0.into()),
+            (":text", seal_text(cipher, &draft.text)?.into()),
+            (":updated_at", draft.updated_at.into()),
+        ])?;
+        let _ = stmt.next()?;
+        stmt.reset()?;
+    }
+    Ok(())
+}
+
+fn insert_rows(
+    connection: &Connection,
+    cipher: Option<&CacheCipher>,
+    chats: &[ChatSummary],
+    messages: &[CachedMessage],
+) -> Result<()> {
+    {
+        let mut chat_stmt = connection.prepare(
+            "INSERT INTO chats (chat_id, title, peer_kind, last_message_id, last_message_at, unread_count, last_read_message_id, last_outbox_read_message_id, last_accessed_at, pinned_message_ids, folder_id, muted, archived, pinned, updated_at) VALUES (:chat_id, :title, :peer_kind, :last_message_id, :last_message_at, :unread_count, :last_read_message_id, :last_outbox_read_message_id, :last_accessed_at, :pinned_message_ids, :folder_id, :muted, :archived, :pinned, :updated_at)",
+        )?;
+        for chat in chats {
+            let updated_at = chat.last_message_at.unwrap_or(0);
+            chat_stmt.bind_iter::<_, (_, Value)>([
+                (":chat_id", (chat.chat_id.0).into()),
+                (":title", seal_text(cipher, &chat.title)?.into()),
+                (":peer_kind", chat.peer_kind.as_str().into()),
+                (
+                    ":last_message_id",
+                    chat.last_message_id.map(|id| id.0).into(),
+                ),
+                (":last_message_at", chat.last_message_at.into()),
+                (
+                    ":unread_count",
+                    chat.unread_count.map(|value| value as i64).into(),
+                ),
+                (
+                    ":last_read_message_id",
+                    chat.last_read_message_id.map(|id| id.0).into(),
+                ),
+                (
+                    ":last_outbox_read_message_id",
+                    chat.last_outbox_read_message_id.map(|id| id.0).into(),
+                ),
+                (":last_accessed_at", chat.last_accessed_at.into()),
+                (
+                    ":pinned_message_ids",
+                    encode_pins(&chat.pinned_message_ids).into(),
+                ),
+                (":folder_id", chat.flags.folder_id.map(i64::from).into()),
+                (":muted", i64::from(chat.flags.muted).into()),
+                (":archived", i64::from(chat.flags.archived).into()),
+                (":pinned", i64::from(chat.flags.pinned).into()),
+                (":updated_at", updated_at.into()),
+            ])?;
+            let _ = chat_stmt.next()?;
+            chat_stmt.reset()?;
+        }
+    }
+
+    {
+        let mut message_stmt = connection.prepare(
+            "INSERT INTO messages (chat_id, message_id, author_id, timestamp, edit_timestamp, text, outgoing, expires_at, reactions, deleted, delivery, media, reply_to) VALUES (:chat_id, :message_id, :author_id, :timestamp, :edit_timestamp, :text, :outgoing, :expires_at, :reactions, :deleted, :delivery, :media, :reply_to)",
+        )?;
+        for message in messages {
+            message_stmt.bind_iter::<_, (_, Value)>([
+                (":chat_id", (message.chat_id.0).into()),
+                (":message_id", (message.message_id.0).into()),
+                (":author_id", (message.author_id.0).into()),
+                (":timestamp", message.timestamp.into()),
+                (":edit_timestamp", message.edit_timestamp.into()),
+                (":text", seal_text(cipher, &message.text)?.into()),
+                (
+                    ":outgoing",
+                    if message.outgoing { 1i64 } else { 0i64 }.into(),
+                ),
+                (":expires_at", message.expires_at.into()),
+                (":reactions", encode_reactions(&message.reactions).into()),
+                (":deleted", i64::from(message.deleted).into()),
+                (
+                    ":delivery",
+                    message.delivery.map(DeliveryState::as_str).into(),
+                ),
+                (":media", seal_media(cipher, message.media.as_ref())?.into()),
+                (
+                    ":reply_to",
+                    message.reply_to.map(|message_id| message_id.0).into(),
+                ),
+            ])?;
+            let _ = message_stmt.next()?;
+            message_stmt.reset()?;
+        }
+    }
+
+    Ok(())
+}
+
+pub struct CacheManager {
+    inner: Arc<RwLock<ChatCache>>,
+    store: Arc<dyn CacheStore>,
+    flush_tx: mpsc::UnboundedSender<FlushCommand>,
+    join: JoinHandle<()>,
+    flushes: Arc<FlushCounters>,
+}
+
+/// Point-in-time view of the cache, for metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheMetrics {
+    pub chats: usize,
+    pub messages: usize,
+    /// Estimated size of the resident history, as counted against
+    /// [`CacheLimits::max_bytes`].
+    pub bytes: usize,
+    /// Local echoes still waiting for a send result.
+    pub pending_sends: usize,
+    pub flushes: u64,
+    pub flush_failures: u64,
+}
+
+#[derive(Debug, Default)]
+struct FlushCounters {
+    flushes: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl std::fmt::Debug for CacheManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheManager")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CacheManager {
+    pub async fn spawn(store: Arc<dyn CacheStore>, config: CacheConfig) -> Result<Self> {
+        let per_chat = config.startup_messages_per_chat;
+        let snapshot = tokio::task::spawn_blocking({
+            let store = Arc::clone(&store);
+            move || match per_chat {
+                Some(per_chat) => store.load_recent(per_chat),
+                None => store.load(),
+            }
+        })
+        .await
+        .map_err(|err| CacheError::Task(err.to_string()))??;
+
+        let cache = match per_chat {
+            Some(per_chat) => ChatCache::from_recent_snapshot(snapshot, config.limits, per_chat),
+            None => ChatCache::from_snapshot(snapshot, config.limits),
+        }
+        .with_tombstones(config.keep_tombstones);
+        let inner = Arc::new(RwLock::new(cache));
+        let (flush_tx, flush_rx) = mpsc::unbounded_channel();
+        let flushes = Arc::new(FlushCounters::default());
+        let join = spawn_flush_task(
+            Arc::clone(&inner),
+            Arc::clone(&store),
+            flush_rx,
+            config.flush_debounce,
+            Arc::clone(&flushes),
+        );
+
+        info!(
+            chats = inner.read().map(|cache| cache.chat_count()).unwrap_or(0),
+            "cache loaded"
+        );
+
+        Ok(Self {
+            inner,
+            store,
+            flush_tx,
+            join,
+            flushes,
+        })
+    }
+
+    pub fn apply_event(&self, event: &DomainEvent) {
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let stats = cache.apply_event(event);
+        if stats.any_evicted() {
+            info!(
+                chats = stats.chats_evicted,
+                messages = stats.messages_evicted,
+                "cache eviction applied"
+            );
+        }
+        let _ = self.flush_tx.send(FlushCommand::Dirty);
+    }
+
+    /// Applies `events` in order under a single lock, with one flush signal.
+    pub fn apply_events(&self, events: &[DomainEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut stats = EvictionStats::default();
+        for event in events {
+            let applied = cache.apply_event(event);
+            stats.chats_evicted += applied.chats_evicted;
+            stats.messages_evicted += applied.messages_evicted;
+        }
+        if stats.any_evicted() {
+            info!(
+                chats = stats.chats_evicted,
+                messages = stats.messages_evicted,
+                "cache eviction applied"
+            );
+        }
+        let _ = self.flush_tx.send(FlushCommand::Dirty);
+    }
+
+    pub fn upsert_chat(&self, summary: ChatSummary) {
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let stats = cache.upsert_chat(summary);
+        if stats.any_evicted() {
+            info!(
+                chats = stats.chats_evicted,
+                messages = stats.messages_evicted,
+                "cache eviction applied"
+            );
+        }
+        let _ = self.flush_tx.send(FlushCommand::Dirty);
+    }
+
+    pub fn insert_pending(&self, chat_id: ChatId, send_id: SendId, text: &str, now: i64) {
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let stats = cache.insert_pending(chat_id, send_id, text, now);
+        if stats.any_evicted() {
+            info!(
+                chats = stats.chats_evicted,
+                messages = stats.messages_evicted,
+                "cache eviction applied"
+            );
+        }
+        let _ = self.flush_tx.send(FlushCommand::Dirty);
+    }
+
+    pub fn apply_send_status(&self, send_id: SendId, status: &SendStatus) {
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if cache.apply_send_status(send_id, status) {
+            let _ = self.flush_tx.send(FlushCommand::Dirty);
+        }
+    }
+
+    pub fn discard_failed_send(&self, send_id: SendId) {
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if cache.discard_failed_send(send_id) {
+            let _ = self.flush_tx.send(FlushCommand::Dirty);
+        }
+    }
+
+    /// Records that the UI opened `chat_id`, protecting it from eviction.
+    pub fn mark_accessed(&self, chat_id: ChatId, now: i64) {
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if cache.mark_accessed(chat_id, now) {
+            let _ = self.flush_tx.send(FlushCommand::Dirty);
+        }
+    }
+
+    /// Stores the composer text for `chat_id`; blank text clears the draft.
+    pub fn set_draft(&self, chat_id: ChatId, text: &str, now: i64) {
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if cache.set_draft(chat_id, text, now) {
+            let _ = self.flush_tx.send(FlushCommand::Dirty);
+        }
+    }
+
+    pub fn draft(&self, chat_id: ChatId) -> Option<String> {
+        let cache = self.inner.read().map(|cache| cache.draft(chat_id));
+        cache.unwrap_or_default()
+    }
+
+    pub fn presence(&self, user_id: UserId) -> Option<Presence> {
+        let cache = self.inner.read().map(|cache| cache.presence(user_id));
+        cache.unwrap_or_default()
+    }
+
+    pub fn chat_members(&self, chat_id: ChatId) -> Vec<ChatMember> {
+        let cache = self.inner.read().map(|cache| cache.chat_members(chat_id));
+        cache.unwrap_or_default()
+    }
+
+    pub fn evict_expired(&self, now: i64) -> usize {
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let evicted = cache.evict_expired(now);
+        if evicted > 0 {
+            info!(messages = evicted, "expired messages evicted");
+            let _ = self.flush_tx.send(FlushCommand::Dirty);
+        }
+        evicted
+    }
+
+    pub fn chat_summaries(&self) -> Vec<ChatSummary> {
+        let cache = self.inner.read().map(|cache| cache.chat_summaries());
+        cache.unwrap_or_default()
+    }
+
+    pub fn messages_for_chat(&self, chat_id: ChatId, limit: Option<usize>) -> Vec<CachedMessage> {
+        let cache = self
+            .inner
+            .read()
+            .map(|cache| cache.messages_for_chat(chat_id, limit));
+        cache.unwrap_or_default()
+    }
+
+    /// Renders the cached transcript of `chat_id` within `range` as JSON or
+    /// Markdown, for the export subcommand and palette action.
+    pub fn export(
+        &self,
+        chat_id: ChatId,
+        format: ExportFormat,
+        range: ExportRange,
+    ) -> Result<String> {
+        let (summary, messages) = match self.inner.read() {
+            Ok(cache) => cache.export_source(chat_id),
+            Err(poisoned) => poisoned.into_inner().export_source(chat_id),
+        };
+        let summary = summary
+            .ok_or_else(|| CacheError::Export(format!("chat {} is not cached", chat_id.0)))?;
+        let messages = messages
+            .into_iter()
+            .filter(|message| range.contains(message.timestamp))
+            .collect::<Vec<_>>();
+        render_transcript(&summary, &messages, format)
+    }
+
+    /// Searches the persisted history, newest first. Messages received since
+    /// the last debounced flush are not visible until they are written, and
+    /// those expired by `now` never are.
+    pub async fn search(
+        &self,
+        query: &str,
+        chat_id: Option<ChatId>,
+        limit: usize,
+        now: i64,
+    ) -> Result<Vec<MessageSearchHit>> {
+        let store = Arc::clone(&self.store);
+        let query = query.to_string();
+        tokio::task::spawn_blocking(move || store.search(&query, chat_id, limit, now))
+            .await
+            .map_err(|err| CacheError::Task(err.to_string()))?
+    }
+
+    /// Pages older history for `chat_id` straight from the store, so scrolling
+    /// back does not require the whole chat to be resident in memory.
+    pub async fn messages_before(
+        &self,
+        chat_id: ChatId,
+        before: MessageId,
+        limit: usize,
+        now: i64,
+    ) -> Result<Vec<CachedMessage>> {
+        let store = Arc::clone(&self.store);
+        tokio::task::spawn_blocking(move || store.messages_before(chat_id, before, limit, now))
+            .await
+            .map_err(|err| CacheError::Task(err.to_string()))?
+    }
+
+    /// Pulls up to `limit` messages older than the resident history of
+    /// `chat_id` back into memory. Returns how many were added; zero once the
+    /// whole chat is resident. Messages expired by `now` stay in the store.
+    pub async fn hydrate_history(&self, chat_id: ChatId, limit: usize, now: i64) -> Result<usize> {
+        let floor = match self.inner.read() {
+            Ok(cache) => cache.history_floor(chat_id),
+            Err(poisoned) => poisoned.into_inner().history_floor(chat_id),
+        };
+        let Some(floor) = floor else {
+            return Ok(0);
+        };
+        let messages = self.messages_before(chat_id, floor, limit, now).await?;
+        let mut cache = match self.inner.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Ok(cache.hydrate_history(chat_id, messages, limit))
+    }
+
+    pub async fn upsert_embeddings(&self, embeddings: Vec<MessageEmbedding>) -> Result<()> {
+        let store = Arc::clone(&self.store);
+        tokio::task::spawn_blocking(move || store.upsert_embeddings(&embeddings))
+            .await
+            .map_err(|err| CacheError::Task(err.to_string()))?
+    }
+
+    pub async fn load_embeddings(&self, model: &str) -> Result<Vec<MessageEmbedding>> {
+        let store = Arc::clone(&self.store);
+        let model = model.to_string();
+        tokio::task::spawn_blocking(move || store.load_embeddings(&model))
+            .await
+            .map_err(|err| CacheError::Task(err.to_string()))?
+    }
+
+    /// Persisted messages still lacking a vector for `model`, newest first.
+    pub async fn messages_without_embedding(
+        &self,
+        model: &str,
+        limit: usize,
+    ) -> Result<Vec<CachedMessage>> {
+        let store = Arc::clone(&self.store);
+        let model = model.to_string();
+        tokio::task::spawn_blocking(move || store.messages_without_embedding(&model, limit))
+            .await
+            .map_err(|err| CacheError::Task(err.to_string()))?
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        let resident = match self.inner.read() {
+            Ok(cache) => cache.metrics(),
+            Err(poisoned) => poisoned.into_inner().metrics(),
+        };
+        CacheMetrics {
+            flushes: self.flushes.flushes.load(Ordering::Relaxed),
+            flush_failures: self.flushes.failures.load(Ordering::Relaxed),
+            ..resident
+        }
+    }
+
+    pub async fn shutdown(self) {
+        let _ = self.flush_tx.send(FlushCommand::Shutdown);
+        let _ = self.join.await;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EvictionStats {
+    pub chats_evicted: usize,
+    pub messages_evicted: usize,
+}
+
+impl EvictionStats {
+    fn any_evicted(self) -> bool {
+        self.chats_evicted > 0 || self.messages_evicted > 0
+    }
+}
+
+#[derive(Debug)]
+struct ChatEntry {
+    summary: ChatSummary,
+    messages: VecDeque<CachedMessage>,
+    updated_at: i64,
+    message_bytes: usize,
+    summary_bytes: usize,
+}
+
+#[derive(Debug)]
+pub struct ChatCache {
+    chats: HashMap<ChatId, ChatEntry>,
+    limits: CacheLimits,
+    current_bytes: usize,
+    keep_tombstones: bool,
+    /// Chats changed since the last [`ChatCache::take_delta`].
+    dirty_chats: HashSet<ChatId>,
+    removed_chats: HashSet<ChatId>,
+    /// Drafts outlive chat eviction: they are user input, not cached history.
+    drafts: HashMap<ChatId, ChatDraft>,
+    dirty_drafts: HashSet<ChatId>,
+    /// Sends echoed with [`ChatCache::insert_pending`] awaiting a result.
+    pending_sends: HashMap<SendId, ChatId>,
+    /// Failed echoes, reopened when a manual retry queues them again.
+    failed_sends: HashMap<SendId, ChatId>,
+    /// Set when loaded with [`ChatCache::from_recent_snapshot`]: trimming then
+    /// only bounds memory and leaves older history in the store.
+    lazy_history: bool,
+    /// Chats with history in the store older than the given message id.
+    history_floors: HashMap<ChatId, MessageId>,
+    deleted_history: Vec<(ChatId, MessageId)>,
+    /// Latest presence per user; volatile, so never persisted.
+    presence: HashMap<UserId, Presence>,
+    /// Members seen joining or changing role, with their admin flag. Only
+    /// as complete as the membership updates received this session.
+    rosters: HashMap<ChatId, HashMap<UserId, bool>>,
+}
+
+impl ChatCache {
+    pub fn new(limits: CacheLimits) -> Self {
+        Self {
+            chats: HashMap::new(),
+            limits,
+            current_bytes: 0,
+            keep_tombstones: false,
+            dirty_chats: HashSet::new(),
+            removed_chats: HashSet::new(),
+            drafts: HashMap::new(),
+            dirty_drafts: HashSet::new(),
+            pending_sends: HashMap::new(),
+            failed_sends: HashMap::new(),
+            lazy_history: false,
+            history_floors: HashMap::new(),
+            deleted_history: Vec::new(),
+            presence: HashMap::new(),
+            rosters: HashMap::new(),
+        }
+    }
+
+    pub fn with_tombstones(mut self, keep_tombstones: bool) -> Self {
+        self.keep_tombstones = keep_tombstones;
+        self
     }
 
     pub fn from_snapshot(snapshot: CacheSnapshot, limits: CacheLimits) -> Self {
+        Self::new(limits).populate(snapshot)
+    }
+
+    /// Builds a cache from a snapshot holding at most `per_chat` messages per
+    /// chat (see [`CacheStore::load_recent`]). Chats that hit the bound keep
+    /// a history floor so older rows survive flushes and can be hydrated.
+    pub fn from_recent_snapshot(
+        snapshot: CacheSnapshot,
+        limits: CacheLimits,
+        per_chat: usize,
+    ) -> Self {
+        let mut loaded: HashMap<ChatId, (usize, MessageId)> = HashMap::new();
+        for message in snapshot
+            .messages
+            .iter()
+            .filter(|message| message.message_id.0 > 0)
+        {
+            let (count, lowest) = loaded
+                .entry(message.chat_id)
+                .or_insert((0, message.message_id));
+            *count += 1;
+            if message.message_id.0 < lowest.0 {
+                *lowest = message.message_id;
+            }
+        }
         let mut cache = Self::new(limits);
+        cache.lazy_history = true;
+        cache.history_floors = loaded
+            .into_iter()
+            .filter(|(_, (count, _))| *count >= per_chat.max(1))
+            .map(|(chat_id, (_, lowest))| (chat_id, lowest))
+            .collect();
+        cache.populate(snapshot)
+    }
+
+    fn populate(mut self, snapshot: CacheSnapshot) -> Self {
         for chat in snapshot.chats {
-            cache.insert_chat(chat);
+            self.insert_chat(chat);
+        }
+        for mut message in snapshot.messages {
+            // The send pipeline did not survive the restart.
+            if matches!(
+                message.delivery,
+                Some(DeliveryState::Pending | DeliveryState::Sending)
+            ) {
+                message.delivery = Some(DeliveryState::Failed);
+            }
+            self.insert_message(message);
+        }
+        for draft in snapshot.drafts {
+            self.drafts.insert(draft.chat_id, draft);
+        }
+        // Everything loaded is already persisted; only evictions need writing.
+        self.dirty_chats.clear();
+        let _ = self.enforce_limits();
+        self
+    }
+
+    /// Drains the set of chats changed since the previous call.
+    pub fn take_delta(&mut self) -> CacheDelta {
+        let mut delta = CacheDelta {
+            removed_chats: self.removed_chats.drain().collect(),
+            deleted_history: std::mem::take(&mut self.deleted_history),
+            ..CacheDelta::default()
+        };
+        for chat_id in self.dirty_chats.drain() {
+            if let Some(entry) = self.chats.get(&chat_id) {
+                delta.chats.push(entry.summary.clone());
+                delta.messages.extend(entry.messages.iter().cloned());
+                if let Some(floor) = self.history_floors.get(&chat_id) {
+                    // Never keep a stored row that the resident set rewrites.
+                    let floor = entry
+                        .messages
+                        .iter()
+                        .map(|message| message.message_id.0)
+                        .filter(|id| *id > 0)
+                        .fold(floor.0, i64::min);
+                    delta.history_floors.push((chat_id, MessageId(floor)));
+                }
+            }
+        }
+        for chat_id in self.dirty_drafts.drain() {
+            delta
+                .drafts
+                .push(self.drafts.get(&chat_id).cloned().unwrap_or(ChatDraft {
+                    chat_id,
+                    text: String::new(),
+                    updated_at: 0,
+                }));
+        }
+        delta
+    }
+
+    /// Re-queues a delta whose write failed so the next flush retries it.
+    pub fn requeue_delta(&mut self, delta: &CacheDelta) {
+        for chat_id in delta.touched_chats() {
+            if self.chats.contains_key(&chat_id) {
+                self.dirty_chats.insert(chat_id);
+            } else {
+                self.removed_chats.insert(chat_id);
+            }
+        }
+        self.dirty_drafts
+            .extend(delta.drafts.iter().map(|draft| draft.chat_id));
+        self.deleted_history.extend(
+            delta
+                .deleted_history
+                .iter()
+                .filter(|(chat_id, _)| self.chats.contains_key(chat_id)),
+        );
+    }
+
+    pub fn chat_count(&self) -> usize {
+        self.chats.len()
+    }
+
+    /// Resident counts; the flush counters are left at zero.
+    pub fn metrics(&self) -> CacheMetrics {
+        CacheMetrics {
+            chats: self.chats.len(),
+            messages: self.chats.values().map(|entry| entry.messages.len()).sum(),
+            bytes: self.current_bytes,
+            pending_sends: self.pending_sends.len(),
+            ..CacheMetrics::default()
+        }
+    }
+
+    pub fn snapshot(&self) -> CacheSnapshot {
+        let mut chats = Vec::with_capacity(self.chats.len());
+        let mut messages = Vec::new();
+        for entry in self.chats.values() {
+            chats.push(entry.summary.clone());
+            messages.extend(entry.messages.iter().cloned());
+        }
+        CacheSnapshot {
+            chats,
+            messages,
+            drafts: self.drafts.values().cloned().collect(),
+        }
+    }
+
+    /// Message id below which a partly loaded chat still has history in the
+    /// store; `None` once the whole chat is in memory.
+    pub fn history_floor(&self, chat_id: ChatId) -> Option<MessageId> {
+        self.history_floors.get(&chat_id).copied()
+    }
+
+    /// Prepends history paged from the store (oldest first) below the chat's
+    /// floor. A page shorter than `requested` means the store has no older
+    /// rows, so the floor is dropped. Returns how many messages were added.
+    pub fn hydrate_history(
+        &mut self,
+        chat_id: ChatId,
+        messages: Vec<CachedMessage>,
+        requested: usize,
+    ) -> usize {
+        let Some(floor) = self.history_floors.get(&chat_id).copied() else {
+            return 0;
+        };
+        let Some(entry) = self.chats.get_mut(&chat_id) else {
+            return 0;
+        };
+        let exhausted = messages.len() < requested;
+        let lowest = messages.first().map(|message| message.message_id);
+        let mut added = 0;
+        for message in messages.into_iter().rev() {
+            let stale = !below_floor(message.message_id, floor)
+                || self
+                    .deleted_history
+                    .contains(&(chat_id, message.message_id))
+                || entry
+                    .messages
+                    .iter()
+                    .any(|cached| cached.message_id == message.message_id);
+            if stale {
+                continue;
+            }
+            let size = message_size_bytes(&message);
+            entry.message_bytes += size;
+            self.current_bytes += size;
+            entry.messages.push_front(message);
+            added += 1;
+        }
+        match lowest {
+            Some(lowest) if !exhausted => {
+                self.history_floors
+                    .insert(chat_id, MessageId(lowest.0.min(floor.0)));
+            }
+            _ => {
+                self.history_floors.remove(&chat_id);
+            }
+        }
+        added
+    }
+
+    pub fn draft(&self, chat_id: ChatId) -> Option<String> {
+        self.drafts.get(&chat_id).map(|draft| draft.text.clone())
+    }
+
+    pub fn presence(&self, user_id: UserId) -> Option<Presence> {
+        self.presence.get(&user_id).copied()
+    }
+
+    /// Known members of `chat_id`, ordered by user id.
+    pub fn chat_members(&self, chat_id: ChatId) -> Vec<ChatMember> {
+        let mut members: Vec<_> = self
+            .rosters
+            .get(&chat_id)
+            .into_iter()
+            .flatten()
+            .map(|(user_id, admin)| ChatMember {
+                user_id: *user_id,
+                admin: *admin,
+            })
+            .collect();
+        members.sort_by_key(|member| member.user_id.0);
+        members
+    }
+
+    /// Returns `true` when the stored draft changed.
+    pub fn set_draft(&mut self, chat_id: ChatId, text: &str, now: i64) -> bool {
+        let changed = if text.trim().is_empty() {
+            self.drafts.remove(&chat_id).is_some()
+        } else if self
+            .drafts
+            .get(&chat_id)
+            .is_some_and(|draft| draft.text == text)
+        {
+            false
+        } else {
+            self.drafts.insert(
+                chat_id,
+                ChatDraft {
+                    chat_id,
+                    text: text.to_string(),
+                    updated_at: now,
+                },
+            );
+            true
+        };
+        if changed {
+            self.dirty_drafts.insert(chat_id);
         }
-        for message in snapshot.messages {
-            cache.insert_message(message);
+        changed
+    }
+
+    pub fn chat_summaries(&self) -> Vec<ChatSummary> {
+        self.chats
+            .values()
+            .map(|entry| entry.summary.clone())
+            .collect()
+    }
+
+    fn export_source(&self, chat_id: ChatId) -> (Option<ChatSummary>, Vec<CachedMessage>) {
+        match self.chats.get(&chat_id) {
+            Some(entry) => (
+                Some(entry.summary.clone()),
+                entry.messages.iter().cloned().collect(),
+            ),
+            None => (None, Vec::new()),
+        }
+    }
+
+    pub fn messages_for_chat(&self, chat_id: ChatId, limit: Option<usize>) -> Vec<CachedMessage> {
+        let Some(entry) = self.chats.get(&chat_id) else {
+            return Vec::new();
+        };
+        match limit {
+            Some(limit) => {
+                let mut messages = entry
+                    .messages
+                    .iter()
+                    .rev()
+                    .take(limit)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                messages.reverse();
+                messages
+            }
+            None => entry.messages.iter().cloned().collect::<Vec<_>>(),
+        }
+    }
+
+    pub fn apply_event(&mut self, event: &DomainEvent) -> EvictionStats {
+        if let Some(chat_id) = event_chat_id(event) {
+            self.dirty_chats.insert(chat_id);
+        }
+        match event {
+            DomainEvent::MessageNew(message) => {
+                let cached = CachedMessage {
+                    chat_id: message.chat_id,
+                    message_id: message.message_id,
+                    author_id: message.author_id,
+                    timestamp: message.timestamp,
+                    edit_timestamp: None,
+                    text: message.text.clone(),
+                    outgoing: message.outgoing,
+                    expires_at: message
+                        .ttl_period
+                        .map(|ttl| message.timestamp.saturating_add(ttl)),
+                    reactions: Vec::new(),
+                    deleted: false,
+                    delivery: message.outgoing.then_some(DeliveryState::Sent),
+                    media: message.media.clone(),
+                    reply_to: message.reply_to,
+                };
+                let incoming = !cached.outgoing;
+                let message_id = cached.message_id;
+                let inserted = self.insert_message(cached);
+                if let Some(entry) = self.chats.get_mut(&message.chat_id) {
+                    let marker = if incoming {
+                        entry.summary.last_read_message_id
+                    } else {
+                        entry.summary.last_outbox_read_message_id
+                    };
+                    let read = marker.is_some_and(|last_read| message_id.0 <= last_read.0);
+                    if inserted && incoming && !read {
+                        let unread = entry.summary.unread_count.unwrap_or(0);
+                        entry.summary.unread_count = Some(unread.saturating_add(1));
+                    }
+                    if !incoming && read {
+                        mark_read_up_to(entry, message_id);
+                    }
+                }
+            }
+            DomainEvent::MessageEdited(message) => {
+                self.update_message(
+                    message.chat_id,
+                    message.message_id,
+                    &message.text,
+                    message.timestamp,
+                    message.outgoing,
+                );
+            }
+            DomainEvent::ReadReceipt(receipt) => {
+                if let Some(entry) = self.chats.get_mut(&receipt.chat_id) {
+                    let last_read = entry
+                        .summary
+                        .last_outbox_read_message_id
+                        .filter(|current| current.0 > receipt.last_read_message_id.0)
+                        .unwrap_or(receipt.last_read_message_id);
+                    entry.summary.last_outbox_read_message_id = Some(last_read);
+                    mark_read_up_to(entry, last_read);
+                    entry.updated_at = receipt.timestamp;
+                }
+            }
+            DomainEvent::InboxRead(read) => {
+                if let Some(entry) = self.chats.get_mut(&read.chat_id) {
+                    // Reads can arrive out of order; a stale one changes nothing.
+                    let stale = entry
+                        .summary
+                        .last_read_message_id
+                        .is_some_and(|current| current.0 > read.last_read_message_id.0);
+                    if !stale {
+                        entry.summary.last_read_message_id = Some(read.last_read_message_id);
+                        entry.summary.unread_count = Some(read.still_unread);
+                        entry.updated_at = read.timestamp;
+                    }
+                }
+            }
+            DomainEvent::MessageReactions(update) => {
+                if let Some(entry) = self.chats.get_mut(&update.chat_id) {
+                    if let Some(existing) = entry
+                        .messages
+                        .iter_mut()
+                        .find(|cached| cached.message_id == update.message_id)
+                    {
+                        let old_size = message_size_bytes(existing);
+                        existing.reactions = update.reactions.clone();
+                        let new_size = message_size_bytes(existing);
+                        entry.message_bytes =
+                            entry.message_bytes.saturating_sub(old_size) + new_size;
+                        self.current_bytes = self.current_bytes.saturating_sub(old_size) + new_size;
+                        entry.updated_at = update.timestamp;
+                    }
+                }
+            }
+            DomainEvent::MessagesDeleted(deleted) => match deleted.chat_id {
+                Some(chat_id) => self.delete_messages(chat_id, &deleted.message_ids),
+                None => {
+                    // Ids outside channels are unique per account, so the
+                    // chat holding them is the one the deletion was for.
+                    let chats: Vec<ChatId> = self
+                        .chats
+                        .iter()
+                        .filter(|(chat_id, entry)| {
+                            !is_channel_chat(**chat_id, entry.summary.peer_kind)
+                        })
+                        .filter(|(_, entry)| {
+                            entry
+                                .messages
+                                .iter()
+                                .any(|cached| deleted.message_ids.contains(&cached.message_id))
+                        })
+                        .map(|(chat_id, _)| *chat_id)
+                        .collect();
+                    for chat_id in chats {
+                        self.dirty_chats.insert(chat_id);
+                        self.delete_messages(chat_id, &deleted.message_ids);
+                    }
+                }
+            },
+            DomainEvent::PinnedChanged(change) => {
+                if let Some(entry) = self.chats.get_mut(&change.chat_id) {
+                    let pins = &mut entry.summary.pinned_message_ids;
+                    pins.retain(|id| !change.message_ids.contains(id));
+                    if change.pinned {
+                        let older = std::mem::take(pins);
+                        pins.extend(change.message_ids.iter().copied());
+                        pins.extend(older);
+                    }
+                }
+            }
+            DomainEvent::ChatFlagsChanged(update) => {
+                for (chat_id, change) in &update.changes {
+                    if let Some(entry) = self.chats.get_mut(chat_id) {
+                        entry.summary.flags.apply(*change);
+                        self.dirty_chats.insert(*chat_id);
+                    }
+                }
+            }
+            DomainEvent::AudioTranscribed(update) => self.set_transcription(update),
+            DomainEvent::SendProgress(progress) => {
+                if let SendStatus::Sent(SendResult::AudioTranscribed(update)) = &progress.status {
+                    self.set_transcription(update);
+                }
+                self.apply_send_status(progress.send_id, &progress.status);
+            }
+            DomainEvent::OnlineStatus(status) => {
+                self.presence.insert(status.user_id, status.presence);
+            }
+            DomainEvent::ChatMemberUpdate(update) => {
+                let roster = self.rosters.entry(update.chat_id).or_default();
+                match update.change {
+                    MemberChange::Joined { .. } => {
+                        roster.entry(update.user_id).or_insert(false);
+                    }
+                    MemberChange::Left => {
+                        roster.remove(&update.user_id);
+                    }
+                    MemberChange::Admin(admin) => {
+                        roster.insert(update.user_id, admin);
+                    }
+                }
+            }
+            DomainEvent::Typing(_)
+            | DomainEvent::TypingStopped(_)
+            | DomainEvent::ConnectionState(_) => {}
+        }
+        self.enforce_limits()
+    }
+
+    fn set_transcription(&mut self, update: &AudioTranscribed) {
+        let Some(entry) = self.chats.get_mut(&update.chat_id) else {
+            return;
+        };
+        let Some(cached) = entry
+            .messages
+            .iter_mut()
+            .find(|cached| cached.message_id == update.message_id)
+        else {
+            return;
+        };
+        let old_size = message_size_bytes(cached);
+        let Some(media) = cached.media.as_mut() else {
+            return;
+        };
+        media.transcription = Some(update.transcription.clone());
+        let new_size = message_size_bytes(cached);
+        entry.message_bytes = entry.message_bytes.saturating_sub(old_size) + new_size;
+        self.current_bytes = self.current_bytes.saturating_sub(old_size) + new_size;
+    }
+
+    pub fn upsert_chat(&mut self, summary: ChatSummary) -> EvictionStats {
+        self.insert_chat(summary);
+        self.enforce_limits()
+    }
+
+    /// Echoes an enqueued send under a placeholder id until the pipeline
+    /// reports the real message id.
+    pub fn insert_pending(
+        &mut self,
+        chat_id: ChatId,
+        send_id: SendId,
+        text: &str,
+        now: i64,
+    ) -> EvictionStats {
+        self.insert_message(CachedMessage {
+            chat_id,
+            message_id: pending_message_id(send_id),
+            author_id: UserId(0),
+            timestamp: now,
+            edit_timestamp: None,
+            text: text.to_string(),
+            outgoing: true,
+            expires_at: None,
+            reactions: Vec::new(),
+            deleted: false,
+            delivery: Some(DeliveryState::Pending),
+            media: None,
+            reply_to: None,
+        });
+        self.pending_sends.insert(send_id, chat_id);
+        self.enforce_limits()
+    }
+
+    /// Resolves the placeholder of `send_id` once its status is final.
+    /// Returns `true` when the cache changed.
+    pub fn apply_send_status(&mut self, send_id: SendId, status: &SendStatus) -> bool {
+        let (sent_id, delivered) = match status {
+            SendStatus::Queued { .. } => {
+                return self.mark_in_flight(send_id, DeliveryState::Pending)
+            }
+            SendStatus::Sending { .. } | SendStatus::Uploading { .. } => {
+                return self.mark_in_flight(send_id, DeliveryState::Sending)
+            }
+            SendStatus::Sent(SendResult::MessageSent { message_id }) => (Some(*message_id), true),
+            // The update stream delivers the real message, and scheduled ones
+            // stay out of history until posted; only the echo goes.
+            SendStatus::Sent(
+                SendResult::MessageDelivered | SendResult::MessageScheduled { .. },
+            ) => (None, true),
+            // Edits and deletions never answer a SendText.
+            SendStatus::Sent(_) | SendStatus::Failed(_) | SendStatus::Cancelled => (None, false),
+        };
+        let Some(chat_id) = self.pending_sends.remove(&send_id) else {
+            return false;
+        };
+        let Some(entry) = self.chats.get_mut(&chat_id) else {
+            return false;
+        };
+        let placeholder = pending_message_id(send_id);
+        let Some(index) = entry
+            .messages
+            .iter()
+            .position(|message| message.message_id == placeholder)
+        else {
+            return false;
+        };
+
+        let echo_superseded = match sent_id {
+            // The update for the real message arrived first.
+            Some(message_id) => entry
+                .messages
+                .iter()
+                .any(|cached| cached.message_id == message_id),
+            None => delivered,
+        };
+        match sent_id {
+            _ if echo_superseded => {
+                if let Some(removed) = entry.messages.remove(index) {
+                    let size = message_size_bytes(&removed);
+                    entry.message_bytes = entry.message_bytes.saturating_sub(size);
+                    self.current_bytes = self.current_bytes.saturating_sub(size);
+                }
+            }
+            Some(message_id) => {
+                let read = entry
+                    .summary
+                    .last_outbox_read_message_id
+                    .is_some_and(|last_read| message_id.0 <= last_read.0);
+                let message = &mut entry.messages[index];
+                message.message_id = message_id;
+                message.delivery = Some(if read {
+                    DeliveryState::Read
+                } else {
+                    DeliveryState::Sent
+                });
+            }
+            None => {
+                entry.messages[index].delivery = Some(DeliveryState::Failed);
+                self.failed_sends.insert(send_id, chat_id);
+            }
         }
-        let _ = cache.enforce_limits();
-        cache
+        entry.summary.last_message_id = entry.messages.back().map(|last| last.message_id);
+        entry.summary.last_message_at = entry.messages.back().map(|last| last.timestamp);
+        self.dirty_chats.insert(chat_id);
+        true
     }
 
-    pub fn chat_count(&self) -> usize {
-        self.chats.len()
-    }
-
-    pub fn snapshot(&self) -> CacheSnapshot {
-        let mut chats = Vec::with_capacity(self.chats.len());
-        let mut messages = Vec::new();
-        for entry in self.chats.values() {
-            chats.push(entry.summary.clone());
-            messages.extend(entry.messages.iter().cloned());
+    /// Moves the echo of a send that is not done yet to `delivery`, reopening
+    /// it first when it had failed and is being retried.
+    fn mark_in_flight(&mut self, send_id: SendId, delivery: DeliveryState) -> bool {
+        let reopened = self.reopen_failed_send(send_id);
+        let Some(chat_id) = self.pending_sends.get(&send_id).copied() else {
+            return reopened;
+        };
+        let placeholder = pending_message_id(send_id);
+        let Some(message) = self.chats.get_mut(&chat_id).and_then(|entry| {
+            entry
+                .messages
+                .iter_mut()
+                .find(|message| message.message_id == placeholder)
+        }) else {
+            return reopened;
+        };
+        if message.delivery == Some(delivery) {
+            return reopened;
         }
-        CacheSnapshot { chats, messages }
+        message.delivery = Some(delivery);
+        self.dirty_chats.insert(chat_id);
+        true
     }
 
-    pub fn chat_summaries(&self) -> Vec<ChatSummary> {
-        self.chats
-            .values()
-            .map(|entry| entry.summary.clone())
-            .collect()
+    fn reopen_failed_send(&mut self, send_id: SendId) -> bool {
+        let Some(chat_id) = self.failed_sends.remove(&send_id) else {
+            return false;
+        };
+        let placeholder = pending_message_id(send_id);
+        let Some(message) = self.chats.get_mut(&chat_id).and_then(|entry| {
+            entry
+                .messages
+                .iter_mut()
+                .find(|message| message.message_id == placeholder)
+        }) else {
+            return false;
+        };
+        message.delivery = Some(DeliveryState::Pending);
+        self.pending_sends.insert(send_id, chat_id);
+        self.dirty_chats.insert(chat_id);
+        true
     }
 
-    pub fn messages_for_chat(&self, chat_id: ChatId, limit: Option<usize>) -> Vec<CachedMessage> {
-        let Some(entry) = self.chats.get(&chat_id) else {
-            return Vec::new();
+    /// Drops the echo of a failed send the user gave up on.
+    pub fn discard_failed_send(&mut self, send_id: SendId) -> bool {
+        let Some(chat_id) = self.failed_sends.remove(&send_id) else {
+            return false;
         };
-        match limit {
-            Some(limit) => {
-                let mut messages = entry
-                    .messages
-                    .iter()
-                    .rev()
-                    .take(limit)
-                    .cloned()
-                    .collect::<Vec<_>>();
-                messages.reverse();
-                messages
-            }
-            None => entry.messages.iter().cloned().collect::<Vec<_>>(),
+        let Some(entry) = self.chats.get_mut(&chat_id) else {
+            return false;
+        };
+        let placeholder = pending_message_id(send_id);
+        let Some(index) = entry
+            .messages
+            .iter()
+            .position(|message| message.message_id == placeholder)
+        else {
+            return false;
+        };
+        if let Some(removed) = entry.messages.remove(index) {
+            let size = message_size_bytes(&removed);
+            entry.message_bytes = entry.message_bytes.saturating_sub(size);
+            self.current_bytes = self.current_bytes.saturating_sub(size);
         }
+        entry.summary.last_message_id = entry.messages.back().map(|last| last.message_id);
+        entry.summary.last_message_at = entry.messages.back().map(|last| last.timestamp);
+        self.dirty_chats.insert(chat_id);
+        true
     }
 
-    pub fn apply_event(&mut self, event: &DomainEvent) -> EvictionStats {
-        match event {
-            DomainEvent::MessageNew(message) => {
-                let cached = CachedMessage {
-                    chat_id: message.chat_id,
-                    message_id: message.message_id,
-                    author_id: message.author_id,
-                    timestamp: message.timestamp,
-                    edit_timestamp: None,
-                    text: message.text.clone(),
-                    outgoing: message.outgoing,
-                };
-                self.insert_message(cached);
-            }
-            DomainEvent::MessageEdited(message) => {
-                self.update_message(
-                    message.chat_id,
-                    message.message_id,
-                    &message.text,
-                    message.timestamp,
-                );
-            }
-            DomainEvent::ReadReceipt(receipt) => {
-                if let Some(entry) = self.chats.get_mut(&receipt.chat_id) {
-                    entry.summary.unread_count = Some(0);
-                    entry.updated_at = receipt.timestamp;
-                }
+    pub fn mark_accessed(&mut self, chat_id: ChatId, now: i64) -> bool {
+        match self.chats.get_mut(&chat_id) {
+            Some(entry) => {
+                entry.summary.last_accessed_at = Some(now);
+                self.dirty_chats.insert(chat_id);
+                true
             }
-            DomainEvent::Typing(_) => {}
+            None => false,
         }
-        self.enforce_limits()
     }
 
-    pub fn upsert_chat(&mut self, summary: ChatSummary) -> EvictionStats {
-        self.insert_chat(summary);
-        self.enforce_limits()
+    /// Drops messages whose Telegram auto-delete timer has elapsed.
+    pub fn evict_expired(&mut self, now: i64) -> usize {
+        let mut evicted = 0;
+        for (chat_id, entry) in self.chats.iter_mut() {
+            let mut freed_bytes = 0;
+            let before = entry.messages.len();
+            entry.messages.retain(|message| {
+                if message.is_expired(now) {
+                    freed_bytes += message_size_bytes(message);
+                    false
+                } else {
+                    true
+                }
+            });
+            let removed = before - entry.messages.len();
+            if removed == 0 {
+                continue;
+            }
+            evicted += removed;
+            self.dirty_chats.insert(*chat_id);
+            entry.message_bytes = entry.message_bytes.saturating_sub(freed_bytes);
+            self.current_bytes = self.current_bytes.saturating_sub(freed_bytes);
+            entry.summary.last_message_id = entry.messages.back().map(|last| last.message_id);
+            entry.summary.last_message_at = entry.messages.back().map(|last| last.timestamp);
+        }
+        evicted
     }
 
-    fn insert_chat(&mut self, summary: ChatSummary) {
+    fn insert_chat(&mut self, mut summary: ChatSummary) {
         let updated_at = summary.last_message_at.unwrap_or(0);
+        self.dirty_chats.insert(summary.chat_id);
+        self.removed_chats.remove(&summary.chat_id);
         if let Some(entry) = self.chats.get_mut(&summary.chat_id) {
+            if summary.last_read_message_id.is_none() {
+                summary.last_read_message_id = entry.summary.last_read_message_id;
+            }
+            if summary.last_outbox_read_message_id.is_none() {
+                summary.last_outbox_read_message_id = entry.summary.last_outbox_read_message_id;
+            }
+            if summary.last_accessed_at.is_none() {
+                summary.last_accessed_at = entry.summary.last_accessed_at;
+            }
+            if summary.pinned_message_ids.is_empty() {
+                summary.pinned_message_ids = std::mem::take(&mut entry.summary.pinned_message_ids);
+            }
             self.current_bytes = self.current_bytes.saturating_sub(entry.summary_bytes);
             entry.summary = summary;
             entry.summary_bytes = summary_size_bytes(&entry.summary);
@@ -530,7 +2734,10 @@ impl ChatCache {
         self.chats.insert(entry.summary.chat_id, entry);
     }
 
-    fn insert_message(&mut self, message: CachedMessage) {
+    /// Returns `true` when the message was not cached before.
+    fn insert_message(&mut self, message: CachedMessage) -> bool {
+        self.dirty_chats.insert(message.chat_id);
+        self.removed_chats.remove(&message.chat_id);
         let entry = self.chats.entry(message.chat_id).or_insert_with(|| {
             let summary = ChatSummary {
                 chat_id: message.chat_id,
@@ -539,6 +2746,11 @@ impl ChatCache {
                 last_message_id: None,
                 last_message_at: None,
                 unread_count: None,
+                last_read_message_id: None,
+                last_outbox_read_message_id: None,
+                last_accessed_at: None,
+                pinned_message_ids: Vec::new(),
+                flags: ChatFlags::default(),
             };
             let summary_bytes = summary_size_bytes(&summary);
             self.current_bytes += summary_bytes;
@@ -551,7 +2763,7 @@ impl ChatCache {
             }
         });
 
-        if let Some(existing) = entry
+        let inserted = if let Some(existing) = entry
             .messages
             .iter_mut()
             .find(|cached| cached.message_id == message.message_id)
@@ -561,18 +2773,81 @@ impl ChatCache {
             let new_size = message_size_bytes(existing);
             entry.message_bytes = entry.message_bytes.saturating_sub(old_size) + new_size;
             self.current_bytes = self.current_bytes.saturating_sub(old_size) + new_size;
+            false
         } else {
             entry.messages.push_back(message);
             let size = message_size_bytes(entry.messages.back().expect("message added"));
             entry.message_bytes += size;
             self.current_bytes += size;
-        }
+            true
+        };
 
         if let Some(last) = entry.messages.back() {
             entry.summary.last_message_id = Some(last.message_id);
             entry.summary.last_message_at = Some(last.timestamp);
             entry.updated_at = last.timestamp;
         }
+        inserted
+    }
+
+    /// Drops deleted messages, or blanks them into tombstones when configured.
+    fn delete_messages(&mut self, chat_id: ChatId, message_ids: &[MessageId]) {
+        let Some(entry) = self.chats.get_mut(&chat_id) else {
+            return;
+        };
+        // Deleting a pinned message unpins it on Telegram's side as well.
+        entry
+            .summary
+            .pinned_message_ids
+            .retain(|id| !message_ids.contains(id));
+        if let Some(floor) = self.history_floors.get(&chat_id) {
+            self.deleted_history.extend(
+                message_ids
+                    .iter()
+                    .filter(|id| below_floor(**id, *floor))
+                    .filter(|id| {
+                        !entry
+                            .messages
+                            .iter()
+                            .any(|cached| cached.message_id == **id)
+                    })
+                    .map(|id| (chat_id, *id)),
+            );
+        }
+        let last_read = entry.summary.last_read_message_id;
+        let mut freed_bytes = 0;
+        let mut unread_removed = 0u32;
+        let keep_tombstones = self.keep_tombstones;
+        entry.messages.retain_mut(|message| {
+            if message.deleted || !message_ids.contains(&message.message_id) {
+                return true;
+            }
+            let is_unread = !message.outgoing
+                && last_read.is_none_or(|last_read| message.message_id.0 > last_read.0);
+            if is_unread {
+                unread_removed += 1;
+            }
+            let old_size = message_size_bytes(message);
+            if keep_tombstones {
+                message.text.clear();
+                message.reactions.clear();
+                message.media = None;
+                message.reply_to = None;
+                message.deleted = true;
+                freed_bytes += old_size - message_size_bytes(message);
+                true
+            } else {
+                freed_bytes += old_size;
+                false
+            }
+        });
+        entry.message_bytes = entry.message_bytes.saturating_sub(freed_bytes);
+        self.current_bytes = self.current_bytes.saturating_sub(freed_bytes);
+        if let Some(unread) = entry.summary.unread_count {
+            entry.summary.unread_count = Some(unread.saturating_sub(unread_removed));
+        }
+        entry.summary.last_message_id = entry.messages.back().map(|last| last.message_id);
+        entry.summary.last_message_at = entry.messages.back().map(|last| last.timestamp);
     }
 
     fn update_message(
@@ -581,6 +2856,7 @@ impl ChatCache {
         message_id: MessageId,
         text: &str,
         timestamp: i64,
+        outgoing: bool,
     ) {
         let Some(entry) = self.chats.get_mut(&chat_id) else {
             return;
@@ -588,11 +2864,15 @@ impl ChatCache {
         if let Some(existing) = entry
             .messages
             .iter_mut()
-            .find(|cached| cached.message_id == message_id)
+            .find(|cached| cached.message_id == message_id && !cached.deleted)
         {
             let old_size = message_size_bytes(existing);
             existing.text = text.to_string();
             existing.edit_timestamp = Some(timestamp);
+            if outgoing && !existing.outgoing {
+                existing.outgoing = true;
+                existing.delivery = Some(DeliveryState::Sent);
+            }
             let new_size = message_size_bytes(existing);
             entry.message_bytes = entry.message_bytes.saturating_sub(old_size) + new_size;
             self.current_bytes = self.current_bytes.saturating_sub(old_size) + new_size;
@@ -602,15 +2882,31 @@ impl ChatCache {
 
     fn enforce_limits(&mut self) -> EvictionStats {
         let mut stats = EvictionStats::default();
-        if self.limits.max_messages_per_chat > 0 {
-            for entry in self.chats.values_mut() {
-                while entry.messages.len() > self.limits.max_messages_per_chat {
-                    if let Some(removed) = entry.messages.pop_front() {
-                        let size = message_size_bytes(&removed);
-                        entry.message_bytes = entry.message_bytes.saturating_sub(size);
-                        self.current_bytes = self.current_bytes.saturating_sub(size);
-                        stats.messages_evicted += 1;
+        let max_messages = self.limits.max_messages_per_chat;
+        let max_chat_bytes = self.limits.max_bytes_per_chat;
+        if max_messages > 0 || max_chat_bytes > 0 {
+            for (chat_id, entry) in self.chats.iter_mut() {
+                while (max_messages > 0 && entry.messages.len() > max_messages)
+                    || (max_chat_bytes > 0
+                        && entry.message_bytes > max_chat_bytes
+                        && entry.messages.len() > 1)
+                {
+                    let Some(removed) = entry.messages.pop_front() else {
+                        break;
+                    };
+                    self.dirty_chats.insert(*chat_id);
+                    if self.lazy_history && removed.message_id.0 > 0 {
+                        // Trimmed history stays in the store for hydration.
+                        let floor = self
+                            .history_floors
+                            .entry(*chat_id)
+                            .or_insert(removed.message_id);
+                        floor.0 = floor.0.max(removed.message_id.0 + 1);
                     }
+                    let size = message_size_bytes(&removed);
+                    entry.message_bytes = entry.message_bytes.saturating_sub(size);
+                    self.current_bytes = self.current_bytes.saturating_sub(size);
+                    stats.messages_evicted += 1;
                 }
             }
         }
@@ -638,15 +2934,25 @@ impl ChatCache {
         stats
     }
 
+    /// Picks the eviction victim: chats never opened in the UI go first (oldest
+    /// activity first), then viewed chats by their most recent view or activity.
     fn least_recent_chat(&self) -> Option<ChatId> {
         self.chats
             .iter()
-            .min_by_key(|(_, entry)| entry.updated_at)
+            .min_by_key(|(_, entry)| match entry.summary.last_accessed_at {
+                Some(accessed_at) => (true, accessed_at.max(entry.updated_at)),
+                None => (false, entry.updated_at),
+            })
             .map(|(chat_id, _)| *chat_id)
     }
 
     fn remove_chat(&mut self, chat_id: ChatId, stats: &mut EvictionStats) {
         if let Some(entry) = self.chats.remove(&chat_id) {
+            self.dirty_chats.remove(&chat_id);
+            self.history_floors.remove(&chat_id);
+            self.deleted_history
+                .retain(|(deleted, _)| *deleted != chat_id);
+            self.removed_chats.insert(chat_id);
             stats.chats_evicted += 1;
             stats.messages_evicted += entry.messages.len();
             self.current_bytes = self
@@ -656,6 +2962,50 @@ impl ChatCache {
     }
 }
 
+/// Placeholder ids are negative so they never collide with Telegram's.
+fn pending_message_id(send_id: SendId) -> MessageId {
+    MessageId(-(send_id.0.min(i64::MAX as u64) as i64))
+}
+
+/// Bot API dialog ids of channels and supergroups start at -10^12, which
+/// also covers chats cached before their peer kind was known.
+fn is_channel_chat(chat_id: ChatId, peer_kind: ChatPeerKind) -> bool {
+    peer_kind == ChatPeerKind::Channel || chat_id.0 <= -1_000_000_000_000
+}
+
+/// Promotes sent outgoing messages covered by an outbox read receipt.
+fn mark_read_up_to(entry: &mut ChatEntry, last_read: MessageId) {
+    for message in entry.messages.iter_mut() {
+        if message.outgoing
+            && message.delivery == Some(DeliveryState::Sent)
+            && message.message_id.0 <= last_read.0
+        {
+            message.delivery = Some(DeliveryState::Read);
+        }
+    }
+}
+
+fn event_chat_id(event: &DomainEvent) -> Option<ChatId> {
+    match event {
+        DomainEvent::MessageNew(message) => Some(message.chat_id),
+        DomainEvent::MessageEdited(message) => Some(message.chat_id),
+        DomainEvent::ReadReceipt(receipt) => Some(receipt.chat_id),
+        DomainEvent::InboxRead(read) => Some(read.chat_id),
+        DomainEvent::MessageReactions(update) => Some(update.chat_id),
+        DomainEvent::MessagesDeleted(deleted) => deleted.chat_id,
+        DomainEvent::PinnedChanged(change) => Some(change.chat_id),
+        DomainEvent::AudioTranscribed(update) => Some(update.chat_id),
+        // Spans several chats; marked dirty per chat when applied.
+        DomainEvent::ChatFlagsChanged(_)
+        | DomainEvent::SendProgress(_)
+        | DomainEvent::Typing(_)
+        | DomainEvent::TypingStopped(_)
+        | DomainEvent::ConnectionState(_)
+        | DomainEvent::OnlineStatus(_)
+        | DomainEvent::ChatMemberUpdate(_) => None,
+    }
+}
+
 #[derive(Debug)]
 enum FlushCommand {
     Dirty,
@@ -667,6 +3017,7 @@ fn spawn_flush_task(
     store: Arc<dyn CacheStore>,
     mut flush_rx: mpsc::UnboundedReceiver<FlushCommand>,
     debounce: Duration,
+    counters: Arc<FlushCounters>,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
         let mut dirty = false;
@@ -683,7 +3034,7 @@ fn spawn_flush_task(
                             }
                             Some(FlushCommand::Shutdown) | None => {
                                 if dirty {
-                                    flush_snapshot(&inner, &store).await;
+                                    flush_snapshot(&inner, &store, &counters).await;
                                 }
                                 break;
                             }
@@ -691,7 +3042,7 @@ fn spawn_flush_task(
                     }
                     _ = tokio::time::sleep_until(deadline) => {
                         if dirty {
-                            flush_snapshot(&inner, &store).await;
+                            flush_snapshot(&inner, &store, &counters).await;
                             dirty = false;
                         }
                         next_flush = None;
@@ -705,7 +3056,7 @@ fn spawn_flush_task(
                     }
                     Some(FlushCommand::Shutdown) | None => {
                         if dirty {
-                            flush_snapshot(&inner, &store).await;
+                            flush_snapshot(&inner, &store, &counters).await;
                         }
                         break;
                     }
@@ -715,128 +3066,887 @@ fn spawn_flush_task(
     })
 }
 
-async fn flush_snapshot(inner: &Arc<RwLock<ChatCache>>, store: &Arc<dyn CacheStore>) {
-    let snapshot = match inner.read() {
-        Ok(cache) => cache.snapshot(),
-        Err(poisoned) => poisoned.into_inner().snapshot(),
+async fn flush_snapshot(
+    inner: &Arc<RwLock<ChatCache>>,
+    store: &Arc<dyn CacheStore>,
+    counters: &FlushCounters,
+) {
+    let delta = match inner.write() {
+        Ok(mut cache) => cache.take_delta(),
+        Err(poisoned) => poisoned.into_inner().take_delta(),
     };
+    if delta.is_empty() {
+        return;
+    }
 
     let result = tokio::task::spawn_blocking({
         let store = Arc::clone(store);
-        let snapshot = snapshot.clone();
-        move || store.save(&snapshot)
+        let delta = delta.clone();
+        move || store.save_delta(&delta)
     })
     .await;
 
-    match result {
+    let failed = match result {
         Ok(Ok(())) => {
             info!(
-                chats = snapshot.chats.len(),
-                messages = snapshot.messages.len(),
+                chats = delta.chats.len(),
+                messages = delta.messages.len(),
+                removed_chats = delta.removed_chats.len(),
                 "cache flushed"
             );
+            counters.flushes.fetch_add(1, Ordering::Relaxed);
+            false
         }
         Ok(Err(err)) => {
             warn!(error = %err, "cache flush failed");
+            true
         }
         Err(err) => {
             warn!(error = %err, "cache flush task failed");
+            true
+        }
+    };
+    if failed {
+        counters.failures.fetch_add(1, Ordering::Relaxed);
+        match inner.write() {
+            Ok(mut cache) => cache.requeue_delta(&delta),
+            Err(poisoned) => poisoned.into_inner().requeue_delta(&delta),
         }
     }
 }
 
-fn message_size_bytes(message: &CachedMessage) -> usize {
-    message.text.len().saturating_add(MESSAGE_OVERHEAD_BYTES)
-}
+fn message_size_bytes(message: &CachedMessage) -> usize {
+    let reactions = message
+        .reactions
+        .iter()
+        .map(|reaction| reaction.emoji.len() + REACTION_OVERHEAD_BYTES)
+        .sum::<usize>();
+    // Media is counted as stored, plus the transcription kept beside it.
+    let media = message.media.as_ref().map_or(0, |media| {
+        let encoded = encode_media(Some(media)).map_or(0, |encoded| encoded.len());
+        let transcription = media
+            .transcription
+            .as_ref()
+            .map_or(0, |transcription| transcription.text.len());
+        encoded + transcription
+    });
+    let reply_to = if message.reply_to.is_some() {
+        std::mem::size_of::<i64>()
+    } else {
+        0
+    };
+    message
+        .text
+        .len()
+        .saturating_add(reactions)
+        .saturating_add(media)
+        .saturating_add(reply_to)
+        .saturating_add(MESSAGE_OVERHEAD_BYTES)
+}
+
+fn summary_size_bytes(summary: &ChatSummary) -> usize {
+    summary.title.len().saturating_add(CHAT_OVERHEAD_BYTES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::events::{
+        ChatFlagChange, ChatFlagsChanged, ChatMemberUpdate, DomainEvent, InboxRead, MessageEdited,
+        MessageNew, MessageReactions, MessagesDeleted, OnlineStatus, PinnedChanged, ReadReceipt,
+        SendProgress, Transcription, ARCHIVE_FOLDER_ID,
+    };
+    use crate::telegram::send::SendFailure;
+
+    fn cache_limits() -> CacheLimits {
+        CacheLimits {
+            max_chats: 2,
+            max_messages_per_chat: 3,
+            max_bytes: 0,
+            max_bytes_per_chat: 0,
+        }
+    }
+
+    fn base_message(chat_id: i64, message_id: i64, timestamp: i64, text: &str) -> MessageNew {
+        MessageNew {
+            chat_id: ChatId(chat_id),
+            message_id: MessageId(message_id),
+            author_id: UserId(1),
+            timestamp,
+            text: text.to_string(),
+            outgoing: false,
+            ttl_period: None,
+            media: None,
+            reply_to: None,
+        }
+    }
+
+    #[test]
+    fn applies_message_edit_updates_text() {
+        let mut cache = ChatCache::new(cache_limits());
+        let new = base_message(1, 10, 100, "hello");
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(new)));
+
+        let edit = MessageEdited {
+            chat_id: ChatId(1),
+            message_id: MessageId(10),
+            editor_id: UserId(1),
+            timestamp: 120,
+            text: "updated".to_string(),
+            outgoing: false,
+        };
+        cache.apply_event(&DomainEvent::MessageEdited(edit));
+
+        let messages = cache.messages_for_chat(ChatId(1), None);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text, "updated");
+        assert_eq!(messages[0].edit_timestamp, Some(120));
+    }
+
+    #[test]
+    fn tracks_latest_presence() {
+        let mut cache = ChatCache::new(cache_limits());
+        for presence in [
+            Presence::Online { expires: 200 },
+            Presence::Offline { last_seen: 150 },
+        ] {
+            cache.apply_event(&DomainEvent::OnlineStatus(OnlineStatus {
+                user_id: UserId(5),
+                presence,
+                timestamp: 100,
+            }));
+        }
+
+        assert_eq!(
+            cache.presence(UserId(5)),
+            Some(Presence::Offline { last_seen: 150 })
+        );
+        assert_eq!(cache.presence(UserId(6)), None);
+    }
+
+    #[test]
+    fn tracks_chat_rosters() {
+        let mut cache = ChatCache::new(cache_limits());
+        let member = |user_id: i64, change: MemberChange| {
+            DomainEvent::ChatMemberUpdate(ChatMemberUpdate {
+                chat_id: ChatId(-5),
+                user_id: UserId(user_id),
+                change,
+                timestamp: 100,
+            })
+        };
+        cache.apply_event(&member(2, MemberChange::Joined { inviter_id: None }));
+        cache.apply_event(&member(1, MemberChange::Joined { inviter_id: None }));
+        cache.apply_event(&member(1, MemberChange::Admin(true)));
+        cache.apply_event(&member(3, MemberChange::Joined { inviter_id: None }));
+        cache.apply_event(&member(3, MemberChange::Left));
+
+        assert_eq!(
+            cache.chat_members(ChatId(-5)),
+            vec![
+                ChatMember {
+                    user_id: UserId(1),
+                    admin: true,
+                },
+                ChatMember {
+                    user_id: UserId(2),
+                    admin: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn edits_carry_the_outgoing_flag() {
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 10, 100, "post",
+        ))));
+
+        cache.apply_event(&DomainEvent::MessageEdited(MessageEdited {
+            chat_id: ChatId(1),
+            message_id: MessageId(10),
+            editor_id: UserId(1),
+            timestamp: 120,
+            text: "post, edited".to_string(),
+            outgoing: true,
+        }));
+
+        let messages = cache.messages_for_chat(ChatId(1), None);
+        assert!(messages[0].outgoing);
+        assert_eq!(messages[0].delivery, Some(DeliveryState::Sent));
+    }
+
+    #[test]
+    fn evicts_oldest_messages_and_chats() {
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 101, "two",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 3, 102, "three",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 4, 103, "four",
+        ))));
+
+        let messages = cache.messages_for_chat(ChatId(1), None);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].message_id, MessageId(2));
+
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            2, 1, 200, "second",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            3, 1, 300, "third",
+        ))));
+        assert_eq!(cache.chat_count(), 2);
+        assert!(cache.chats.contains_key(&ChatId(2)));
+        assert!(cache.chats.contains_key(&ChatId(3)));
+    }
+
+    #[test]
+    fn trims_chats_over_their_byte_budget() {
+        let message_bytes = MESSAGE_OVERHEAD_BYTES + 4;
+        let mut cache = ChatCache::new(CacheLimits {
+            max_messages_per_chat: 0,
+            max_bytes_per_chat: message_bytes * 2,
+            ..cache_limits()
+        });
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "aaaa",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 101, "bbbb",
+        ))));
+        assert_eq!(cache.messages_for_chat(ChatId(1), None).len(), 2);
+
+        // Reactions count towards the budget, pushing out the oldest message.
+        let stats = cache.apply_event(&DomainEvent::MessageReactions(MessageReactions {
+            chat_id: ChatId(1),
+            message_id: MessageId(2),
+            timestamp: 102,
+            reactions: vec![MessageReaction {
+                emoji: "👍".to_string(),
+                count: 1,
+                mine: false,
+            }],
+        }));
+        assert_eq!(stats.messages_evicted, 1);
+        let messages = cache.messages_for_chat(ChatId(1), None);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_id, MessageId(2));
+
+        // So do attachments and reply targets.
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            2, 1, 200, "aaaa",
+        ))));
+        let mut photo = base_message(2, 2, 201, "");
+        photo.reply_to = Some(MessageId(1));
+        photo.media = Some(MessageMedia {
+            kind: MessageMediaKind::Photo,
+            size: None,
+            thumbnail: None,
+            duration: None,
+            document: None,
+            transcription: None,
+        });
+        let stats = cache.apply_event(&DomainEvent::MessageNew(Box::new(photo)));
+        assert_eq!(stats.messages_evicted, 1);
+        let messages = cache.messages_for_chat(ChatId(2), None);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_id, MessageId(2));
+
+        // The newest message stays even when it alone exceeds the budget.
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1,
+            3,
+            103,
+            &"c".repeat(message_bytes * 3),
+        ))));
+        let messages = cache.messages_for_chat(ChatId(1), None);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_id, MessageId(3));
+    }
+
+    #[test]
+    fn eviction_prefers_chats_never_viewed() {
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "quiet",
+        ))));
+        cache.mark_accessed(ChatId(1), 105);
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            2, 1, 200, "noisy",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            3, 1, 300, "noisier",
+        ))));
+
+        let mut remaining = cache
+            .chat_summaries()
+            .into_iter()
+            .map(|summary| summary.chat_id.0)
+            .collect::<Vec<_>>();
+        remaining.sort_unstable();
+        assert_eq!(remaining, vec![1, 3]);
+    }
+
+    #[test]
+    fn take_delta_returns_only_changed_chats() {
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            2, 1, 200, "two",
+        ))));
+        let first = cache.take_delta();
+        assert_eq!(first.chats.len(), 2);
+        assert!(cache.take_delta().is_empty());
+
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 101, "again",
+        ))));
+        let second = cache.take_delta();
+        assert_eq!(second.chats.len(), 1);
+        assert_eq!(second.chats[0].chat_id, ChatId(1));
+        assert_eq!(second.messages.len(), 2);
+
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            3, 1, 300, "three",
+        ))));
+        let third = cache.take_delta();
+        assert_eq!(third.removed_chats, vec![ChatId(1)]);
+
+        cache.requeue_delta(&third);
+        let retried = cache.take_delta();
+        assert_eq!(retried.chats.len(), 1);
+        assert_eq!(retried.removed_chats, vec![ChatId(1)]);
+    }
+
+    #[test]
+    fn inbox_reads_set_the_marker_and_unread_count() {
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 101, "two",
+        ))));
+        let read = |last_read: i64, still_unread: u32| {
+            DomainEvent::InboxRead(InboxRead {
+                chat_id: ChatId(1),
+                timestamp: 150,
+                last_read_message_id: MessageId(last_read),
+                still_unread,
+            })
+        };
+        let summary = |cache: &ChatCache| cache.chat_summaries().pop().expect("summary");
+
+        cache.apply_event(&read(1, 1));
+        assert_eq!(summary(&cache).unread_count, Some(1));
+        assert_eq!(summary(&cache).last_read_message_id, Some(MessageId(1)));
+
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 3, 103, "three",
+        ))));
+        assert_eq!(summary(&cache).unread_count, Some(2));
+
+        // A stale read must not resurrect already-read messages.
+        cache.apply_event(&read(3, 0));
+        cache.apply_event(&read(2, 1));
+        assert_eq!(summary(&cache).unread_count, Some(0));
+        assert_eq!(summary(&cache).last_read_message_id, Some(MessageId(3)));
+    }
+
+    #[test]
+    fn outbox_receipts_leave_the_unread_count_alone() {
+        let mut cache = ChatCache::new(cache_limits());
+        let mut outgoing = base_message(1, 3, 102, "mine");
+        outgoing.outgoing = true;
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 101, "two",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(outgoing)));
+        cache.insert_pending(ChatId(1), SendId(1), "late", 103);
+        let receipt = |last_read: i64| {
+            DomainEvent::ReadReceipt(ReadReceipt {
+                chat_id: ChatId(1),
+                reader_id: UserId(1),
+                timestamp: 150,
+                last_read_message_id: MessageId(last_read),
+            })
+        };
+        let delivery = |cache: &ChatCache, message_id: i64| {
+            cache
+                .messages_for_chat(ChatId(1), None)
+                .iter()
+                .find(|message| message.message_id == MessageId(message_id))
+                .and_then(|message| message.delivery)
+        };
+
+        cache.apply_event(&receipt(4));
+        cache.apply_event(&receipt(3));
+        let summary = cache.chat_summaries().pop().expect("summary");
+        assert_eq!(summary.unread_count, Some(2));
+        assert_eq!(summary.last_read_message_id, None);
+        assert_eq!(summary.last_outbox_read_message_id, Some(MessageId(4)));
+        assert_eq!(delivery(&cache, 3), Some(DeliveryState::Read));
+
+        // A send confirmed after the receipt is already read.
+        assert!(cache.apply_send_status(
+            SendId(1),
+            &SendStatus::Sent(SendResult::MessageSent {
+                message_id: MessageId(4),
+            }),
+        ));
+        assert_eq!(delivery(&cache, 4), Some(DeliveryState::Read));
+    }
+
+    #[test]
+    fn tracks_delivery_of_outgoing_messages() {
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "hi",
+        ))));
+        cache.insert_pending(ChatId(1), SendId(7), "hello", 110);
+        cache.insert_pending(ChatId(1), SendId(8), "oops", 111);
+        let delivery = |cache: &ChatCache| {
+            cache
+                .messages_for_chat(ChatId(1), None)
+                .iter()
+                .map(|message| (message.message_id.0, message.delivery))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(delivery(&cache)[1], (-7, Some(DeliveryState::Pending)));
+
+        assert!(cache.apply_send_status(SendId(7), &SendStatus::Sending { attempt: 1 }));
+        assert!(!cache.apply_send_status(SendId(7), &SendStatus::Sending { attempt: 1 }));
+        assert_eq!(delivery(&cache)[1], (-7, Some(DeliveryState::Sending)));
+        assert!(cache.apply_send_status(
+            SendId(7),
+            &SendStatus::Queued {
+                attempt: 1,
+                next_retry_in: Some(Duration::from_secs(1)),
+            },
+        ));
+        assert_eq!(delivery(&cache)[1], (-7, Some(DeliveryState::Pending)));
+        assert!(cache.apply_send_status(
+            SendId(7),
+            &SendStatus::Uploading {
+                attempt: 2,
+                pct: 40
+            },
+        ));
+        assert_eq!(delivery(&cache)[1], (-7, Some(DeliveryState::Sending)));
+        assert!(cache.apply_send_status(
+            SendId(7),
+            &SendStatus::Sent(SendResult::MessageSent {
+                message_id: MessageId(2),
+            }),
+        ));
+        assert!(cache.apply_send_status(
+            SendId(8),
+            &SendStatus::Failed(SendFailure {
+                error: "flood".to_string(),
+                attempts: 3,
+                retryable: false,
+            }),
+        ));
+        assert_eq!(
+            delivery(&cache),
+            vec![
+                (1, None),
+                (2, Some(DeliveryState::Sent)),
+                (-8, Some(DeliveryState::Failed)),
+            ]
+        );
 
-fn summary_size_bytes(summary: &ChatSummary) -> usize {
-    summary.title.len().saturating_add(CHAT_OVERHEAD_BYTES)
-}
+        cache.apply_event(&DomainEvent::ReadReceipt(ReadReceipt {
+            chat_id: ChatId(1),
+            reader_id: UserId(2),
+            timestamp: 120,
+            last_read_message_id: MessageId(2),
+        }));
+        assert_eq!(delivery(&cache)[1], (2, Some(DeliveryState::Read)));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::telegram::events::{DomainEvent, MessageEdited, MessageNew, ReadReceipt};
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::Mutex;
+        // A duplicate resend leaves the real message to the update stream.
+        cache.insert_pending(ChatId(1), SendId(9), "again", 112);
+        assert!(
+            cache.apply_send_status(SendId(9), &SendStatus::Sent(SendResult::MessageDelivered),)
+        );
+        assert!(delivery(&cache).iter().all(|(id, _)| *id != -9));
 
-    fn cache_limits() -> CacheLimits {
-        CacheLimits {
-            max_chats: 2,
-            max_messages_per_chat: 3,
-            max_bytes: 0,
-        }
+        // Scheduled messages stay out of history until Telegram posts them.
+        cache.insert_pending(ChatId(1), SendId(10), "later", 113);
+        assert!(cache.apply_send_status(
+            SendId(10),
+            &SendStatus::Sent(SendResult::MessageScheduled {
+                message_id: MessageId(1),
+            }),
+        ));
+        assert!(delivery(&cache).iter().all(|(id, _)| *id != -10));
     }
 
-    fn base_message(chat_id: i64, message_id: i64, timestamp: i64, text: &str) -> MessageNew {
-        MessageNew {
-            chat_id: ChatId(chat_id),
-            message_id: MessageId(message_id),
-            author_id: UserId(1),
-            timestamp,
-            text: text.to_string(),
-            outgoing: false,
-        }
+    #[test]
+    fn retried_failed_sends_reopen_and_discarded_ones_vanish() {
+        let mut cache = ChatCache::new(cache_limits());
+        cache.insert_pending(ChatId(1), SendId(7), "hello", 110);
+        let failed = SendStatus::Failed(SendFailure {
+            error: "flood".to_string(),
+            attempts: 3,
+            retryable: true,
+        });
+        let delivery = |cache: &ChatCache| {
+            cache
+                .messages_for_chat(ChatId(1), None)
+                .iter()
+                .map(|message| (message.message_id.0, message.delivery))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(cache.apply_send_status(SendId(7), &failed));
+        assert!(cache.apply_send_status(
+            SendId(7),
+            &SendStatus::Queued {
+                attempt: 0,
+                next_retry_in: None,
+            },
+        ));
+        assert_eq!(delivery(&cache), vec![(-7, Some(DeliveryState::Pending))]);
+
+        assert!(cache.apply_send_status(SendId(7), &failed));
+        assert!(cache.discard_failed_send(SendId(7)));
+        assert!(delivery(&cache).is_empty());
+        assert!(!cache.discard_failed_send(SendId(7)));
     }
 
     #[test]
-    fn applies_message_edit_updates_text() {
+    fn reaction_event_replaces_message_reactions() {
         let mut cache = ChatCache::new(cache_limits());
-        let new = base_message(1, 10, 100, "hello");
-        cache.apply_event(&DomainEvent::MessageNew(new));
-
-        let edit = MessageEdited {
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        let reactions = vec![
+            MessageReaction {
+                emoji: "🔥".to_string(),
+                count: 3,
+                mine: false,
+            },
+            MessageReaction {
+                emoji: "custom:42".to_string(),
+                count: 1,
+                mine: true,
+            },
+        ];
+        cache.apply_event(&DomainEvent::MessageReactions(MessageReactions {
             chat_id: ChatId(1),
-            message_id: MessageId(10),
-            editor_id: UserId(1),
-            timestamp: 120,
-            text: "updated".to_string(),
-            outgoing: false,
-        };
-        cache.apply_event(&DomainEvent::MessageEdited(edit));
+            message_id: MessageId(1),
+            timestamp: 110,
+            reactions: reactions.clone(),
+        }));
 
         let messages = cache.messages_for_chat(ChatId(1), None);
-        assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0].text, "updated");
-        assert_eq!(messages[0].edit_timestamp, Some(120));
+        assert_eq!(messages[0].reactions, reactions);
+        let encoded = encode_reactions(&reactions).expect("encoded reactions");
+        assert_eq!(decode_reactions(&encoded), reactions);
     }
 
     #[test]
-    fn evicts_oldest_messages_and_chats() {
+    fn message_media_is_cached_and_round_trips() {
         let mut cache = ChatCache::new(cache_limits());
-        cache.apply_event(&DomainEvent::MessageNew(base_message(1, 1, 100, "one")));
-        cache.apply_event(&DomainEvent::MessageNew(base_message(1, 2, 101, "two")));
-        cache.apply_event(&DomainEvent::MessageNew(base_message(1, 3, 102, "three")));
-        cache.apply_event(&DomainEvent::MessageNew(base_message(1, 4, 103, "four")));
+        let photo = MessageMedia {
+            kind: MessageMediaKind::Photo,
+            size: Some(1_250_000),
+            thumbnail: Some(PhotoThumbnail {
+                photo_id: 5,
+                access_hash: -9,
+                file_reference: vec![0, 1, 254, 255],
+                dc_id: 2,
+                size_type: "m".to_string(),
+            }),
+            duration: None,
+            document: None,
+            transcription: None,
+        };
+        let mut message = base_message(1, 1, 100, "");
+        message.media = Some(photo.clone());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(message)));
 
         let messages = cache.messages_for_chat(ChatId(1), None);
-        assert_eq!(messages.len(), 3);
-        assert_eq!(messages[0].message_id, MessageId(2));
+        assert_eq!(messages[0].media.as_ref(), Some(&photo));
+        let encoded = encode_media(Some(&photo)).expect("encoded media");
+        assert_eq!(decode_media(&encoded), Some(photo));
 
-        cache.apply_event(&DomainEvent::MessageNew(base_message(2, 1, 200, "second")));
-        cache.apply_event(&DomainEvent::MessageNew(base_message(3, 1, 300, "third")));
-        assert_eq!(cache.chat_count(), 2);
-        assert!(cache.chats.contains_key(&ChatId(2)));
-        assert!(cache.chats.contains_key(&ChatId(3)));
+        let file = MessageMedia {
+            kind: MessageMediaKind::Document,
+            size: None,
+            thumbnail: None,
+            duration: None,
+            document: None,
+            transcription: None,
+        };
+        let encoded = encode_media(Some(&file)).expect("encoded media");
+        assert_eq!(encoded, "document");
+        assert_eq!(decode_media(&encoded), Some(file));
+        assert_eq!(encode_media(None), None);
     }
 
     #[test]
-    fn read_receipt_sets_unread_count() {
+    fn voice_messages_keep_their_file_and_take_transcriptions() {
         let mut cache = ChatCache::new(cache_limits());
-        cache.apply_event(&DomainEvent::MessageNew(base_message(1, 1, 100, "one")));
-        cache.apply_event(&DomainEvent::ReadReceipt(ReadReceipt {
+        let voice = MessageMedia {
+            kind: MessageMediaKind::Voice,
+            size: Some(38_000),
+            thumbnail: None,
+            duration: Some(42),
+            document: Some(DocumentFile {
+                id: 7,
+                access_hash: -3,
+                file_reference: vec![9, 8],
+                dc_id: 4,
+                mime_type: "audio/ogg".to_string(),
+                file_name: Some("note.ogg".to_string()),
+            }),
+            transcription: None,
+        };
+        let encoded = encode_media(Some(&voice)).expect("encoded media");
+        assert_eq!(decode_media(&encoded), Some(voice.clone()));
+        assert_eq!(
+            decode_media("voice\t38000").map(|media| media.document),
+            Some(None)
+        );
+
+        let mut message = base_message(1, 1, 100, "");
+        message.media = Some(voice);
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(message)));
+        let transcription = Transcription {
+            text: "see you at".to_string(),
+            pending: true,
+        };
+        cache.apply_event(&DomainEvent::AudioTranscribed(AudioTranscribed {
             chat_id: ChatId(1),
-            reader_id: UserId(1),
-            timestamp: 150,
-            last_read_message_id: MessageId(1),
+            message_id: MessageId(1),
+            transcription: transcription.clone(),
         }));
-        let summary = cache
-            .snapshot()
-            .chats
-            .into_iter()
-            .find(|summary| summary.chat_id == ChatId(1))
-            .expect("summary");
-        assert_eq!(summary.unread_count, Some(0));
+        let media = |cache: &ChatCache| cache.messages_for_chat(ChatId(1), None)[0].media.clone();
+        assert_eq!(
+            media(&cache).and_then(|media| media.transcription),
+            Some(transcription)
+        );
+
+        let done = AudioTranscribed {
+            chat_id: ChatId(1),
+            message_id: MessageId(1),
+            transcription: Transcription {
+                text: "see you at noon".to_string(),
+                pending: false,
+            },
+        };
+        cache.apply_event(&DomainEvent::SendProgress(SendProgress {
+            send_id: SendId(3),
+            status: SendStatus::Sent(SendResult::AudioTranscribed(done.clone())),
+        }));
+        assert_eq!(
+            media(&cache).and_then(|media| media.transcription),
+            Some(done.transcription)
+        );
+    }
+
+    #[test]
+    fn pin_events_keep_most_recent_pin_first() {
+        let mut cache = ChatCache::new(cache_limits());
+        for id in 1..=3 {
+            cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+                1,
+                id,
+                100 + id,
+                "msg",
+            ))));
+        }
+        let pin = |ids: &[i64], pinned: bool| {
+            DomainEvent::PinnedChanged(PinnedChanged {
+                chat_id: ChatId(1),
+                message_ids: ids.iter().copied().map(MessageId).collect(),
+                pinned,
+            })
+        };
+        let pins = |cache: &ChatCache| cache.chat_summaries()[0].pinned_message_ids.clone();
+
+        cache.apply_event(&pin(&[1], true));
+        cache.apply_event(&pin(&[3], true));
+        cache.apply_event(&pin(&[1], true));
+        assert_eq!(pins(&cache), vec![MessageId(1), MessageId(3)]);
+
+        cache.apply_event(&pin(&[1], false));
+        assert_eq!(pins(&cache), vec![MessageId(3)]);
+
+        cache.upsert_chat(ChatSummary {
+            chat_id: ChatId(1),
+            title: "Synced".to_string(),
+            peer_kind: ChatPeerKind::Group,
+            last_message_id: None,
+            last_message_at: Some(103),
+            unread_count: None,
+            last_read_message_id: None,
+            last_outbox_read_message_id: None,
+            last_accessed_at: None,
+            pinned_message_ids: Vec::new(),
+            flags: ChatFlags::default(),
+        });
+        assert_eq!(pins(&cache), vec![MessageId(3)]);
+
+        cache.apply_event(&DomainEvent::MessagesDeleted(MessagesDeleted {
+            chat_id: Some(ChatId(1)),
+            message_ids: vec![MessageId(3)],
+        }));
+        assert!(pins(&cache).is_empty());
+    }
+
+    #[test]
+    fn resolves_deletions_without_a_chat() {
+        // All three chats must stay resident for the deletion to find them.
+        let mut cache = ChatCache::new(CacheLimits {
+            max_chats: 0,
+            ..cache_limits()
+        });
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 10, 100, "one",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            2, 11, 110, "two",
+        ))));
+        let channel = -1_000_000_000_005;
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            channel, 10, 120, "post",
+        ))));
+        let _ = cache.take_delta();
+
+        cache.apply_event(&DomainEvent::MessagesDeleted(MessagesDeleted {
+            chat_id: None,
+            message_ids: vec![MessageId(10)],
+        }));
+
+        assert!(cache.messages_for_chat(ChatId(1), None).is_empty());
+        assert_eq!(cache.messages_for_chat(ChatId(2), None).len(), 1);
+        assert_eq!(cache.messages_for_chat(ChatId(channel), None).len(), 1);
+        assert_eq!(
+            cache.take_delta().touched_chats(),
+            HashSet::from([ChatId(1)])
+        );
+    }
+
+    #[test]
+    fn chat_flag_events_update_summaries() {
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        let _ = cache.take_delta();
+
+        cache.apply_event(&DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+            changes: vec![
+                (ChatId(1), ChatFlagChange::Folder(Some(ARCHIVE_FOLDER_ID))),
+                (ChatId(1), ChatFlagChange::Muted(true)),
+                (ChatId(9), ChatFlagChange::Pinned(true)),
+            ],
+        }));
+        let flags = cache.chat_summaries()[0].flags;
+        assert!(flags.archived && flags.muted && !flags.pinned);
+        assert_eq!(cache.chat_count(), 1);
+        assert_eq!(cache.take_delta().chats.len(), 1);
+
+        cache.apply_event(&DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+            changes: vec![(ChatId(1), ChatFlagChange::Folder(None))],
+        }));
+        let flags = cache.chat_summaries()[0].flags;
+        assert!(!flags.archived);
+        assert_eq!(flags.folder_id, None);
+    }
+
+    #[test]
+    fn deleted_messages_are_dropped_or_tombstoned() {
+        let deletion = DomainEvent::MessagesDeleted(MessagesDeleted {
+            chat_id: Some(ChatId(1)),
+            message_ids: vec![MessageId(2)],
+        });
+
+        let mut dropping = ChatCache::new(cache_limits());
+        dropping.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        dropping.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 101, "two",
+        ))));
+        dropping.apply_event(&deletion);
+        let messages = dropping.messages_for_chat(ChatId(1), None);
+        assert_eq!(messages.len(), 1);
+        let summary = dropping.chat_summaries().pop().expect("summary");
+        assert_eq!(summary.last_message_id, Some(MessageId(1)));
+        assert_eq!(summary.unread_count, Some(1));
+
+        let mut keeping = ChatCache::new(cache_limits()).with_tombstones(true);
+        keeping.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        let mut document = base_message(1, 2, 101, "two");
+        document.reply_to = Some(MessageId(1));
+        document.media = Some(MessageMedia {
+            kind: MessageMediaKind::Document,
+            size: Some(2048),
+            thumbnail: None,
+            duration: None,
+            document: Some(DocumentFile {
+                id: 7,
+                access_hash: -3,
+                file_reference: vec![9, 8],
+                dc_id: 4,
+                mime_type: "application/pdf".to_string(),
+                file_name: Some("minutes.pdf".to_string()),
+            }),
+            transcription: None,
+        });
+        keeping.apply_event(&DomainEvent::MessageNew(Box::new(document)));
+        let before = keeping.current_bytes;
+        let original_size = message_size_bytes(&keeping.messages_for_chat(ChatId(1), None)[1]);
+        keeping.apply_event(&deletion);
+        let messages = keeping.messages_for_chat(ChatId(1), None);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[1].deleted);
+        assert!(messages[1].text.is_empty());
+        assert_eq!(messages[1].media, None);
+        assert_eq!(messages[1].reply_to, None);
+        assert_eq!(
+            before - keeping.current_bytes,
+            original_size - message_size_bytes(&messages[1])
+        );
+    }
+
+    #[test]
+    fn evicts_expired_messages() {
+        let mut cache = ChatCache::new(cache_limits());
+        let mut expiring = base_message(1, 1, 100, "secret");
+        expiring.ttl_period = Some(60);
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 90, "keep",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(expiring)));
+
+        assert_eq!(cache.evict_expired(159), 0);
+        assert_eq!(cache.evict_expired(160), 1);
+
+        let messages = cache.messages_for_chat(ChatId(1), None);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_id, MessageId(2));
+        let summary = cache.chat_summaries().pop().expect("summary");
+        assert_eq!(summary.last_message_id, Some(MessageId(2)));
     }
 
     #[test]
@@ -852,6 +3962,16 @@ mod tests {
                 last_message_id: Some(MessageId(2)),
                 last_message_at: Some(123),
                 unread_count: Some(1),
+                last_read_message_id: Some(MessageId(1)),
+                last_outbox_read_message_id: Some(MessageId(2)),
+                last_accessed_at: None,
+                pinned_message_ids: vec![MessageId(2), MessageId(1)],
+                flags: ChatFlags {
+                    folder_id: Some(1),
+                    muted: true,
+                    archived: true,
+                    pinned: false,
+                },
             }],
             messages: vec![CachedMessage {
                 chat_id: ChatId(1),
@@ -861,6 +3981,21 @@ mod tests {
                 edit_timestamp: None,
                 text: "hello".to_string(),
                 outgoing: true,
+                expires_at: Some(456),
+                reactions: vec![MessageReaction {
+                    emoji: "👍".to_string(),
+                    count: 2,
+                    mine: true,
+                }],
+                deleted: false,
+                delivery: Some(DeliveryState::Read),
+                media: None,
+                reply_to: Some(MessageId(1)),
+            }],
+            drafts: vec![ChatDraft {
+                chat_id: ChatId(1),
+                text: "half-written reply".to_string(),
+                updated_at: 130,
             }],
         };
 
@@ -868,12 +4003,449 @@ mod tests {
         let loaded = store.load().expect("load snapshot");
         assert_eq!(loaded, snapshot);
 
-        let _ = std::fs::remove_file(temp_path);
+        drop(store);
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn sqlite_store_reuses_connection_in_wal_mode() {
+        let temp_path = temp_cache_path("wal");
+        let store = SqliteCacheStore::new(temp_path.clone());
+
+        store
+            .save(&CacheSnapshot::default())
+            .expect("save snapshot");
+        store.load().expect("load snapshot");
+        let journal_mode = store
+            .with_connection(|connection| {
+                let mut stmt = connection.prepare("PRAGMA journal_mode")?;
+                stmt.next()?;
+                Ok(stmt.read::<String, _>(0)?)
+            })
+            .expect("read journal mode");
+        assert_eq!(journal_mode, "wal");
+
+        drop(store);
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn sqlite_store_searches_full_text() {
+        let temp_path = temp_cache_path("fts");
+        let store = SqliteCacheStore::new(temp_path.clone());
+        let message = |chat_id: i64, message_id: i64, timestamp: i64, text: &str| CachedMessage {
+            chat_id: ChatId(chat_id),
+            message_id: MessageId(message_id),
+            author_id: UserId(1),
+            timestamp,
+            edit_timestamp: None,
+            text: text.to_string(),
+            outgoing: false,
+            expires_at: None,
+            reactions: Vec::new(),
+            deleted: false,
+            delivery: None,
+            media: None,
+            reply_to: None,
+        };
+        let expiring = CachedMessage {
+            expires_at: Some(150),
+            ..message(1, 3, 130, "release party")
+        };
+        let snapshot = CacheSnapshot {
+            chats: Vec::new(),
+            messages: vec![
+                message(1, 1, 100, "deploy the release tonight"),
+                message(1, 2, 110, "lunch?"),
+                message(2, 1, 120, "release notes are \"ready\""),
+                expiring,
+            ],
+            drafts: Vec::new(),
+        };
+        store.save(&snapshot).expect("save snapshot");
+
+        // Message 3 has expired by 200.
+        assert_eq!(
+            store.search("party", None, 10, 140).expect("search").len(),
+            1
+        );
+        let hits = store.search("releas", None, 10, 200).expect("search");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].message.chat_id, ChatId(2));
+        assert!(hits[0].snippet.contains("[release]"));
+
+        let filtered = store
+            .search("release", Some(ChatId(1)), 10, 200)
+            .expect("search");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].message.message_id, MessageId(1));
+        let page = store
+            .messages_before(ChatId(1), MessageId(10), 10, 200)
+            .expect("page messages");
+        assert_eq!(page.len(), 2);
+
+        assert!(store.search("\"ready", None, 10, 0).is_ok());
+
+        drop(store);
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn encrypted_store_seals_text_at_rest() {
+        let temp_path = temp_cache_path("encrypted");
+        let snapshot = CacheSnapshot {
+            chats: vec![ChatSummary {
+                chat_id: ChatId(1),
+                title: "Secret chat".to_string(),
+                peer_kind: ChatPeerKind::Group,
+                last_message_id: Some(MessageId(1)),
+                last_message_at: Some(100),
+                unread_count: None,
+                last_read_message_id: None,
+                last_outbox_read_message_id: None,
+                last_accessed_at: None,
+                pinned_message_ids: Vec::new(),
+                flags: ChatFlags::default(),
+            }],
+            messages: vec![CachedMessage {
+                chat_id: ChatId(1),
+                message_id: MessageId(1),
+                author_id: UserId(1),
+                timestamp: 100,
+                edit_timestamp: None,
+                text: "launch codes".to_string(),
+                outgoing: false,
+                expires_at: None,
+                reactions: Vec::new(),
+                deleted: false,
+                delivery: None,
+                media: Some(MessageMedia {
+                    kind: MessageMediaKind::Document,
+                    size: Some(1024),
+                    thumbnail: None,
+                    duration: None,
+                    document: Some(DocumentFile {
+                        id: 7,
+                        access_hash: 8,
+                        dc_id: 2,
+                        mime_type: "application/pdf".to_string(),
+                        file_reference: vec![1, 2, 3],
+                        file_name: Some("launch-plan.pdf".to_string()),
+                    }),
+                    transcription: None,
+                }),
+                reply_to: None,
+            }],
+            drafts: Vec::new(),
+        };
+
+        let store = SqliteCacheStore::encrypted(temp_path.clone(), "hunter2");
+        store.save(&snapshot).expect("save snapshot");
+        let (raw_text, raw_media) = store
+            .with_connection(|connection| {
+                let mut stmt = connection.prepare("SELECT text, media FROM messages")?;
+                stmt.next()?;
+                Ok((stmt.read::<String, _>(0)?, stmt.read::<String, _>(1)?))
+            })
+            .expect("read raw text");
+        assert!(!raw_text.contains("launch"));
+        assert!(!raw_media.contains(&STANDARD.encode("launch-plan.pdf")));
+        let hits = store.search("launch", None, 10, 0).expect("search");
+        assert_eq!(hits.len(), 1);
+        drop(store);
+
+        let reopened = SqliteCacheStore::encrypted(temp_path.clone(), "hunter2");
+        assert_eq!(reopened.load().expect("load snapshot"), snapshot);
+        drop(reopened);
+
+        let wrong = SqliteCacheStore::encrypted(temp_path.clone(), "hunter3");
+        assert!(matches!(wrong.load(), Err(CacheError::Crypto(_))));
+        drop(wrong);
+
+        let plain = SqliteCacheStore::new(temp_path.clone());
+        assert!(matches!(plain.load(), Err(CacheError::Crypto(_))));
+        drop(plain);
+
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn sqlite_store_pages_older_messages() {
+        let temp_path = temp_cache_path("paging");
+        let store = SqliteCacheStore::new(temp_path.clone());
+        let snapshot = CacheSnapshot {
+            chats: Vec::new(),
+            messages: (1..=5)
+                .map(|id| CachedMessage {
+                    chat_id: ChatId(1),
+                    message_id: MessageId(id),
+                    author_id: UserId(1),
+                    timestamp: 100 + id,
+                    edit_timestamp: None,
+                    text: format!("message {id}"),
+                    outgoing: false,
+                    expires_at: None,
+                    reactions: Vec::new(),
+                    deleted: false,
+                    delivery: None,
+                    media: None,
+                    reply_to: None,
+                })
+                .collect(),
+            drafts: Vec::new(),
+        };
+        store.save(&snapshot).expect("save snapshot");
+
+        let page = store
+            .messages_before(ChatId(1), MessageId(5), 2, 0)
+            .expect("page messages");
+        let ids = page
+            .iter()
+            .map(|message| message.message_id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![MessageId(3), MessageId(4)]);
+
+        let first = store
+            .messages_before(ChatId(1), MessageId(2), 10, 0)
+            .expect("page messages");
+        assert_eq!(first.len(), 1);
+
+        store
+            .delete_message(ChatId(1), MessageId(4))
+            .expect("delete message");
+        let page = store
+            .messages_before(ChatId(1), MessageId(5), 2, 0)
+            .expect("page messages");
+        assert_eq!(page[1].message_id, MessageId(3));
+        assert!(store
+            .messages_before(ChatId(2), MessageId(5), 10, 0)
+            .expect("page messages")
+            .is_empty());
+
+        drop(store);
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn sqlite_store_saves_delta_per_chat() {
+        let temp_path = temp_cache_path("delta");
+        let store = SqliteCacheStore::new(temp_path.clone());
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            2, 1, 200, "two",
+        ))));
+        store.save_delta(&cache.take_delta()).expect("save delta");
+
+        cache.apply_event(&DomainEvent::MessageEdited(MessageEdited {
+            chat_id: ChatId(2),
+            message_id: MessageId(1),
+            editor_id: UserId(1),
+            timestamp: 210,
+            text: "two, edited".to_string(),
+            outgoing: false,
+        }));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            3, 1, 300, "three",
+        ))));
+        let delta = cache.take_delta();
+        assert_eq!(delta.removed_chats, vec![ChatId(1)]);
+        store.save_delta(&delta).expect("save delta");
+
+        let mut loaded = store.load().expect("load snapshot");
+        loaded.messages.sort_by_key(|message| message.chat_id.0);
+        let texts = loaded
+            .messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["two, edited", "three"]);
+        assert_eq!(loaded.chats.len(), 2);
+
+        drop(store);
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn lazy_load_keeps_older_history_and_hydrates_it() {
+        let temp_path = temp_cache_path("lazy");
+        let store = SqliteCacheStore::new(temp_path.clone());
+        let mut seed = ChatCache::new(CacheLimits {
+            max_messages_per_chat: 10,
+            ..cache_limits()
+        });
+        for message_id in 1..=5 {
+            seed.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+                1,
+                message_id,
+                100 + message_id,
+                "history",
+            ))));
+        }
+        seed.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            2, 1, 200, "short",
+        ))));
+        store.save_delta(&seed.take_delta()).expect("seed store");
+
+        let recent = store.load_recent(2).expect("load recent");
+        assert_eq!(recent.messages.len(), 3);
+        let mut cache = ChatCache::from_recent_snapshot(recent, cache_limits(), 2);
+        assert_eq!(cache.history_floor(ChatId(1)), Some(MessageId(4)));
+        assert_eq!(cache.history_floor(ChatId(2)), None);
+
+        // Trimming to three resident messages raises the floor past id 4.
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 6, 106, "new",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 7, 107, "new",
+        ))));
+        cache.apply_event(&DomainEvent::MessagesDeleted(MessagesDeleted {
+            chat_id: Some(ChatId(1)),
+            message_ids: vec![MessageId(2)],
+        }));
+        let delta = cache.take_delta();
+        assert_eq!(delta.history_floors, vec![(ChatId(1), MessageId(5))]);
+        assert_eq!(delta.deleted_history, vec![(ChatId(1), MessageId(2))]);
+        store.save_delta(&delta).expect("save delta");
+
+        let stored = store
+            .load()
+            .expect("load snapshot")
+            .messages
+            .into_iter()
+            .filter(|message| message.chat_id == ChatId(1))
+            .map(|message| message.message_id.0)
+            .collect::<Vec<_>>();
+        assert_eq!(stored, vec![1, 3, 4, 5, 6, 7]);
+
+        let page = store
+            .messages_before(ChatId(1), MessageId(5), 2, 0)
+            .expect("page history");
+        assert_eq!(cache.hydrate_history(ChatId(1), page, 2), 2);
+        assert_eq!(cache.history_floor(ChatId(1)), Some(MessageId(3)));
+        let page = store
+            .messages_before(ChatId(1), MessageId(3), 2, 0)
+            .expect("page history");
+        assert_eq!(cache.hydrate_history(ChatId(1), page, 2), 1);
+        assert_eq!(cache.history_floor(ChatId(1)), None);
+        let resident = cache
+            .messages_for_chat(ChatId(1), None)
+            .into_iter()
+            .map(|message| message.message_id.0)
+            .collect::<Vec<_>>();
+        assert_eq!(resident, vec![1, 3, 4, 5, 6, 7]);
+
+        drop(store);
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn drafts_survive_eviction_and_clear_through_delta() {
+        let temp_path = temp_cache_path("drafts");
+        let store = SqliteCacheStore::new(temp_path.clone());
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        assert!(cache.set_draft(ChatId(1), "see you at", 110));
+        assert!(!cache.set_draft(ChatId(1), "see you at", 111));
+        assert!(cache.set_draft(ChatId(2), "brb", 120));
+        store.save_delta(&cache.take_delta()).expect("save delta");
+
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            2, 1, 200, "two",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            3, 1, 300, "three",
+        ))));
+        assert_eq!(cache.draft(ChatId(1)).as_deref(), Some("see you at"));
+        assert!(cache.set_draft(ChatId(2), "  ", 210));
+        store.save_delta(&cache.take_delta()).expect("save delta");
+
+        let restored = ChatCache::from_snapshot(store.load().expect("load"), cache_limits());
+        assert_eq!(restored.draft(ChatId(1)).as_deref(), Some("see you at"));
+        assert_eq!(restored.draft(ChatId(2)), None);
+
+        drop(store);
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn sqlite_store_upserts_and_prunes_embeddings() {
+        let temp_path = temp_cache_path("embeddings");
+        let store = SqliteCacheStore::encrypted(temp_path.clone(), "hunter2");
+        let mut cache = ChatCache::new(cache_limits());
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        cache.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 101, "two",
+        ))));
+        store.save_delta(&cache.take_delta()).expect("save delta");
+
+        let embedding = |message_id: i64, vector: Vec<f32>| MessageEmbedding {
+            chat_id: ChatId(1),
+            message_id: MessageId(message_id),
+            model: "mini".to_string(),
+            vector,
+        };
+        store
+            .upsert_embeddings(&[embedding(1, vec![0.0, 1.0])])
+            .expect("upsert");
+        store
+            .upsert_embeddings(&[embedding(1, vec![0.5, -2.25])])
+            .expect("replace");
+        assert_eq!(
+            store.load_embeddings("mini").expect("load"),
+            vec![embedding(1, vec![0.5, -2.25])]
+        );
+        assert!(store.load_embeddings("other").expect("load").is_empty());
+
+        let missing = store
+            .messages_without_embedding("mini", 10)
+            .expect("missing");
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].text, "two");
+
+        cache.apply_event(&DomainEvent::MessagesDeleted(MessagesDeleted {
+            chat_id: Some(ChatId(1)),
+            message_ids: vec![MessageId(1)],
+        }));
+        store.save_delta(&cache.take_delta()).expect("save delta");
+        assert!(store.load_embeddings("mini").expect("load").is_empty());
+
+        drop(store);
+        remove_cache_files(&temp_path);
+    }
+
+    #[test]
+    fn migrates_legacy_messages_table() {
+        let temp_path = temp_cache_path("legacy");
+        {
+            let connection = sqlite::open(&temp_path).expect("open legacy db");
+            connection
+                .execute(
+                    "CREATE TABLE messages (chat_id INTEGER NOT NULL, message_id INTEGER NOT NULL, author_id INTEGER NOT NULL, timestamp INTEGER NOT NULL, edit_timestamp INTEGER, text TEXT NOT NULL, outgoing INTEGER NOT NULL, PRIMARY KEY (chat_id, message_id));
+                     INSERT INTO messages VALUES (1, 2, 3, 100, NULL, 'old', 0);",
+                )
+                .expect("create legacy schema");
+        }
+
+        let store = SqliteCacheStore::new(temp_path.clone());
+        let loaded = store.load().expect("load migrated snapshot");
+        assert_eq!(loaded.messages.len(), 1);
+        assert_eq!(loaded.messages[0].expires_at, None);
+
+        drop(store);
+        remove_cache_files(&temp_path);
     }
 
     #[tokio::test]
     async fn debounced_flush_coalesces_updates() {
-        let store = Arc::new(InMemoryStore::default());
+        let store = Arc::new(MemoryCacheStore::new());
         let store_for_manager: Arc<dyn CacheStore> = store.clone();
         let config = CacheConfig {
             db_path: PathBuf::from(":memory:"),
@@ -881,16 +4453,23 @@ mod tests {
                 max_chats: 0,
                 max_messages_per_chat: 10,
                 max_bytes: 0,
+                max_bytes_per_chat: 0,
             },
             flush_debounce: Duration::from_millis(20),
+            keep_tombstones: false,
+            startup_messages_per_chat: None,
         };
 
         let manager = CacheManager::spawn(store_for_manager, config)
             .await
             .expect("spawn manager");
 
-        manager.apply_event(&DomainEvent::MessageNew(base_message(1, 1, 100, "one")));
-        manager.apply_event(&DomainEvent::MessageNew(base_message(1, 2, 101, "two")));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 1, 100, "one",
+        ))));
+        manager.apply_event(&DomainEvent::MessageNew(Box::new(base_message(
+            1, 2, 101, "two",
+        ))));
 
         tokio::task::yield_now().await;
         tokio::time::sleep(Duration::from_millis(5)).await;
@@ -898,6 +4477,9 @@ mod tests {
 
         tokio::time::sleep(Duration::from_millis(40)).await;
         assert_eq!(store.save_count(), 1);
+        let metrics = manager.metrics();
+        assert_eq!((metrics.chats, metrics.messages), (1, 2));
+        assert_eq!((metrics.flushes, metrics.flush_failures), (1, 0));
 
         manager.shutdown().await;
     }
@@ -913,33 +4495,14 @@ mod tests {
         std::env::temp_dir().join(file_name)
     }
 
-    static CACHE_TEST_COUNTER: AtomicUsize = AtomicUsize::new(0);
-
-    #[derive(Default)]
-    struct InMemoryStore {
-        snapshots: Mutex<Vec<CacheSnapshot>>,
-    }
-
-    impl InMemoryStore {
-        fn save_count(&self) -> usize {
-            self.snapshots.lock().unwrap().len()
+    fn remove_cache_files(path: &std::path::Path) {
+        let _ = std::fs::remove_file(path);
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.as_os_str().to_owned();
+            sidecar.push(suffix);
+            let _ = std::fs::remove_file(sidecar);
         }
     }
 
-    impl CacheStore for InMemoryStore {
-        fn load(&self) -> Result<CacheSnapshot> {
-            Ok(self
-                .snapshots
-                .lock()
-                .unwrap()
-                .last()
-                .cloned()
-                .unwrap_or_default())
-        }
-
-        fn save(&self, snapshot: &CacheSnapshot) -> Result<()> {
-            self.snapshots.lock().unwrap().push(snapshot.clone());
-            Ok(())
-        }
-    }
+    static CACHE_TEST_COUNTER: AtomicUsize = AtomicUsize::new(0);
 }
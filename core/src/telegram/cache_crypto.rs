@@ -0,0 +1,121 @@
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+use crate::telegram::cache::{CacheError, Result};
+
+const CIPHERTEXT_PREFIX: &str = "enc1:";
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+/// Known plaintext sealed into `cache_meta` to verify the passphrase on open.
+pub(crate) const KEY_CHECK_PLAINTEXT: &str = "telegram-llm-cache";
+
+/// Application-level cipher for cached text columns.
+///
+/// Values are sealed with XChaCha20-Poly1305 under a key derived from the
+/// user's passphrase with Argon2id and a per-database salt.
+pub struct CacheCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl std::fmt::Debug for CacheCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CacheCipher(..)")
+    }
+}
+
+impl CacheCipher {
+    pub fn derive(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        let mut key = [0u8; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|err| CacheError::Crypto(format!("key derivation failed: {err}")))?;
+        let cipher = XChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|err| CacheError::Crypto(format!("invalid key: {err}")))?;
+        key.fill(0);
+        Ok(Self { cipher })
+    }
+
+    pub fn generate_salt() -> [u8; SALT_LEN] {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    }
+
+    pub fn encode_salt(salt: &[u8]) -> String {
+        STANDARD_NO_PAD.encode(salt)
+    }
+
+    pub fn decode_salt(encoded: &str) -> Result<Vec<u8>> {
+        STANDARD_NO_PAD
+            .decode(encoded)
+            .map_err(|err| CacheError::Crypto(format!("invalid kdf salt: {err}")))
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let sealed = self.seal(plaintext.as_bytes())?;
+        Ok(format!(
+            "{CIPHERTEXT_PREFIX}{}",
+            STANDARD_NO_PAD.encode(sealed)
+        ))
+    }
+
+    /// Seals raw bytes as `nonce || ciphertext`, for binary columns.
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| CacheError::Crypto("encryption failed".to_string()))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return Err(CacheError::Crypto("truncated ciphertext".to_string()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CacheError::Crypto("wrong passphrase or corrupted cache".to_string()))
+    }
+
+    /// Decrypts a sealed value. Plaintext written before encryption was
+    /// enabled is passed through so it can be re-sealed on the next flush.
+    pub fn decrypt(&self, stored: &str) -> Result<String> {
+        let Some(encoded) = stored.strip_prefix(CIPHERTEXT_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let sealed = STANDARD_NO_PAD
+            .decode(encoded)
+            .map_err(|err| CacheError::Crypto(format!("invalid ciphertext: {err}")))?;
+        String::from_utf8(self.open(&sealed)?)
+            .map_err(|err| CacheError::Crypto(format!("invalid utf-8 plaintext: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_passes_through_plaintext() {
+        let salt = CacheCipher::generate_salt();
+        let cipher = CacheCipher::derive("correct horse", &salt).expect("derive key");
+
+        let sealed = cipher.encrypt("hello").expect("encrypt");
+        assert!(sealed.starts_with(CIPHERTEXT_PREFIX));
+        assert!(!sealed.contains("hello"));
+        assert_eq!(cipher.decrypt(&sealed).expect("decrypt"), "hello");
+        assert_eq!(cipher.decrypt("legacy").expect("passthrough"), "legacy");
+
+        let wrong = CacheCipher::derive("battery staple", &salt).expect("derive key");
+        assert!(matches!(wrong.decrypt(&sealed), Err(CacheError::Crypto(_))));
+    }
+}
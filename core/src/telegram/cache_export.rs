@@ -0,0 +1,302 @@
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::telegram::cache::{CacheError, CachedMessage, ChatSummary, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+}
+
+/// Inclusive unix-timestamp bounds; `None` leaves that side open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportRange {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl ExportRange {
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp <= until)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ExportTranscript<'a> {
+    chat_id: i64,
+    title: &'a str,
+    messages: Vec<ExportMessage<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportMessage<'a> {
+    id: i64,
+    author: String,
+    timestamp: i64,
+    date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edited: Option<String>,
+    text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    media: Option<ExportMedia<'a>>,
+}
+
+/// The attachment of a message; the caption is the message text.
+#[derive(Debug, Serialize)]
+struct ExportMedia<'a> {
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    caption: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transcription: Option<&'a str>,
+}
+
+impl<'a> ExportMedia<'a> {
+    fn new(message: &'a CachedMessage) -> Option<Self> {
+        let media = message.media.as_ref()?;
+        Some(Self {
+            kind: media.kind.as_str(),
+            file_name: media
+                .document
+                .as_ref()
+                .and_then(|document| document.file_name.as_deref()),
+            caption: Some(message.text.as_str()).filter(|text| !text.is_empty()),
+            transcription: media
+                .transcription
+                .as_ref()
+                .map(|transcription| transcription.text.as_str()),
+        })
+    }
+
+    /// Stands in for the attachment, like `[document: report.pdf]`.
+    fn placeholder(&self) -> String {
+        match self.file_name {
+            Some(file_name) => format!("[{}: {file_name}]", self.kind),
+            None => format!("[{}]", self.kind),
+        }
+    }
+}
+
+/// Renders a chat transcript. Tombstones are skipped; messages must already
+/// be in chronological order.
+pub(crate) fn render_transcript(
+    summary: &ChatSummary,
+    messages: &[CachedMessage],
+    format: ExportFormat,
+) -> Result<String> {
+    let transcript = ExportTranscript {
+        chat_id: summary.chat_id.0,
+        title: &summary.title,
+        messages: messages
+            .iter()
+            .filter(|message| !message.deleted)
+            .map(|message| ExportMessage {
+                id: message.message_id.0,
+                author: author_label(message),
+                timestamp: message.timestamp,
+                date: format_date(message.timestamp),
+                edited: message.edit_timestamp.map(format_date),
+                text: &message.text,
+                media: ExportMedia::new(message),
+            })
+            .collect(),
+    };
+
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&transcript)
+            .map_err(|err| CacheError::Export(err.to_string())),
+        ExportFormat::Markdown => Ok(render_markdown(&transcript)),
+    }
+}
+
+fn render_markdown(transcript: &ExportTranscript<'_>) -> String {
+    let title = if transcript.title.trim().is_empty() {
+        format!("Chat {}", transcript.chat_id)
+    } else {
+        transcript.title.to_string()
+    };
+    let mut output = format!("# {title}\n");
+    for message in &transcript.messages {
+        output.push_str(&format!("\n**{}** — {}", message.author, message.date));
+        if message.edited.is_some() {
+            output.push_str(" (edited)");
+        }
+        output.push_str("\n\n");
+        if let Some(media) = &message.media {
+            push_quoted(&mut output, &media.placeholder());
+        }
+        push_quoted(&mut output, message.text);
+        if let Some(transcription) = message.media.as_ref().and_then(|media| media.transcription) {
+            push_quoted(&mut output, &format!("Transcript: {transcription}"));
+        }
+    }
+    output
+}
+
+fn push_quoted(output: &mut String, text: &str) {
+    for line in text.lines() {
+        output.push_str("> ");
+        output.push_str(line);
+        output.push('\n');
+    }
+}
+
+fn author_label(message: &CachedMessage) -> String {
+    if message.outgoing {
+        "You".to_string()
+    } else {
+        format!("User {}", message.author_id.0)
+    }
+}
+
+fn format_date(timestamp: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .ok()
+        .and_then(|date| date.format(&Rfc3339).ok())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::cache::ChatPeerKind;
+    use crate::telegram::events::{
+        ChatFlags, ChatId, DocumentFile, MessageId, MessageMedia, MessageMediaKind, Transcription,
+        UserId,
+    };
+
+    fn message(message_id: i64, timestamp: i64, text: &str, outgoing: bool) -> CachedMessage {
+        CachedMessage {
+            chat_id: ChatId(1),
+            message_id: MessageId(message_id),
+            author_id: UserId(7),
+            timestamp,
+            edit_timestamp: None,
+            text: text.to_string(),
+            outgoing,
+            expires_at: None,
+            reactions: Vec::new(),
+            deleted: false,
+            delivery: None,
+            media: None,
+            reply_to: None,
+        }
+    }
+
+    #[test]
+    fn renders_json_and_markdown_transcripts() {
+        let summary = ChatSummary {
+            chat_id: ChatId(1),
+            title: "Team".to_string(),
+            peer_kind: ChatPeerKind::Group,
+            last_message_id: None,
+            last_message_at: None,
+            unread_count: None,
+            last_read_message_id: None,
+            last_outbox_read_message_id: None,
+            last_accessed_at: None,
+            pinned_message_ids: Vec::new(),
+            flags: ChatFlags::default(),
+        };
+        let mut removed = message(3, 0, "gone", false);
+        removed.deleted = true;
+        let messages = vec![
+            message(1, 0, "hello\nworld", false),
+            message(2, 60, "hi", true),
+            removed,
+        ];
+
+        let markdown =
+            render_transcript(&summary, &messages, ExportFormat::Markdown).expect("markdown");
+        assert_eq!(
+            markdown,
+            "# Team\n\n**User 7** — 1970-01-01T00:00:00Z\n\n> hello\n> world\n\n**You** — 1970-01-01T00:01:00Z\n\n> hi\n"
+        );
+
+        let json = render_transcript(&summary, &messages, ExportFormat::Json).expect("json");
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value["title"], "Team");
+        assert_eq!(value["messages"].as_array().map(Vec::len), Some(2));
+        assert_eq!(value["messages"][1]["author"], "You");
+        assert!(value["messages"][0].get("media").is_none());
+    }
+
+    #[test]
+    fn attachments_are_exported_with_a_placeholder() {
+        let summary = ChatSummary {
+            chat_id: ChatId(1),
+            title: "Team".to_string(),
+            peer_kind: ChatPeerKind::Group,
+            last_message_id: None,
+            last_message_at: None,
+            unread_count: None,
+            last_read_message_id: None,
+            last_outbox_read_message_id: None,
+            last_accessed_at: None,
+            pinned_message_ids: Vec::new(),
+            flags: ChatFlags::default(),
+        };
+        let media = |kind, file_name: Option<&str>, transcription: Option<&str>| MessageMedia {
+            kind,
+            size: None,
+            thumbnail: None,
+            duration: None,
+            document: Some(DocumentFile {
+                id: 1,
+                access_hash: 2,
+                file_reference: Vec::new(),
+                dc_id: 2,
+                mime_type: "application/pdf".to_string(),
+                file_name: file_name.map(str::to_string),
+            }),
+            transcription: transcription.map(|text| Transcription {
+                text: text.to_string(),
+                pending: false,
+            }),
+        };
+        let mut report = message(1, 0, "minutes", false);
+        report.media = Some(media(MessageMediaKind::Document, Some("report.pdf"), None));
+        let mut voice = message(2, 60, "", false);
+        voice.media = Some(media(MessageMediaKind::Voice, None, Some("on my way")));
+        let messages = vec![report, voice];
+
+        let markdown =
+            render_transcript(&summary, &messages, ExportFormat::Markdown).expect("markdown");
+        assert_eq!(
+            markdown,
+            "# Team\n\n**User 7** — 1970-01-01T00:00:00Z\n\n> [document: report.pdf]\n> minutes\n\n**User 7** — 1970-01-01T00:01:00Z\n\n> [voice]\n> Transcript: on my way\n"
+        );
+
+        let json = render_transcript(&summary, &messages, ExportFormat::Json).expect("json");
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(
+            value["messages"][0]["media"],
+            serde_json::json!({
+                "kind": "document",
+                "file_name": "report.pdf",
+                "caption": "minutes",
+            })
+        );
+        assert_eq!(
+            value["messages"][1]["media"],
+            serde_json::json!({ "kind": "voice", "transcription": "on my way" })
+        );
+    }
+
+    #[test]
+    fn range_bounds_are_inclusive() {
+        let range = ExportRange {
+            since: Some(10),
+            until: Some(20),
+        };
+        assert!(range.contains(10));
+        assert!(range.contains(20));
+        assert!(!range.contains(21));
+        assert!(ExportRange::default().contains(i64::MIN));
+    }
+}
@@ -0,0 +1,470 @@
+use std::sync::Mutex;
+
+use postgres::{Client, NoTls, Row, Transaction};
+
+use crate::telegram::cache::{
+    decode_media, decode_reactions, decode_vector, encode_media, encode_reactions, encode_vector,
+    CacheDelta, CacheSnapshot, CacheStore, CachedMessage, ChatDraft, ChatPeerKind, ChatSummary,
+    DeliveryState, MessageEmbedding, MessageSearchHit, Result,
+};
+use crate::telegram::events::{ChatFlags, ChatId, MessageId, UserId};
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS chats (
+    chat_id BIGINT PRIMARY KEY,
+    title TEXT NOT NULL,
+    peer_kind TEXT NOT NULL,
+    last_message_id BIGINT,
+    last_message_at BIGINT,
+    unread_count BIGINT,
+    updated_at BIGINT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS messages (
+    chat_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    edit_timestamp BIGINT,
+    text TEXT NOT NULL,
+    outgoing BOOLEAN NOT NULL,
+    expires_at BIGINT,
+    PRIMARY KEY (chat_id, message_id)
+);
+CREATE TABLE IF NOT EXISTS drafts (
+    chat_id BIGINT PRIMARY KEY,
+    text TEXT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS message_embeddings (
+    chat_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    model TEXT NOT NULL,
+    vector BYTEA NOT NULL,
+    PRIMARY KEY (chat_id, message_id, model)
+);
+CREATE INDEX IF NOT EXISTS idx_messages_chat_timestamp ON messages(chat_id, timestamp);
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS last_read_message_id BIGINT;
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS last_accessed_at BIGINT;
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS reactions TEXT;
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS deleted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS pinned_message_ids BIGINT[] NOT NULL DEFAULT '{}';
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS delivery TEXT;
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS media TEXT;
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS reply_to BIGINT;
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS folder_id INTEGER;
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS muted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS archived BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS pinned BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS last_outbox_read_message_id BIGINT;
+"#;
+
+const PRUNE_EMBEDDINGS: &str = "DELETE FROM message_embeddings e WHERE NOT EXISTS (\
+     SELECT 1 FROM messages m WHERE m.chat_id = e.chat_id AND m.message_id = e.message_id)";
+
+const MESSAGE_COLUMNS: &str =
+    "chat_id, message_id, author_id, timestamp, edit_timestamp, text, outgoing, expires_at, reactions, deleted, delivery, media, reply_to";
+
+/// Postgres-backed store for headless deployments that want a shared,
+/// queryable message store. The schema mirrors [`super::SqliteCacheStore`].
+pub struct PostgresCacheStore {
+    url: String,
+    client: Mutex<Option<Client>>,
+}
+
+impl std::fmt::Debug for PostgresCacheStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostgresCacheStore").finish_non_exhaustive()
+    }
+}
+
+impl PostgresCacheStore {
+    /// The connection is opened lazily on first use, like the sqlite store.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: Mutex::new(None),
+        }
+    }
+
+    fn with_client<T>(&self, op: impl FnOnce(&mut Client) -> Result<T>) -> Result<T> {
+        let mut guard = match self.client.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if guard.as_ref().is_none_or(|client| client.is_closed()) {
+            let mut client = Client::connect(&self.url, NoTls)?;
+            client.batch_execute(SCHEMA)?;
+            *guard = Some(client);
+        }
+        let client = guard.as_mut().expect("postgres client connected");
+        op(client)
+    }
+}
+
+impl CacheStore for PostgresCacheStore {
+    fn load(&self) -> Result<CacheSnapshot> {
+        self.with_client(|client| load_snapshot(client, None))
+    }
+
+    fn load_recent(&self, per_chat: usize) -> Result<CacheSnapshot> {
+        let per_chat = per_chat.min(i64::MAX as usize) as i64;
+        self.with_client(|client| load_snapshot(client, Some(per_chat)))
+    }
+
+    fn save(&self, snapshot: &CacheSnapshot) -> Result<()> {
+        self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            tx.batch_execute("DELETE FROM messages; DELETE FROM chats; DELETE FROM drafts;")?;
+            insert_rows(&mut tx, &snapshot.chats, &snapshot.messages)?;
+            insert_drafts(&mut tx, &snapshot.drafts)?;
+            tx.batch_execute(PRUNE_EMBEDDINGS)?;
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    fn save_delta(&self, delta: &CacheDelta) -> Result<()> {
+        if delta.is_empty() {
+            return Ok(());
+        }
+        let touched = delta
+            .chats
+            .iter()
+            .map(|chat| chat.chat_id.0)
+            .chain(delta.removed_chats.iter().map(|chat_id| chat_id.0))
+            .collect::<Vec<_>>();
+        let drafted = delta
+            .drafts
+            .iter()
+            .map(|draft| draft.chat_id.0)
+            .collect::<Vec<_>>();
+        let rewritten = touched
+            .iter()
+            .copied()
+            .filter(|chat_id| {
+                !delta
+                    .history_floors
+                    .iter()
+                    .any(|(floored, _)| floored.0 == *chat_id)
+            })
+            .collect::<Vec<_>>();
+        self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            tx.execute(
+                "DELETE FROM messages WHERE chat_id = ANY($1)",
+                &[&rewritten],
+            )?;
+            for (chat_id, floor) in &delta.history_floors {
+                tx.execute(
+                    "DELETE FROM messages WHERE chat_id = $1 \
+                     AND (message_id >= $2 OR message_id <= 0)",
+                    &[&chat_id.0, &floor.0],
+                )?;
+            }
+            for (chat_id, message_id) in &delta.deleted_history {
+                tx.execute(
+                    "DELETE FROM messages WHERE chat_id = $1 AND message_id = $2",
+                    &[&chat_id.0, &message_id.0],
+                )?;
+            }
+            tx.execute("DELETE FROM chats WHERE chat_id = ANY($1)", &[&touched])?;
+            tx.execute("DELETE FROM drafts WHERE chat_id = ANY($1)", &[&drafted])?;
+            insert_rows(&mut tx, &delta.chats, &delta.messages)?;
+            insert_drafts(&mut tx, &delta.drafts)?;
+            if !touched.is_empty() || !delta.deleted_history.is_empty() {
+                tx.batch_execute(PRUNE_EMBEDDINGS)?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    fn search(
+        &self,
+        query: &str,
+        chat_id: Option<ChatId>,
+        limit: usize,
+        now: i64,
+    ) -> Result<Vec<MessageSearchHit>> {
+        let needle = query.trim();
+        if needle.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let pattern = format!("%{}%", escape_like(needle));
+        let limit = limit.min(i64::MAX as usize) as i64;
+        self.with_client(|client| {
+            let rows = client.query(
+                &format!(
+                    "SELECT {MESSAGE_COLUMNS} FROM messages \
+                     WHERE text ILIKE $1 AND ($2::BIGINT IS NULL OR chat_id = $2) \
+                         AND (expires_at IS NULL OR expires_at > $4) \
+                     ORDER BY timestamp DESC LIMIT $3"
+                ),
+                &[&pattern, &chat_id.map(|id| id.0), &limit, &now],
+            )?;
+            Ok(rows
+                .iter()
+                .map(read_message_row)
+                .map(|message| MessageSearchHit {
+                    snippet: message.text.clone(),
+                    message,
+                })
+                .collect())
+        })
+    }
+
+    fn messages_before(
+        &self,
+        chat_id: ChatId,
+        before: MessageId,
+        limit: usize,
+        now: i64,
+    ) -> Result<Vec<CachedMessage>> {
+        let limit = limit.min(i64::MAX as usize) as i64;
+        self.with_client(|client| {
+            let rows = client.query(
+                &format!(
+                    "SELECT {MESSAGE_COLUMNS} FROM messages \
+                     WHERE chat_id = $1 AND message_id < $2 \
+                         AND (expires_at IS NULL OR expires_at > $4) \
+                     ORDER BY message_id DESC LIMIT $3"
+                ),
+                &[&chat_id.0, &before.0, &limit, &now],
+            )?;
+            let mut messages = rows.iter().map(read_message_row).collect::<Vec<_>>();
+            messages.reverse();
+            Ok(messages)
+        })
+    }
+
+    fn upsert_embeddings(&self, embeddings: &[MessageEmbedding]) -> Result<()> {
+        if embeddings.is_empty() {
+            return Ok(());
+        }
+        self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            let stmt = tx.prepare(
+                "INSERT INTO message_embeddings (chat_id, message_id, model, vector) \
+                 VALUES ($1, $2, $3, $4) \
+                 ON CONFLICT (chat_id, message_id, model) DO UPDATE SET vector = EXCLUDED.vector",
+            )?;
+            for embedding in embeddings {
+                tx.execute(
+                    &stmt,
+                    &[
+                        &embedding.chat_id.0,
+                        &embedding.message_id.0,
+                        &embedding.model,
+                        &encode_vector(&embedding.vector),
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    fn load_embeddings(&self, model: &str) -> Result<Vec<MessageEmbedding>> {
+        self.with_client(|client| {
+            let rows = client.query(
+                "SELECT chat_id, message_id, vector FROM message_embeddings WHERE model = $1",
+                &[&model],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| MessageEmbedding {
+                    chat_id: ChatId(row.get(0)),
+                    message_id: MessageId(row.get(1)),
+                    model: model.to_string(),
+                    vector: decode_vector(row.get(2)),
+                })
+                .collect())
+        })
+    }
+
+    fn messages_without_embedding(&self, model: &str, limit: usize) -> Result<Vec<CachedMessage>> {
+        let limit = limit.min(i64::MAX as usize) as i64;
+        self.with_client(|client| {
+            let rows = client.query(
+                &format!(
+                    "SELECT {MESSAGE_COLUMNS} FROM messages m \
+                     WHERE NOT m.deleted AND m.text <> '' AND NOT EXISTS (\
+                         SELECT 1 FROM message_embeddings e \
+                         WHERE e.chat_id = m.chat_id AND e.message_id = m.message_id AND e.model = $1) \
+                     ORDER BY m.timestamp DESC LIMIT $2"
+                ),
+                &[&model, &limit],
+            )?;
+            Ok(rows.iter().map(read_message_row).collect())
+        })
+    }
+}
+
+/// Loads the whole cache, or with `per_chat` only the newest messages of each
+/// chat (plus placeholder echoes).
+fn load_snapshot(client: &mut Client, per_chat: Option<i64>) -> Result<CacheSnapshot> {
+    let chats = client
+        .query(
+            "SELECT chat_id, title, peer_kind, last_message_id, last_message_at, unread_count, last_read_message_id, last_accessed_at, pinned_message_ids, folder_id, muted, archived, pinned, last_outbox_read_message_id FROM chats",
+            &[],
+        )?
+        .iter()
+        .map(|row| ChatSummary {
+            chat_id: ChatId(row.get(0)),
+            title: row.get(1),
+            peer_kind: ChatPeerKind::from_str(row.get(2)),
+            last_message_id: row.get::<_, Option<i64>>(3).map(MessageId),
+            last_message_at: row.get(4),
+            unread_count: row.get::<_, Option<i64>>(5).map(|value| value as u32),
+            last_read_message_id: row.get::<_, Option<i64>>(6).map(MessageId),
+            last_outbox_read_message_id: row.get::<_, Option<i64>>(13).map(MessageId),
+            last_accessed_at: row.get(7),
+            pinned_message_ids: row
+                .get::<_, Vec<i64>>(8)
+                .into_iter()
+                .map(MessageId)
+                .collect(),
+            flags: ChatFlags {
+                folder_id: row.get(9),
+                muted: row.get(10),
+                archived: row.get(11),
+                pinned: row.get(12),
+            },
+        })
+        .collect();
+    let messages = match per_chat {
+        Some(per_chat) => client.query(
+            &format!(
+                "SELECT {MESSAGE_COLUMNS} FROM (\
+                     SELECT *, ROW_NUMBER() OVER (\
+                         PARTITION BY chat_id ORDER BY message_id DESC) AS recency \
+                     FROM messages) recent \
+                 WHERE recency <= $1 OR message_id <= 0 \
+                 ORDER BY chat_id, timestamp"
+            ),
+            &[&per_chat],
+        )?,
+        None => client.query(
+            &format!("SELECT {MESSAGE_COLUMNS} FROM messages ORDER BY chat_id, timestamp"),
+            &[],
+        )?,
+    }
+    .iter()
+    .map(read_message_row)
+    .collect();
+    let drafts = client
+        .query("SELECT chat_id, text, updated_at FROM drafts", &[])?
+        .iter()
+        .map(|row| ChatDraft {
+            chat_id: ChatId(row.get(0)),
+            text: row.get(1),
+            updated_at: row.get(2),
+        })
+        .collect();
+    Ok(CacheSnapshot {
+        chats,
+        messages,
+        drafts,
+    })
+}
+
+fn insert_rows(
+    tx: &mut Transaction<'_>,
+    chats: &[ChatSummary],
+    messages: &[CachedMessage],
+) -> Result<()> {
+    let chat_stmt = tx.prepare(
+        "INSERT INTO chats (chat_id, title, peer_kind, last_message_id, last_message_at, unread_count, last_read_message_id, last_accessed_at, pinned_message_ids, folder_id, muted, archived, pinned, updated_at, last_outbox_read_message_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
+    )?;
+    for chat in chats {
+        let pins = chat
+            .pinned_message_ids
+            .iter()
+            .map(|id| id.0)
+            .collect::<Vec<_>>();
+        tx.execute(
+            &chat_stmt,
+            &[
+                &chat.chat_id.0,
+                &chat.title,
+                &chat.peer_kind.as_str(),
+                &chat.last_message_id.map(|id| id.0),
+                &chat.last_message_at,
+                &chat.unread_count.map(|value| value as i64),
+                &chat.last_read_message_id.map(|id| id.0),
+                &chat.last_accessed_at,
+                &pins,
+                &chat.flags.folder_id,
+                &chat.flags.muted,
+                &chat.flags.archived,
+                &chat.flags.pinned,
+                &chat.last_message_at.unwrap_or(0),
+                &chat.last_outbox_read_message_id.map(|id| id.0),
+            ],
+        )?;
+    }
+
+    let message_stmt = tx.prepare(&format!(
+        "INSERT INTO messages ({MESSAGE_COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"
+    ))?;
+    for message in messages {
+        tx.execute(
+            &message_stmt,
+            &[
+                &message.chat_id.0,
+                &message.message_id.0,
+                &message.author_id.0,
+                &message.timestamp,
+                &message.edit_timestamp,
+                &message.text,
+                &message.outgoing,
+                &message.expires_at,
+                &encode_reactions(&message.reactions),
+                &message.deleted,
+                &message.delivery.map(DeliveryState::as_str),
+                &encode_media(message.media.as_ref()),
+                &message.reply_to.map(|message_id| message_id.0),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn insert_drafts(tx: &mut Transaction<'_>, drafts: &[ChatDraft]) -> Result<()> {
+    let stmt = tx.prepare("INSERT INTO drafts (chat_id, text, updated_at) VALUES ($1, $2, $3)")?;
+    for draft in drafts.iter().filter(|draft| !draft.text.is_empty()) {
+        tx.execute(&stmt, &[&draft.chat_id.0, &draft.text, &draft.updated_at])?;
+    }
+    Ok(())
+}
+
+/// Reads a row laid out as `MESSAGE_COLUMNS`.
+fn read_message_row(row: &Row) -> CachedMessage {
+    CachedMessage {
+        chat_id: ChatId(row.get(0)),
+        message_id: MessageId(row.get(1)),
+        author_id: UserId(row.get(2)),
+        timestamp: row.get(3),
+        edit_timestamp: row.get(4),
+        text: row.get(5),
+        outgoing: row.get(6),
+        expires_at: row.get(7),
+        reactions: row
+            .get::<_, Option<&str>>(8)
+            .map(decode_reactions)
+            .unwrap_or_default(),
+        deleted: row.get(9),
+        delivery: row
+            .get::<_, Option<&str>>(10)
+            .and_then(DeliveryState::from_str),
+        media: row.get::<_, Option<&str>>(11).and_then(decode_media),
+        reply_to: row.get::<_, Option<i64>>(12).map(MessageId),
+    }
+}
+
+fn escape_like(raw: &str) -> String {
+    raw.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
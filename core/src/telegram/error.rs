@@ -12,6 +12,8 @@ pub enum TelegramError {
     Io(#[from] std::io::Error),
     #[error("update pump already started or stopped")]
     UpdatePumpUnavailable,
+    #[error("file download was redirected to a CDN")]
+    CdnRedirect,
 }
 
 pub type Result<T> = std::result::Result<T, TelegramError>;
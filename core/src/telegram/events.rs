@@ -1,22 +1,33 @@
 use grammers_session::defs::PeerId;
 use grammers_tl_types as tl;
-use tokio::sync::{broadcast, watch};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use tokio::sync::{broadcast, mpsc, watch};
 use tokio::task::JoinHandle;
-use tracing::warn;
+use tracing::{info, warn};
 
+use crate::telegram::bootstrap::EventStreamConfig;
 use crate::telegram::error::{Result, TelegramError};
+use crate::telegram::journal::EventJournal;
+use crate::telegram::send::{SendId, SendStatus};
 use crate::telegram::updates::{UpdateEvent, UpdatePump};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ChatId(pub i64);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MessageId(pub i64);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct UserId(pub i64);
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MessageNew {
     pub chat_id: ChatId,
     pub message_id: MessageId,
@@ -24,9 +35,116 @@ pub struct MessageNew {
     pub timestamp: i64,
     pub text: String,
     pub outgoing: bool,
+    pub ttl_period: Option<i64>,
+    #[serde(default)]
+    pub media: Option<MessageMedia>,
+    /// Message in the same chat this one replies to.
+    #[serde(default)]
+    pub reply_to: Option<MessageId>,
+}
+
+/// Attachment carried by a message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageMedia {
+    pub kind: MessageMediaKind,
+    /// Bytes of the largest variant Telegram reports, when known.
+    pub size: Option<u64>,
+    /// Preview-sized variant to download, set for photos.
+    pub thumbnail: Option<PhotoThumbnail>,
+    /// Play length in seconds of voice messages, audio and video.
+    #[serde(default)]
+    pub duration: Option<u32>,
+    /// Where the full file lives, set for documents (voice messages, audio,
+    /// video and files).
+    #[serde(default)]
+    pub document: Option<DocumentFile>,
+    /// Speech-to-text of a voice message, once requested.
+    #[serde(default)]
+    pub transcription: Option<Transcription>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageMediaKind {
+    Photo,
+    Video,
+    Audio,
+    /// Audio recorded in the chat, which Telegram can transcribe.
+    Voice,
+    Document,
+    /// Locations, polls, contacts and the like.
+    Other,
+}
+
+impl MessageMediaKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Photo => "photo",
+            Self::Video => "video",
+            Self::Audio => "audio",
+            Self::Voice => "voice",
+            Self::Document => "file",
+            Self::Other => "media",
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Photo => "photo",
+            Self::Video => "video",
+            Self::Audio => "audio",
+            Self::Voice => "voice",
+            Self::Document => "document",
+            Self::Other => "other",
+        }
+    }
+
+    pub(crate) fn from_str(raw: &str) -> Option<Self> {
+        match raw {
+            "photo" => Some(Self::Photo),
+            "video" => Some(Self::Video),
+            "audio" => Some(Self::Audio),
+            "voice" => Some(Self::Voice),
+            "document" => Some(Self::Document),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+}
+
+/// Where one size of a photo lives, enough to download it with
+/// `upload.getFile`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhotoThumbnail {
+    pub photo_id: i64,
+    pub access_hash: i64,
+    pub file_reference: Vec<u8>,
+    pub dc_id: i32,
+    /// Telegram's size type, such as `m` for the 320px variant.
+    pub size_type: String,
+}
+
+/// Where a document lives, enough to download it with `upload.getFile`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentFile {
+    pub id: i64,
+    pub access_hash: i64,
+    pub file_reference: Vec<u8>,
+    pub dc_id: i32,
+    pub mime_type: String,
+    /// Name the sender gave the file, when there is one.
+    #[serde(default)]
+    pub file_name: Option<String>,
+}
+
+/// Telegram's speech-to-text of a voice message. `pending` transcriptions
+/// carry the text so far and are completed by a later update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transcription {
+    pub text: String,
+    pub pending: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MessageEdited {
     pub chat_id: ChatId,
     pub message_id: MessageId,
@@ -36,7 +154,9 @@ pub struct MessageEdited {
     pub outgoing: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// The other side of a private chat read our messages up to
+/// `last_read_message_id`; drives the read ticks of outgoing messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ReadReceipt {
     pub chat_id: ChatId,
     pub reader_id: UserId,
@@ -44,27 +164,293 @@ pub struct ReadReceipt {
     pub last_read_message_id: MessageId,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// We read a chat up to `last_read_message_id`, here or on another device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InboxRead {
+    pub chat_id: ChatId,
+    pub timestamp: i64,
+    pub last_read_message_id: MessageId,
+    /// Unread messages Telegram still counts in the chat.
+    pub still_unread: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessagesDeleted {
+    /// `None` outside channels: Telegram's deletion update does not name the
+    /// chat there, since those message ids are unique per account.
+    pub chat_id: Option<ChatId>,
+    pub message_ids: Vec<MessageId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageReaction {
+    /// Emoji, or `custom:<document_id>` for custom emoji reactions.
+    pub emoji: String,
+    pub count: u32,
+    /// Whether the current user chose this reaction.
+    pub mine: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageReactions {
+    pub chat_id: ChatId,
+    pub message_id: MessageId,
+    pub timestamp: i64,
+    /// Full replacement set; an empty list clears all reactions.
+    pub reactions: Vec<MessageReaction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedChanged {
+    pub chat_id: ChatId,
+    /// Affected messages, in the order Telegram listed them.
+    pub message_ids: Vec<MessageId>,
+    /// `true` when the messages were pinned, `false` when unpinned.
+    pub pinned: bool,
+}
+
+/// Telegram's folder id for archived chats.
+pub const ARCHIVE_FOLDER_ID: i32 = 1;
+
+/// Chat-list state taken from the dialog and kept current by updates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChatFlags {
+    pub folder_id: Option<i32>,
+    pub muted: bool,
+    pub archived: bool,
+    pub pinned: bool,
+}
+
+impl ChatFlags {
+    pub fn apply(&mut self, change: ChatFlagChange) {
+        match change {
+            ChatFlagChange::Pinned(pinned) => self.pinned = pinned,
+            ChatFlagChange::Muted(muted) => self.muted = muted,
+            ChatFlagChange::Folder(folder_id) => {
+                self.folder_id = folder_id;
+                self.archived = folder_id == Some(ARCHIVE_FOLDER_ID);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatFlagChange {
+    Pinned(bool),
+    Muted(bool),
+    /// `None` moves the chat back to the main list.
+    Folder(Option<i32>),
+}
+
+/// Telegram can move several chats in one update, so changes are batched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatFlagsChanged {
+    pub changes: Vec<(ChatId, ChatFlagChange)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Typing {
     pub chat_id: ChatId,
     pub user_id: UserId,
     pub timestamp: i64,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemberChange {
+    /// `inviter_id` is set when someone else added the member.
+    Joined {
+        inviter_id: Option<UserId>,
+    },
+    Left,
+    /// Admin rights were granted (`true`) or revoked (`false`).
+    Admin(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMemberUpdate {
+    pub chat_id: ChatId,
+    pub user_id: UserId,
+    pub change: MemberChange,
+    pub timestamp: i64,
+}
+
+/// Last-seen state Telegram reports for a user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Presence {
+    /// Online until `expires` unless Telegram renews it.
+    Online {
+        expires: i64,
+    },
+    Offline {
+        last_seen: i64,
+    },
+    Recently,
+    LastWeek,
+    LastMonth,
+    /// Hidden by the user's privacy settings, or never reported.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnlineStatus {
+    pub user_id: UserId,
+    pub presence: Presence,
+    pub timestamp: i64,
+}
+
+/// Emitted once a typing indicator lapses or the typed message arrives.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypingStopped {
+    pub chat_id: ChatId,
+    pub user_id: UserId,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioTranscribed {
+    pub chat_id: ChatId,
+    pub message_id: MessageId,
+    pub transcription: Transcription,
+}
+
+/// A status transition of an enqueued send, republished by the pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SendProgress {
+    pub send_id: SendId,
+    pub status: SendStatus,
+}
+
+/// Health of the update stream, as the event pump sees it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionStatus {
+    Connected,
+    /// Waiting to retry; `since` is when the connection was lost.
+    Reconnecting {
+        attempt: u32,
+        since: i64,
+    },
+    /// The pump gave up after a transport error at `since`.
+    Offline {
+        since: i64,
+    },
+}
+
+/// Published on every connectivity change and repeated every
+/// [`EventStreamConfig::heartbeat_interval`], so late subscribers learn the
+/// current state without probing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionState {
+    pub status: ConnectionStatus,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DomainEvent {
-    MessageNew(MessageNew),
+    MessageNew(Box<MessageNew>),
     MessageEdited(MessageEdited),
     ReadReceipt(ReadReceipt),
+    InboxRead(InboxRead),
+    MessageReactions(MessageReactions),
+    MessagesDeleted(MessagesDeleted),
+    PinnedChanged(PinnedChanged),
+    ChatFlagsChanged(ChatFlagsChanged),
     Typing(Typing),
+    TypingStopped(TypingStopped),
+    OnlineStatus(OnlineStatus),
+    ChatMemberUpdate(ChatMemberUpdate),
+    AudioTranscribed(AudioTranscribed),
+    /// Local to one process; never journaled, though it still serializes for
+    /// consumers such as IPC bridges.
+    SendProgress(SendProgress),
+    /// Local to one process and never journaled, like `SendProgress`.
+    ConnectionState(ConnectionState),
+}
+
+/// An event as logs should show it; see [`DomainEvent::for_log`].
+pub struct LoggedEvent<'a> {
+    event: &'a DomainEvent,
+    content: bool,
+}
+
+impl DomainEvent {
+    /// Debug-formats like the event itself, except that message text,
+    /// transcriptions and file names show only their length unless
+    /// `content` is set.
+    pub fn for_log(&self, content: bool) -> LoggedEvent<'_> {
+        LoggedEvent {
+            event: self,
+            content,
+        }
+    }
+}
+
+impl fmt::Debug for LoggedEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.content {
+            return fmt::Debug::fmt(self.event, f);
+        }
+        let mut event = self.event.clone();
+        match &mut event {
+            DomainEvent::MessageNew(message) => {
+                redact(&mut message.text);
+                if let Some(media) = message.media.as_mut() {
+                    if let Some(transcription) = media.transcription.as_mut() {
+                        redact(&mut transcription.text);
+                    }
+                    if let Some(name) = media
+                        .document
+                        .as_mut()
+                        .and_then(|document| document.file_name.as_mut())
+                    {
+                        redact(name);
+                    }
+                }
+            }
+            DomainEvent::MessageEdited(edit) => redact(&mut edit.text),
+            DomainEvent::AudioTranscribed(update) => redact(&mut update.transcription.text),
+            _ => {}
+        }
+        fmt::Debug::fmt(&event, f)
+    }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
-pub struct EventMapper;
+fn redact(text: &mut String) {
+    *text = format!("<{} chars redacted>", text.chars().count());
+}
+
+/// A raw Telegram value for a log line: in full with `content`, else only
+/// its constructor name, as raw updates and messages carry text.
+fn raw_for_log(value: &impl fmt::Debug, content: bool) -> String {
+    let debug = format!("{value:?}");
+    if content {
+        return debug;
+    }
+    debug
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EventMapper {
+    log_content: bool,
+}
+
+impl Default for EventMapper {
+    fn default() -> Self {
+        Self { log_content: true }
+    }
+}
 
 impl EventMapper {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Whether warnings about unmapped updates may include their content.
+    pub fn with_log_content(mut self, log_content: bool) -> Self {
+        self.log_content = log_content;
+        self
     }
 
     pub fn map_update(&self, update: &grammers_client::Update) -> Option<DomainEvent> {
@@ -79,11 +465,125 @@ impl EventMapper {
             tl::enums::Update::ReadHistoryOutbox(update) => {
                 self.map_read_receipt(&update.peer, update.max_id, state_timestamp)
             }
+            // Reading a single forum topic leaves the rest of the chat unread.
+            tl::enums::Update::ReadHistoryInbox(update) if update.top_msg_id.is_none() => {
+                Some(DomainEvent::InboxRead(InboxRead {
+                    chat_id: chat_id_from_peer(&update.peer),
+                    timestamp: state_timestamp,
+                    last_read_message_id: MessageId(update.max_id as i64),
+                    still_unread: update.still_unread_count.max(0) as u32,
+                }))
+            }
+            tl::enums::Update::ReadChannelInbox(update) => {
+                Some(DomainEvent::InboxRead(InboxRead {
+                    chat_id: chat_id_from_peer(&tl::enums::Peer::Channel(tl::types::PeerChannel {
+                        channel_id: update.channel_id,
+                    })),
+                    timestamp: state_timestamp,
+                    last_read_message_id: MessageId(update.max_id as i64),
+                    still_unread: update.still_unread_count.max(0) as u32,
+                }))
+            }
+            tl::enums::Update::MessageReactions(update) => Some(self.map_message_reactions(
+                &update.peer,
+                update.msg_id,
+                &update.reactions,
+                state_timestamp,
+            )),
             tl::enums::Update::UserTyping(update) => {
                 self.map_typing_user(update.user_id, state_timestamp)
             }
+            tl::enums::Update::DeleteMessages(update) => {
+                Some(DomainEvent::MessagesDeleted(MessagesDeleted {
+                    chat_id: None,
+                    message_ids: message_ids(&update.messages),
+                }))
+            }
+            tl::enums::Update::DeleteChannelMessages(update) => {
+                Some(DomainEvent::MessagesDeleted(MessagesDeleted {
+                    chat_id: Some(chat_id_from_peer(&tl::enums::Peer::Channel(
+                        tl::types::PeerChannel {
+                            channel_id: update.channel_id,
+                        },
+                    ))),
+                    message_ids: message_ids(&update.messages),
+                }))
+            }
+            tl::enums::Update::PinnedMessages(update) => {
+                Some(DomainEvent::PinnedChanged(PinnedChanged {
+                    chat_id: chat_id_from_peer(&update.peer),
+                    message_ids: message_ids(&update.messages),
+                    pinned: update.pinned,
+                }))
+            }
+            tl::enums::Update::PinnedChannelMessages(update) => {
+                Some(DomainEvent::PinnedChanged(PinnedChanged {
+                    chat_id: chat_id_from_peer(&tl::enums::Peer::Channel(tl::types::PeerChannel {
+                        channel_id: update.channel_id,
+                    })),
+                    message_ids: message_ids(&update.messages),
+                    pinned: update.pinned,
+                }))
+            }
+            tl::enums::Update::UserStatus(update) => {
+                Some(DomainEvent::OnlineStatus(OnlineStatus {
+                    user_id: UserId(update.user_id),
+                    presence: presence_from_status(&update.status),
+                    timestamp: state_timestamp,
+                }))
+            }
+            tl::enums::Update::DialogPinned(update) => match &update.peer {
+                tl::enums::DialogPeer::Peer(dialog) => {
+                    Some(DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+                        changes: vec![(
+                            chat_id_from_peer(&dialog.peer),
+                            ChatFlagChange::Pinned(update.pinned),
+                        )],
+                    }))
+                }
+                tl::enums::DialogPeer::Folder(_) => None,
+            },
+            tl::enums::Update::FolderPeers(update) => Some(self.map_folder_peers(update)),
+            tl::enums::Update::NotifySettings(update) => {
+                self.map_notify_settings(update, state_timestamp)
+            }
+            tl::enums::Update::ChatParticipantAdd(update) => Some(member_update(
+                basic_group_id(update.chat_id),
+                update.user_id,
+                MemberChange::Joined {
+                    inviter_id: (update.inviter_id != update.user_id)
+                        .then_some(UserId(update.inviter_id)),
+                },
+                update.date as i64,
+            )),
+            tl::enums::Update::ChatParticipantDelete(update) => Some(member_update(
+                basic_group_id(update.chat_id),
+                update.user_id,
+                MemberChange::Left,
+                state_timestamp,
+            )),
+            tl::enums::Update::ChatParticipantAdmin(update) => Some(member_update(
+                basic_group_id(update.chat_id),
+                update.user_id,
+                MemberChange::Admin(update.is_admin),
+                state_timestamp,
+            )),
+            tl::enums::Update::ChannelParticipant(update) => self.map_channel_participant(update),
+            tl::enums::Update::TranscribedAudio(update) => {
+                Some(DomainEvent::AudioTranscribed(AudioTranscribed {
+                    chat_id: chat_id_from_peer(&update.peer),
+                    message_id: MessageId(update.msg_id as i64),
+                    transcription: Transcription {
+                        text: update.text.clone(),
+                        pending: update.pending,
+                    },
+                }))
+            }
             unsupported => {
-                warn!(update = ?unsupported, "unsupported telegram update");
+                warn!(
+                    update = %raw_for_log(unsupported, self.log_content),
+                    "unsupported telegram update"
+                );
                 None
             }
         }
@@ -91,14 +591,17 @@ impl EventMapper {
 
     fn map_message_new(&self, message: &tl::enums::Message) -> Option<DomainEvent> {
         let fields = self.parse_message(message)?;
-        Some(DomainEvent::MessageNew(MessageNew {
+        Some(DomainEvent::MessageNew(Box::new(MessageNew {
             chat_id: fields.chat_id,
             message_id: fields.message_id,
             author_id: fields.author_id,
             timestamp: fields.date,
             text: fields.text,
             outgoing: fields.outgoing,
-        }))
+            ttl_period: fields.ttl_period,
+            media: fields.media,
+            reply_to: fields.reply_to,
+        })))
     }
 
     fn map_message_edited(&self, message: &tl::enums::Message) -> Option<DomainEvent> {
@@ -136,6 +639,137 @@ impl EventMapper {
         }))
     }
 
+    fn map_message_reactions(
+        &self,
+        peer: &tl::enums::Peer,
+        message_id: i32,
+        reactions: &tl::enums::MessageReactions,
+        timestamp: i64,
+    ) -> DomainEvent {
+        let tl::enums::MessageReactions::Reactions(reactions) = reactions;
+        let reactions = reactions
+            .results
+            .iter()
+            .filter_map(|result| {
+                let tl::enums::ReactionCount::Count(result) = result;
+                let emoji = match &result.reaction {
+                    tl::enums::Reaction::Emoji(reaction) => reaction.emoticon.clone(),
+                    tl::enums::Reaction::CustomEmoji(reaction) => {
+                        format!("custom:{}", reaction.document_id)
+                    }
+                    tl::enums::Reaction::Empty | tl::enums::Reaction::Paid => return None,
+                };
+                Some(MessageReaction {
+                    emoji,
+                    count: result.count.max(0) as u32,
+                    mine: result.chosen_order.is_some(),
+                })
+            })
+            .collect();
+        DomainEvent::MessageReactions(MessageReactions {
+            chat_id: ChatId(PeerId::from(peer.clone()).bot_api_dialog_id()),
+            message_id: MessageId(message_id as i64),
+            timestamp,
+            reactions,
+        })
+    }
+
+    /// The chat-list flags of synced dialogs, as one
+    /// [`DomainEvent::ChatFlagsChanged`] the cache applies like any update.
+    pub fn map_dialogs(&self, dialogs: &[tl::enums::Dialog], now: i64) -> DomainEvent {
+        let changes = dialogs
+            .iter()
+            .filter_map(|dialog| self.dialog_flags(dialog, now))
+            .flat_map(|(chat_id, flags)| {
+                [
+                    (chat_id, ChatFlagChange::Pinned(flags.pinned)),
+                    (chat_id, ChatFlagChange::Muted(flags.muted)),
+                    (chat_id, ChatFlagChange::Folder(flags.folder_id)),
+                ]
+            })
+            .collect();
+        DomainEvent::ChatFlagsChanged(ChatFlagsChanged { changes })
+    }
+
+    fn dialog_flags(&self, dialog: &tl::enums::Dialog, now: i64) -> Option<(ChatId, ChatFlags)> {
+        let tl::enums::Dialog::Dialog(dialog) = dialog else {
+            return None;
+        };
+        let mut flags = ChatFlags {
+            pinned: dialog.pinned,
+            muted: is_muted(&dialog.notify_settings, now),
+            ..ChatFlags::default()
+        };
+        flags.apply(ChatFlagChange::Folder(dialog.folder_id));
+        Some((chat_id_from_peer(&dialog.peer), flags))
+    }
+
+    fn map_channel_participant(
+        &self,
+        update: &tl::types::UpdateChannelParticipant,
+    ) -> Option<DomainEvent> {
+        let chat_id = chat_id_from_peer(&tl::enums::Peer::Channel(tl::types::PeerChannel {
+            channel_id: update.channel_id,
+        }));
+        let was_member = is_channel_member(update.prev_participant.as_ref());
+        let is_member = is_channel_member(update.new_participant.as_ref());
+        let change = match (was_member, is_member) {
+            (false, true) => MemberChange::Joined {
+                inviter_id: (update.actor_id != update.user_id).then_some(UserId(update.actor_id)),
+            },
+            (true, false) => MemberChange::Left,
+            _ => {
+                let was_admin = is_channel_admin(update.prev_participant.as_ref());
+                let is_admin = is_channel_admin(update.new_participant.as_ref());
+                if was_admin == is_admin {
+                    // Rights or title edits within the same role.
+                    return None;
+                }
+                MemberChange::Admin(is_admin)
+            }
+        };
+        Some(member_update(
+            chat_id,
+            update.user_id,
+            change,
+            update.date as i64,
+        ))
+    }
+
+    /// Peers moved to folder 0 return to the main list.
+    fn map_folder_peers(&self, update: &tl::types::UpdateFolderPeers) -> DomainEvent {
+        let changes = update
+            .folder_peers
+            .iter()
+            .map(|folder_peer| {
+                let tl::enums::FolderPeer::Peer(folder_peer) = folder_peer;
+                let folder_id = Some(folder_peer.folder_id).filter(|folder_id| *folder_id != 0);
+                (
+                    chat_id_from_peer(&folder_peer.peer),
+                    ChatFlagChange::Folder(folder_id),
+                )
+            })
+            .collect();
+        DomainEvent::ChatFlagsChanged(ChatFlagsChanged { changes })
+    }
+
+    fn map_notify_settings(
+        &self,
+        update: &tl::types::UpdateNotifySettings,
+        timestamp: i64,
+    ) -> Option<DomainEvent> {
+        // Defaults for all users/groups/channels are not per-chat state.
+        let tl::enums::NotifyPeer::Peer(notify_peer) = &update.peer else {
+            return None;
+        };
+        Some(DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+            changes: vec![(
+                chat_id_from_peer(&notify_peer.peer),
+                ChatFlagChange::Muted(is_muted(&update.notify_settings, timestamp)),
+            )],
+        }))
+    }
+
     fn map_typing_user(&self, user_id: i64, timestamp: i64) -> Option<DomainEvent> {
         let peer_id = PeerId::user(user_id);
         Some(DomainEvent::Typing(Typing {
@@ -149,13 +783,18 @@ impl EventMapper {
         match message {
             tl::enums::Message::Message(message) => {
                 let chat_id = ChatId(PeerId::from(message.peer_id.clone()).bot_api_dialog_id());
-                let author_peer = message.from_id.as_ref().or(if message.out {
-                    None
-                } else {
-                    Some(&message.peer_id)
-                });
+                let author_peer = message
+                    .from_id
+                    .as_ref()
+                    .or(if message.out && !message.post {
+                        None
+                    } else {
+                        Some(&message.peer_id)
+                    });
                 let author_id = match author_peer.and_then(user_id_from_peer) {
                     Some(author_id) => author_id,
+                    // Channel posts are authored by the channel itself.
+                    None if message.post => UserId(chat_id.0),
                     None => {
                         warn!(peer = ?message.peer_id, "message missing author user id");
                         return None;
@@ -169,26 +808,393 @@ impl EventMapper {
                     edit_date: message.edit_date.map(|value| value as i64),
                     text: message.message.clone(),
                     outgoing: message.out,
+                    ttl_period: message
+                        .ttl_period
+                        .map(|value| value as i64)
+                        .filter(|value| *value > 0),
+                    media: message.media.as_ref().and_then(media_from_tl),
+                    reply_to: message.reply_to.as_ref().and_then(reply_to_from_tl),
                 })
             }
             _ => {
-                warn!(message = ?message, "unsupported message variant");
+                warn!(
+                    message = %raw_for_log(message, self.log_content),
+                    "unsupported message variant"
+                );
                 None
             }
         }
     }
 }
 
+/// How the event stream treats a subscriber that falls behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventDropPolicy {
+    /// Slow subscribers block the pump, which pushes back on the update
+    /// stream instead of losing events.
+    BlockProducer,
+    /// Slow subscribers lose their oldest buffered events and see
+    /// [`broadcast::error::RecvError::Lagged`].
+    DropOldest,
+    /// Events that do not fit a slow subscriber's buffer are dropped for it.
+    DropNewest,
+}
+
+/// Chats a filtered subscription receives events for.
+///
+/// Events that are not tied to a chat, such as [`DomainEvent::SendProgress`]
+/// or deletions outside channels, always pass;
+/// [`DomainEvent::ChatFlagsChanged`] passes when any of its changes concerns
+/// a selected chat.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChatFilter {
+    chats: HashSet<ChatId>,
+}
+
+impl ChatFilter {
+    pub fn new(chats: impl IntoIterator<Item = ChatId>) -> Self {
+        Self {
+            chats: chats.into_iter().collect(),
+        }
+    }
+
+    pub fn matches(&self, event: &DomainEvent) -> bool {
+        let chat_id = match event {
+            DomainEvent::MessageNew(message) => message.chat_id,
+            DomainEvent::MessageEdited(message) => message.chat_id,
+            DomainEvent::ReadReceipt(receipt) => receipt.chat_id,
+            DomainEvent::InboxRead(read) => read.chat_id,
+            DomainEvent::MessageReactions(update) => update.chat_id,
+            DomainEvent::MessagesDeleted(deleted) => match deleted.chat_id {
+                Some(chat_id) => chat_id,
+                None => return true,
+            },
+            DomainEvent::PinnedChanged(change) => change.chat_id,
+            DomainEvent::Typing(typing) => typing.chat_id,
+            DomainEvent::TypingStopped(typing) => typing.chat_id,
+            // Presence belongs to the private chat with that user.
+            DomainEvent::OnlineStatus(status) => ChatId(status.user_id.0),
+            DomainEvent::ChatMemberUpdate(update) => update.chat_id,
+            DomainEvent::AudioTranscribed(update) => update.chat_id,
+            DomainEvent::ChatFlagsChanged(update) => {
+                return update
+                    .changes
+                    .iter()
+                    .any(|(chat_id, _)| self.chats.contains(chat_id));
+            }
+            DomainEvent::SendProgress(_) | DomainEvent::ConnectionState(_) => return true,
+        };
+        self.chats.contains(&chat_id)
+    }
+}
+
+/// How one subscription buffers events; unset fields follow the publisher.
+///
+/// A subscriber that can afford to lose events, such as an exporter, can opt
+/// out of [`EventDropPolicy::BlockProducer`] so it never holds up the others.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubscribeOptions {
+    pub filter: Option<ChatFilter>,
+    /// Events buffered for this subscriber before its drop policy applies.
+    pub buffer: Option<usize>,
+    pub drop_policy: Option<EventDropPolicy>,
+}
+
+/// A domain event stamped with its position in the stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SequencedEvent {
+    /// Grows by one per published event, from 1 or, with an event journal,
+    /// from after the journal's last seq. Filtered subscriptions see gaps for
+    /// the events they filtered out.
+    pub seq: u64,
+    pub event: DomainEvent,
+}
+
+/// Fans domain events out to subscribers according to an [`EventDropPolicy`].
+#[derive(Clone)]
+pub struct EventPublisher {
+    inner: Arc<PublisherInner>,
+}
+
+struct PublisherInner {
+    policy: EventDropPolicy,
+    buffer: usize,
+    next_seq: AtomicU64,
+    broadcast: broadcast::Sender<SequencedEvent>,
+    /// Subscribers with a filter or their own buffer, and every subscriber
+    /// under the policies broadcast cannot express.
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+struct Subscriber {
+    filter: Option<ChatFilter>,
+    queue: SubscriberQueue,
+}
+
+enum SubscriberQueue {
+    /// Drops the oldest events, like the shared broadcast channel.
+    Lossy(broadcast::Sender<SequencedEvent>),
+    /// Raises the shared flag whenever an event does not fit, unless
+    /// `blocking` lets [`EventPublisher::publish`] wait for room.
+    Bounded {
+        sender: mpsc::Sender<SequencedEvent>,
+        dropped: Arc<AtomicBool>,
+        blocking: bool,
+    },
+}
+
+impl Subscriber {
+    fn accepts(&self, event: &DomainEvent) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(event))
+    }
+
+    fn is_closed(&self) -> bool {
+        match &self.queue {
+            SubscriberQueue::Lossy(sender) => sender.receiver_count() == 0,
+            SubscriberQueue::Bounded { sender, .. } => sender.is_closed(),
+        }
+    }
+}
+
+impl std::fmt::Debug for EventPublisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventPublisher")
+            .field("policy", &self.inner.policy)
+            .field("buffer", &self.inner.buffer)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EventPublisher {
+    pub fn new(policy: EventDropPolicy, buffer: usize) -> Self {
+        let buffer = buffer.max(1);
+        let (broadcast, _) = broadcast::channel(buffer);
+        Self {
+            inner: Arc::new(PublisherInner {
+                policy,
+                buffer,
+                next_seq: AtomicU64::new(1),
+                broadcast,
+                subscribers: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn subscribe(&self) -> EventReceiver {
+        self.subscribe_with(SubscribeOptions::default())
+    }
+
+    /// Subscribes to the events `filter` matches only; other events never
+    /// reach, or wake, the receiver.
+    pub fn subscribe_filtered(&self, filter: ChatFilter) -> EventReceiver {
+        self.subscribe_with(SubscribeOptions {
+            filter: Some(filter),
+            ..SubscribeOptions::default()
+        })
+    }
+
+    /// Subscribes with its own filter, buffer, or drop policy.
+    pub fn subscribe_with(&self, options: SubscribeOptions) -> EventReceiver {
+        let policy = options.drop_policy.unwrap_or(self.inner.policy);
+        let buffer = options
+            .buffer
+            .map_or(self.inner.buffer, |buffer| buffer.max(1));
+        match policy {
+            EventDropPolicy::DropOldest => {
+                if self.inner.policy == EventDropPolicy::DropOldest
+                    && options.filter.is_none()
+                    && buffer == self.inner.buffer
+                {
+                    return EventReceiver::from_receiver(self.inner.broadcast.subscribe());
+                }
+                let (sender, receiver) = broadcast::channel(buffer);
+                self.subscribers().push(Subscriber {
+                    filter: options.filter,
+                    queue: SubscriberQueue::Lossy(sender),
+                });
+                EventReceiver::from_receiver(receiver)
+            }
+            EventDropPolicy::BlockProducer | EventDropPolicy::DropNewest => {
+                let (sender, receiver) = mpsc::channel(buffer);
+                let dropped = Arc::new(AtomicBool::new(false));
+                self.subscribers().push(Subscriber {
+                    filter: options.filter,
+                    queue: SubscriberQueue::Bounded {
+                        sender,
+                        dropped: Arc::clone(&dropped),
+                        blocking: policy == EventDropPolicy::BlockProducer,
+                    },
+                });
+                EventReceiver {
+                    inner: ReceiverInner::Queue(receiver, dropped),
+                    gap: false,
+                }
+            }
+        }
+    }
+
+    fn stamp(&self, event: DomainEvent) -> SequencedEvent {
+        SequencedEvent {
+            seq: self.inner.next_seq.fetch_add(1, Ordering::Relaxed),
+            event,
+        }
+    }
+
+    /// Numbers the following events after `seq`, unless they already are.
+    fn continue_after(&self, seq: u64) {
+        self.inner
+            .next_seq
+            .fetch_max(seq.saturating_add(1), Ordering::Relaxed);
+    }
+
+    /// Delivers `event` to every subscriber, waiting for room in the queues
+    /// of [`EventDropPolicy::BlockProducer`] subscribers.
+    pub async fn publish(&self, event: DomainEvent) {
+        self.publish_stamped(self.stamp(event)).await;
+    }
+
+    async fn publish_stamped(&self, event: SequencedEvent) {
+        let waiting = self.offer(&event, true);
+        let mut closed = false;
+        for queue in &waiting {
+            closed |= queue.send(event.clone()).await.is_err();
+        }
+        if closed {
+            self.subscribers()
+                .retain(|subscriber| !subscriber.is_closed());
+        }
+    }
+
+    /// Delivers `event` without waiting; a full subscriber under
+    /// [`EventDropPolicy::BlockProducer`] loses it like under
+    /// [`EventDropPolicy::DropNewest`].
+    pub fn try_publish(&self, event: DomainEvent) {
+        let event = self.stamp(event);
+        self.offer(&event, false);
+    }
+
+    /// Hands `event` to every subscriber with room for it. Full blocking
+    /// queues are returned for the caller to wait on when `wait` is set, and
+    /// lose the event otherwise.
+    fn offer(&self, event: &SequencedEvent, wait: bool) -> Vec<mpsc::Sender<SequencedEvent>> {
+        let mut waiting = Vec::new();
+        let mut subscribers = self.subscribers();
+        let mut active = !subscribers.is_empty();
+        subscribers.retain(|subscriber| {
+            if !subscriber.accepts(&event.event) {
+                return !subscriber.is_closed();
+            }
+            match &subscriber.queue {
+                SubscriberQueue::Lossy(sender) => sender.send(event.clone()).is_ok(),
+                SubscriberQueue::Bounded {
+                    sender,
+                    dropped,
+                    blocking,
+                } => match sender.try_send(event.clone()) {
+                    Ok(()) => true,
+                    Err(mpsc::error::TrySendError::Full(_)) if wait && *blocking => {
+                        waiting.push(sender.clone());
+                        true
+                    }
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        warn!(
+                            seq = event.seq,
+                            "dropped newest domain event for a full subscriber"
+                        );
+                        dropped.store(true, Ordering::Relaxed);
+                        true
+                    }
+                    Err(mpsc::error::TrySendError::Closed(_)) => false,
+                },
+            }
+        });
+        drop(subscribers);
+        if self.inner.policy == EventDropPolicy::DropOldest {
+            active |= self.inner.broadcast.send(event.clone()).is_ok();
+        }
+        if !active {
+            warn!("dropped domain event because no subscribers are active");
+        }
+        waiting
+    }
+
+    /// Events waiting per subscriber. The first entry is the shared
+    /// broadcast channel, measured at its slowest receiver.
+    pub fn backlog(&self) -> Vec<usize> {
+        let mut backlog = vec![self.inner.broadcast.len()];
+        backlog.extend(
+            self.subscribers()
+                .iter()
+                .map(|subscriber| match &subscriber.queue {
+                    SubscriberQueue::Lossy(sender) => sender.len(),
+                    SubscriberQueue::Bounded { sender, .. } => {
+                        sender.max_capacity() - sender.capacity()
+                    }
+                }),
+        );
+        backlog
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, Vec<Subscriber>> {
+        match self.inner.subscribers.lock() {
+            Ok(subscribers) => subscribers,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
 pub struct EventStream {
-    sender: broadcast::Sender<DomainEvent>,
+    publisher: EventPublisher,
+    counters: Arc<StreamCounters>,
     stop_tx: watch::Sender<bool>,
+    online: watch::Receiver<bool>,
+    raw_updates: Option<mpsc::Receiver<grammers_client::Update>>,
     join: JoinHandle<()>,
     update_pump: Option<UpdatePump<grammers_client::Update, grammers_mtsender::InvocationError>>,
 }
 
 impl EventStream {
     pub fn subscribe(&self) -> EventReceiver {
-        EventReceiver::from_receiver(self.sender.subscribe())
+        self.publisher.subscribe()
+    }
+
+    /// See [`EventPublisher::subscribe_filtered`].
+    pub fn subscribe_filtered(&self, filter: ChatFilter) -> EventReceiver {
+        self.publisher.subscribe_filtered(filter)
+    }
+
+    /// See [`EventPublisher::subscribe_with`].
+    pub fn subscribe_with(&self, options: SubscribeOptions) -> EventReceiver {
+        self.publisher.subscribe_with(options)
+    }
+
+    /// Publisher for events produced outside the update stream, such as
+    /// [`SendPipeline::with_status_events`].
+    ///
+    /// [`SendPipeline::with_status_events`]: crate::telegram::send::SendPipeline::with_status_events
+    pub fn publisher(&self) -> EventPublisher {
+        self.publisher.clone()
+    }
+
+    /// Reads `false` while the update pump is reconnecting, and after it
+    /// gives up on a transport error. See [`SendPipeline::follow_connectivity`].
+    ///
+    /// [`SendPipeline::follow_connectivity`]: crate::telegram::send::SendPipeline::follow_connectivity
+    pub fn connectivity(&self) -> watch::Receiver<bool> {
+        self.online.clone()
+    }
+
+    pub fn metrics(&self) -> EventStreamMetrics {
+        self.counters.snapshot(&self.publisher)
+    }
+
+    /// Raw updates, each forwarded after it has been mapped, when
+    /// [`EventStreamConfig::raw_update_buffer`] is set. Updates the consumer
+    /// cannot keep up with are dropped rather than stalling mapped events.
+    pub fn take_raw_updates(&mut self) -> Option<mpsc::Receiver<grammers_client::Update>> {
+        self.raw_updates.take()
     }
 
     pub async fn stop(mut self) {
@@ -200,59 +1206,250 @@ impl EventStream {
     }
 }
 
+/// Events that arrived within one [`BatchConfig::window`], oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventBatch {
+    pub events: Vec<DomainEvent>,
+}
+
+/// Coalescing applied by [`EventReceiver::recv_batch`], so catch-up floods
+/// are applied in a few large steps instead of one lock per event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchConfig {
+    /// How long to keep collecting after the first event of a batch.
+    pub window: Duration,
+    pub max_events: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(5),
+            max_events: 256,
+        }
+    }
+}
+
 pub struct EventReceiver {
-    inner: broadcast::Receiver<DomainEvent>,
+    inner: ReceiverInner,
+    gap: bool,
+}
+
+enum ReceiverInner {
+    Broadcast(broadcast::Receiver<SequencedEvent>),
+    Queue(mpsc::Receiver<SequencedEvent>, Arc<AtomicBool>),
 }
 
 impl EventReceiver {
-    pub fn from_receiver(receiver: broadcast::Receiver<DomainEvent>) -> Self {
-        Self { inner: receiver }
+    pub fn from_receiver(receiver: broadcast::Receiver<SequencedEvent>) -> Self {
+        Self {
+            inner: ReceiverInner::Broadcast(receiver),
+            gap: false,
+        }
     }
 
     pub async fn recv(&mut self) -> std::result::Result<DomainEvent, broadcast::error::RecvError> {
-        match self.inner.recv().await {
+        self.recv_sequenced().await.map(|sequenced| sequenced.event)
+    }
+
+    /// Like [`EventReceiver::recv`], keeping the event's sequence number.
+    pub async fn recv_sequenced(
+        &mut self,
+    ) -> std::result::Result<SequencedEvent, broadcast::error::RecvError> {
+        let receiver = match &mut self.inner {
+            ReceiverInner::Broadcast(receiver) => receiver,
+            ReceiverInner::Queue(receiver, dropped) => {
+                let event = receiver.recv().await;
+                if dropped.swap(false, Ordering::Relaxed) {
+                    self.gap = true;
+                }
+                return event.ok_or(broadcast::error::RecvError::Closed);
+            }
+        };
+        match receiver.recv().await {
             Ok(event) => Ok(event),
             Err(broadcast::error::RecvError::Lagged(count)) => {
                 warn!(lagged = count, "event receiver lagged");
+                self.gap = true;
                 Err(broadcast::error::RecvError::Lagged(count))
             }
             Err(err) => Err(err),
         }
     }
+
+    /// Waits for an event, then collects what follows it as in
+    /// [`EventReceiver::collect_batch`]. Returns `None` once the stream has
+    /// closed. Lag is skipped over; check [`EventReceiver::take_gap`].
+    ///
+    /// Not cancel safe: events collected so far are lost if the future is
+    /// dropped. Wait on [`EventReceiver::recv`] and call `collect_batch` when
+    /// racing against other branches.
+    pub async fn recv_batch(&mut self, config: BatchConfig) -> Option<EventBatch> {
+        loop {
+            match self.recv().await {
+                Ok(event) => return Some(self.collect_batch(event, config).await),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Starts a batch with `first` and adds events arriving within
+    /// `config.window` of now, up to `config.max_events`.
+    pub async fn collect_batch(&mut self, first: DomainEvent, config: BatchConfig) -> EventBatch {
+        let deadline = tokio::time::Instant::now() + config.window;
+        let mut events = vec![first];
+        while events.len() < config.max_events {
+            match tokio::time::timeout_at(deadline, self.recv()).await {
+                Ok(Ok(event)) => events.push(event),
+                Ok(Err(broadcast::error::RecvError::Lagged(_))) => {}
+                Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => break,
+            }
+        }
+        EventBatch { events }
+    }
+
+    /// Returns `true`, once, after events meant for this receiver were lost
+    /// to the drop policy. State built from the stream may then be stale and
+    /// should be resynced.
+    pub fn take_gap(&mut self) -> bool {
+        if let ReceiverInner::Queue(_, dropped) = &self.inner {
+            if dropped.swap(false, Ordering::Relaxed) {
+                self.gap = true;
+            }
+        }
+        std::mem::take(&mut self.gap)
+    }
 }
 
 pub fn spawn_domain_event_pump(
     mut update_pump: UpdatePump<grammers_client::Update, grammers_mtsender::InvocationError>,
-    buffer: usize,
+    config: &EventStreamConfig,
 ) -> Result<EventStream> {
     let mut update_rx = update_pump
         .take_receiver()
         .ok_or(TelegramError::UpdatePumpUnavailable)?;
-    let (sender, _) = broadcast::channel(buffer);
-    let sender_task = sender.clone();
+    let publisher = EventPublisher::new(config.drop_policy, config.buffer_size);
+    let (mut raw_tx, raw_updates) = match config.raw_update_buffer {
+        Some(buffer) => {
+            let (sender, receiver) = mpsc::channel(buffer.max(1));
+            (Some(sender), Some(receiver))
+        }
+        None => (None, None),
+    };
+    let publisher_task = publisher.clone();
+    let journal = config.journal.clone();
+    if let Some(journal) = &journal {
+        match journal.last_seq() {
+            Ok(seq) => publisher.continue_after(seq),
+            Err(err) => warn!(error = %err, "failed to read the event journal's last seq"),
+        }
+    }
     let (stop_tx, mut stop_rx) = watch::channel(false);
-    let mapper = EventMapper::new();
+    let (online_tx, online) = watch::channel(true);
+    let mapper = EventMapper::new().with_log_content(config.log_content);
+    let mut typing = TypingTracker::default();
+    let counters = Arc::new(StreamCounters::default());
+    let counters_task = Arc::clone(&counters);
+    let metrics_publisher = publisher.clone();
+    let mut metrics_log = config
+        .metrics_log_interval
+        .map(|period| tokio::time::interval_at(tokio::time::Instant::now() + period, period));
+    let mut heartbeat = config
+        .heartbeat_interval
+        .map(|period| tokio::time::interval_at(tokio::time::Instant::now() + period, period));
+    let mut connection = ConnectionStatus::Connected;
 
     let join = tokio::spawn(async move {
-        loop {
+        'pump: loop {
             tokio::select! {
                 _ = stop_rx.changed() => {
                     break;
                 }
+                _ = typing_due(typing.next_deadline()) => {
+                    let now = OffsetDateTime::now_utc().unix_timestamp();
+                    for event in typing.expire(tokio::time::Instant::now(), now) {
+                        if !deliver(&publisher_task, journal.as_ref(), &mut stop_rx, event).await {
+                            break 'pump;
+                        }
+                    }
+                }
+                _ = interval_due(&mut heartbeat) => {
+                    let event = connection_event(connection);
+                    if !deliver(&publisher_task, journal.as_ref(), &mut stop_rx, event).await {
+                        break 'pump;
+                    }
+                }
+                _ = interval_due(&mut metrics_log) => {
+                    let metrics = counters_task.snapshot(&metrics_publisher);
+                    info!(
+                        received = metrics.updates_received,
+                        mapped = metrics.events_mapped,
+                        unsupported = metrics.updates_unsupported,
+                        mapping_avg_us = metrics.mapping_latency_avg.as_micros() as u64,
+                        mapping_max_us = metrics.mapping_latency_max.as_micros() as u64,
+                        backlog = ?metrics.subscriber_backlog,
+                        "event stream metrics"
+                    );
+                }
                 update = update_rx.recv() => {
                     let Some(update) = update else {
                         break;
                     };
                     match update {
                         UpdateEvent::Update(update) => {
-                            if let Some(event) = mapper.map_update(&update) {
-                                if sender_task.send(event).is_err() {
-                                    warn!("dropped domain event because no subscribers are active");
+                            let started = std::time::Instant::now();
+                            let event = mapper.map_update(&update);
+                            counters_task.record(event.is_some(), started.elapsed());
+                            if let Some(tap) = raw_tx.as_ref() {
+                                match tap.try_send(update) {
+                                    Ok(()) => {}
+                                    Err(mpsc::error::TrySendError::Full(_)) => {
+                                        warn!("raw update tap full; dropping update");
+                                    }
+                                    Err(mpsc::error::TrySendError::Closed(_)) => raw_tx = None,
                                 }
                             }
+                            if let Some(event) = event {
+                                let stopped = typing.observe(&event, tokio::time::Instant::now());
+                                for event in std::iter::once(event).chain(stopped) {
+                                    if !deliver(&publisher_task, journal.as_ref(), &mut stop_rx, event).await {
+                                        break 'pump;
+                                    }
+                                }
+                            }
+                        }
+                        UpdateEvent::Reconnecting { error, attempt, delay } => {
+                            warn!(error = %error, attempt, ?delay, "update stream failed; reconnecting");
+                            online_tx.send_replace(false);
+                            connection = ConnectionStatus::Reconnecting {
+                                attempt,
+                                since: lost_since(connection),
+                            };
+                            let event = connection_event(connection);
+                            if !deliver(&publisher_task, journal.as_ref(), &mut stop_rx, event).await {
+                                break 'pump;
+                            }
+                        }
+                        UpdateEvent::Reconnected => {
+                            info!("update stream reconnected");
+                            online_tx.send_replace(true);
+                            connection = ConnectionStatus::Connected;
+                            let event = connection_event(connection);
+                            if !deliver(&publisher_task, journal.as_ref(), &mut stop_rx, event).await {
+                                break 'pump;
+                            }
                         }
                         UpdateEvent::Error(err) => {
                             warn!(error = %err, "update pump error while mapping domain events");
+                            if is_transport_error(&err) {
+                                online_tx.send_replace(false);
+                                connection = ConnectionStatus::Offline {
+                                    since: lost_since(connection),
+                                };
+                                let event = connection_event(connection);
+                                deliver(&publisher_task, journal.as_ref(), &mut stop_rx, event).await;
+                            }
                             break;
                         }
                     }
@@ -262,13 +1459,195 @@ pub fn spawn_domain_event_pump(
     });
 
     Ok(EventStream {
-        sender,
+        publisher,
+        counters,
         stop_tx,
+        online,
+        raw_updates,
         join,
         update_pump: Some(update_pump),
     })
 }
 
+/// Point-in-time view of the event pump, for the status bar and logs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventStreamMetrics {
+    pub updates_received: u64,
+    pub events_mapped: u64,
+    /// Updates the mapper produced no domain event for.
+    pub updates_unsupported: u64,
+    pub mapping_latency_avg: Duration,
+    pub mapping_latency_max: Duration,
+    /// See [`EventPublisher::backlog`].
+    pub subscriber_backlog: Vec<usize>,
+}
+
+#[derive(Debug, Default)]
+struct StreamCounters {
+    received: AtomicU64,
+    mapped: AtomicU64,
+    mapping_nanos: AtomicU64,
+    mapping_max_nanos: AtomicU64,
+}
+
+impl StreamCounters {
+    fn record(&self, mapped: bool, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.received.fetch_add(1, Ordering::Relaxed);
+        if mapped {
+            self.mapped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.mapping_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.mapping_max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, publisher: &EventPublisher) -> EventStreamMetrics {
+        let received = self.received.load(Ordering::Relaxed);
+        let mapped = self.mapped.load(Ordering::Relaxed);
+        let total_nanos = self.mapping_nanos.load(Ordering::Relaxed);
+        EventStreamMetrics {
+            updates_received: received,
+            events_mapped: mapped,
+            updates_unsupported: received.saturating_sub(mapped),
+            mapping_latency_avg: Duration::from_nanos(
+                total_nanos.checked_div(received).unwrap_or(0),
+            ),
+            mapping_latency_max: Duration::from_nanos(
+                self.mapping_max_nanos.load(Ordering::Relaxed),
+            ),
+            subscriber_backlog: publisher.backlog(),
+        }
+    }
+}
+
+async fn interval_due(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// A [`DomainEvent::ConnectionState`] stamped with the current time.
+fn connection_event(status: ConnectionStatus) -> DomainEvent {
+    DomainEvent::ConnectionState(ConnectionState {
+        status,
+        timestamp: OffsetDateTime::now_utc().unix_timestamp(),
+    })
+}
+
+/// When the connection was lost, keeping the time of the first failure
+/// across reconnect attempts.
+fn lost_since(status: ConnectionStatus) -> i64 {
+    match status {
+        ConnectionStatus::Reconnecting { since, .. } | ConnectionStatus::Offline { since } => since,
+        ConnectionStatus::Connected => OffsetDateTime::now_utc().unix_timestamp(),
+    }
+}
+
+/// Publishes one event, journaling it first. Returns `false` when the stream
+/// was stopped while waiting on a blocked subscriber.
+async fn deliver(
+    publisher: &EventPublisher,
+    journal: Option<&Arc<dyn EventJournal>>,
+    stop_rx: &mut watch::Receiver<bool>,
+    event: DomainEvent,
+) -> bool {
+    // Stamped first so the journal records the seq subscribers see.
+    let event = publisher.stamp(event);
+    if let Some(journal) = journal {
+        record_event(journal, &event).await;
+    }
+    // A blocked subscriber must not hold up shutdown.
+    tokio::select! {
+        _ = stop_rx.changed() => false,
+        _ = publisher.publish_stamped(event) => true,
+    }
+}
+
+/// How long a typing indicator lasts without a fresh typing update.
+const TYPING_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// Telegram only reports that someone started typing; this turns silence,
+/// or the message they were typing, into [`DomainEvent::TypingStopped`].
+#[derive(Debug, Default)]
+struct TypingTracker {
+    deadlines: HashMap<(ChatId, UserId), tokio::time::Instant>,
+}
+
+impl TypingTracker {
+    fn observe(&mut self, event: &DomainEvent, now: tokio::time::Instant) -> Option<DomainEvent> {
+        match event {
+            DomainEvent::Typing(typing) => {
+                self.deadlines
+                    .insert((typing.chat_id, typing.user_id), now + TYPING_TIMEOUT);
+                None
+            }
+            DomainEvent::MessageNew(message) => self
+                .deadlines
+                .remove(&(message.chat_id, message.author_id))
+                .map(|_| {
+                    DomainEvent::TypingStopped(TypingStopped {
+                        chat_id: message.chat_id,
+                        user_id: message.author_id,
+                        timestamp: message.timestamp,
+                    })
+                }),
+            _ => None,
+        }
+    }
+
+    fn next_deadline(&self) -> Option<tokio::time::Instant> {
+        self.deadlines.values().min().copied()
+    }
+
+    fn expire(&mut self, now: tokio::time::Instant, timestamp: i64) -> Vec<DomainEvent> {
+        let mut expired = Vec::new();
+        self.deadlines.retain(|(chat_id, user_id), deadline| {
+            if *deadline > now {
+                return true;
+            }
+            expired.push(DomainEvent::TypingStopped(TypingStopped {
+                chat_id: *chat_id,
+                user_id: *user_id,
+                timestamp,
+            }));
+            false
+        });
+        expired
+    }
+}
+
+async fn typing_due(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn record_event(journal: &Arc<dyn EventJournal>, event: &SequencedEvent) {
+    let journal = Arc::clone(journal);
+    let event = event.clone();
+    let recorded_at = OffsetDateTime::now_utc().unix_timestamp();
+    match tokio::task::spawn_blocking(move || journal.append(event.seq, &event.event, recorded_at))
+        .await
+    {
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => warn!(error = %err, "failed to journal domain event"),
+        Err(err) => warn!(error = %err, "event journal task failed"),
+    }
+}
+
+fn is_transport_error(err: &grammers_mtsender::InvocationError) -> bool {
+    use grammers_mtsender::InvocationError;
+
+    matches!(
+        err,
+        InvocationError::Io(_) | InvocationError::Transport(_) | InvocationError::Dropped
+    )
+}
+
 struct ParsedMessage {
     chat_id: ChatId,
     message_id: MessageId,
@@ -277,6 +1656,195 @@ struct ParsedMessage {
     edit_date: Option<i64>,
     text: String,
     outgoing: bool,
+    ttl_period: Option<i64>,
+    media: Option<MessageMedia>,
+    reply_to: Option<MessageId>,
+}
+
+/// The replied-to message, when it is in the same chat; replies to other
+/// chats and to stories have nothing to quote.
+fn reply_to_from_tl(header: &tl::enums::MessageReplyHeader) -> Option<MessageId> {
+    match header {
+        tl::enums::MessageReplyHeader::Header(header) if header.reply_to_peer_id.is_none() => {
+            header.reply_to_msg_id.map(|id| MessageId(id as i64))
+        }
+        _ => None,
+    }
+}
+
+/// Photo sizes up to this many pixels on the long side make good inline
+/// previews; Telegram's `m` variant is 320px.
+const THUMBNAIL_MAX_SIDE: i32 = 320;
+
+fn media_from_tl(media: &tl::enums::MessageMedia) -> Option<MessageMedia> {
+    match media {
+        tl::enums::MessageMedia::Empty => None,
+        tl::enums::MessageMedia::Photo(media) => {
+            let Some(tl::enums::Photo::Photo(photo)) = &media.photo else {
+                return Some(other_media(MessageMediaKind::Photo));
+            };
+            Some(photo_media(photo))
+        }
+        tl::enums::MessageMedia::Document(media) => {
+            let Some(tl::enums::Document::Document(document)) = &media.document else {
+                return Some(other_media(MessageMediaKind::Document));
+            };
+            let mut kind = match document.mime_type.split('/').next() {
+                Some("video") => MessageMediaKind::Video,
+                Some("audio") => MessageMediaKind::Audio,
+                Some("image") => MessageMediaKind::Photo,
+                _ => MessageMediaKind::Document,
+            };
+            let mut duration = None;
+            let mut file_name = None;
+            for attribute in &document.attributes {
+                match attribute {
+                    tl::enums::DocumentAttribute::Audio(audio) => {
+                        if audio.voice {
+                            kind = MessageMediaKind::Voice;
+                        }
+                        duration = u32::try_from(audio.duration).ok();
+                    }
+                    tl::enums::DocumentAttribute::Video(video) => {
+                        duration = Some(video.duration.round() as u32);
+                    }
+                    tl::enums::DocumentAttribute::Filename(name) => {
+                        file_name = Some(name.file_name.clone());
+                    }
+                    _ => {}
+                }
+            }
+            Some(MessageMedia {
+                kind,
+                size: u64::try_from(document.size).ok(),
+                thumbnail: None,
+                duration,
+                document: Some(DocumentFile {
+                    id: document.id,
+                    access_hash: document.access_hash,
+                    file_reference: document.file_reference.clone(),
+                    dc_id: document.dc_id,
+                    mime_type: document.mime_type.clone(),
+                    file_name,
+                }),
+                transcription: None,
+            })
+        }
+        _ => Some(other_media(MessageMediaKind::Other)),
+    }
+}
+
+fn other_media(kind: MessageMediaKind) -> MessageMedia {
+    MessageMedia {
+        kind,
+        size: None,
+        thumbnail: None,
+        duration: None,
+        document: None,
+        transcription: None,
+    }
+}
+
+fn photo_media(photo: &tl::types::Photo) -> MessageMedia {
+    // (type, long side, bytes) of every downloadable size.
+    let sizes: Vec<(&str, i32, i32)> = photo
+        .sizes
+        .iter()
+        .filter_map(|size| match size {
+            tl::enums::PhotoSize::Size(size) => {
+                Some((size.r#type.as_str(), size.w.max(size.h), size.size))
+            }
+            tl::enums::PhotoSize::Progressive(size) => Some((
+                size.r#type.as_str(),
+                size.w.max(size.h),
+                size.sizes.iter().copied().max().unwrap_or_default(),
+            )),
+            _ => None,
+        })
+        .collect();
+    let largest = sizes.iter().max_by_key(|(_, side, _)| *side);
+    let preview = sizes
+        .iter()
+        .filter(|(_, side, _)| *side <= THUMBNAIL_MAX_SIDE)
+        .max_by_key(|(_, side, _)| *side)
+        .or_else(|| sizes.iter().min_by_key(|(_, side, _)| *side));
+    MessageMedia {
+        kind: MessageMediaKind::Photo,
+        size: largest.and_then(|(_, _, bytes)| u64::try_from(*bytes).ok()),
+        thumbnail: preview.map(|(size_type, _, _)| PhotoThumbnail {
+            photo_id: photo.id,
+            access_hash: photo.access_hash,
+            file_reference: photo.file_reference.clone(),
+            dc_id: photo.dc_id,
+            size_type: size_type.to_string(),
+        }),
+        duration: None,
+        document: None,
+        transcription: None,
+    }
+}
+
+fn chat_id_from_peer(peer: &tl::enums::Peer) -> ChatId {
+    ChatId(PeerId::from(peer.clone()).bot_api_dialog_id())
+}
+
+fn is_muted(settings: &tl::enums::PeerNotifySettings, now: i64) -> bool {
+    let tl::enums::PeerNotifySettings::Settings(settings) = settings;
+    settings
+        .mute_until
+        .is_some_and(|mute_until| mute_until as i64 > now)
+}
+
+fn message_ids(ids: &[i32]) -> Vec<MessageId> {
+    ids.iter().map(|id| MessageId(*id as i64)).collect()
+}
+
+fn basic_group_id(chat_id: i64) -> ChatId {
+    chat_id_from_peer(&tl::enums::Peer::Chat(tl::types::PeerChat { chat_id }))
+}
+
+fn member_update(
+    chat_id: ChatId,
+    user_id: i64,
+    change: MemberChange,
+    timestamp: i64,
+) -> DomainEvent {
+    DomainEvent::ChatMemberUpdate(ChatMemberUpdate {
+        chat_id,
+        user_id: UserId(user_id),
+        change,
+        timestamp,
+    })
+}
+
+fn is_channel_member(participant: Option<&tl::enums::ChannelParticipant>) -> bool {
+    match participant {
+        None | Some(tl::enums::ChannelParticipant::Left(_)) => false,
+        Some(tl::enums::ChannelParticipant::Banned(banned)) => !banned.left,
+        Some(_) => true,
+    }
+}
+
+fn is_channel_admin(participant: Option<&tl::enums::ChannelParticipant>) -> bool {
+    matches!(
+        participant,
+        Some(tl::enums::ChannelParticipant::Admin(_) | tl::enums::ChannelParticipant::Creator(_))
+    )
+}
+
+fn presence_from_status(status: &tl::enums::UserStatus) -> Presence {
+    match status {
+        tl::enums::UserStatus::Online(online) => Presence::Online {
+            expires: online.expires as i64,
+        },
+        tl::enums::UserStatus::Offline(offline) => Presence::Offline {
+            last_seen: offline.was_online as i64,
+        },
+        tl::enums::UserStatus::Recently(_) => Presence::Recently,
+        tl::enums::UserStatus::LastWeek(_) => Presence::LastWeek,
+        tl::enums::UserStatus::LastMonth(_) => Presence::LastMonth,
+        tl::enums::UserStatus::Empty => Presence::Unknown,
+    }
 }
 
 fn user_id_from_peer(peer: &tl::enums::Peer) -> Option<UserId> {
@@ -0,0 +1,285 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use sqlite::{Connection, State, Value};
+use thiserror::Error;
+
+use crate::telegram::events::DomainEvent;
+
+const SQLITE_BUSY_TIMEOUT_MS: u64 = 5_000;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS events (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    recorded_at INTEGER NOT NULL,
+    event TEXT NOT NULL
+);
+"#;
+
+#[derive(Debug, Error)]
+pub enum JournalError {
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] sqlite::Error),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("event encoding error: {0}")]
+    Encoding(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, JournalError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub recorded_at: i64,
+    pub event: DomainEvent,
+}
+
+/// Append-only record of mapped domain events, so a consumer that missed
+/// part of the live stream can catch up from the last sequence it applied.
+///
+/// Entries keep the [`SequencedEvent::seq`] their event was published under,
+/// so replaying from the seq of a live, journaled event yields exactly what
+/// followed it.
+/// [`DomainEvent::SendProgress`] and [`DomainEvent::ConnectionState`] are
+/// local to one process and never journaled; they leave gaps.
+///
+/// [`SequencedEvent::seq`]: crate::telegram::events::SequencedEvent::seq
+pub trait EventJournal: Send + Sync + std::fmt::Debug {
+    /// Appends `event` under `seq`, which must be above every seq appended
+    /// before. Returns `false` when the event is not journaled.
+    fn append(&self, seq: u64, event: &DomainEvent, recorded_at: i64) -> Result<bool>;
+
+    /// The highest seq ever appended, truncated or not; 0 for a new journal.
+    /// A restarted stream numbers its events after it.
+    fn last_seq(&self) -> Result<u64>;
+
+    /// Entries after `seq`, oldest first. `replay_since(0)` replays everything.
+    fn replay_since(&self, seq: u64) -> Result<Vec<JournalEntry>>;
+
+    /// Drops entries up to and including `seq` once every consumer has them.
+    fn truncate_through(&self, seq: u64) -> Result<()>;
+}
+
+fn is_journaled(event: &DomainEvent) -> bool {
+    !matches!(
+        event,
+        DomainEvent::SendProgress(_) | DomainEvent::ConnectionState(_)
+    )
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryEventJournal {
+    inner: Mutex<MemoryJournal>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryJournal {
+    last_seq: u64,
+    entries: Vec<JournalEntry>,
+}
+
+impl MemoryEventJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn journal(&self) -> MutexGuard<'_, MemoryJournal> {
+        match self.inner.lock() {
+            Ok(journal) => journal,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl EventJournal for MemoryEventJournal {
+    fn append(&self, seq: u64, event: &DomainEvent, recorded_at: i64) -> Result<bool> {
+        if !is_journaled(event) {
+            return Ok(false);
+        }
+        let mut journal = self.journal();
+        journal.last_seq = journal.last_seq.max(seq);
+        journal.entries.push(JournalEntry {
+            seq,
+            recorded_at,
+            event: event.clone(),
+        });
+        Ok(true)
+    }
+
+    fn last_seq(&self) -> Result<u64> {
+        Ok(self.journal().last_seq)
+    }
+
+    fn replay_since(&self, seq: u64) -> Result<Vec<JournalEntry>> {
+        Ok(self
+            .journal()
+            .entries
+            .iter()
+            .filter(|entry| entry.seq > seq)
+            .cloned()
+            .collect())
+    }
+
+    fn truncate_through(&self, seq: u64) -> Result<()> {
+        self.journal().entries.retain(|entry| entry.seq > seq);
+        Ok(())
+    }
+}
+
+/// Journal kept in its own sqlite database, opened on first use.
+pub struct SqliteEventJournal {
+    path: PathBuf,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl SqliteEventJournal {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            connection: Mutex::new(None),
+        }
+    }
+
+    fn open(&self) -> Result<Connection> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = sqlite::open(&self.path)?;
+        connection.execute(format!(
+            "PRAGMA busy_timeout = {SQLITE_BUSY_TIMEOUT_MS}; PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;"
+        ))?;
+        connection.execute(SCHEMA)?;
+        Ok(connection)
+    }
+
+    fn with_connection<T>(&self, op: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        let mut guard = match self.connection.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if guard.is_none() {
+            *guard = Some(self.open()?);
+        }
+        op(guard.as_ref().expect("sqlite connection opened"))
+    }
+}
+
+impl EventJournal for SqliteEventJournal {
+    fn append(&self, seq: u64, event: &DomainEvent, recorded_at: i64) -> Result<bool> {
+        if !is_journaled(event) {
+            return Ok(false);
+        }
+        let encoded = serde_json::to_string(event)?;
+        self.with_connection(|connection| {
+            let mut stmt = connection.prepare(
+                "INSERT INTO events (seq, recorded_at, event) VALUES (:seq, :recorded_at, :event)",
+            )?;
+            stmt.bind_iter::<_, (_, Value)>([
+                (":seq", (seq as i64).into()),
+                (":recorded_at", recorded_at.into()),
+                (":event", encoded.into()),
+            ])?;
+            let _ = stmt.next()?;
+            Ok(true)
+        })
+    }
+
+    fn last_seq(&self) -> Result<u64> {
+        // AUTOINCREMENT keeps the highest seq in sqlite_sequence even after
+        // the rows are truncated.
+        self.with_connection(|connection| {
+            let mut stmt =
+                connection.prepare("SELECT seq FROM sqlite_sequence WHERE name = 'events'")?;
+            match stmt.next()? {
+                State::Row => Ok(stmt.read::<i64, _>(0)? as u64),
+                State::Done => Ok(0),
+            }
+        })
+    }
+
+    fn replay_since(&self, seq: u64) -> Result<Vec<JournalEntry>> {
+        self.with_connection(|connection| {
+            let mut stmt = connection.prepare(
+                "SELECT seq, recorded_at, event FROM events WHERE seq > :seq ORDER BY seq",
+            )?;
+            stmt.bind((":seq", seq as i64))?;
+            let mut entries = Vec::new();
+            while let State::Row = stmt.next()? {
+                entries.push(JournalEntry {
+                    seq: stmt.read::<i64, _>(0)? as u64,
+                    recorded_at: stmt.read::<i64, _>(1)?,
+                    event: serde_json::from_str(&stmt.read::<String, _>(2)?)?,
+                });
+            }
+            Ok(entries)
+        })
+    }
+
+    fn truncate_through(&self, seq: u64) -> Result<()> {
+        self.with_connection(|connection| {
+            let mut stmt = connection.prepare("DELETE FROM events WHERE seq <= :seq")?;
+            stmt.bind((":seq", seq as i64))?;
+            let _ = stmt.next()?;
+            Ok(())
+        })
+    }
+}
+
+impl fmt::Debug for SqliteEventJournal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteEventJournal")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::events::{ChatId, SendProgress, Typing, UserId};
+    use crate::telegram::send::{SendId, SendStatus};
+
+    fn typing(chat_id: i64) -> DomainEvent {
+        DomainEvent::Typing(Typing {
+            chat_id: ChatId(chat_id),
+            user_id: UserId(chat_id),
+            timestamp: chat_id,
+        })
+    }
+
+    fn exercise(journal: &dyn EventJournal) {
+        assert_eq!(journal.last_seq().expect("last seq"), 0);
+        assert!(journal.append(1, &typing(1), 10).expect("append"));
+        assert!(journal.append(2, &typing(2), 20).expect("append"));
+        let progress = DomainEvent::SendProgress(SendProgress {
+            send_id: SendId(1),
+            status: SendStatus::Sending { attempt: 1 },
+        });
+        assert!(!journal.append(3, &progress, 25).expect("append"));
+        assert!(journal.append(4, &typing(3), 30).expect("append"));
+
+        let replayed = journal.replay_since(1).expect("replay");
+        assert_eq!(
+            replayed.iter().map(|entry| entry.seq).collect::<Vec<_>>(),
+            vec![2, 4]
+        );
+        assert_eq!(replayed[0].event, typing(2));
+        assert_eq!(replayed[0].recorded_at, 20);
+
+        journal.truncate_through(4).expect("truncate");
+        assert!(journal.replay_since(0).expect("replay").is_empty());
+        assert_eq!(journal.last_seq().expect("last seq"), 4);
+    }
+
+    #[test]
+    fn memory_journal_replays_in_order() {
+        exercise(&MemoryEventJournal::new());
+    }
+
+    #[test]
+    fn sqlite_journal_replays_in_order() {
+        exercise(&SqliteEventJournal::new(PathBuf::from(":memory:")));
+    }
+}
@@ -0,0 +1,89 @@
+//! Downloads of message media, separate from the send pipeline so previews
+//! never queue behind outgoing messages.
+
+use std::path::Path;
+
+use grammers_client::Client;
+use grammers_tl_types as tl;
+use tokio::io::AsyncWriteExt;
+
+use crate::telegram::error::{Result, TelegramError};
+use crate::telegram::events::{DocumentFile, PhotoThumbnail};
+
+/// Largest single `upload.getFile` chunk Telegram allows; preview-sized
+/// photos fit in one.
+const THUMBNAIL_DOWNLOAD_LIMIT: i32 = 1024 * 1024;
+/// Chunk size for whole files. Offsets must be multiples of it.
+const DOCUMENT_CHUNK_SIZE: i32 = 512 * 1024;
+
+/// Cheap to clone, so each download can run on its own task.
+#[derive(Clone)]
+pub struct MediaDownloader {
+    client: Client,
+}
+
+impl MediaDownloader {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Image bytes (usually JPEG) of a photo thumbnail, fetched from the
+    /// data center holding the photo.
+    pub async fn download_thumbnail(&self, thumbnail: &PhotoThumbnail) -> Result<Vec<u8>> {
+        let request = tl::functions::upload::GetFile {
+            precise: false,
+            cdn_supported: false,
+            location: tl::types::InputPhotoFileLocation {
+                id: thumbnail.photo_id,
+                access_hash: thumbnail.access_hash,
+                file_reference: thumbnail.file_reference.clone(),
+                thumb_size: thumbnail.size_type.clone(),
+            }
+            .into(),
+            offset: 0,
+            limit: THUMBNAIL_DOWNLOAD_LIMIT,
+        };
+        match self.client.invoke_in_dc(thumbnail.dc_id, &request).await? {
+            tl::enums::upload::File::File(file) => Ok(file.bytes),
+            tl::enums::upload::File::CdnRedirect(_) => Err(TelegramError::CdnRedirect),
+        }
+    }
+
+    /// Downloads a whole document into `destination`, chunk by chunk,
+    /// replacing any file already there.
+    pub async fn download_document(
+        &self,
+        document: &DocumentFile,
+        destination: &Path,
+    ) -> Result<()> {
+        let mut file = tokio::fs::File::create(destination).await?;
+        let mut offset = 0i64;
+        loop {
+            let request = tl::functions::upload::GetFile {
+                precise: false,
+                cdn_supported: false,
+                location: tl::types::InputDocumentFileLocation {
+                    id: document.id,
+                    access_hash: document.access_hash,
+                    file_reference: document.file_reference.clone(),
+                    thumb_size: String::new(),
+                }
+                .into(),
+                offset,
+                limit: DOCUMENT_CHUNK_SIZE,
+            };
+            let bytes = match self.client.invoke_in_dc(document.dc_id, &request).await? {
+                tl::enums::upload::File::File(chunk) => chunk.bytes,
+                tl::enums::upload::File::CdnRedirect(_) => return Err(TelegramError::CdnRedirect),
+            };
+            file.write_all(&bytes).await?;
+            // A short chunk is the last one.
+            if bytes.len() < DOCUMENT_CHUNK_SIZE as usize {
+                break;
+            }
+            offset += i64::from(DOCUMENT_CHUNK_SIZE);
+        }
+        file.flush().await?;
+        Ok(())
+    }
+}
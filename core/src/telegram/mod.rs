@@ -1,28 +1,47 @@
 pub mod auth;
 pub mod bootstrap;
 pub mod cache;
+mod cache_crypto;
+pub mod cache_export;
+#[cfg(feature = "postgres")]
+pub mod cache_postgres;
 pub mod error;
 pub mod events;
+pub mod journal;
+pub mod media;
 pub mod send;
 pub mod updates;
 
 pub use auth::{AuthFlow, AuthResult, PhoneLogin, QrLogin, QrLoginResult};
-pub use bootstrap::{
-    EventDropPolicy, EventStreamConfig, TelegramBootstrap, TelegramConfig, UpdatesConfig,
-};
+pub use bootstrap::{EventStreamConfig, TelegramBootstrap, TelegramConfig, UpdatesConfig};
 pub use cache::{
-    CacheConfig, CacheError, CacheLimits, CacheManager, CacheSnapshot, CacheStore, CachedMessage,
-    ChatPeerKind, ChatSummary, SqliteCacheStore,
+    CacheConfig, CacheDelta, CacheError, CacheLimits, CacheManager, CacheMetrics, CacheSnapshot,
+    CacheStore, CachedMessage, ChatDraft, ChatMember, ChatPeerKind, ChatSummary, DeliveryState,
+    MemoryCacheStore, MessageEmbedding, MessageSearchHit, SqliteCacheStore,
 };
+pub use cache_export::{ExportFormat, ExportRange};
+#[cfg(feature = "postgres")]
+pub use cache_postgres::PostgresCacheStore;
 pub use error::{Result, TelegramError};
 pub use events::{
-    spawn_domain_event_pump, ChatId, DomainEvent, EventMapper, EventReceiver, EventStream,
-    MessageEdited, MessageId, MessageNew, ReadReceipt, Typing, UserId,
+    spawn_domain_event_pump, AudioTranscribed, BatchConfig, ChatFilter, ChatFlagChange, ChatFlags,
+    ChatFlagsChanged, ChatId, ChatMemberUpdate, ConnectionState, ConnectionStatus, DocumentFile,
+    DomainEvent, EventBatch, EventDropPolicy, EventMapper, EventPublisher, EventReceiver,
+    EventStream, EventStreamMetrics, InboxRead, LoggedEvent, MemberChange, MessageEdited,
+    MessageId, MessageMedia, MessageMediaKind, MessageNew, MessageReaction, MessageReactions,
+    MessagesDeleted, OnlineStatus, PhotoThumbnail, PinnedChanged, Presence, ReadReceipt,
+    SendProgress, SequencedEvent, SubscribeOptions, Transcription, Typing, TypingStopped, UserId,
+};
+pub use journal::{
+    EventJournal, JournalEntry, JournalError, MemoryEventJournal, SqliteEventJournal,
 };
+pub use media::MediaDownloader;
 pub use send::{
-    spawn_grammers_send_pipeline, spawn_send_pipeline, SendEnqueueError, SendFailure, SendId,
-    SendPipeline, SendPipelineConfig, SendRequest, SendResult, SendStatus, SendTicket,
+    spawn_grammers_send_pipeline, spawn_send_pipeline, DeadLetter, MediaKind, SendEnqueueError,
+    SendFailure, SendId, SendPipeline, SendPipelineConfig, SendPipelineMetrics, SendRequest,
+    SendResult, SendStatus, SendTicket, UploadProgress,
 };
 pub use updates::{
-    spawn_telegram_update_pump, spawn_update_pump, UpdateEvent, UpdatePump, UpdateSource,
+    spawn_telegram_update_pump, spawn_update_pump, spawn_update_pump_with, ReconnectPolicy,
+    UpdateEvent, UpdatePump, UpdateSource,
 };
@@ -1,7 +1,11 @@
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 use std::time::Duration;
 
 use async_trait::async_trait;
@@ -9,12 +13,18 @@ use grammers_client::types::InputMessage;
 use grammers_client::Client;
 use grammers_mtsender::{InvocationError, RpcError};
 use grammers_session::defs::PeerRef;
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, ReadBuf};
 use tokio::sync::{mpsc, watch, OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinHandle;
+use tokio::time::error::Elapsed;
 use tokio::time::{sleep_until, Instant};
 use tracing::{info, warn};
 
-use crate::telegram::events::MessageId;
+use crate::telegram::events::{
+    AudioTranscribed, ChatId, DomainEvent, EventPublisher, MessageId, SendProgress, Transcription,
+};
 
 #[derive(Debug, Clone)]
 pub struct SendPipelineConfig {
@@ -22,6 +32,9 @@ pub struct SendPipelineConfig {
     pub max_retry_attempts: Option<u32>,
     pub retry_base_delay: Duration,
     pub retry_max_delay: Duration,
+    /// Failed items kept for [`SendPipeline::retry_dead_letter`]; the oldest
+    /// is dropped beyond this.
+    pub dead_letter_limit: usize,
 }
 
 impl Default for SendPipelineConfig {
@@ -31,11 +44,12 @@ impl Default for SendPipelineConfig {
             max_retry_attempts: None,
             retry_base_delay: Duration::from_millis(500),
             retry_max_delay: Duration::from_secs(30),
+            dead_letter_limit: 32,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SendId(pub u64);
 
 #[derive(Debug, Clone)]
@@ -44,24 +58,87 @@ pub enum SendRequest {
         peer: PeerRef,
         text: String,
         reply_to: Option<MessageId>,
+        /// Unix timestamp to post at; Telegram holds the message until then.
+        schedule_date: Option<i64>,
     },
     EditText {
         peer: PeerRef,
         message_id: MessageId,
         text: String,
     },
+    /// Deletes every listed message of `peer` in a single request.
     DeleteMessage {
+        peer: PeerRef,
+        message_ids: Vec<MessageId>,
+    },
+    /// Uploads a local file in chunks and sends it with an optional caption.
+    SendMedia {
+        peer: PeerRef,
+        path: PathBuf,
+        kind: MediaKind,
+        caption: String,
+        reply_to: Option<MessageId>,
+    },
+    /// Forwards `message_ids` from `from_peer` into `peer`.
+    Forward {
+        peer: PeerRef,
+        from_peer: PeerRef,
+        message_ids: Vec<MessageId>,
+    },
+    /// Sets the user's reaction on a message; `None` removes it. Custom
+    /// emoji use the `custom:<document_id>` form of [`MessageReaction`].
+    ///
+    /// [`MessageReaction`]: crate::telegram::events::MessageReaction
+    SetReaction {
+        peer: PeerRef,
+        message_id: MessageId,
+        emoji: Option<String>,
+    },
+    /// Acknowledges incoming messages in `peer` up to and including the id.
+    MarkRead {
+        peer: PeerRef,
+        up_to_message_id: MessageId,
+    },
+    /// Stores the composer text as the cloud draft of `peer`, shared with
+    /// other Telegram clients. Empty text clears it.
+    SaveDraft {
+        peer: PeerRef,
+        text: String,
+        reply_to: Option<MessageId>,
+    },
+    /// Mutes notifications from `peer` indefinitely, or unmutes them.
+    SetChatMuted { peer: PeerRef, muted: bool },
+    /// Pins `peer` to the top of the chat list, or unpins it.
+    SetChatPinned { peer: PeerRef, pinned: bool },
+    /// Asks Telegram for the speech-to-text of a voice message.
+    TranscribeAudio {
         peer: PeerRef,
         message_id: MessageId,
     },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    /// Compressed and shown inline by Telegram clients.
+    Photo,
+    /// Sent as-is, keeping the original file name.
+    Document,
+}
+
 impl SendRequest {
     fn kind(&self) -> &'static str {
         match self {
             Self::SendText { .. } => "send_text",
             Self::EditText { .. } => "edit_text",
             Self::DeleteMessage { .. } => "delete_message",
+            Self::SendMedia { .. } => "send_media",
+            Self::Forward { .. } => "forward",
+            Self::SetReaction { .. } => "set_reaction",
+            Self::MarkRead { .. } => "mark_read",
+            Self::SaveDraft { .. } => "save_draft",
+            Self::SetChatMuted { .. } => "set_chat_muted",
+            Self::SetChatPinned { .. } => "set_chat_pinned",
+            Self::TranscribeAudio { .. } => "transcribe_audio",
         }
     }
 
@@ -69,26 +146,82 @@ impl SendRequest {
         match self {
             Self::SendText { peer, .. }
             | Self::EditText { peer, .. }
-            | Self::DeleteMessage { peer, .. } => peer.id.bot_api_dialog_id(),
+            | Self::DeleteMessage { peer, .. }
+            | Self::SendMedia { peer, .. }
+            | Self::Forward { peer, .. }
+            | Self::SetReaction { peer, .. }
+            | Self::MarkRead { peer, .. }
+            | Self::SaveDraft { peer, .. }
+            | Self::SetChatMuted { peer, .. }
+            | Self::SetChatPinned { peer, .. }
+            | Self::TranscribeAudio { peer, .. } => peer.id.bot_api_dialog_id(),
+        }
+    }
+
+    /// `(peer, message)` of an edit; later edits of it replace queued ones.
+    fn edit_key(&self) -> Option<(i64, MessageId)> {
+        match self {
+            Self::EditText {
+                peer, message_id, ..
+            } => Some((peer.id.bot_api_dialog_id(), *message_id)),
+            _ => None,
         }
     }
+
+    /// Requests that post new messages, which a chat's slowmode throttles.
+    fn posts_message(&self) -> bool {
+        matches!(
+            self,
+            Self::SendText { .. } | Self::SendMedia { .. } | Self::Forward { .. }
+        )
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SendResult {
     MessageSent {
         message_id: MessageId,
     },
+    /// Accepted for a future `schedule_date`; the id belongs to the chat's
+    /// scheduled messages, not its history.
+    MessageScheduled {
+        message_id: MessageId,
+    },
     MessageEdited {
         message_id: MessageId,
     },
     MessageDeleted {
-        message_id: MessageId,
+        message_ids: Vec<MessageId>,
         deleted_count: usize,
     },
+    /// `(source id, new id)` pairs in request order; messages Telegram did
+    /// not forward are left out.
+    MessagesForwarded {
+        forwarded: Vec<(MessageId, MessageId)>,
+    },
+    ReactionSet {
+        message_id: MessageId,
+    },
+    MarkedRead {
+        up_to_message_id: MessageId,
+    },
+    DraftSaved,
+    ChatMuteSet {
+        muted: bool,
+    },
+    ChatPinSet {
+        pinned: bool,
+    },
+    /// Long recordings come back pending; the rest of the text follows as
+    /// [`DomainEvent::AudioTranscribed`] updates.
+    AudioTranscribed(AudioTranscribed),
+    /// Telegram accepted the message without reporting its id, typically
+    /// because an earlier attempt with the same random id already delivered
+    /// it. The real message arrives through the update stream.
+    MessageDelivered,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SendStatus {
     Queued {
         attempt: u32,
@@ -97,42 +230,234 @@ pub enum SendStatus {
     Sending {
         attempt: u32,
     },
+    /// Media upload in progress; `pct` runs from 0 to 100.
+    Uploading {
+        attempt: u32,
+        pct: u8,
+    },
     Sent(SendResult),
     Failed(SendFailure),
+    /// The pipeline stopped before the item was sent.
+    Cancelled,
+}
+
+impl SendStatus {
+    /// `true` once the item will not change status again on its own.
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Sent(_) | Self::Failed(_) | Self::Cancelled)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SendFailure {
     pub error: String,
     pub attempts: u32,
     pub retryable: bool,
 }
 
+/// A request that reached [`SendStatus::Failed`], kept for inspection.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub id: SendId,
+    pub request: SendRequest,
+    pub failure: SendFailure,
+}
+
 #[derive(Debug)]
 pub struct SendTicket {
     pub id: SendId,
     pub status: watch::Receiver<SendStatus>,
 }
 
+impl SendTicket {
+    /// Waits up to `timeout` for a final status. A pipeline that goes away
+    /// before finishing the item yields [`SendStatus::Cancelled`].
+    pub async fn wait_final(&mut self, timeout: Duration) -> Result<SendStatus, Elapsed> {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let current = self.status.borrow_and_update().clone();
+                if current.is_final() {
+                    return current;
+                }
+                if self.status.changed().await.is_err() {
+                    let last = self.status.borrow().clone();
+                    return if last.is_final() {
+                        last
+                    } else {
+                        SendStatus::Cancelled
+                    };
+                }
+            }
+        })
+        .await
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum SendEnqueueError {
     #[error("send queue is full")]
     QueueFull,
     #[error("send pipeline is closed")]
     Closed,
+    #[error("no failed send with id {0:?}")]
+    UnknownDeadLetter(SendId),
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum SendError {
     #[error("invalid message id for {field}: {value}")]
     InvalidMessageId { field: &'static str, value: i64 },
+    #[error("invalid schedule date: {0}")]
+    InvalidScheduleDate(i64),
     #[error("telegram invocation error: {0}")]
     Invocation(#[from] InvocationError),
+    #[error("media upload failed: {0}")]
+    Upload(#[from] io::Error),
 }
 
 #[async_trait]
 pub trait SendTransport: Send + Sync + 'static {
     async fn execute(&self, request: &SendRequest) -> Result<SendResult, SendError>;
+
+    /// Like [`SendTransport::execute`], reporting upload progress for media
+    /// requests. Transports without uploads can rely on the default.
+    async fn execute_with_progress(
+        &self,
+        request: &SendRequest,
+        _progress: &UploadProgress<'_>,
+    ) -> Result<SendResult, SendError> {
+        self.execute(request).await
+    }
+}
+
+/// Publishes [`SendStatus::Uploading`] for the item being executed and
+/// carries its random id.
+#[derive(Debug)]
+pub struct UploadProgress<'a> {
+    status: &'a StatusSink,
+    attempt: u32,
+    random_id: i64,
+}
+
+impl UploadProgress<'_> {
+    /// Stays the same across retries of one item, so Telegram discards a
+    /// resend whose earlier attempt arrived despite a network failure.
+    pub fn random_id(&self) -> i64 {
+        self.random_id
+    }
+
+    pub fn report(&self, uploaded: usize, total: usize) {
+        let pct = if total == 0 {
+            100
+        } else {
+            (uploaded.min(total) as u128 * 100 / total as u128) as u8
+        };
+        let next = SendStatus::Uploading {
+            attempt: self.attempt,
+            pct,
+        };
+        self.status.send_if_changed(next);
+    }
+}
+
+/// Delivers an item's status to its ticket and, when the pipeline was built
+/// with [`SendPipeline::with_status_events`], to domain event subscribers.
+#[derive(Debug)]
+struct StatusSink {
+    id: SendId,
+    ticket: watch::Sender<SendStatus>,
+    events: Option<EventPublisher>,
+    /// Sinks of queued edits this item superseded; they share its outcome.
+    merged: Vec<StatusSink>,
+    counters: Arc<SendCounters>,
+}
+
+impl StatusSink {
+    fn send(&self, status: SendStatus) {
+        self.counters.record(&status);
+        self.deliver(status);
+    }
+
+    /// Sends without counting, so merged edits are not counted twice.
+    fn deliver(&self, status: SendStatus) {
+        for merged in &self.merged {
+            merged.deliver(status.clone());
+        }
+        self.publish(&status);
+        let _ = self.ticket.send(status);
+    }
+
+    fn send_if_changed(&self, status: SendStatus) {
+        if *self.ticket.borrow() == status {
+            return;
+        }
+        self.send(status);
+    }
+
+    fn publish(&self, status: &SendStatus) {
+        if let Some(events) = &self.events {
+            events.try_publish(DomainEvent::SendProgress(SendProgress {
+                send_id: self.id,
+                status: status.clone(),
+            }));
+        }
+    }
+}
+
+/// Point-in-time view of a send pipeline, for metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SendPipelineMetrics {
+    /// Items queued or in flight, counted against
+    /// [`SendPipelineConfig::queue_limit`].
+    pub queued: usize,
+    pub dead_letters: usize,
+    pub attempts: u64,
+    pub sent: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, Default)]
+struct SendCounters {
+    attempts: AtomicU64,
+    sent: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl SendCounters {
+    fn record(&self, status: &SendStatus) {
+        let counter = match status {
+            SendStatus::Sending { .. } => &self.attempts,
+            SendStatus::Sent(_) => &self.sent,
+            SendStatus::Failed(_) => &self.failed,
+            _ => return,
+        };
+        counter.fetch_add(1, AtomicOrdering::Relaxed);
+    }
+}
+
+/// Counts bytes read by the chunked uploader and reports them as progress.
+struct ProgressReader<'a, R> {
+    inner: R,
+    read: usize,
+    total: usize,
+    progress: &'a UploadProgress<'a>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ProgressReader<'_, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = &poll {
+            this.read += buf.filled().len() - before;
+            this.progress.report(this.read, this.total);
+        }
+        poll
+    }
 }
 
 #[derive(Clone)]
@@ -149,20 +474,75 @@ impl GrammersSendTransport {
 #[async_trait]
 impl SendTransport for GrammersSendTransport {
     async fn execute(&self, request: &SendRequest) -> Result<SendResult, SendError> {
+        let (ticket, _) = watch::channel(SendStatus::Sending { attempt: 1 });
+        let status = StatusSink {
+            id: SendId(0),
+            ticket,
+            events: None,
+            merged: Vec::new(),
+            counters: Arc::default(),
+        };
+        let progress = UploadProgress {
+            status: &status,
+            attempt: 1,
+            random_id: generate_random_id(),
+        };
+        self.execute_with_progress(request, &progress).await
+    }
+
+    async fn execute_with_progress(
+        &self,
+        request: &SendRequest,
+        progress: &UploadProgress<'_>,
+    ) -> Result<SendResult, SendError> {
         match request {
             SendRequest::SendText {
                 peer,
                 text,
                 reply_to,
+                schedule_date,
             } => {
                 let reply_to = match reply_to {
                     Some(message_id) => Some(message_id_i32(*message_id, "reply_to")?),
                     None => None,
                 };
-                let input = InputMessage::new().text(text.clone()).reply_to(reply_to);
-                let message = self.client.send_message(*peer, input).await?;
-                Ok(SendResult::MessageSent {
-                    message_id: MessageId(message.id() as i64),
+                let schedule_date = match schedule_date {
+                    Some(date) => Some(
+                        i32::try_from(*date).map_err(|_| SendError::InvalidScheduleDate(*date))?,
+                    ),
+                    None => None,
+                };
+                // Invoked directly so retries reuse the item's random id.
+                let random_id = progress.random_id();
+                let request = tl::functions::messages::SendMessage {
+                    no_webpage: false,
+                    silent: false,
+                    background: false,
+                    clear_draft: false,
+                    noforwards: false,
+                    update_stickersets_order: false,
+                    invert_media: false,
+                    allow_paid_floodskip: false,
+                    peer: (*peer).into(),
+                    reply_to: reply_to.map(input_reply_to),
+                    message: text.clone(),
+                    random_id,
+                    reply_markup: None,
+                    entities: None,
+                    schedule_date,
+                    send_as: None,
+                    quick_reply_shortcut: None,
+                    effect: None,
+                    allow_paid_stars: None,
+                    suggested_post: None,
+                };
+                let updates = self.client.invoke(&request).await?;
+                let message_id = sent_message_id(&updates, random_id)
+                    .map(|message_id| MessageId(message_id as i64));
+                Ok(match (message_id, schedule_date) {
+                    (Some(message_id), Some(_)) => SendResult::MessageScheduled { message_id },
+                    (Some(message_id), None) => SendResult::MessageSent { message_id },
+                    (None, _) => SendResult::MessageDelivered,
                 })
             }
             SendRequest::EditText {
@@ -179,18 +559,323 @@ impl SendTransport for GrammersSendTransport {
                     message_id: *message_id,
                 })
             }
-            SendRequest::DeleteMessage { peer, message_id } => {
-                let message_id_value = message_id_i32(*message_id, "message_id")?;
-                let deleted = self
+            SendRequest::DeleteMessage { peer, message_ids } => {
+                let ids = message_ids
+                    .iter()
+                    .map(|message_id| message_id_i32(*message_id, "message_ids"))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let deleted = self.client.delete_messages(*peer, &ids).await?;
+                Ok(SendResult::MessageDeleted {
+                    message_ids: message_ids.clone(),
+                    deleted_count: deleted,
+                })
+            }
+            SendRequest::SendMedia {
+                peer,
+                path,
+                kind,
+                caption,
+                reply_to,
+            } => {
+                let reply_to = match reply_to {
+                    Some(message_id) => Some(message_id_i32(*message_id, "reply_to")?),
+                    None => None,
+                };
+                let file = tokio::fs::File::open(path).await?;
+                let total = file.metadata().await?.len() as usize;
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "upload".to_string());
+                let mut reader = ProgressReader {
+                    inner: file,
+                    read: 0,
+                    total,
+                    progress,
+                };
+                progress.report(0, total);
+                let uploaded = self
                     .client
-                    .delete_messages(*peer, &[message_id_value])
+                    .upload_stream(&mut reader, total, name.clone())
                     .await?;
-                Ok(SendResult::MessageDeleted {
+                let media: tl::enums::InputMedia = match kind {
+                    MediaKind::Photo => tl::types::InputMediaUploadedPhoto {
+                        spoiler: false,
+                        file: uploaded.raw,
+                        stickers: None,
+                        ttl_seconds: None,
+                    }
+                    .into(),
+                    MediaKind::Document => tl::types::InputMediaUploadedDocument {
+                        nosound_video: false,
+                        force_file: true,
+                        spoiler: false,
+                        file: uploaded.raw,
+                        thumb: None,
+                        mime_type: mime_guess::from_path(path)
+                            .first_or_octet_stream()
+                            .to_string(),
+                        attributes: vec![
+                            tl::types::DocumentAttributeFilename { file_name: name }.into()
+                        ],
+                        stickers: None,
+                        video_cover: None,
+                        video_timestamp: None,
+                        ttl_seconds: None,
+                    }
+                    .into(),
+                };
+                // Invoked directly so retries reuse the item's random id.
+                let random_id = progress.random_id();
+                let request = tl::functions::messages::SendMedia {
+                    silent: false,
+                    background: false,
+                    clear_draft: false,
+                    noforwards: false,
+                    update_stickersets_order: false,
+                    invert_media: false,
+                    allow_paid_floodskip: false,
+                    peer: (*peer).into(),
+                    reply_to: reply_to.map(input_reply_to),
+                    media,
+                    message: caption.clone(),
+                    random_id,
+                    reply_markup: None,
+                    entities: None,
+                    schedule_date: None,
+                    send_as: None,
+                    quick_reply_shortcut: None,
+                    effect: None,
+                    allow_paid_stars: None,
+                    suggested_post: None,
+                };
+                let updates = self.client.invoke(&request).await?;
+                Ok(match sent_message_id(&updates, random_id) {
+                    Some(message_id) => SendResult::MessageSent {
+                        message_id: MessageId(message_id as i64),
+                    },
+                    None => SendResult::MessageDelivered,
+                })
+            }
+            SendRequest::Forward {
+                peer,
+                from_peer,
+                message_ids,
+            } => {
+                let ids = message_ids
+                    .iter()
+                    .map(|message_id| message_id_i32(*message_id, "message_ids"))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let random_ids = forward_random_ids(progress.random_id(), ids.len());
+                // Invoked directly so retries reuse the random ids.
+                let request = tl::functions::messages::ForwardMessages {
+                    silent: false,
+                    background: false,
+                    with_my_score: false,
+                    drop_author: false,
+                    drop_media_captions: false,
+                    noforwards: false,
+                    allow_paid_floodskip: false,
+                    from_peer: (*from_peer).into(),
+                    id: ids,
+                    random_id: random_ids.clone(),
+                    to_peer: (*peer).into(),
+                    top_msg_id: None,
+                    reply_to: None,
+                    schedule_date: None,
+                    send_as: None,
+                    quick_reply_shortcut: None,
+                    video_timestamp: None,
+                    allow_paid_stars: None,
+                    suggested_post: None,
+                };
+                let updates = self.client.invoke(&request).await?;
+                Ok(SendResult::MessagesForwarded {
+                    forwarded: message_ids
+                        .iter()
+                        .zip(random_ids)
+                        .filter_map(|(source, random_id)| {
+                            sent_message_id(&updates, random_id)
+                                .map(|message_id| (*source, MessageId(message_id as i64)))
+                        })
+                        .collect(),
+                })
+            }
+            SendRequest::SetReaction {
+                peer,
+                message_id,
+                emoji,
+            } => {
+                let request = tl::functions::messages::SendReaction {
+                    big: false,
+                    add_to_recent: emoji.is_some(),
+                    peer: (*peer).into(),
+                    msg_id: message_id_i32(*message_id, "message_id")?,
+                    reaction: emoji.as_deref().map(|emoji| vec![reaction_for(emoji)]),
+                };
+                self.client.invoke(&request).await?;
+                Ok(SendResult::ReactionSet {
                     message_id: *message_id,
-                    deleted_count: deleted,
                 })
             }
+            SendRequest::MarkRead {
+                peer,
+                up_to_message_id,
+            } => {
+                let max_id = message_id_i32(*up_to_message_id, "up_to_message_id")?;
+                // Channels and supergroups keep their own read state.
+                match tl::enums::InputPeer::from(*peer) {
+                    tl::enums::InputPeer::Channel(channel) => {
+                        let request = tl::functions::channels::ReadHistory {
+                            channel: tl::enums::InputChannel::Channel(tl::types::InputChannel {
+                                channel_id: channel.channel_id,
+                                access_hash: channel.access_hash,
+                            }),
+                            max_id,
+                        };
+                        self.client.invoke(&request).await?;
+                    }
+                    input_peer => {
+                        let request = tl::functions::messages::ReadHistory {
+                            peer: input_peer,
+                            max_id,
+                        };
+                        self.client.invoke(&request).await?;
+                    }
+                }
+                Ok(SendResult::MarkedRead {
+                    up_to_message_id: *up_to_message_id,
+                })
+            }
+            SendRequest::SaveDraft {
+                peer,
+                text,
+                reply_to,
+            } => {
+                let reply_to = match reply_to {
+                    Some(message_id) => Some(message_id_i32(*message_id, "reply_to")?),
+                    None => None,
+                };
+                let request = tl::functions::messages::SaveDraft {
+                    no_webpage: false,
+                    invert_media: false,
+                    reply_to: reply_to.map(input_reply_to),
+                    peer: (*peer).into(),
+                    message: text.clone(),
+                    entities: None,
+                    media: None,
+                    effect: None,
+                    suggested_post: None,
+                };
+                self.client.invoke(&request).await?;
+                Ok(SendResult::DraftSaved)
+            }
+            SendRequest::SetChatMuted { peer, muted } => {
+                let request = tl::functions::account::UpdateNotifySettings {
+                    peer: tl::enums::InputNotifyPeer::Peer(tl::types::InputNotifyPeer {
+                        peer: (*peer).into(),
+                    }),
+                    settings: tl::enums::InputPeerNotifySettings::Settings(
+                        tl::types::InputPeerNotifySettings {
+                            show_previews: None,
+                            silent: None,
+                            // Telegram reads a mute until the end of time as
+                            // muted for good.
+                            mute_until: Some(if *muted { i32::MAX } else { 0 }),
+                            sound: None,
+                            stories_muted: None,
+                            stories_hide_sender: None,
+                            stories_sound: None,
+                        },
+                    ),
+                };
+                self.client.invoke(&request).await?;
+                Ok(SendResult::ChatMuteSet { muted: *muted })
+            }
+            SendRequest::SetChatPinned { peer, pinned } => {
+                let request = tl::functions::messages::ToggleDialogPin {
+                    pinned: *pinned,
+                    peer: tl::enums::InputDialogPeer::Peer(tl::types::InputDialogPeer {
+                        peer: (*peer).into(),
+                    }),
+                };
+                self.client.invoke(&request).await?;
+                Ok(SendResult::ChatPinSet { pinned: *pinned })
+            }
+            SendRequest::TranscribeAudio { peer, message_id } => {
+                let request = tl::functions::messages::TranscribeAudio {
+                    peer: (*peer).into(),
+                    msg_id: message_id_i32(*message_id, "message_id")?,
+                };
+                let tl::enums::messages::TranscribedAudio::Audio(transcribed) =
+                    self.client.invoke(&request).await?;
+                Ok(SendResult::AudioTranscribed(AudioTranscribed {
+                    chat_id: ChatId(peer.id.bot_api_dialog_id()),
+                    message_id: *message_id,
+                    transcription: Transcription {
+                        text: transcribed.text,
+                        pending: transcribed.pending,
+                    },
+                }))
+            }
+        }
+    }
+}
+
+fn input_reply_to(reply_to_msg_id: i32) -> tl::enums::InputReplyTo {
+    tl::enums::InputReplyTo::Message(tl::types::InputReplyToMessage {
+        reply_to_msg_id,
+        top_msg_id: None,
+        reply_to_peer_id: None,
+        quote_text: None,
+        quote_entities: None,
+        quote_offset: None,
+        monoforum_peer_id: None,
+        todo_item_id: None,
+    })
+}
+
+/// Finds the id Telegram assigned to the message sent with `random_id`.
+fn sent_message_id(updates: &tl::enums::Updates, random_id: i64) -> Option<i32> {
+    let updates = match updates {
+        tl::enums::Updates::UpdateShortSentMessage(sent) => return Some(sent.id),
+        tl::enums::Updates::Updates(updates) => &updates.updates,
+        tl::enums::Updates::Combined(updates) => &updates.updates,
+        _ => return None,
+    };
+    updates.iter().find_map(|update| match update {
+        tl::enums::Update::MessageId(assigned) if assigned.random_id == random_id => {
+            Some(assigned.id)
+        }
+        _ => None,
+    })
+}
+
+/// One random id per forwarded message, derived from the item's so they
+/// stay the same across retries.
+fn forward_random_ids(random_id: i64, count: usize) -> Vec<i64> {
+    (0..count as i64)
+        .map(|offset| random_id.wrapping_add(offset))
+        .collect()
+}
+
+fn generate_random_id() -> i64 {
+    let mut bytes = [0; 8];
+    getrandom::getrandom(&mut bytes).expect("the OS random number generator is available");
+    i64::from_le_bytes(bytes)
+}
+
+fn reaction_for(emoji: &str) -> tl::enums::Reaction {
+    match emoji
+        .strip_prefix("custom:")
+        .and_then(|id| id.parse::<i64>().ok())
+    {
+        Some(document_id) => {
+            tl::enums::Reaction::CustomEmoji(tl::types::ReactionCustomEmoji { document_id })
         }
+        None => tl::enums::Reaction::Emoji(tl::types::ReactionEmoji {
+            emoticon: emoji.to_string(),
+        }),
     }
 }
 
@@ -211,29 +896,57 @@ pub fn spawn_send_pipeline_with_transport(
 ) -> SendPipeline {
     let (tx, rx) = mpsc::channel(config.queue_limit.max(1));
     let (stop_tx, stop_rx) = watch::channel(false);
-    let permits = Arc::new(Semaphore::new(config.queue_limit.max(1)));
+    let (control_tx, control_rx) = watch::channel(SendControl::default());
+    let queue_limit = config.queue_limit.max(1);
+    let permits = Arc::new(Semaphore::new(queue_limit));
     let id_counter = Arc::new(AtomicU64::new(1));
+    let dead_letters = Arc::new(Mutex::new(VecDeque::new()));
 
-    let join = tokio::spawn(run_send_worker(rx, stop_rx, transport, config));
+    let join = tokio::spawn(run_send_worker(
+        rx,
+        stop_rx,
+        control_rx,
+        transport,
+        config,
+        Arc::clone(&dead_letters),
+    ));
 
     SendPipeline {
         tx,
         stop_tx,
+        control_tx,
         join,
         permits,
         id_counter,
+        dead_letters,
+        events: None,
+        queue_limit,
+        counters: Arc::default(),
     }
 }
 
 pub struct SendPipeline {
     tx: mpsc::Sender<SendCommand>,
     stop_tx: watch::Sender<bool>,
+    control_tx: watch::Sender<SendControl>,
     join: JoinHandle<()>,
     permits: Arc<Semaphore>,
     id_counter: Arc<AtomicU64>,
+    dead_letters: Arc<Mutex<VecDeque<DeadLetterEntry>>>,
+    events: Option<EventPublisher>,
+    queue_limit: usize,
+    counters: Arc<SendCounters>,
 }
 
 impl SendPipeline {
+    /// Republishes every status transition of later enqueued items as
+    /// [`DomainEvent::SendProgress`], so the UI and cache can follow outgoing
+    /// messages without holding their tickets.
+    pub fn with_status_events(mut self, events: EventPublisher) -> Self {
+        self.events = Some(events);
+        self
+    }
+
     pub fn enqueue(&self, request: SendRequest) -> Result<SendTicket, SendEnqueueError> {
         let permit = self
             .permits
@@ -248,7 +961,14 @@ impl SendPipeline {
         let command = SendCommand::Enqueue {
             id,
             request,
-            status: status_tx,
+            status: StatusSink {
+                id,
+                ticket: status_tx,
+                events: self.events.clone(),
+                merged: Vec::new(),
+                counters: Arc::clone(&self.counters),
+            },
+            random_id: generate_random_id(),
             permit,
         };
         match self.tx.try_send(command) {
@@ -267,27 +987,179 @@ impl SendPipeline {
         }
     }
 
+    pub fn metrics(&self) -> SendPipelineMetrics {
+        let dead_letters = match self.dead_letters.lock() {
+            Ok(dead_letters) => dead_letters.len(),
+            Err(poisoned) => poisoned.into_inner().len(),
+        };
+        SendPipelineMetrics {
+            queued: self
+                .queue_limit
+                .saturating_sub(self.permits.available_permits()),
+            dead_letters,
+            attempts: self.counters.attempts.load(AtomicOrdering::Relaxed),
+            sent: self.counters.sent.load(AtomicOrdering::Relaxed),
+            failed: self.counters.failed.load(AtomicOrdering::Relaxed),
+        }
+    }
+
+    /// Failed items, oldest first.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        let dead_letters = match self.dead_letters.lock() {
+            Ok(dead_letters) => dead_letters,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        dead_letters
+            .iter()
+            .map(|entry| entry.letter.clone())
+            .collect()
+    }
+
+    /// Queues a failed item again with a fresh attempt budget. Its original
+    /// ticket follows the retry, and the random id is kept so a send that
+    /// did arrive is not duplicated.
+    pub fn retry_dead_letter(&self, id: SendId) -> Result<(), SendEnqueueError> {
+        let permit = self
+            .permits
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| SendEnqueueError::QueueFull)?;
+        let entry = self
+            .take_dead_letter(id)
+            .ok_or(SendEnqueueError::UnknownDeadLetter(id))?;
+        let command = SendCommand::Enqueue {
+            id,
+            request: entry.letter.request,
+            status: entry.status,
+            random_id: entry.random_id,
+            permit,
+        };
+        match self.tx.try_send(command) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let (error, command) = match err {
+                    mpsc::error::TrySendError::Full(command) => {
+                        (SendEnqueueError::QueueFull, command)
+                    }
+                    mpsc::error::TrySendError::Closed(command) => {
+                        (SendEnqueueError::Closed, command)
+                    }
+                };
+                let SendCommand::Enqueue {
+                    request,
+                    status,
+                    random_id,
+                    ..
+                } = command;
+                let mut dead_letters = match self.dead_letters.lock() {
+                    Ok(dead_letters) => dead_letters,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                dead_letters.push_front(DeadLetterEntry {
+                    letter: DeadLetter {
+                        id,
+                        request,
+                        failure: entry.letter.failure,
+                    },
+                    status,
+                    random_id,
+                });
+                Err(error)
+            }
+        }
+    }
+
+    /// Forgets a failed item; its ticket keeps the failed status.
+    pub fn discard_dead_letter(&self, id: SendId) -> Option<DeadLetter> {
+        self.take_dead_letter(id).map(|entry| entry.letter)
+    }
+
+    fn take_dead_letter(&self, id: SendId) -> Option<DeadLetterEntry> {
+        let mut dead_letters = match self.dead_letters.lock() {
+            Ok(dead_letters) => dead_letters,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let index = dead_letters
+            .iter()
+            .position(|entry| entry.letter.id == id)?;
+        dead_letters.remove(index)
+    }
+
+    /// Holds queued items, without spending retry attempts, until
+    /// [`SendPipeline::resume`]. A request already in flight still finishes.
+    pub fn pause(&self) {
+        self.control_tx.send_modify(|control| control.paused = true);
+    }
+
+    pub fn resume(&self) {
+        self.control_tx
+            .send_modify(|control| control.paused = false);
+    }
+
+    /// Also pauses while `online` reads `false`, such as the receiver from
+    /// [`EventStream::connectivity`]. Replaces any earlier receiver, so a
+    /// reconnected stream can take over.
+    ///
+    /// [`EventStream::connectivity`]: crate::telegram::events::EventStream::connectivity
+    pub fn follow_connectivity(&self, online: watch::Receiver<bool>) {
+        self.control_tx
+            .send_modify(|control| control.connectivity = Some(online));
+    }
+
+    /// Applies the retry settings and dead letter limit of `config` to later
+    /// attempts. The queue limit stays as spawned.
+    pub fn retune(&self, config: SendPipelineConfig) {
+        self.control_tx
+            .send_modify(|control| control.retune = Some(config));
+    }
+
     pub async fn stop(self) {
         let _ = self.stop_tx.send(true);
         let _ = self.join.await;
     }
 }
 
+#[derive(Debug, Default)]
+struct SendControl {
+    paused: bool,
+    connectivity: Option<watch::Receiver<bool>>,
+    retune: Option<SendPipelineConfig>,
+}
+
+impl SendControl {
+    fn is_held(&self) -> bool {
+        self.paused
+            || self
+                .connectivity
+                .as_ref()
+                .is_some_and(|online| !*online.borrow())
+    }
+}
+
 #[derive(Debug)]
 enum SendCommand {
     Enqueue {
         id: SendId,
         request: SendRequest,
-        status: watch::Sender<SendStatus>,
+        status: StatusSink,
+        random_id: i64,
         permit: OwnedSemaphorePermit,
     },
 }
 
+#[derive(Debug)]
+struct DeadLetterEntry {
+    letter: DeadLetter,
+    status: StatusSink,
+    random_id: i64,
+}
+
 #[derive(Debug)]
 struct QueueItem {
     id: SendId,
     request: SendRequest,
-    status: watch::Sender<SendStatus>,
+    status: StatusSink,
+    random_id: i64,
     attempts: u32,
     next_attempt: Instant,
     sequence: u64,
@@ -320,51 +1192,98 @@ impl Ord for QueueItem {
 async fn run_send_worker(
     mut rx: mpsc::Receiver<SendCommand>,
     mut stop_rx: watch::Receiver<bool>,
+    mut control_rx: watch::Receiver<SendControl>,
     transport: Arc<dyn SendTransport>,
-    config: SendPipelineConfig,
+    mut config: SendPipelineConfig,
+    dead_letters: Arc<Mutex<VecDeque<DeadLetterEntry>>>,
 ) {
     let mut queue: BinaryHeap<QueueItem> = BinaryHeap::new();
     let mut sequence = 0u64;
+    // Peers whose slowmode blocks new messages until the given instant.
+    let mut slowmode: HashMap<i64, Instant> = HashMap::new();
+    // Only used to wake up; `held` always reads the latest state.
+    let mut online: Option<watch::Receiver<bool>> = None;
 
     loop {
         let next_deadline = queue.peek().map(|item| item.next_attempt);
         let sleep_deadline = next_deadline.unwrap_or_else(Instant::now);
+        let held = control_rx.borrow().is_held();
 
         tokio::select! {
             _ = stop_rx.changed() => {
                 break;
             }
+            changed = control_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                let control = control_rx.borrow_and_update();
+                online = control.connectivity.clone();
+                if let Some(retune) = &control.retune {
+                    config = SendPipelineConfig {
+                        queue_limit: config.queue_limit,
+                        ..retune.clone()
+                    };
+                }
+            }
+            changed = connectivity_changed(&mut online) => {
+                match changed {
+                    Some(is_online) => {
+                        info!(online = is_online, "send pipeline connectivity changed");
+                    }
+                    // The stream ended; its last reported state still holds
+                    // until a new one is followed.
+                    None => online = None,
+                }
+            }
             command = rx.recv() => {
                 let Some(command) = command else {
                     break;
                 };
                 match command {
-                    SendCommand::Enqueue { id, request, status, permit } => {
+                    SendCommand::Enqueue { id, request, status, random_id, permit } => {
                         sequence = sequence.wrapping_add(1);
-                        let _ = status.send(SendStatus::Queued { attempt: 0, next_retry_in: None });
-                        queue.push(QueueItem {
+                        status.send(SendStatus::Queued { attempt: 0, next_retry_in: None });
+                        let mut item = QueueItem {
                             id,
                             request,
                             status,
+                            random_id,
                             attempts: 0,
                             next_attempt: Instant::now(),
                             sequence,
                             _permit: permit,
-                        });
+                        };
+                        coalesce_edits(&mut queue, &mut item);
+                        queue.push(item);
                     }
                 }
             }
-            _ = sleep_until(sleep_deadline), if next_deadline.is_some() => {
+            _ = sleep_until(sleep_deadline), if next_deadline.is_some() && !held => {
                 let now = Instant::now();
                 while queue.peek().is_some_and(|item| item.next_attempt <= now) {
                     let Some(item) = queue.pop() else {
                         break;
                     };
-                    process_queue_item(item, &transport, &config, &mut queue, &mut sequence).await;
+                    process_queue_item(
+                        item,
+                        &transport,
+                        &config,
+                        &mut queue,
+                        &mut sequence,
+                        &dead_letters,
+                        &mut slowmode,
+                    )
+                    .await;
                 }
             }
         }
     }
+
+    // Nothing still queued will be sent now.
+    for item in queue.into_vec() {
+        item.status.send(SendStatus::Cancelled);
+    }
 }
 
 async fn process_queue_item(
@@ -373,10 +1292,41 @@ async fn process_queue_item(
     config: &SendPipelineConfig,
     queue: &mut BinaryHeap<QueueItem>,
     sequence: &mut u64,
+    dead_letters: &Mutex<VecDeque<DeadLetterEntry>>,
+    slowmode: &mut HashMap<i64, Instant>,
 ) {
+    let peer_id = item.request.peer_id();
+    if item.request.posts_message() {
+        match slowmode.get(&peer_id) {
+            Some(until) if *until > Instant::now() => {
+                // Wait out the known cooldown instead of spending an attempt.
+                let delay = *until - Instant::now();
+                item.status.send(SendStatus::Queued {
+                    attempt: item.attempts,
+                    next_retry_in: Some(delay),
+                });
+                info!(
+                    send_id = item.id.0,
+                    peer_id,
+                    delay_ms = delay.as_millis(),
+                    "deferring send until peer slowmode ends"
+                );
+                item.next_attempt = *until;
+                *sequence = sequence.wrapping_add(1);
+                item.sequence = *sequence;
+                queue.push(item);
+                return;
+            }
+            Some(_) => {
+                slowmode.remove(&peer_id);
+            }
+            None => {}
+        }
+    }
+
     let attempt = item.attempts.saturating_add(1);
     item.attempts = attempt;
-    let _ = item.status.send(SendStatus::Sending { attempt });
+    item.status.send(SendStatus::Sending { attempt });
     info!(
         send_id = item.id.0,
         attempt,
@@ -385,9 +1335,21 @@ async fn process_queue_item(
         "sending telegram request"
     );
 
-    match transport.execute(&item.request).await {
+    let progress = UploadProgress {
+        status: &item.status,
+        attempt,
+        random_id: item.random_id,
+    };
+    let outcome = match transport
+        .execute_with_progress(&item.request, &progress)
+        .await
+    {
+        Err(error) if is_already_sent(&error) => Ok(SendResult::MessageDelivered),
+        outcome => outcome,
+    };
+    match outcome {
         Ok(result) => {
-            let _ = item.status.send(SendStatus::Sent(result.clone()));
+            item.status.send(SendStatus::Sent(result.clone()));
             info!(
                 send_id = item.id.0,
                 attempt,
@@ -397,15 +1359,19 @@ async fn process_queue_item(
             );
         }
         Err(error) => {
+            if let Some(delay) = slowmode_delay(&error) {
+                slowmode.insert(peer_id, Instant::now() + delay);
+            }
             let decision = retry_decision(&error, attempt, config);
             match decision {
                 RetryDecision::RetryAfter(delay) => {
                     if exceeded_max_attempts(attempt, config.max_retry_attempts) {
-                        let _ = item.status.send(SendStatus::Failed(SendFailure {
+                        let failure = SendFailure {
                             error: error.to_string(),
                             attempts: attempt,
                             retryable: true,
-                        }));
+                        };
+                        item.status.send(SendStatus::Failed(failure.clone()));
                         warn!(
                             send_id = item.id.0,
                             attempt,
@@ -414,9 +1380,10 @@ async fn process_queue_item(
                             error = %error,
                             "send pipeline exceeded retry attempts"
                         );
+                        push_dead_letter(dead_letters, item, failure, config.dead_letter_limit);
                         return;
                     }
-                    let _ = item.status.send(SendStatus::Queued {
+                    item.status.send(SendStatus::Queued {
                         attempt,
                         next_retry_in: Some(delay),
                     });
@@ -435,11 +1402,12 @@ async fn process_queue_item(
                     queue.push(item);
                 }
                 RetryDecision::Fail { retryable } => {
-                    let _ = item.status.send(SendStatus::Failed(SendFailure {
+                    let failure = SendFailure {
                         error: error.to_string(),
                         attempts: attempt,
                         retryable,
-                    }));
+                    };
+                    item.status.send(SendStatus::Failed(failure.clone()));
                     warn!(
                         send_id = item.id.0,
                         attempt,
@@ -448,12 +1416,81 @@ async fn process_queue_item(
                         error = %error,
                         "failed to send telegram request"
                     );
+                    push_dead_letter(dead_letters, item, failure, config.dead_letter_limit);
                 }
             }
         }
     }
 }
 
+/// Folds queued edits of the same message into `item`, which carries the
+/// latest text. Superseded tickets resolve with its outcome, and it keeps the
+/// latest schedule among them so pending rate limits are still honoured.
+fn coalesce_edits(queue: &mut BinaryHeap<QueueItem>, item: &mut QueueItem) {
+    let Some(key) = item.request.edit_key() else {
+        return;
+    };
+    if !queue
+        .iter()
+        .any(|queued| queued.request.edit_key() == Some(key))
+    {
+        return;
+    }
+    let (superseded, kept): (Vec<_>, Vec<_>) = std::mem::take(queue)
+        .into_vec()
+        .into_iter()
+        .partition(|queued| queued.request.edit_key() == Some(key));
+    *queue = kept.into();
+    for older in superseded {
+        info!(
+            send_id = older.id.0,
+            superseded_by = item.id.0,
+            "coalescing queued edit"
+        );
+        item.next_attempt = item.next_attempt.max(older.next_attempt);
+        item.status.merged.push(older.status);
+    }
+}
+
+fn push_dead_letter(
+    dead_letters: &Mutex<VecDeque<DeadLetterEntry>>,
+    item: QueueItem,
+    failure: SendFailure,
+    limit: usize,
+) {
+    if limit == 0 {
+        return;
+    }
+    let mut dead_letters = match dead_letters.lock() {
+        Ok(dead_letters) => dead_letters,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    while dead_letters.len() >= limit {
+        dead_letters.pop_front();
+    }
+    dead_letters.push_back(DeadLetterEntry {
+        letter: DeadLetter {
+            id: item.id,
+            request: item.request,
+            failure,
+        },
+        status: item.status,
+        random_id: item.random_id,
+    });
+}
+
+/// Waits for the next connectivity change; `None` once its sender is gone.
+/// Never resolves while no receiver is followed.
+async fn connectivity_changed(online: &mut Option<watch::Receiver<bool>>) -> Option<bool> {
+    match online {
+        Some(receiver) => match receiver.changed().await {
+            Ok(()) => Some(*receiver.borrow_and_update()),
+            Err(_) => None,
+        },
+        None => std::future::pending().await,
+    }
+}
+
 fn exceeded_max_attempts(attempt: u32, max_attempts: Option<u32>) -> bool {
     match max_attempts {
         Some(max) => attempt >= max,
@@ -468,9 +1505,24 @@ enum RetryDecision {
 
 fn retry_decision(error: &SendError, attempt: u32, config: &SendPipelineConfig) -> RetryDecision {
     match error {
-        SendError::InvalidMessageId { .. } => RetryDecision::Fail { retryable: false },
+        SendError::InvalidMessageId { .. } | SendError::InvalidScheduleDate(_) => {
+            RetryDecision::Fail { retryable: false }
+        }
+        // A missing or unreadable file will not appear by retrying.
+        SendError::Upload(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+            ) =>
+        {
+            RetryDecision::Fail { retryable: false }
+        }
+        SendError::Upload(_) => RetryDecision::RetryAfter(backoff_delay(attempt, config)),
         SendError::Invocation(err) => match err {
             InvocationError::Rpc(rpc) => {
+                if is_permanent_rpc_error(rpc) {
+                    return RetryDecision::Fail { retryable: false };
+                }
                 if let Some(delay) = rate_limit_delay(rpc) {
                     return RetryDecision::RetryAfter(delay);
                 }
@@ -492,6 +1544,39 @@ fn retry_decision(error: &SendError, attempt: u32, config: &SendPipelineConfig)
     }
 }
 
+/// Telegram rejects a reused random id once the message it belongs to was
+/// delivered, which after a retry means an earlier attempt succeeded.
+fn is_already_sent(error: &SendError) -> bool {
+    matches!(
+        error,
+        SendError::Invocation(InvocationError::Rpc(rpc)) if rpc.name == "RANDOM_ID_DUPLICATE"
+    )
+}
+
+/// Errors caused by the request itself, which no retry can fix even when
+/// Telegram reports them with an unusual code.
+fn is_permanent_rpc_error(rpc: &RpcError) -> bool {
+    matches!(
+        rpc.name.as_str(),
+        "REACTION_INVALID"
+            | "REACTION_EMPTY"
+            | "REACTIONS_TOO_MANY"
+            | "MESSAGE_ID_INVALID"
+            | "SCHEDULE_DATE_INVALID"
+            | "SCHEDULE_DATE_TOO_LATE"
+            | "SCHEDULE_TOO_MUCH"
+    )
+}
+
+fn slowmode_delay(error: &SendError) -> Option<Duration> {
+    match error {
+        SendError::Invocation(InvocationError::Rpc(rpc)) if rpc.name == "SLOWMODE_WAIT" => {
+            rate_limit_delay(rpc)
+        }
+        _ => None,
+    }
+}
+
 fn rate_limit_delay(rpc: &RpcError) -> Option<Duration> {
     match rpc.name.as_str() {
         "FLOOD_WAIT" | "SLOWMODE_WAIT" | "FLOOD_PREMIUM_WAIT" => rpc
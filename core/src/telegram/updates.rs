@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
 use tokio::sync::{mpsc, watch};
 use tokio::task::JoinHandle;
@@ -10,6 +12,15 @@ use crate::telegram::error::{Result, TelegramError};
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UpdateEvent<U, E> {
     Update(U),
+    /// The source failed and the pump will reconnect after `delay`.
+    Reconnecting {
+        error: E,
+        attempt: u32,
+        delay: Duration,
+    },
+    /// The source was recreated after one or more failures.
+    Reconnected,
+    /// The source failed and the pump gave up; nothing follows.
     Error(E),
 }
 
@@ -19,9 +30,58 @@ pub trait UpdateSource: Send + 'static {
     type Error: Send + 'static;
 
     async fn next_update(&mut self) -> std::result::Result<Self::Update, Self::Error>;
+
+    /// Recreates the underlying stream after `next_update` failed. Sources
+    /// that recover by simply being polled again keep the default.
+    async fn reconnect(&mut self) -> std::result::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Exponential backoff between reconnect attempts of an update pump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Consecutive failures tolerated before the pump gives up; `None`
+    /// retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    /// Gives up on the first error.
+    pub fn disabled() -> Self {
+        Self {
+            max_attempts: Some(0),
+            ..Self::default()
+        }
+    }
+
+    fn delay(&self, attempt: u32) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| attempt > max) {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        Some(
+            self.initial_backoff
+                .saturating_mul(factor)
+                .min(self.max_backoff),
+        )
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
 }
 
 pub struct GrammersUpdateSource {
+    client: Client,
     inner: grammers_client::client::updates::UpdateStream,
 }
 
@@ -32,6 +92,7 @@ impl GrammersUpdateSource {
         configuration: UpdatesConfiguration,
     ) -> Self {
         Self {
+            client: client.clone(),
             inner: client.stream_updates(updates, configuration),
         }
     }
@@ -45,6 +106,13 @@ impl UpdateSource for GrammersUpdateSource {
     async fn next_update(&mut self) -> std::result::Result<Self::Update, Self::Error> {
         self.inner.next().await
     }
+
+    /// Succeeds once Telegram answers again. The stream itself outlives a
+    /// dropped connection: the sender reconnects underneath it and it
+    /// fetches the difference for the gap on the next poll.
+    async fn reconnect(&mut self) -> std::result::Result<(), Self::Error> {
+        self.client.get_me().await.map(drop)
+    }
 }
 
 pub struct UpdatePump<U, E> {
@@ -70,7 +138,18 @@ impl<U, E> UpdatePump<U, E> {
     }
 }
 
-pub fn spawn_update_pump<S>(mut source: S, buffer: usize) -> UpdatePump<S::Update, S::Error>
+pub fn spawn_update_pump<S>(source: S, buffer: usize) -> UpdatePump<S::Update, S::Error>
+where
+    S: UpdateSource,
+{
+    spawn_update_pump_with(source, buffer, ReconnectPolicy::default())
+}
+
+pub fn spawn_update_pump_with<S>(
+    mut source: S,
+    buffer: usize,
+    policy: ReconnectPolicy,
+) -> UpdatePump<S::Update, S::Error>
 where
     S: UpdateSource,
 {
@@ -78,24 +157,48 @@ where
     let (stop_tx, mut stop_rx) = watch::channel(false);
 
     let join = tokio::spawn(async move {
+        let mut failures = 0u32;
         loop {
-            tokio::select! {
-                _ = stop_rx.changed() => {
-                    break;
-                }
-                update = source.next_update() => {
-                    match update {
-                        Ok(update) => {
-                            if tx.send(UpdateEvent::Update(update)).await.is_err() {
-                                break;
-                            }
-                        }
-                        Err(err) => {
-                            let _ = tx.send(UpdateEvent::Error(err)).await;
-                            break;
-                        }
+            let result = tokio::select! {
+                _ = stop_rx.changed() => break,
+                result = source.next_update() => result,
+            };
+            let mut error = match result {
+                Ok(update) => {
+                    failures = 0;
+                    if tx.send(UpdateEvent::Update(update)).await.is_err() {
+                        break;
                     }
+                    continue;
+                }
+                Err(err) => err,
+            };
+            // Reconnect until the source comes back or the policy gives up.
+            loop {
+                failures += 1;
+                let Some(delay) = policy.delay(failures) else {
+                    let _ = tx.send(UpdateEvent::Error(error)).await;
+                    return;
+                };
+                let reconnecting = UpdateEvent::Reconnecting {
+                    error,
+                    attempt: failures,
+                    delay,
+                };
+                if tx.send(reconnecting).await.is_err() {
+                    return;
+                }
+                tokio::select! {
+                    _ = stop_rx.changed() => return,
+                    _ = tokio::time::sleep(delay) => {}
                 }
+                match source.reconnect().await {
+                    Ok(()) => break,
+                    Err(err) => error = err,
+                }
+            }
+            if tx.send(UpdateEvent::Reconnected).await.is_err() {
+                break;
             }
         }
     });
@@ -112,9 +215,10 @@ pub fn spawn_telegram_update_pump(
     updates: mpsc::UnboundedReceiver<UpdatesLike>,
     configuration: UpdatesConfiguration,
     buffer: usize,
+    reconnect: ReconnectPolicy,
 ) -> UpdatePump<grammers_client::Update, grammers_mtsender::InvocationError> {
     let source = GrammersUpdateSource::new(client, updates, configuration);
-    spawn_update_pump(source, buffer)
+    spawn_update_pump_with(source, buffer, reconnect)
 }
 
 pub fn take_updates(
@@ -130,12 +234,15 @@ mod tests {
 
     struct MockUpdateSource {
         queue: VecDeque<std::result::Result<&'static str, &'static str>>,
+        /// Results of the reconnect attempts; succeeds once they run out.
+        reconnects: VecDeque<std::result::Result<(), &'static str>>,
     }
 
     impl MockUpdateSource {
         fn new(items: Vec<std::result::Result<&'static str, &'static str>>) -> Self {
             Self {
                 queue: items.into(),
+                reconnects: VecDeque::new(),
             }
         }
     }
@@ -148,12 +255,16 @@ mod tests {
         async fn next_update(&mut self) -> std::result::Result<Self::Update, Self::Error> {
             self.queue.pop_front().unwrap_or(Err("end"))
         }
+
+        async fn reconnect(&mut self) -> std::result::Result<(), Self::Error> {
+            self.reconnects.pop_front().unwrap_or(Ok(()))
+        }
     }
 
     #[tokio::test]
     async fn update_pump_forwards_events() {
         let source = MockUpdateSource::new(vec![Ok("one"), Ok("two"), Err("boom")]);
-        let mut pump = spawn_update_pump(source, 4);
+        let mut pump = spawn_update_pump_with(source, 4, ReconnectPolicy::disabled());
 
         let first = pump.receiver().recv().await;
         assert_eq!(first, Some(UpdateEvent::Update("one")));
@@ -166,4 +277,70 @@ mod tests {
 
         pump.stop().await;
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_pump_reconnects_with_backoff() {
+        let source = MockUpdateSource::new(vec![Ok("one"), Err("boom"), Err("again"), Ok("two")]);
+        let mut pump = spawn_update_pump(source, 4);
+
+        assert_eq!(
+            pump.receiver().recv().await,
+            Some(UpdateEvent::Update("one"))
+        );
+        assert_eq!(
+            pump.receiver().recv().await,
+            Some(UpdateEvent::Reconnecting {
+                error: "boom",
+                attempt: 1,
+                delay: Duration::from_secs(1),
+            })
+        );
+        assert_eq!(pump.receiver().recv().await, Some(UpdateEvent::Reconnected));
+        assert_eq!(
+            pump.receiver().recv().await,
+            Some(UpdateEvent::Reconnecting {
+                error: "again",
+                attempt: 2,
+                delay: Duration::from_secs(2),
+            })
+        );
+        assert_eq!(pump.receiver().recv().await, Some(UpdateEvent::Reconnected));
+        assert_eq!(
+            pump.receiver().recv().await,
+            Some(UpdateEvent::Update("two"))
+        );
+
+        pump.stop().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_pump_retries_until_the_source_reconnects() {
+        let mut source = MockUpdateSource::new(vec![Err("boom"), Ok("back")]);
+        source.reconnects = VecDeque::from([Err("offline")]);
+        let mut pump = spawn_update_pump(source, 4);
+
+        assert_eq!(
+            pump.receiver().recv().await,
+            Some(UpdateEvent::Reconnecting {
+                error: "boom",
+                attempt: 1,
+                delay: Duration::from_secs(1),
+            })
+        );
+        assert_eq!(
+            pump.receiver().recv().await,
+            Some(UpdateEvent::Reconnecting {
+                error: "offline",
+                attempt: 2,
+                delay: Duration::from_secs(2),
+            })
+        );
+        assert_eq!(pump.receiver().recv().await, Some(UpdateEvent::Reconnected));
+        assert_eq!(
+            pump.receiver().recv().await,
+            Some(UpdateEvent::Update("back"))
+        );
+
+        pump.stop().await;
+    }
 }
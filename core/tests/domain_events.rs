@@ -3,9 +3,52 @@ use grammers_client::Update;
 use grammers_session::updates::State;
 use grammers_tl_types as tl;
 use telegram_llm_core::telegram::{
-    ChatId, DomainEvent, EventMapper, EventReceiver, MessageId, ReadReceipt, Typing, UserId,
+    spawn_domain_event_pump, spawn_update_pump, spawn_update_pump_with, BatchConfig, ChatFilter,
+    ChatFlagChange, ChatFlagsChanged, ChatId, ChatMemberUpdate, ConnectionStatus, DocumentFile,
+    DomainEvent, EventDropPolicy, EventJournal, EventMapper, EventPublisher, EventStreamConfig,
+    InboxRead, MemberChange, MemoryEventJournal, MessageId, MessageMedia, MessageMediaKind,
+    MessageReaction, MessagesDeleted, OnlineStatus, PinnedChanged, Presence, ReadReceipt,
+    ReconnectPolicy, SendId, SendProgress, SendStatus, SequencedEvent, SubscribeOptions, Typing,
+    TypingStopped, UpdateSource, UserId,
 };
 
+/// Fails on the first poll, as a dropped connection does.
+struct DisconnectedSource;
+
+#[async_trait::async_trait]
+impl UpdateSource for DisconnectedSource {
+    type Update = Update;
+    type Error = grammers_mtsender::InvocationError;
+
+    async fn next_update(&mut self) -> Result<Self::Update, Self::Error> {
+        Err(grammers_mtsender::InvocationError::Dropped)
+    }
+}
+
+/// Yields each scripted update once, then waits forever.
+struct ScriptedSource(Vec<Update>);
+
+#[async_trait::async_trait]
+impl UpdateSource for ScriptedSource {
+    type Update = Update;
+    type Error = grammers_mtsender::InvocationError;
+
+    async fn next_update(&mut self) -> Result<Self::Update, Self::Error> {
+        match self.0.pop() {
+            Some(update) => Ok(update),
+            None => std::future::pending().await,
+        }
+    }
+}
+
+fn stream_config() -> EventStreamConfig {
+    EventStreamConfig {
+        buffer_size: 4,
+        heartbeat_interval: None,
+        ..EventStreamConfig::default()
+    }
+}
+
 fn state_with_date(date: i32) -> State {
     State {
         date,
@@ -102,6 +145,134 @@ fn maps_new_message_update() {
     }
 }
 
+#[test]
+fn logged_events_show_message_text_only_with_log_content() {
+    let message = base_message(1001, 1001, 42, 111, "hello");
+    let update = tl::types::UpdateNewMessage {
+        message: tl::enums::Message::Message(message),
+        pts: 1,
+        pts_count: 1,
+    };
+    let update = wrap_raw_update(tl::enums::Update::NewMessage(update), state_with_date(999));
+    let mut event = EventMapper::new()
+        .map_update(&update)
+        .expect("expected domain event");
+    if let DomainEvent::MessageNew(message) = &mut event {
+        message.media = Some(MessageMedia {
+            kind: MessageMediaKind::Document,
+            size: None,
+            thumbnail: None,
+            duration: None,
+            document: Some(DocumentFile {
+                id: 1,
+                access_hash: 2,
+                dc_id: 2,
+                mime_type: "application/pdf".to_string(),
+                file_reference: Vec::new(),
+                file_name: Some("salaries.pdf".to_string()),
+            }),
+            transcription: None,
+        });
+    }
+
+    assert_eq!(format!("{:?}", event.for_log(true)), format!("{event:?}"));
+    let redacted = format!("{:?}", event.for_log(false));
+    assert!(!redacted.contains("hello"), "{redacted}");
+    assert!(!redacted.contains("salaries"), "{redacted}");
+    assert!(redacted.contains("<5 chars redacted>"), "{redacted}");
+    assert!(redacted.contains("message_id: MessageId(42)"), "{redacted}");
+}
+
+#[test]
+fn maps_message_ttl_period() {
+    let mapper = EventMapper::new();
+    let mut message = base_message(1001, 1001, 43, 111, "self-destruct");
+    message.ttl_period = Some(86_400);
+    let update = tl::types::UpdateNewMessage {
+        message: tl::enums::Message::Message(message),
+        pts: 1,
+        pts_count: 1,
+    };
+    let update = wrap_raw_update(tl::enums::Update::NewMessage(update), state_with_date(999));
+
+    match mapper.map_update(&update) {
+        Some(DomainEvent::MessageNew(payload)) => {
+            assert_eq!(payload.ttl_period, Some(86_400));
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
+#[test]
+fn maps_message_reactions_update() {
+    let mapper = EventMapper::new();
+    let reaction = |reaction: tl::enums::Reaction, count: i32, chosen_order: Option<i32>| {
+        tl::enums::ReactionCount::Count(tl::types::ReactionCount {
+            chosen_order,
+            reaction,
+            count,
+        })
+    };
+    let update = tl::types::UpdateMessageReactions {
+        peer: peer_user(1001),
+        msg_id: 42,
+        top_msg_id: None,
+        saved_peer_id: None,
+        reactions: tl::enums::MessageReactions::Reactions(tl::types::MessageReactions {
+            min: false,
+            can_see_list: false,
+            reactions_as_tags: false,
+            results: vec![
+                reaction(
+                    tl::enums::Reaction::Emoji(tl::types::ReactionEmoji {
+                        emoticon: "👍".to_string(),
+                    }),
+                    2,
+                    Some(0),
+                ),
+                reaction(
+                    tl::enums::Reaction::CustomEmoji(tl::types::ReactionCustomEmoji {
+                        document_id: 77,
+                    }),
+                    1,
+                    None,
+                ),
+                reaction(tl::enums::Reaction::Paid, 5, None),
+            ],
+            recent_reactions: None,
+            top_reactors: None,
+        }),
+    };
+    let update = wrap_raw_update(
+        tl::enums::Update::MessageReactions(update),
+        state_with_date(500),
+    );
+
+    match mapper.map_update(&update) {
+        Some(DomainEvent::MessageReactions(payload)) => {
+            assert_eq!(payload.chat_id, ChatId(1001));
+            assert_eq!(payload.message_id, MessageId(42));
+            assert_eq!(payload.timestamp, 500);
+            assert_eq!(
+                payload.reactions,
+                vec![
+                    MessageReaction {
+                        emoji: "👍".to_string(),
+                        count: 2,
+                        mine: true,
+                    },
+                    MessageReaction {
+                        emoji: "custom:77".to_string(),
+                        count: 1,
+                        mine: false,
+                    },
+                ]
+            );
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
 #[test]
 fn maps_edited_message_update() {
     let mapper = EventMapper::new();
@@ -128,6 +299,36 @@ fn maps_edited_message_update() {
     }
 }
 
+#[test]
+fn maps_outgoing_channel_posts() {
+    let mapper = EventMapper::new();
+    let mut message = base_message(0, 0, 9, 300, "announcement");
+    message.out = true;
+    message.post = true;
+    message.from_id = None;
+    message.peer_id = tl::enums::Peer::Channel(tl::types::PeerChannel { channel_id: 3003 });
+    let update = tl::types::UpdateNewChannelMessage {
+        message: tl::enums::Message::Message(message),
+        pts: 3,
+        pts_count: 1,
+    };
+    let update = wrap_raw_update(
+        tl::enums::Update::NewChannelMessage(update),
+        state_with_date(999),
+    );
+
+    let event = mapper.map_update(&update).expect("expected domain event");
+    match event {
+        DomainEvent::MessageNew(payload) => {
+            assert!(payload.chat_id.0 < 0);
+            assert_eq!(payload.author_id, UserId(payload.chat_id.0));
+            assert_eq!(payload.text, "announcement");
+            assert!(payload.outgoing);
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
 #[test]
 fn maps_read_receipt_update() {
     let mapper = EventMapper::new();
@@ -159,6 +360,58 @@ fn maps_read_receipt_update() {
     }
 }
 
+#[test]
+fn maps_inbox_reads_apart_from_outbox_receipts() {
+    let mapper = EventMapper::new();
+    let private = tl::types::UpdateReadHistoryInbox {
+        folder_id: None,
+        peer: peer_user(2001),
+        top_msg_id: None,
+        max_id: 80,
+        still_unread_count: 2,
+        pts: 11,
+        pts_count: 1,
+    };
+    let channel = tl::types::UpdateReadChannelInbox {
+        folder_id: None,
+        channel_id: 5001,
+        max_id: 90,
+        still_unread_count: 0,
+        pts: 12,
+    };
+    let topic = tl::types::UpdateReadHistoryInbox {
+        top_msg_id: Some(7),
+        ..private.clone()
+    };
+
+    let events: Vec<_> = [
+        tl::enums::Update::ReadHistoryInbox(private),
+        tl::enums::Update::ReadChannelInbox(channel),
+        tl::enums::Update::ReadHistoryInbox(topic),
+    ]
+    .into_iter()
+    .map(|update| mapper.map_update(&wrap_raw_update(update, state_with_date(444))))
+    .collect();
+    assert_eq!(
+        events,
+        vec![
+            Some(DomainEvent::InboxRead(InboxRead {
+                chat_id: ChatId(2001),
+                timestamp: 444,
+                last_read_message_id: MessageId(80),
+                still_unread: 2,
+            })),
+            Some(DomainEvent::InboxRead(InboxRead {
+                chat_id: ChatId(-1_000_000_005_001),
+                timestamp: 444,
+                last_read_message_id: MessageId(90),
+                still_unread: 0,
+            })),
+            None,
+        ]
+    );
+}
+
 #[test]
 fn maps_typing_update() {
     let mapper = EventMapper::new();
@@ -184,10 +437,260 @@ fn maps_typing_update() {
     }
 }
 
+#[test]
+fn maps_user_status_update() {
+    let mapper = EventMapper::new();
+    let update = tl::types::UpdateUserStatus {
+        user_id: 4001,
+        status: tl::enums::UserStatus::Offline(tl::types::UserStatusOffline { was_online: 90 }),
+    };
+    let update = wrap_raw_update(tl::enums::Update::UserStatus(update), state_with_date(100));
+
+    let event = mapper.map_update(&update).expect("expected domain event");
+    assert_eq!(
+        event,
+        DomainEvent::OnlineStatus(OnlineStatus {
+            user_id: UserId(4001),
+            presence: Presence::Offline { last_seen: 90 },
+            timestamp: 100,
+        })
+    );
+}
+
+#[test]
+fn maps_pinned_message_updates() {
+    let mapper = EventMapper::new();
+    let update = tl::types::UpdatePinnedMessages {
+        pinned: true,
+        peer: peer_user(77),
+        messages: vec![12, 9],
+        pts: 4,
+        pts_count: 1,
+    };
+    let update = wrap_raw_update(
+        tl::enums::Update::PinnedMessages(update),
+        state_with_date(1),
+    );
+    assert_eq!(
+        mapper.map_update(&update),
+        Some(DomainEvent::PinnedChanged(PinnedChanged {
+            chat_id: ChatId(77),
+            message_ids: vec![MessageId(12), MessageId(9)],
+            pinned: true,
+        }))
+    );
+
+    let update = tl::types::UpdatePinnedChannelMessages {
+        pinned: false,
+        channel_id: 3003,
+        messages: vec![5],
+        pts: 6,
+        pts_count: 1,
+    };
+    let update = wrap_raw_update(
+        tl::enums::Update::PinnedChannelMessages(update),
+        state_with_date(2),
+    );
+    let Some(DomainEvent::PinnedChanged(change)) = mapper.map_update(&update) else {
+        panic!("expected a pin change");
+    };
+    assert!(change.chat_id.0 < 0);
+    assert_eq!(change.message_ids, vec![MessageId(5)]);
+    assert!(!change.pinned);
+}
+
+#[test]
+fn maps_message_deletions() {
+    let mapper = EventMapper::new();
+    let update = tl::types::UpdateDeleteMessages {
+        messages: vec![4, 5],
+        pts: 9,
+        pts_count: 2,
+    };
+    let update = wrap_raw_update(
+        tl::enums::Update::DeleteMessages(update),
+        state_with_date(1),
+    );
+    assert_eq!(
+        mapper.map_update(&update),
+        Some(DomainEvent::MessagesDeleted(MessagesDeleted {
+            chat_id: None,
+            message_ids: vec![MessageId(4), MessageId(5)],
+        }))
+    );
+
+    let update = tl::types::UpdateDeleteChannelMessages {
+        channel_id: 3003,
+        messages: vec![6],
+        pts: 10,
+        pts_count: 1,
+    };
+    let update = wrap_raw_update(
+        tl::enums::Update::DeleteChannelMessages(update),
+        state_with_date(2),
+    );
+    let Some(DomainEvent::MessagesDeleted(deleted)) = mapper.map_update(&update) else {
+        panic!("expected a deletion");
+    };
+    assert!(deleted.chat_id.is_some_and(|chat_id| chat_id.0 < 0));
+    assert_eq!(deleted.message_ids, vec![MessageId(6)]);
+}
+
+#[test]
+fn maps_participant_updates() {
+    let mapper = EventMapper::new();
+    let added = tl::types::UpdateChatParticipantAdd {
+        chat_id: 50,
+        user_id: 7,
+        inviter_id: 8,
+        date: 120,
+        version: 1,
+    };
+    let update = wrap_raw_update(
+        tl::enums::Update::ChatParticipantAdd(added),
+        state_with_date(130),
+    );
+    let Some(DomainEvent::ChatMemberUpdate(joined)) = mapper.map_update(&update) else {
+        panic!("expected a member update");
+    };
+    assert!(joined.chat_id.0 < 0);
+    assert_eq!(joined.user_id, UserId(7));
+    assert_eq!(
+        joined.change,
+        MemberChange::Joined {
+            inviter_id: Some(UserId(8))
+        }
+    );
+    assert_eq!(joined.timestamp, 120);
+
+    let promoted = tl::types::UpdateChatParticipantAdmin {
+        chat_id: 50,
+        user_id: 7,
+        is_admin: true,
+        version: 2,
+    };
+    let update = wrap_raw_update(
+        tl::enums::Update::ChatParticipantAdmin(promoted),
+        state_with_date(140),
+    );
+    assert_eq!(
+        mapper.map_update(&update),
+        Some(DomainEvent::ChatMemberUpdate(ChatMemberUpdate {
+            chat_id: joined.chat_id,
+            user_id: UserId(7),
+            change: MemberChange::Admin(true),
+            timestamp: 140,
+        }))
+    );
+}
+
+#[test]
+fn maps_chat_flag_updates() {
+    let mapper = EventMapper::new();
+    let pinned = wrap_raw_update(
+        tl::enums::Update::DialogPinned(tl::types::UpdateDialogPinned {
+            pinned: true,
+            folder_id: None,
+            peer: tl::enums::DialogPeer::Peer(tl::types::DialogPeer { peer: peer_user(7) }),
+        }),
+        state_with_date(10),
+    );
+    assert_eq!(
+        mapper.map_update(&pinned),
+        Some(DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+            changes: vec![(ChatId(7), ChatFlagChange::Pinned(true))],
+        }))
+    );
+
+    let folder_peer = |user_id: i64, folder_id: i32| {
+        tl::enums::FolderPeer::Peer(tl::types::FolderPeer {
+            peer: peer_user(user_id),
+            folder_id,
+        })
+    };
+    let archived = wrap_raw_update(
+        tl::enums::Update::FolderPeers(tl::types::UpdateFolderPeers {
+            folder_peers: vec![folder_peer(7, 1), folder_peer(8, 0)],
+            pts: 0,
+            pts_count: 0,
+        }),
+        state_with_date(10),
+    );
+    assert_eq!(
+        mapper.map_update(&archived),
+        Some(DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+            changes: vec![
+                (ChatId(7), ChatFlagChange::Folder(Some(1))),
+                (ChatId(8), ChatFlagChange::Folder(None)),
+            ],
+        }))
+    );
+}
+
+#[test]
+fn maps_synced_dialogs_to_chat_flags() {
+    let dialog = |user_id: i64, pinned: bool, mute_until: Option<i32>, folder_id: Option<i32>| {
+        tl::enums::Dialog::Dialog(tl::types::Dialog {
+            pinned,
+            unread_mark: false,
+            view_forum_as_messages: false,
+            peer: peer_user(user_id),
+            top_message: 1,
+            read_inbox_max_id: 1,
+            read_outbox_max_id: 1,
+            unread_count: 0,
+            unread_mentions_count: 0,
+            unread_reactions_count: 0,
+            notify_settings: tl::enums::PeerNotifySettings::Settings(
+                tl::types::PeerNotifySettings {
+                    show_previews: None,
+                    silent: None,
+                    mute_until,
+                    ios_sound: None,
+                    android_sound: None,
+                    other_sound: None,
+                    stories_muted: None,
+                    stories_hide_sender: None,
+                    stories_ios_sound: None,
+                    stories_android_sound: None,
+                    stories_other_sound: None,
+                },
+            ),
+            pts: None,
+            draft: None,
+            folder_id,
+            ttl_period: None,
+        })
+    };
+    let dialogs = [
+        dialog(7, true, None, None),
+        dialog(8, false, Some(200), Some(1)),
+        // Muted until a time already past.
+        dialog(9, false, Some(50), None),
+    ];
+
+    assert_eq!(
+        EventMapper::new().map_dialogs(&dialogs, 100),
+        DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+            changes: vec![
+                (ChatId(7), ChatFlagChange::Pinned(true)),
+                (ChatId(7), ChatFlagChange::Muted(false)),
+                (ChatId(7), ChatFlagChange::Folder(None)),
+                (ChatId(8), ChatFlagChange::Pinned(false)),
+                (ChatId(8), ChatFlagChange::Muted(true)),
+                (ChatId(8), ChatFlagChange::Folder(Some(1))),
+                (ChatId(9), ChatFlagChange::Pinned(false)),
+                (ChatId(9), ChatFlagChange::Muted(false)),
+                (ChatId(9), ChatFlagChange::Folder(None)),
+            ],
+        })
+    );
+}
+
 #[tokio::test]
 async fn drops_oldest_when_buffer_full() {
-    let (sender, receiver) = tokio::sync::broadcast::channel(2);
-    let mut receiver = EventReceiver::from_receiver(receiver);
+    let publisher = EventPublisher::new(EventDropPolicy::DropOldest, 2);
+    let mut receiver = publisher.subscribe();
 
     let first = DomainEvent::Typing(Typing {
         chat_id: ChatId(1),
@@ -205,20 +708,22 @@ async fn drops_oldest_when_buffer_full() {
         timestamp: 3,
     });
 
-    let _ = sender.send(first);
-    let _ = sender.send(second);
-    let _ = sender.send(third);
+    publisher.publish(first).await;
+    publisher.publish(second).await;
+    publisher.publish(third).await;
 
     match receiver.recv().await {
         Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
         other => panic!("expected lagged receiver error, got: {other:?}"),
     }
+    assert!(receiver.take_gap());
+    assert!(!receiver.take_gap());
 }
 
 #[tokio::test]
 async fn warns_on_lagged_subscriber() {
-    let (sender, receiver) = tokio::sync::broadcast::channel(1);
-    let mut receiver = EventReceiver::from_receiver(receiver);
+    let publisher = EventPublisher::new(EventDropPolicy::DropOldest, 1);
+    let mut receiver = publisher.subscribe();
 
     let first = DomainEvent::Typing(Typing {
         chat_id: ChatId(10),
@@ -231,16 +736,20 @@ async fn warns_on_lagged_subscriber() {
         timestamp: 11,
     });
 
-    let _ = sender.send(first);
-    let _ = sender.send(second);
+    publisher.publish(first).await;
+    publisher.publish(second).await;
 
     match receiver.recv().await {
         Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
         other => panic!("expected lagged receiver error, got: {other:?}"),
     }
 
-    let next = receiver.recv().await.expect("expected next event");
-    match next {
+    let next = receiver
+        .recv_sequenced()
+        .await
+        .expect("expected next event");
+    assert_eq!(next.seq, 2);
+    match next.event {
         DomainEvent::Typing(payload) => {
             assert_eq!(payload.chat_id, ChatId(11));
         }
@@ -248,6 +757,189 @@ async fn warns_on_lagged_subscriber() {
     }
 }
 
+fn typing(chat_id: i64) -> DomainEvent {
+    DomainEvent::Typing(Typing {
+        chat_id: ChatId(chat_id),
+        user_id: UserId(chat_id),
+        timestamp: chat_id,
+    })
+}
+
+fn typing_chat(event: DomainEvent) -> i64 {
+    match event {
+        DomainEvent::Typing(payload) => payload.chat_id.0,
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn drop_newest_keeps_buffered_events() {
+    let publisher = EventPublisher::new(EventDropPolicy::DropNewest, 2);
+    let mut receiver = publisher.subscribe();
+
+    for chat_id in 1..=3 {
+        publisher.publish(typing(chat_id)).await;
+    }
+
+    assert_eq!(typing_chat(receiver.recv().await.expect("first")), 1);
+    assert_eq!(typing_chat(receiver.recv().await.expect("second")), 2);
+    assert!(receiver.take_gap());
+    publisher.publish(typing(4)).await;
+    let fourth = receiver.recv_sequenced().await.expect("fourth");
+    assert_eq!(fourth.seq, 4);
+    assert_eq!(typing_chat(fourth.event), 4);
+    assert!(!receiver.take_gap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn block_producer_waits_for_slow_subscribers() {
+    let publisher = EventPublisher::new(EventDropPolicy::BlockProducer, 1);
+    let mut receiver = publisher.subscribe();
+
+    publisher.publish(typing(1)).await;
+    let blocked = publisher.clone();
+    let mut pending = tokio::spawn(async move { blocked.publish(typing(2)).await });
+    assert!(
+        tokio::time::timeout(std::time::Duration::from_secs(1), &mut pending)
+            .await
+            .is_err(),
+        "publish should wait while the subscriber is full"
+    );
+
+    assert_eq!(typing_chat(receiver.recv().await.expect("first")), 1);
+    pending.await.expect("publish completes");
+    assert_eq!(typing_chat(receiver.recv().await.expect("second")), 2);
+
+    drop(receiver);
+    publisher.publish(typing(3)).await;
+}
+
+#[tokio::test]
+async fn lossy_subscribers_do_not_block_the_producer() {
+    let publisher = EventPublisher::new(EventDropPolicy::BlockProducer, 4);
+    let mut interactive = publisher.subscribe();
+    let mut exporter = publisher.subscribe_with(SubscribeOptions {
+        buffer: Some(1),
+        drop_policy: Some(EventDropPolicy::DropNewest),
+        ..SubscribeOptions::default()
+    });
+
+    for chat_id in 1..=3 {
+        publisher.publish(typing(chat_id)).await;
+    }
+
+    for chat_id in 1..=3 {
+        assert_eq!(
+            typing_chat(interactive.recv().await.expect("event")),
+            chat_id
+        );
+    }
+    assert!(!interactive.take_gap());
+    assert_eq!(typing_chat(exporter.recv().await.expect("first")), 1);
+    assert!(exporter.take_gap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn batches_events_arriving_within_the_window() {
+    let publisher = EventPublisher::new(EventDropPolicy::DropOldest, 16);
+    let mut receiver = publisher.subscribe();
+    let config = BatchConfig {
+        window: std::time::Duration::from_millis(5),
+        max_events: 3,
+    };
+
+    for chat_id in 1..=4 {
+        publisher.publish(typing(chat_id)).await;
+    }
+    let batch = receiver.recv_batch(config).await.expect("first batch");
+    assert_eq!(
+        batch
+            .events
+            .into_iter()
+            .map(typing_chat)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+
+    let late = publisher.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        late.publish(typing(5)).await;
+    });
+    let batch = receiver.recv_batch(config).await.expect("second batch");
+    assert_eq!(
+        batch
+            .events
+            .into_iter()
+            .map(typing_chat)
+            .collect::<Vec<_>>(),
+        vec![4]
+    );
+
+    drop(publisher);
+    let batch = receiver.recv_batch(config).await.expect("last batch");
+    assert_eq!(batch.events.len(), 1);
+}
+
+#[tokio::test]
+async fn filtered_subscriptions_only_see_their_chats() {
+    for policy in [EventDropPolicy::DropOldest, EventDropPolicy::DropNewest] {
+        let publisher = EventPublisher::new(policy, 4);
+        let mut everything = publisher.subscribe();
+        let mut filtered = publisher.subscribe_filtered(ChatFilter::new([ChatId(2)]));
+
+        for chat_id in 1..=3 {
+            publisher.publish(typing(chat_id)).await;
+        }
+        publisher
+            .publish(DomainEvent::ChatFlagsChanged(ChatFlagsChanged {
+                changes: vec![(ChatId(2), ChatFlagChange::Pinned(true))],
+            }))
+            .await;
+
+        assert_eq!(typing_chat(everything.recv().await.expect("first")), 1);
+        assert_eq!(typing_chat(filtered.recv().await.expect("chat 2")), 2);
+        assert!(matches!(
+            filtered.recv().await.expect("flags"),
+            DomainEvent::ChatFlagsChanged(_)
+        ));
+    }
+}
+
+#[test]
+fn domain_events_round_trip_through_json() {
+    let events = vec![
+        typing(1),
+        DomainEvent::OnlineStatus(OnlineStatus {
+            user_id: UserId(2),
+            presence: Presence::Offline { last_seen: 30 },
+            timestamp: 31,
+        }),
+        DomainEvent::ChatMemberUpdate(ChatMemberUpdate {
+            chat_id: ChatId(-3),
+            user_id: UserId(4),
+            change: MemberChange::Joined {
+                inviter_id: Some(UserId(5)),
+            },
+            timestamp: 40,
+        }),
+        DomainEvent::SendProgress(SendProgress {
+            send_id: SendId(6),
+            status: SendStatus::Queued {
+                attempt: 2,
+                next_retry_in: Some(std::time::Duration::from_millis(1500)),
+            },
+        }),
+    ];
+
+    for event in events {
+        let sequenced = SequencedEvent { seq: 9, event };
+        let encoded = serde_json::to_string(&sequenced).expect("encode");
+        let decoded: SequencedEvent = serde_json::from_str(&encoded).expect("decode");
+        assert_eq!(decoded, sequenced);
+    }
+}
+
 #[test]
 fn ignores_unsupported_updates() {
     let mapper = EventMapper::new();
@@ -255,3 +947,239 @@ fn ignores_unsupported_updates() {
 
     assert!(mapper.map_update(&update).is_none());
 }
+
+#[tokio::test]
+async fn transport_errors_mark_the_stream_offline() {
+    let stream =
+        spawn_domain_event_pump(spawn_update_pump(DisconnectedSource, 4), &stream_config())
+            .expect("event stream");
+    let mut online = stream.connectivity();
+
+    if *online.borrow_and_update() {
+        online.changed().await.expect("offline signal");
+    }
+    assert!(!*online.borrow());
+
+    stream.stop().await;
+}
+
+fn connection_status(event: DomainEvent) -> ConnectionStatus {
+    match event {
+        DomainEvent::ConnectionState(state) => state.status,
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn publishes_connection_state_changes() {
+    let stream =
+        spawn_domain_event_pump(spawn_update_pump(DisconnectedSource, 4), &stream_config())
+            .expect("event stream");
+    let mut receiver = stream.subscribe();
+
+    let ConnectionStatus::Reconnecting { attempt, since } =
+        connection_status(receiver.recv().await.expect("reconnecting"))
+    else {
+        panic!("expected reconnecting");
+    };
+    assert_eq!(attempt, 1);
+    assert!(since > 0);
+    assert_eq!(
+        connection_status(receiver.recv().await.expect("reconnected")),
+        ConnectionStatus::Connected
+    );
+    stream.stop().await;
+
+    let stream = spawn_domain_event_pump(
+        spawn_update_pump_with(DisconnectedSource, 4, ReconnectPolicy::disabled()),
+        &stream_config(),
+    )
+    .expect("event stream");
+    let mut receiver = stream.subscribe();
+    assert!(matches!(
+        connection_status(receiver.recv().await.expect("offline")),
+        ConnectionStatus::Offline { .. }
+    ));
+    stream.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn repeats_connection_state_as_a_heartbeat() {
+    let config = EventStreamConfig {
+        heartbeat_interval: Some(std::time::Duration::from_secs(10)),
+        ..stream_config()
+    };
+    let stream = spawn_domain_event_pump(spawn_update_pump(ScriptedSource(Vec::new()), 4), &config)
+        .expect("event stream");
+    let mut receiver = stream.subscribe();
+
+    let started = tokio::time::Instant::now();
+    for _ in 0..2 {
+        assert_eq!(
+            connection_status(receiver.recv().await.expect("heartbeat")),
+            ConnectionStatus::Connected
+        );
+    }
+    assert!(started.elapsed() >= std::time::Duration::from_secs(20));
+
+    stream.stop().await;
+}
+
+#[tokio::test]
+async fn raw_update_tap_forwards_unmapped_updates() {
+    let source = ScriptedSource(vec![wrap_raw_update(
+        tl::enums::Update::Config,
+        state_with_date(1),
+    )]);
+    let config = EventStreamConfig {
+        raw_update_buffer: Some(4),
+        ..stream_config()
+    };
+    let mut stream =
+        spawn_domain_event_pump(spawn_update_pump(source, 4), &config).expect("event stream");
+    let mut raw_updates = stream.take_raw_updates().expect("raw tap enabled");
+    assert!(stream.take_raw_updates().is_none());
+
+    match raw_updates.recv().await.expect("raw update") {
+        Update::Raw(raw) => assert!(matches!(raw.raw, tl::enums::Update::Config)),
+        other => panic!("unexpected update: {other:?}"),
+    }
+
+    stream.stop().await;
+}
+
+#[tokio::test]
+async fn journals_mapped_events_before_publishing() {
+    let typing_update = tl::types::UpdateUserTyping {
+        user_id: 3001,
+        top_msg_id: None,
+        action: tl::enums::SendMessageAction::SendMessageTypingAction,
+    };
+    let source = ScriptedSource(vec![wrap_raw_update(
+        tl::enums::Update::UserTyping(typing_update),
+        state_with_date(5),
+    )]);
+    let journal = std::sync::Arc::new(MemoryEventJournal::new());
+    let config = EventStreamConfig {
+        journal: Some(journal.clone()),
+        ..stream_config()
+    };
+    let stream =
+        spawn_domain_event_pump(spawn_update_pump(source, 4), &config).expect("event stream");
+    let mut receiver = stream.subscribe();
+
+    let event = receiver.recv().await.expect("mapped event");
+    let replayed = journal.replay_since(0).expect("replay");
+    assert_eq!(replayed.len(), 1);
+    assert_eq!(replayed[0].seq, 1);
+    assert_eq!(replayed[0].event, event);
+
+    stream.stop().await;
+}
+
+#[tokio::test]
+async fn journal_replays_from_the_seq_of_a_live_event() {
+    let typing_update = |user_id| {
+        wrap_raw_update(
+            tl::enums::Update::UserTyping(tl::types::UpdateUserTyping {
+                user_id,
+                top_msg_id: None,
+                action: tl::enums::SendMessageAction::SendMessageTypingAction,
+            }),
+            state_with_date(5),
+        )
+    };
+    let source = ScriptedSource(vec![typing_update(3002), typing_update(3001)]);
+    // A journal left behind by an earlier run.
+    let journal = std::sync::Arc::new(MemoryEventJournal::new());
+    journal.append(7, &typing(1), 1).expect("append");
+    let config = EventStreamConfig {
+        journal: Some(journal.clone()),
+        ..stream_config()
+    };
+    let stream =
+        spawn_domain_event_pump(spawn_update_pump(source, 4), &config).expect("event stream");
+    let mut receiver = stream.subscribe();
+
+    let first = receiver.recv_sequenced().await.expect("first event");
+    let second = receiver.recv_sequenced().await.expect("second event");
+    assert_eq!((first.seq, second.seq), (8, 9));
+    let replayed = journal.replay_since(first.seq).expect("replay");
+    assert_eq!(replayed.len(), 1);
+    assert_eq!(replayed[0].seq, second.seq);
+    assert_eq!(replayed[0].event, second.event);
+
+    // Events that are never journaled still take a seq.
+    stream
+        .publisher()
+        .try_publish(DomainEvent::SendProgress(SendProgress {
+            send_id: SendId(1),
+            status: SendStatus::Sending { attempt: 1 },
+        }));
+    assert_eq!(receiver.recv_sequenced().await.expect("progress").seq, 10);
+    assert_eq!(journal.last_seq().expect("last seq"), 9);
+
+    stream.stop().await;
+}
+
+#[tokio::test]
+async fn counts_received_and_unsupported_updates() {
+    let typing_update = tl::types::UpdateUserTyping {
+        user_id: 3001,
+        top_msg_id: None,
+        action: tl::enums::SendMessageAction::SendMessageTypingAction,
+    };
+    let source = ScriptedSource(vec![
+        wrap_raw_update(
+            tl::enums::Update::UserTyping(typing_update),
+            state_with_date(5),
+        ),
+        wrap_raw_update(tl::enums::Update::Config, state_with_date(4)),
+    ]);
+    let stream = spawn_domain_event_pump(spawn_update_pump(source, 4), &stream_config())
+        .expect("event stream");
+    let mut receiver = stream.subscribe();
+
+    receiver.recv().await.expect("mapped event");
+    let metrics = stream.metrics();
+    assert_eq!(metrics.updates_received, 2);
+    assert_eq!(metrics.events_mapped, 1);
+    assert_eq!(metrics.updates_unsupported, 1);
+    assert_eq!(metrics.subscriber_backlog, vec![0]);
+
+    stream.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn typing_indicators_expire() {
+    let typing_update = tl::types::UpdateUserTyping {
+        user_id: 3001,
+        top_msg_id: None,
+        action: tl::enums::SendMessageAction::SendMessageTypingAction,
+    };
+    let source = ScriptedSource(vec![wrap_raw_update(
+        tl::enums::Update::UserTyping(typing_update),
+        state_with_date(5),
+    )]);
+    let stream = spawn_domain_event_pump(spawn_update_pump(source, 4), &stream_config())
+        .expect("event stream");
+    let mut receiver = stream.subscribe();
+
+    assert!(matches!(
+        receiver.recv().await.expect("typing"),
+        DomainEvent::Typing(_)
+    ));
+    let started = tokio::time::Instant::now();
+    match receiver.recv().await.expect("typing stopped") {
+        DomainEvent::TypingStopped(TypingStopped {
+            chat_id, user_id, ..
+        }) => {
+            assert_eq!(chat_id, ChatId(3001));
+            assert_eq!(user_id, UserId(3001));
+        }
+        other => panic!("unexpected event: {other:?}"),
+    }
+    assert!(started.elapsed() >= std::time::Duration::from_secs(5));
+
+    stream.stop().await;
+}
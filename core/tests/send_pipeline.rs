@@ -7,9 +7,11 @@ use grammers_mtsender::{InvocationError, RpcError};
 use grammers_session::defs::{PeerAuth, PeerId, PeerRef};
 use telegram_llm_core::telegram::send::{SendError, SendTransport};
 use telegram_llm_core::telegram::{
-    spawn_send_pipeline, MessageId, SendEnqueueError, SendPipelineConfig, SendRequest, SendResult,
-    SendStatus,
+    spawn_send_pipeline, AudioTranscribed, ChatId, DomainEvent, EventDropPolicy, EventPublisher,
+    MediaKind, MessageId, SendEnqueueError, SendPipelineConfig, SendPipelineMetrics, SendRequest,
+    SendResult, SendStatus, Transcription, UploadProgress,
 };
+use tokio::sync::Notify;
 
 #[derive(Clone)]
 struct MockTransport {
@@ -32,6 +34,93 @@ impl SendTransport for MockTransport {
     }
 }
 
+/// Reports half of an upload, then waits for the test before finishing.
+struct UploadingTransport {
+    release: Arc<Notify>,
+}
+
+#[async_trait]
+impl SendTransport for UploadingTransport {
+    async fn execute(&self, _request: &SendRequest) -> Result<SendResult, SendError> {
+        unreachable!("the pipeline reports progress")
+    }
+
+    async fn execute_with_progress(
+        &self,
+        _request: &SendRequest,
+        progress: &UploadProgress<'_>,
+    ) -> Result<SendResult, SendError> {
+        progress.report(512, 1024);
+        self.release.notified().await;
+        Ok(SendResult::MessageSent {
+            message_id: MessageId(9),
+        })
+    }
+}
+
+/// Records every executed request and answers edits, deletions, drafts,
+/// mutes, pins and transcriptions.
+struct RecordingTransport {
+    calls: Arc<Mutex<Vec<SendRequest>>>,
+}
+
+#[async_trait]
+impl SendTransport for RecordingTransport {
+    async fn execute(&self, request: &SendRequest) -> Result<SendResult, SendError> {
+        self.calls.lock().unwrap().push(request.clone());
+        match request {
+            SendRequest::EditText { message_id, .. } => Ok(SendResult::MessageEdited {
+                message_id: *message_id,
+            }),
+            SendRequest::SaveDraft { .. } => Ok(SendResult::DraftSaved),
+            SendRequest::DeleteMessage { message_ids, .. } => Ok(SendResult::MessageDeleted {
+                message_ids: message_ids.clone(),
+                deleted_count: message_ids.len(),
+            }),
+            SendRequest::SetChatMuted { muted, .. } => {
+                Ok(SendResult::ChatMuteSet { muted: *muted })
+            }
+            SendRequest::SetChatPinned { pinned, .. } => {
+                Ok(SendResult::ChatPinSet { pinned: *pinned })
+            }
+            SendRequest::TranscribeAudio { message_id, .. } => {
+                Ok(SendResult::AudioTranscribed(AudioTranscribed {
+                    chat_id: ChatId(test_peer().id.bot_api_dialog_id()),
+                    message_id: *message_id,
+                    transcription: Transcription {
+                        text: "hello".to_string(),
+                        pending: false,
+                    },
+                }))
+            }
+            other => panic!("unexpected request {other:?}"),
+        }
+    }
+}
+
+/// Records the random id of every attempt and replays scripted responses.
+struct RandomIdTransport {
+    random_ids: Arc<Mutex<Vec<i64>>>,
+    responses: Mutex<VecDeque<Result<SendResult, SendError>>>,
+}
+
+#[async_trait]
+impl SendTransport for RandomIdTransport {
+    async fn execute(&self, _request: &SendRequest) -> Result<SendResult, SendError> {
+        unreachable!("the pipeline passes the random id")
+    }
+
+    async fn execute_with_progress(
+        &self,
+        _request: &SendRequest,
+        progress: &UploadProgress<'_>,
+    ) -> Result<SendResult, SendError> {
+        self.random_ids.lock().unwrap().push(progress.random_id());
+        let mut guard = self.responses.lock().unwrap();
+        guard.pop_front().expect("missing mock transport response")
+    }
+}
+
 fn test_peer() -> PeerRef {
     PeerRef {
         id: PeerId::user(123),
@@ -44,6 +133,7 @@ fn send_request() -> SendRequest {
         peer: test_peer(),
         text: "hello".to_string(),
         reply_to: None,
+        schedule_date: None,
     }
 }
 
@@ -85,6 +175,7 @@ async fn retries_on_rate_limit_then_succeeds() {
         max_retry_attempts: Some(3),
         retry_base_delay: Duration::from_millis(10),
         retry_max_delay: Duration::from_millis(1000),
+        dead_letter_limit: 8,
     };
     let pipeline = spawn_send_pipeline(transport, config);
 
@@ -138,6 +229,7 @@ async fn fails_after_max_retry_attempts() {
         max_retry_attempts: Some(2),
         retry_base_delay: Duration::from_millis(5),
         retry_max_delay: Duration::from_millis(5),
+        dead_letter_limit: 8,
     };
     let pipeline = spawn_send_pipeline(transport, config);
 
@@ -169,6 +261,50 @@ async fn fails_after_max_retry_attempts() {
         }
         other => panic!("expected failed status, got {other:?}"),
     }
+    assert_eq!(
+        pipeline.metrics(),
+        SendPipelineMetrics {
+            queued: 0,
+            dead_letters: 1,
+            attempts: 2,
+            sent: 0,
+            failed: 1,
+        }
+    );
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn retuned_retry_limit_applies_to_later_attempts() {
+    let error = SendError::Invocation(InvocationError::Io(std::io::Error::other("boom")));
+    let transport = MockTransport::new(vec![Err(error)]);
+    let config = SendPipelineConfig {
+        queue_limit: 2,
+        max_retry_attempts: None,
+        retry_base_delay: Duration::from_millis(5),
+        retry_max_delay: Duration::from_millis(5),
+        dead_letter_limit: 8,
+    };
+    let pipeline = spawn_send_pipeline(transport, config.clone());
+    pipeline.retune(SendPipelineConfig {
+        max_retry_attempts: Some(1),
+        ..config
+    });
+    tokio::time::advance(Duration::from_millis(1)).await;
+
+    let ticket = pipeline.enqueue(send_request()).expect("enqueue");
+    let mut status_rx = ticket.status;
+    tokio::time::advance(Duration::from_millis(1)).await;
+    let failed = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Failed(_))
+    })
+    .await;
+
+    match failed {
+        SendStatus::Failed(failure) => assert_eq!(failure.attempts, 1),
+        other => panic!("expected failed status, got {other:?}"),
+    }
 
     pipeline.stop().await;
 }
@@ -184,6 +320,7 @@ async fn rejects_enqueue_when_queue_full() {
         max_retry_attempts: Some(1),
         retry_base_delay: Duration::from_millis(1),
         retry_max_delay: Duration::from_millis(1),
+        dead_letter_limit: 8,
     };
     let pipeline = spawn_send_pipeline(transport, config);
 
@@ -208,6 +345,7 @@ async fn invalid_message_ids_fail_without_retry() {
         max_retry_attempts: Some(3),
         retry_base_delay: Duration::from_millis(5),
         retry_max_delay: Duration::from_millis(5),
+        dead_letter_limit: 8,
     };
     let pipeline = spawn_send_pipeline(transport, config);
 
@@ -230,3 +368,703 @@ async fn invalid_message_ids_fail_without_retry() {
 
     pipeline.stop().await;
 }
+
+#[tokio::test]
+async fn reports_upload_progress_for_media() {
+    let release = Arc::new(Notify::new());
+    let transport = UploadingTransport {
+        release: Arc::clone(&release),
+    };
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let ticket = pipeline
+        .enqueue(SendRequest::SendMedia {
+            peer: test_peer(),
+            path: "photo.jpg".into(),
+            kind: MediaKind::Photo,
+            caption: "look".to_string(),
+            reply_to: None,
+        })
+        .expect("enqueue");
+    let mut status_rx = ticket.status;
+
+    let uploading = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Uploading { .. })
+    })
+    .await;
+    assert_eq!(
+        uploading,
+        SendStatus::Uploading {
+            attempt: 1,
+            pct: 50
+        }
+    );
+
+    release.notify_one();
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+    assert_eq!(
+        sent,
+        SendStatus::Sent(SendResult::MessageSent {
+            message_id: MessageId(9)
+        })
+    );
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn forward_results_map_new_message_ids() {
+    let responses = vec![Ok(SendResult::MessagesForwarded {
+        forwarded: vec![(MessageId(3), MessageId(40)), (MessageId(5), MessageId(41))],
+    })];
+    let transport = MockTransport::new(responses);
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let ticket = pipeline
+        .enqueue(SendRequest::Forward {
+            peer: test_peer(),
+            from_peer: PeerRef {
+                id: PeerId::user(456),
+                auth: PeerAuth::default(),
+            },
+            message_ids: vec![MessageId(3), MessageId(5)],
+        })
+        .expect("enqueue");
+    let mut status_rx = ticket.status;
+
+    tokio::time::advance(Duration::from_millis(1)).await;
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+    match sent {
+        SendStatus::Sent(SendResult::MessagesForwarded { forwarded }) => {
+            assert_eq!(forwarded[1], (MessageId(5), MessageId(41)));
+        }
+        other => panic!("expected forwarded result, got {other:?}"),
+    }
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn invalid_reactions_fail_without_retry() {
+    let rpc_error = RpcError {
+        code: 400,
+        name: "REACTION_INVALID".to_string(),
+        value: None,
+        caused_by: None,
+    };
+    let responses = vec![Err(SendError::Invocation(InvocationError::Rpc(rpc_error)))];
+    let transport = MockTransport::new(responses);
+    let config = SendPipelineConfig {
+        queue_limit: 2,
+        max_retry_attempts: Some(3),
+        retry_base_delay: Duration::from_millis(5),
+        retry_max_delay: Duration::from_millis(5),
+        dead_letter_limit: 8,
+    };
+    let pipeline = spawn_send_pipeline(transport, config);
+
+    let ticket = pipeline
+        .enqueue(SendRequest::SetReaction {
+            peer: test_peer(),
+            message_id: MessageId(12),
+            emoji: Some("🦀".to_string()),
+        })
+        .expect("enqueue");
+    let mut status_rx = ticket.status;
+
+    tokio::time::advance(Duration::from_millis(1)).await;
+    let failed = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Failed(_))
+    })
+    .await;
+
+    match failed {
+        SendStatus::Failed(failure) => {
+            assert_eq!(failure.attempts, 1);
+            assert!(!failure.retryable);
+        }
+        other => panic!("expected failed status, got {other:?}"),
+    }
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn mark_read_retries_through_rate_limits() {
+    let rpc_error = RpcError {
+        code: 420,
+        name: "FLOOD_WAIT".to_string(),
+        value: Some(2),
+        caused_by: None,
+    };
+    let responses = vec![
+        Err(SendError::Invocation(InvocationError::Rpc(rpc_error))),
+        Ok(SendResult::MarkedRead {
+            up_to_message_id: MessageId(30),
+        }),
+    ];
+    let transport = MockTransport::new(responses);
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let ticket = pipeline
+        .enqueue(SendRequest::MarkRead {
+            peer: test_peer(),
+            up_to_message_id: MessageId(30),
+        })
+        .expect("enqueue");
+    let mut status_rx = ticket.status;
+
+    tokio::time::advance(Duration::from_millis(1)).await;
+    let _ = wait_for_status(&mut status_rx, |status| {
+        matches!(
+            status,
+            SendStatus::Queued {
+                attempt: 1,
+                next_retry_in: Some(_)
+            }
+        )
+    })
+    .await;
+
+    tokio::time::advance(Duration::from_secs(2)).await;
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+    assert_eq!(
+        sent,
+        SendStatus::Sent(SendResult::MarkedRead {
+            up_to_message_id: MessageId(30)
+        })
+    );
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn deletes_several_messages_in_one_request() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let transport = RecordingTransport {
+        calls: Arc::clone(&calls),
+    };
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let ticket = pipeline
+        .enqueue(SendRequest::DeleteMessage {
+            peer: test_peer(),
+            message_ids: vec![MessageId(4), MessageId(5), MessageId(6)],
+        })
+        .expect("enqueue");
+    let mut status_rx = ticket.status;
+
+    tokio::time::advance(Duration::from_millis(1)).await;
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+    assert_eq!(
+        sent,
+        SendStatus::Sent(SendResult::MessageDeleted {
+            message_ids: vec![MessageId(4), MessageId(5), MessageId(6)],
+            deleted_count: 3,
+        })
+    );
+    assert_eq!(calls.lock().unwrap().len(), 1);
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn mutes_and_unmutes_chats() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let transport = RecordingTransport {
+        calls: Arc::clone(&calls),
+    };
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    for muted in [true, false] {
+        let ticket = pipeline
+            .enqueue(SendRequest::SetChatMuted {
+                peer: test_peer(),
+                muted,
+            })
+            .expect("enqueue");
+        let mut status_rx = ticket.status;
+        tokio::time::advance(Duration::from_millis(1)).await;
+        let sent = wait_for_status(&mut status_rx, |status| {
+            matches!(status, SendStatus::Sent(_))
+        })
+        .await;
+        assert_eq!(sent, SendStatus::Sent(SendResult::ChatMuteSet { muted }));
+    }
+    assert_eq!(calls.lock().unwrap().len(), 2);
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn pins_chats() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let transport = RecordingTransport {
+        calls: Arc::clone(&calls),
+    };
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let ticket = pipeline
+        .enqueue(SendRequest::SetChatPinned {
+            peer: test_peer(),
+            pinned: true,
+        })
+        .expect("enqueue");
+    let mut status_rx = ticket.status;
+    tokio::time::advance(Duration::from_millis(1)).await;
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+
+    assert_eq!(
+        sent,
+        SendStatus::Sent(SendResult::ChatPinSet { pinned: true })
+    );
+    assert!(matches!(
+        calls.lock().unwrap().as_slice(),
+        [SendRequest::SetChatPinned { pinned: true, .. }]
+    ));
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn transcribes_voice_messages() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let transport = RecordingTransport {
+        calls: Arc::clone(&calls),
+    };
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let ticket = pipeline
+        .enqueue(SendRequest::TranscribeAudio {
+            peer: test_peer(),
+            message_id: MessageId(12),
+        })
+        .expect("enqueue");
+    let mut status_rx = ticket.status;
+    tokio::time::advance(Duration::from_millis(1)).await;
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+
+    let SendStatus::Sent(SendResult::AudioTranscribed(transcribed)) = sent else {
+        panic!("unexpected status: {sent:?}");
+    };
+    assert_eq!(transcribed.message_id, MessageId(12));
+    assert_eq!(transcribed.transcription.text, "hello");
+    assert_eq!(calls.lock().unwrap().len(), 1);
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn republishes_status_transitions_as_domain_events() {
+    let events = EventPublisher::new(EventDropPolicy::DropOldest, 16);
+    let mut events_rx = events.subscribe();
+    let transport = MockTransport::new(vec![Ok(SendResult::MessageSent {
+        message_id: MessageId(77),
+    })]);
+    let pipeline =
+        spawn_send_pipeline(transport, SendPipelineConfig::default()).with_status_events(events);
+
+    let ticket = pipeline.enqueue(send_request()).expect("enqueue");
+    let mut status_rx = ticket.status;
+    tokio::time::advance(Duration::from_millis(1)).await;
+    wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        match events_rx.recv().await.expect("status event") {
+            DomainEvent::SendProgress(progress) => {
+                assert_eq!(progress.send_id, ticket.id);
+                statuses.push(progress.status);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+    assert_eq!(
+        statuses,
+        vec![
+            SendStatus::Queued {
+                attempt: 0,
+                next_retry_in: None,
+            },
+            SendStatus::Sending { attempt: 1 },
+            SendStatus::Sent(SendResult::MessageSent {
+                message_id: MessageId(77),
+            }),
+        ]
+    );
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn retries_reuse_random_id_and_treat_duplicates_as_sent() {
+    let random_ids = Arc::new(Mutex::new(Vec::new()));
+    let rpc_error = |code, name: &str| {
+        SendError::Invocation(InvocationError::Rpc(RpcError {
+            code,
+            name: name.to_string(),
+            value: None,
+            caused_by: None,
+        }))
+    };
+    let transport = RandomIdTransport {
+        random_ids: Arc::clone(&random_ids),
+        responses: Mutex::new(
+            vec![
+                Err(rpc_error(500, "INTERNAL")),
+                Err(rpc_error(400, "RANDOM_ID_DUPLICATE")),
+            ]
+            .into(),
+        ),
+    };
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let ticket = pipeline.enqueue(send_request()).expect("enqueue");
+    let mut status_rx = ticket.status;
+
+    tokio::time::advance(Duration::from_millis(1)).await;
+    wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Queued { attempt: 1, .. })
+    })
+    .await;
+    tokio::time::advance(Duration::from_secs(1)).await;
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_) | SendStatus::Failed(_))
+    })
+    .await;
+    assert_eq!(sent, SendStatus::Sent(SendResult::MessageDelivered));
+
+    let random_ids = random_ids.lock().unwrap().clone();
+    assert_eq!(random_ids.len(), 2);
+    assert_eq!(random_ids[0], random_ids[1]);
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn paused_pipeline_holds_items_until_resumed() {
+    let transport = MockTransport::new(vec![Ok(SendResult::MessageSent {
+        message_id: MessageId(77),
+    })]);
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+    pipeline.pause();
+
+    let ticket = pipeline.enqueue(send_request()).expect("enqueue");
+    let mut status_rx = ticket.status;
+    tokio::time::advance(Duration::from_secs(60)).await;
+    tokio::task::yield_now().await;
+    assert_eq!(
+        *status_rx.borrow(),
+        SendStatus::Queued {
+            attempt: 0,
+            next_retry_in: None,
+        }
+    );
+
+    pipeline.resume();
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+    assert!(matches!(sent, SendStatus::Sent(_)));
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn follows_connectivity_without_spending_attempts() {
+    let transport = MockTransport::new(vec![Ok(SendResult::MessageSent {
+        message_id: MessageId(77),
+    })]);
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+    let (online_tx, online_rx) = tokio::sync::watch::channel(false);
+    pipeline.follow_connectivity(online_rx);
+
+    let ticket = pipeline.enqueue(send_request()).expect("enqueue");
+    let mut status_rx = ticket.status;
+    tokio::time::advance(Duration::from_secs(60)).await;
+    tokio::task::yield_now().await;
+    assert!(matches!(
+        *status_rx.borrow(),
+        SendStatus::Queued { attempt: 0, .. }
+    ));
+
+    online_tx.send_replace(true);
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+    assert!(matches!(sent, SendStatus::Sent(_)));
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_sends_can_be_retried_or_discarded() {
+    let invalid = || {
+        Err(SendError::Invocation(InvocationError::Rpc(RpcError {
+            code: 400,
+            name: "PEER_ID_INVALID".to_string(),
+            value: None,
+            caused_by: None,
+        })))
+    };
+    let transport = MockTransport::new(vec![
+        invalid(),
+        invalid(),
+        Ok(SendResult::MessageSent {
+            message_id: MessageId(77),
+        }),
+    ]);
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let first = pipeline.enqueue(send_request()).expect("enqueue");
+    let second = pipeline.enqueue(send_request()).expect("enqueue");
+    let mut first_rx = first.status;
+    let mut second_rx = second.status;
+    tokio::time::advance(Duration::from_millis(1)).await;
+    for status_rx in [&mut first_rx, &mut second_rx] {
+        wait_for_status(status_rx, |status| matches!(status, SendStatus::Failed(_))).await;
+    }
+
+    let dead_letters = pipeline.dead_letters();
+    assert_eq!(
+        dead_letters
+            .iter()
+            .map(|letter| letter.id)
+            .collect::<Vec<_>>(),
+        vec![first.id, second.id]
+    );
+    assert!(!dead_letters[0].failure.retryable);
+
+    assert!(pipeline.discard_dead_letter(second.id).is_some());
+    pipeline.retry_dead_letter(first.id).expect("retry");
+    assert!(matches!(
+        pipeline.retry_dead_letter(second.id),
+        Err(SendEnqueueError::UnknownDeadLetter(id)) if id == second.id
+    ));
+
+    let sent = wait_for_status(&mut first_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+    assert!(matches!(sent, SendStatus::Sent(_)));
+    assert!(pipeline.dead_letters().is_empty());
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn slowmode_defers_later_sends_to_the_same_peer() {
+    let slowmode = RpcError {
+        code: 420,
+        name: "SLOWMODE_WAIT".to_string(),
+        value: Some(10),
+        caused_by: None,
+    };
+    let transport = MockTransport::new(vec![
+        Err(SendError::Invocation(InvocationError::Rpc(slowmode))),
+        Ok(SendResult::MessageSent {
+            message_id: MessageId(77),
+        }),
+        Ok(SendResult::MessageSent {
+            message_id: MessageId(78),
+        }),
+    ]);
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let first = pipeline.enqueue(send_request()).expect("enqueue");
+    let mut first_rx = first.status;
+    tokio::time::advance(Duration::from_millis(1)).await;
+    wait_for_status(&mut first_rx, |status| {
+        matches!(status, SendStatus::Queued { attempt: 1, .. })
+    })
+    .await;
+
+    let second = pipeline.enqueue(send_request()).expect("enqueue");
+    let mut second_rx = second.status;
+    let deferred = wait_for_status(&mut second_rx, |status| {
+        matches!(
+            status,
+            SendStatus::Queued {
+                next_retry_in: Some(_),
+                ..
+            }
+        )
+    })
+    .await;
+    match deferred {
+        SendStatus::Queued {
+            attempt,
+            next_retry_in: Some(delay),
+        } => {
+            assert_eq!(attempt, 0);
+            assert!(delay <= Duration::from_secs(10));
+        }
+        other => panic!("expected deferred send, got {other:?}"),
+    }
+
+    tokio::time::advance(Duration::from_secs(10)).await;
+    for status_rx in [&mut first_rx, &mut second_rx] {
+        let sent = wait_for_status(status_rx, |status| {
+            matches!(status, SendStatus::Sent(_) | SendStatus::Failed(_))
+        })
+        .await;
+        assert!(matches!(sent, SendStatus::Sent(_)));
+    }
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn queued_edits_of_one_message_coalesce() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let transport = RecordingTransport {
+        calls: Arc::clone(&calls),
+    };
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+    let edit = |text: &str| SendRequest::EditText {
+        peer: test_peer(),
+        message_id: MessageId(5),
+        text: text.to_string(),
+    };
+
+    pipeline.pause();
+    let tickets =
+        ["one", "two", "three"].map(|text| pipeline.enqueue(edit(text)).expect("enqueue"));
+    tokio::time::advance(Duration::from_millis(1)).await;
+    pipeline.resume();
+
+    for ticket in tickets {
+        let mut status_rx = ticket.status;
+        let sent = wait_for_status(&mut status_rx, |status| {
+            matches!(status, SendStatus::Sent(_))
+        })
+        .await;
+        assert_eq!(
+            sent,
+            SendStatus::Sent(SendResult::MessageEdited {
+                message_id: MessageId(5),
+            })
+        );
+    }
+    {
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert!(matches!(
+            &calls[0],
+            SendRequest::EditText { text, .. } if text == "three"
+        ));
+    }
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn saves_cloud_drafts() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let transport = RecordingTransport {
+        calls: Arc::clone(&calls),
+    };
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let ticket = pipeline
+        .enqueue(SendRequest::SaveDraft {
+            peer: test_peer(),
+            text: "half a thought".to_string(),
+            reply_to: Some(MessageId(3)),
+        })
+        .expect("enqueue");
+    let mut status_rx = ticket.status;
+
+    tokio::time::advance(Duration::from_millis(1)).await;
+    let sent = wait_for_status(&mut status_rx, |status| {
+        matches!(status, SendStatus::Sent(_))
+    })
+    .await;
+    assert_eq!(sent, SendStatus::Sent(SendResult::DraftSaved));
+    assert!(matches!(
+        &calls.lock().unwrap()[0],
+        SendRequest::SaveDraft { text, reply_to: Some(MessageId(3)), .. } if text == "half a thought"
+    ));
+
+    pipeline.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn wait_final_returns_terminal_statuses() {
+    let transport = MockTransport::new(vec![Ok(SendResult::MessageSent {
+        message_id: MessageId(77),
+    })]);
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let mut sent = pipeline.enqueue(send_request()).expect("enqueue");
+    assert_eq!(
+        sent.wait_final(Duration::from_secs(1)).await,
+        Ok(SendStatus::Sent(SendResult::MessageSent {
+            message_id: MessageId(77),
+        }))
+    );
+
+    pipeline.pause();
+    let mut held = pipeline.enqueue(send_request()).expect("enqueue");
+    assert!(held.wait_final(Duration::from_secs(1)).await.is_err());
+
+    pipeline.stop().await;
+    assert_eq!(
+        held.wait_final(Duration::from_secs(1)).await,
+        Ok(SendStatus::Cancelled)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejected_schedule_dates_fail_without_retry() {
+    let transport = MockTransport::new(vec![Err(SendError::Invocation(InvocationError::Rpc(
+        RpcError {
+            code: 400,
+            name: "SCHEDULE_DATE_TOO_LATE".to_string(),
+            value: None,
+            caused_by: None,
+        },
+    )))]);
+    let pipeline = spawn_send_pipeline(transport, SendPipelineConfig::default());
+
+    let mut ticket = pipeline
+        .enqueue(SendRequest::SendText {
+            peer: test_peer(),
+            text: "next year".to_string(),
+            reply_to: None,
+            schedule_date: Some(4_102_444_800),
+        })
+        .expect("enqueue");
+    match ticket.wait_final(Duration::from_secs(1)).await {
+        Ok(SendStatus::Failed(failure)) => {
+            assert_eq!(failure.attempts, 1);
+            assert!(!failure.retryable);
+        }
+        other => panic!("expected failure, got {other:?}"),
+    }
+
+    pipeline.stop().await;
+}
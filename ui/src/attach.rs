@@ -0,0 +1,187 @@
+//! Picking a local file to send from the composer.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::input::InputState;
+
+/// Popup state for choosing the file `Ctrl+O` attaches.
+#[derive(Debug, Clone, Default)]
+pub struct AttachPickerState {
+    pub is_open: bool,
+    pub path: InputState,
+    /// Names left after the last `Tab` when the path did not narrow to one.
+    pub completions: Vec<String>,
+}
+
+impl AttachPickerState {
+    pub fn open(&mut self) {
+        *self = Self {
+            is_open: true,
+            ..Default::default()
+        };
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The typed path with a leading `~` expanded, if it names a file.
+    pub fn selected_file(&self) -> Option<PathBuf> {
+        let path = expand_home(self.path.text.trim());
+        path.is_file().then_some(path)
+    }
+
+    /// Completes the last path component from the entries of its
+    /// directory: all the way when one entry matches, otherwise as far as
+    /// the matches agree, listing them in [`Self::completions`].
+    pub fn complete(&mut self) {
+        let typed = self.path.text.trim().to_string();
+        let (dir, prefix) = match typed.rfind('/') {
+            Some(slash) => typed.split_at(slash + 1),
+            None => ("", typed.as_str()),
+        };
+        let listed = if dir.is_empty() {
+            PathBuf::from(".")
+        } else {
+            expand_home(dir)
+        };
+        let Ok(entries) = fs::read_dir(listed) else {
+            self.completions.clear();
+            return;
+        };
+        let mut names: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let mut name = entry.file_name().into_string().ok()?;
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+                if entry.path().is_dir() {
+                    name.push('/');
+                }
+                Some(name)
+            })
+            .collect();
+        names.sort();
+
+        let completed = match names.as_slice() {
+            [] => prefix.to_string(),
+            [only] => only.clone(),
+            [first, rest @ ..] => rest.iter().fold(first.clone(), |common, name| {
+                let len = common
+                    .char_indices()
+                    .zip(name.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .last()
+                    .map_or(0, |((index, a), _)| index + a.len_utf8());
+                common[..len].to_string()
+            }),
+        };
+        self.path.replace(format!("{dir}{completed}"));
+        self.completions = if names.len() > 1 { names } else { Vec::new() };
+    }
+
+    /// What the popup shows under the path: the file's name and size, the
+    /// candidates of the last `Tab`, or why nothing can be sent yet.
+    pub fn preview(&self) -> String {
+        if !self.completions.is_empty() {
+            return self.completions.join("  ");
+        }
+        let typed = self.path.text.trim();
+        if typed.is_empty() {
+            return "Type a path; Tab completes".to_string();
+        }
+        let path = expand_home(typed);
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => {
+                let name = path
+                    .file_name()
+                    .map_or_else(|| typed.to_string(), |name| name.to_string_lossy().into());
+                format!("{name} · {}", format_size(metadata.len()))
+            }
+            Ok(_) => "Not a file".to_string(),
+            Err(_) => "No such file".to_string(),
+        }
+    }
+}
+
+/// `~/` at the start of `path` stands for `$HOME`.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// File size in the largest unit under 1024, like `40KB` or `1.2MB`.
+pub fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    const GB: u64 = 1024 * MB;
+    match bytes {
+        bytes if bytes < KB => format!("{bytes}B"),
+        bytes if bytes < MB => format!("{}KB", (bytes + KB / 2) / KB),
+        bytes if bytes < GB => format!("{:.1}MB", bytes as f64 / MB as f64),
+        bytes => format!("{:.1}GB", bytes as f64 / GB as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("attach-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("photos")).unwrap();
+        fs::write(dir.join("photos/cat.jpg"), [0u8; 2048]).unwrap();
+        fs::write(dir.join("photos/cake.png"), [0u8; 10]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn tab_completes_unique_names_and_common_prefixes() {
+        let dir = scratch_dir("complete");
+        let mut picker = AttachPickerState::default();
+        picker.open();
+        picker.path.replace(format!("{}/ph", dir.display()));
+
+        picker.complete();
+        assert_eq!(picker.path.text, format!("{}/photos/", dir.display()));
+        assert!(picker.completions.is_empty());
+
+        picker.path.insert_str("c");
+        picker.complete();
+        assert_eq!(picker.path.text, format!("{}/photos/ca", dir.display()));
+        assert_eq!(picker.completions, ["cake.png", "cat.jpg"]);
+        assert_eq!(picker.preview(), "cake.png  cat.jpg");
+
+        picker.path.insert_str("t");
+        picker.complete();
+        assert_eq!(picker.preview(), "cat.jpg · 2KB");
+        assert_eq!(picker.selected_file(), Some(dir.join("photos/cat.jpg")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn previews_why_a_path_cannot_be_sent() {
+        let dir = scratch_dir("preview");
+        let mut picker = AttachPickerState::default();
+
+        picker
+            .path
+            .replace(dir.join("photos").display().to_string());
+        assert_eq!(picker.preview(), "Not a file");
+        assert_eq!(picker.selected_file(), None);
+
+        picker
+            .path
+            .replace(dir.join("missing").display().to_string());
+        assert_eq!(picker.preview(), "No such file");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+//! Visual ordering of right-to-left text, so Arabic and Hebrew read the
+//! right way round in a terminal that draws every cell left to right.
+
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+use unicode_bidi::ParagraphBidiInfo;
+
+/// `line` with its characters in display order. Each direction run keeps the
+/// styles of its characters; lines without right-to-left text are returned
+/// untouched.
+///
+/// Lines are reordered whole, before wrapping, so a right-to-left line too
+/// long for the pane continues on the row below from its left end.
+pub fn reorder_line(line: Line<'static>) -> Line<'static> {
+    let text: String = line
+        .spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect();
+    let info = ParagraphBidiInfo::new(&text, None);
+    if !info.has_rtl() {
+        return line;
+    }
+    // Style of every byte of `text`, so runs can be cut anywhere.
+    let mut styles = Vec::with_capacity(text.len());
+    for span in &line.spans {
+        styles.extend(std::iter::repeat_n(span.style, span.content.len()));
+    }
+
+    let (levels, runs) = info.visual_runs(0..text.len());
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    for run in runs {
+        let mut chars: Vec<(usize, char)> = text[run.clone()].char_indices().collect();
+        let rtl = levels[run.start].is_rtl();
+        if rtl {
+            chars.reverse();
+        }
+        for (offset, c) in chars {
+            let c = if rtl { mirrored(c) } else { c };
+            push_char(&mut spans, c, styles[run.start + offset]);
+        }
+    }
+    Line { spans, ..line }
+}
+
+/// Appends `c` to the last span when it has the same style.
+fn push_char(spans: &mut Vec<Span<'static>>, c: char, style: Style) {
+    match spans.last_mut() {
+        Some(last) if last.style == style => last.content.to_mut().push(c),
+        _ => spans.push(Span::styled(c.to_string(), style)),
+    }
+}
+
+/// Brackets face the other way inside right-to-left runs.
+fn mirrored(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Modifier;
+
+    fn text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn leaves_left_to_right_lines_alone() {
+        let line = Line::from("plain (text)");
+        assert_eq!(reorder_line(line.clone()), line);
+    }
+
+    #[test]
+    fn reverses_right_to_left_runs_and_keeps_their_styles() {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let line = Line::from(vec![
+            Span::raw("see "),
+            Span::styled("שלום", bold),
+            Span::raw(" (עולם)"),
+        ]);
+
+        let reordered = reorder_line(line);
+
+        assert_eq!(text(&reordered), "see (םלוע) םולש");
+        assert!(reordered
+            .spans
+            .iter()
+            .any(|span| span.content == "םולש" && span.style == bold));
+    }
+
+    #[test]
+    fn right_to_left_paragraphs_put_embedded_latin_on_the_left() {
+        let reordered = reorder_line(Line::from("שלום world"));
+        assert_eq!(text(&reordered), "world םולש");
+    }
+}
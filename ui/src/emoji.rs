@@ -0,0 +1,240 @@
+//! Emoji lookup for the composer picker.
+
+use crate::fuzzy::fuzzy_score;
+use crate::input::InputState;
+
+/// Recent picks remembered for an empty query, newest first.
+const MAX_RECENTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Emoji {
+    pub name: &'static str,
+    pub glyph: &'static str,
+}
+
+const fn emoji(name: &'static str, glyph: &'static str) -> Emoji {
+    Emoji { name, glyph }
+}
+
+pub const EMOJI: &[Emoji] = &[
+    emoji("thumbsup", "👍"),
+    emoji("thumbsdown", "👎"),
+    emoji("heart", "❤️"),
+    emoji("joy", "😂"),
+    emoji("smile", "😄"),
+    emoji("grinning", "😀"),
+    emoji("slightly_smiling_face", "🙂"),
+    emoji("wink", "😉"),
+    emoji("blush", "😊"),
+    emoji("heart_eyes", "😍"),
+    emoji("kissing_heart", "😘"),
+    emoji("sweat_smile", "😅"),
+    emoji("rofl", "🤣"),
+    emoji("thinking", "🤔"),
+    emoji("neutral_face", "😐"),
+    emoji("unamused", "😒"),
+    emoji("roll_eyes", "🙄"),
+    emoji("grimacing", "😬"),
+    emoji("relieved", "😌"),
+    emoji("pensive", "😔"),
+    emoji("sleepy", "😪"),
+    emoji("sleeping", "😴"),
+    emoji("sunglasses", "😎"),
+    emoji("nerd", "🤓"),
+    emoji("confused", "😕"),
+    emoji("worried", "😟"),
+    emoji("open_mouth", "😮"),
+    emoji("astonished", "😲"),
+    emoji("flushed", "😳"),
+    emoji("pleading", "🥺"),
+    emoji("cry", "😢"),
+    emoji("sob", "😭"),
+    emoji("scream", "😱"),
+    emoji("angry", "😠"),
+    emoji("rage", "😡"),
+    emoji("skull", "💀"),
+    emoji("clown", "🤡"),
+    emoji("ghost", "👻"),
+    emoji("robot", "🤖"),
+    emoji("poop", "💩"),
+    emoji("see_no_evil", "🙈"),
+    emoji("wave", "👋"),
+    emoji("ok_hand", "👌"),
+    emoji("v", "✌️"),
+    emoji("crossed_fingers", "🤞"),
+    emoji("point_up", "☝️"),
+    emoji("clap", "👏"),
+    emoji("raised_hands", "🙌"),
+    emoji("pray", "🙏"),
+    emoji("handshake", "🤝"),
+    emoji("muscle", "💪"),
+    emoji("eyes", "👀"),
+    emoji("brain", "🧠"),
+    emoji("broken_heart", "💔"),
+    emoji("sparkling_heart", "💖"),
+    emoji("fire", "🔥"),
+    emoji("sparkles", "✨"),
+    emoji("star", "⭐"),
+    emoji("boom", "💥"),
+    emoji("zap", "⚡"),
+    emoji("100", "💯"),
+    emoji("tada", "🎉"),
+    emoji("confetti_ball", "🎊"),
+    emoji("gift", "🎁"),
+    emoji("trophy", "🏆"),
+    emoji("rocket", "🚀"),
+    emoji("bulb", "💡"),
+    emoji("warning", "⚠️"),
+    emoji("white_check_mark", "✅"),
+    emoji("x", "❌"),
+    emoji("question", "❓"),
+    emoji("exclamation", "❗"),
+    emoji("hourglass", "⌛"),
+    emoji("alarm_clock", "⏰"),
+    emoji("calendar", "📅"),
+    emoji("memo", "📝"),
+    emoji("pushpin", "📌"),
+    emoji("link", "🔗"),
+    emoji("lock", "🔒"),
+    emoji("key", "🔑"),
+    emoji("bell", "🔔"),
+    emoji("mag", "🔍"),
+    emoji("computer", "💻"),
+    emoji("phone", "📱"),
+    emoji("email", "📧"),
+    emoji("package", "📦"),
+    emoji("bug", "🐛"),
+    emoji("wrench", "🔧"),
+    emoji("hammer", "🔨"),
+    emoji("chart", "📈"),
+    emoji("moneybag", "💰"),
+    emoji("coffee", "☕"),
+    emoji("beer", "🍺"),
+    emoji("pizza", "🍕"),
+    emoji("cake", "🍰"),
+    emoji("apple", "🍎"),
+    emoji("sunny", "☀️"),
+    emoji("rainbow", "🌈"),
+    emoji("snowflake", "❄️"),
+    emoji("umbrella", "☔"),
+    emoji("earth", "🌍"),
+    emoji("cat", "🐱"),
+    emoji("dog", "🐶"),
+    emoji("unicorn", "🦄"),
+    emoji("seedling", "🌱"),
+    emoji("rose", "🌹"),
+    emoji("music", "🎵"),
+    emoji("soccer", "⚽"),
+    emoji("car", "🚗"),
+    emoji("airplane", "✈️"),
+    emoji("house", "🏠"),
+];
+
+/// Popup state for picking an emoji into the composer.
+#[derive(Debug, Clone, Default)]
+pub struct EmojiPickerState {
+    pub is_open: bool,
+    pub query: InputState,
+    pub matches: Vec<Emoji>,
+    pub selected: usize,
+    /// Opened by typing `:`, which goes back into the draft if the picker is
+    /// dismissed.
+    pub opened_by_colon: bool,
+    pub recents: Vec<Emoji>,
+}
+
+impl EmojiPickerState {
+    pub fn open(&mut self, opened_by_colon: bool) {
+        self.is_open = true;
+        self.opened_by_colon = opened_by_colon;
+        self.query = InputState::default();
+        self.recompute_matches();
+    }
+
+    pub fn close(&mut self) {
+        self.is_open = false;
+        self.query = InputState::default();
+        self.matches.clear();
+        self.selected = 0;
+    }
+
+    pub fn recompute_matches(&mut self) {
+        self.matches = search(&self.query.text, &self.recents);
+        self.selected = 0;
+    }
+
+    pub fn selected_emoji(&self) -> Option<Emoji> {
+        self.matches.get(self.selected).copied()
+    }
+
+    pub fn move_selection(&mut self, delta: i32) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let max_index = self.matches.len() as i32 - 1;
+        self.selected = (self.selected as i32 + delta).clamp(0, max_index) as usize;
+    }
+
+    /// Moves `emoji` to the front of the recents.
+    pub fn remember(&mut self, emoji: Emoji) {
+        self.recents.retain(|recent| *recent != emoji);
+        self.recents.insert(0, emoji);
+        self.recents.truncate(MAX_RECENTS);
+    }
+}
+
+/// Emoji whose name fuzzily matches `query`, best match first. An empty
+/// query lists `recents` ahead of the rest of the table.
+pub fn search(query: &str, recents: &[Emoji]) -> Vec<Emoji> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        let rest = EMOJI.iter().filter(|emoji| !recents.contains(emoji));
+        return recents.iter().chain(rest).copied().collect();
+    }
+    let mut scored: Vec<(usize, usize, Emoji)> = EMOJI
+        .iter()
+        .enumerate()
+        .filter_map(|(index, emoji)| {
+            let mut score = fuzzy_score(&query, emoji.name)?;
+            if recents.contains(emoji) {
+                score = score.saturating_sub(1);
+            }
+            Some((score, index, *emoji))
+        })
+        .collect();
+    scored.sort_by_key(|(score, index, _)| (*score, *index));
+    scored.into_iter().map(|(_, _, emoji)| emoji).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(matches: &[Emoji]) -> Vec<&'static str> {
+        matches.iter().map(|emoji| emoji.name).collect()
+    }
+
+    #[test]
+    fn ranks_prefix_then_substring_then_subsequence() {
+        let matches = names(&search("hea", &[]));
+        assert_eq!(&matches[..2], ["heart", "heart_eyes"]);
+        assert!(matches.contains(&"broken_heart"));
+
+        assert_eq!(names(&search("thup", &[])), ["thumbsup"]);
+        assert!(search("zzz", &[]).is_empty());
+    }
+
+    #[test]
+    fn remembers_recent_picks_first() {
+        let mut picker = EmojiPickerState::default();
+        let fire = search("fire", &[])[0];
+        let tada = search("tada", &[])[0];
+        picker.remember(fire);
+        picker.remember(tada);
+        picker.remember(fire);
+
+        picker.open(false);
+        assert_eq!(&picker.matches[..2], [fire, tada]);
+        assert_eq!(picker.matches.len(), EMOJI.len());
+    }
+}
@@ -0,0 +1,24 @@
+//! Fuzzy name matching shared by the pickers and filters.
+
+/// Lower is better: prefixes beat substrings, which beat scattered
+/// subsequences ranked by how spread out the matched characters are.
+/// Matching is case-sensitive, so callers lowercase both sides.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<usize> {
+    if name.starts_with(query) {
+        return Some(name.len() - query.len());
+    }
+    if let Some(position) = name.find(query) {
+        return Some(100 + position);
+    }
+    let mut gaps = 0;
+    let mut last_match: Option<usize> = None;
+    let mut candidates = name.char_indices();
+    for wanted in query.chars() {
+        let (position, _) = candidates.find(|(_, c)| *c == wanted)?;
+        if let Some(last) = last_match {
+            gaps += position - last - 1;
+        }
+        last_match = Some(position);
+    }
+    Some(200 + gaps)
+}
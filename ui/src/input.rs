@@ -1,58 +1,289 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+/// Undo steps kept per input; the oldest are dropped first.
+const MAX_UNDO_STEPS: usize = 100;
+
+/// Single-line or multi-line text with a cursor. `cursor` is a byte offset
+/// that always sits on a grapheme boundary, so emoji, combining marks and
+/// CJK text move and delete as the user sees them.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct InputState {
     pub text: String,
     pub cursor: usize,
+    /// Text removed by the last kill (`Ctrl+W`, `Ctrl+U`, `Ctrl+K`), put
+    /// back by `Ctrl+Y`.
+    pub killed: String,
+    /// Text and cursor before each edit, newest last, restored by `Ctrl+Z`.
+    undo: Vec<(String, usize)>,
+    /// Whether the last key typed a word character, so the rest of the word
+    /// joins the same undo step.
+    typing_run: bool,
 }
 
 impl InputState {
     pub fn clamp_cursor(&mut self) {
         self.cursor = self.cursor.min(self.text.len());
+        while !self.text.is_char_boundary(self.cursor) {
+            self.cursor -= 1;
+        }
+    }
+
+    /// Replaces the whole text, e.g. with another chat's draft, and forgets
+    /// the undo history.
+    pub fn replace(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+        self.undo.clear();
+        self.typing_run = false;
+    }
+
+    /// Inserts `text` at the cursor as one undo step.
+    pub fn insert_str(&mut self, text: &str) {
+        self.checkpoint();
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    /// Inserts pasted text as one undo step, normalizing line endings.
+    pub fn paste(&mut self, text: &str) {
+        self.insert_str(&text.replace("\r\n", "\n").replace('\r', "\n"));
+    }
+
+    /// Restores the text from before the last edit. Returns false when there
+    /// is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        self.typing_run = false;
+        match self.undo.pop() {
+            Some((text, cursor)) => {
+                self.text = text;
+                self.cursor = cursor;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn checkpoint(&mut self) {
+        if self.undo.len() == MAX_UNDO_STEPS {
+            self.undo.remove(0);
+        }
+        self.undo.push((self.text.clone(), self.cursor));
+        self.typing_run = false;
+    }
+
+    /// Types one character; a word and the whitespace after it undo together.
+    fn type_char(&mut self, c: char, continues_word: bool) {
+        if !continues_word {
+            self.checkpoint();
+        }
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+        self.typing_run = !c.is_whitespace();
+    }
+
+    /// Whether the cursor sits at the start of the text or after whitespace.
+    pub fn at_word_start(&self) -> bool {
+        self.text
+            .get(..self.cursor)
+            .and_then(|before| before.chars().next_back())
+            .is_none_or(char::is_whitespace)
+    }
+
+    pub fn insert_newline(&mut self) {
+        self.insert_str("\n");
+    }
+
+    /// Number of lines in the text; empty text still takes one line.
+    pub fn line_count(&self) -> usize {
+        self.text.split('\n').count()
+    }
+
+    /// Zero-based `(row, column)` of the cursor, with the column in terminal
+    /// cells so wide characters count twice.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let before = self.text.get(..self.cursor).unwrap_or(&self.text);
+        let row = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        (row, before[line_start..].width())
+    }
+
+    /// Byte offset of the grapheme before the cursor.
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(index, _)| index)
+    }
+
+    /// Byte offset just past the grapheme after the cursor.
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map_or(self.cursor, |grapheme| self.cursor + grapheme.len())
+    }
+
+    /// Start of the word before the cursor, skipping whitespace first.
+    fn word_start(&self) -> usize {
+        let before = &self.text[..self.cursor];
+        let trimmed = before.trim_end();
+        trimmed
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(index, c)| index + c.len_utf8())
+    }
+
+    /// End of the word after the cursor, skipping whitespace first.
+    fn word_end(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let skipped = after.len() - after.trim_start().len();
+        after[skipped..]
+            .find(char::is_whitespace)
+            .map_or(self.text.len(), |index| self.cursor + skipped + index)
+    }
+
+    /// Removes `start..end` into the kill buffer, leaving the cursor at
+    /// `start`.
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.checkpoint();
+        self.killed = self.text[start..end].to_string();
+        self.text.replace_range(start..end, "");
+        self.cursor = start;
+    }
+
+    fn yank(&mut self) {
+        let killed = self.killed.clone();
+        self.insert_str(&killed);
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+        self.checkpoint();
+        let start = self.previous_boundary();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    fn line_start(&self) -> usize {
+        self.text[..self.cursor]
+            .rfind('\n')
+            .map_or(0, |index| index + 1)
+    }
+
+    fn line_end(&self) -> usize {
+        self.text[self.cursor..]
+            .find('\n')
+            .map_or(self.text.len(), |index| self.cursor + index)
+    }
+
+    /// Moves to the same display column on the previous or next line,
+    /// clamped to the length of that line. Returns false on the first or last
+    /// line.
+    fn move_line(&mut self, up: bool) -> bool {
+        let column = self.text[self.line_start()..self.cursor].width();
+        let target_start = if up {
+            let start = self.line_start();
+            if start == 0 {
+                return false;
+            }
+            self.text[..start - 1]
+                .rfind('\n')
+                .map_or(0, |index| index + 1)
+        } else {
+            let end = self.line_end();
+            if end == self.text.len() {
+                return false;
+            }
+            end + 1
+        };
+        let target_end = self.text[target_start..]
+            .find('\n')
+            .map_or(self.text.len(), |index| target_start + index);
+        let mut width = 0;
+        self.cursor = target_end;
+        for (index, grapheme) in self.text[target_start..target_end].grapheme_indices(true) {
+            width += grapheme.width();
+            if width > column {
+                self.cursor = target_start + index;
+                break;
+            }
+        }
+        true
     }
 }
 
 pub fn handle_key(state: &mut InputState, key: KeyEvent) -> bool {
+    let continues_word = std::mem::take(&mut state.typing_run);
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        return handle_control_key(state, key.code);
+    }
     match key.code {
+        KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::ALT) => {
+            state.cursor = state.word_start();
+            true
+        }
+        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::ALT) => {
+            state.cursor = state.word_end();
+            true
+        }
         KeyCode::Char(c) => {
-            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                return false;
-            }
-            state.text.insert(state.cursor, c);
-            state.cursor += 1;
+            state.type_char(c, continues_word);
             true
         }
         KeyCode::Backspace => {
-            if state.cursor > 0 {
-                state.cursor -= 1;
-                state.text.remove(state.cursor);
-            }
+            state.backspace();
             true
         }
         KeyCode::Left => {
-            if state.cursor > 0 {
-                state.cursor -= 1;
-            }
+            state.cursor = state.previous_boundary();
             true
         }
         KeyCode::Right => {
-            if state.cursor < state.text.len() {
-                state.cursor += 1;
-            }
+            state.cursor = state.next_boundary();
             true
         }
+        KeyCode::Up => state.move_line(true),
+        KeyCode::Down => state.move_line(false),
         KeyCode::Home => {
-            state.cursor = 0;
+            state.cursor = state.line_start();
             true
         }
         KeyCode::End => {
-            state.cursor = state.text.len();
+            state.cursor = state.line_end();
             true
         }
         _ => false,
     }
 }
 
+/// Readline-style editing on Ctrl. Unbound combinations return false so the
+/// caller can use them.
+fn handle_control_key(state: &mut InputState, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Char('a') => state.cursor = state.line_start(),
+        KeyCode::Char('e') => state.cursor = state.line_end(),
+        KeyCode::Char('w') => state.kill(state.word_start(), state.cursor),
+        KeyCode::Char('u') => state.kill(state.line_start(), state.cursor),
+        KeyCode::Char('k') => state.kill(state.cursor, state.line_end()),
+        KeyCode::Char('y') => state.yank(),
+        KeyCode::Char('z') => {
+            state.undo();
+        }
+        KeyCode::Left => state.cursor = state.word_start(),
+        KeyCode::Right => state.cursor = state.word_end(),
+        _ => return false,
+    }
+    true
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,6 +319,7 @@ mod tests {
         let mut state = InputState {
             text: "ok".to_string(),
             cursor: 2,
+            ..Default::default()
         };
 
         handle_key(
@@ -107,4 +339,143 @@ mod tests {
         assert_eq!(state.text, "o");
         assert_eq!(state.cursor, 1);
     }
+
+    #[test]
+    fn tracks_rows_and_moves_between_lines() {
+        let mut state = InputState {
+            text: "first line\nok".to_string(),
+            cursor: 8,
+            ..Default::default()
+        };
+        state.insert_newline();
+
+        assert_eq!(state.text, "first li\nne\nok");
+        assert_eq!(state.line_count(), 3);
+        assert_eq!(state.cursor_position(), (1, 0));
+
+        handle_key(&mut state, KeyEvent::new(KeyCode::End, KeyModifiers::NONE));
+        assert_eq!(state.cursor_position(), (1, 2));
+
+        handle_key(&mut state, KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(state.cursor_position(), (2, 2));
+
+        handle_key(&mut state, KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        handle_key(&mut state, KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        assert_eq!(state.cursor_position(), (0, 2));
+        assert!(!handle_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Up, KeyModifiers::NONE)
+        ));
+
+        handle_key(&mut state, KeyEvent::new(KeyCode::Home, KeyModifiers::NONE));
+        assert_eq!(state.cursor, 0);
+    }
+
+    #[test]
+    fn readline_shortcuts_move_kill_and_yank() {
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let alt = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT);
+        let mut state = InputState {
+            text: "ship the  release".to_string(),
+            cursor: "ship the  release".len(),
+            ..Default::default()
+        };
+
+        handle_key(&mut state, alt('b'));
+        assert_eq!(state.cursor, "ship the  ".len());
+        handle_key(&mut state, alt('b'));
+        assert_eq!(state.cursor, "ship ".len());
+        handle_key(&mut state, alt('f'));
+        assert_eq!(state.cursor, "ship the".len());
+
+        handle_key(&mut state, ctrl('w'));
+        assert_eq!(state.text, "ship   release");
+        assert_eq!(state.killed, "the");
+        handle_key(&mut state, ctrl('e'));
+        handle_key(&mut state, ctrl('y'));
+        assert_eq!(state.text, "ship   releasethe");
+
+        handle_key(&mut state, ctrl('a'));
+        handle_key(&mut state, ctrl('k'));
+        assert_eq!(state.text, "");
+        assert_eq!(state.killed, "ship   releasethe");
+
+        state.text = "one\ntwo three".to_string();
+        state.cursor = "one\ntwo".len();
+        handle_key(&mut state, ctrl('u'));
+        assert_eq!(state.text, "one\n three");
+        assert_eq!(state.killed, "two");
+        assert!(!handle_key(&mut state, ctrl('c')));
+    }
+
+    #[test]
+    fn undo_restores_whole_words_and_edits() {
+        let mut state = InputState::default();
+        for c in "hi there".chars() {
+            handle_key(
+                &mut state,
+                KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+            );
+        }
+        handle_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE),
+        );
+        assert_eq!(state.text, "hi ther");
+
+        let undo = KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL);
+        handle_key(&mut state, undo);
+        assert_eq!(state.text, "hi there");
+        handle_key(&mut state, undo);
+        assert_eq!(state.text, "hi ");
+        handle_key(&mut state, undo);
+        assert_eq!(state.text, "");
+        assert!(!state.undo());
+    }
+
+    #[test]
+    fn edits_whole_graphemes() {
+        let mut state = InputState::default();
+        for c in ['日', '本', 'e', '\u{301}', '👍'] {
+            handle_key(
+                &mut state,
+                KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+            );
+        }
+        assert_eq!(state.text, "日本e\u{301}👍");
+        assert_eq!(state.cursor_position(), (0, 7));
+
+        handle_key(&mut state, KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
+        handle_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE),
+        );
+        assert_eq!(state.text, "日本👍");
+        assert_eq!(state.cursor_position(), (0, 4));
+
+        handle_key(&mut state, KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
+        handle_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE),
+        );
+        assert_eq!(state.text, "日x本👍");
+        handle_key(&mut state, KeyEvent::new(KeyCode::End, KeyModifiers::NONE));
+        assert_eq!(state.cursor, state.text.len());
+    }
+
+    #[test]
+    fn vertical_moves_keep_the_display_column() {
+        let mut state = InputState {
+            text: "日本語\nabcdef".to_string(),
+            cursor: "日本".len(),
+            ..Default::default()
+        };
+
+        handle_key(&mut state, KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(state.cursor, "日本語\nabcd".len());
+
+        state.cursor = "日本語\nabc".len();
+        handle_key(&mut state, KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        assert_eq!(state.cursor, "日".len());
+    }
 }
@@ -1,7 +1,11 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
+use crate::fuzzy::fuzzy_score;
 use crate::input::{handle_key as handle_text_key, InputState};
-use crate::view::{ChatListItem, UiFocus, UiState};
+use crate::view::{
+    message_heights, ChatFilterState, ChatListItem, CommandPaletteState, ForwardState,
+    GlobalSearchState, MessageItem, ReplyTarget, UiAction, UiFocus, UiState,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum KeymapStyle {
@@ -10,7 +14,53 @@ pub enum KeymapStyle {
     Vscode,
 }
 
+/// Commands offered by the `Ctrl+P` palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PaletteCommand {
+    SearchAllChats,
+    FilterChats,
+    CycleChatSort,
+}
+
+impl PaletteCommand {
+    const ALL: [Self; 3] = [Self::SearchAllChats, Self::FilterChats, Self::CycleChatSort];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::SearchAllChats => "Search all chats",
+            Self::FilterChats => "Filter chats",
+            Self::CycleChatSort => "Cycle chat sort",
+        }
+    }
+}
+
 pub fn handle_ui_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -> bool {
+    if state.message_detail.is_some() {
+        return handle_detail_key(state, key, style);
+    }
+
+    if state.command_palette.is_open {
+        return handle_palette_key(state, key);
+    }
+
+    if state.global_search.is_open {
+        return handle_global_search_key(state, key);
+    }
+
+    // Tab completes the path instead of moving focus.
+    if state.attach_picker.is_open {
+        return handle_attach_key(state, key);
+    }
+
+    if key.code == KeyCode::Char('p') && key.modifiers == KeyModifiers::CONTROL {
+        state.command_palette = CommandPaletteState {
+            is_open: true,
+            ..Default::default()
+        };
+        filter_palette(state);
+        return true;
+    }
+
     if state.message_view.search.is_open && state.focus != UiFocus::Search {
         state.focus = UiFocus::Search;
     }
@@ -20,6 +70,17 @@ pub fn handle_ui_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) ->
         return true;
     }
 
+    if key.code == KeyCode::Char('g')
+        && key.modifiers == KeyModifiers::CONTROL
+        && jump_to_toast(state)
+    {
+        return true;
+    }
+
+    if resize_panes(state, key) {
+        return true;
+    }
+
     match state.focus {
         UiFocus::Chats => handle_chats_key(state, key, style),
         UiFocus::Messages => handle_messages_key(state, key, style),
@@ -28,7 +89,231 @@ pub fn handle_ui_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) ->
     }
 }
 
+/// Inserts a bracketed paste into the focused text input as one edit, so
+/// pasted newlines never trigger a send. Returns false when nothing takes
+/// text input.
+pub fn handle_ui_paste(state: &mut UiState, text: &str) -> bool {
+    if state.command_palette.is_open {
+        return false;
+    }
+    if state.attach_picker.is_open {
+        let line = text.replace(['\r', '\n'], " ");
+        state.attach_picker.path.paste(&line);
+        state.attach_picker.completions.clear();
+        return true;
+    }
+    if state.global_search.is_open {
+        let line = text.replace(['\r', '\n'], " ");
+        state.global_search.query.paste(&line);
+        request_global_search(state);
+        return true;
+    }
+    match state.focus {
+        UiFocus::Chats if state.chat_filter.is_open => {
+            let line = text.replace(['\r', '\n'], " ");
+            state.chat_filter.query.paste(&line);
+            select_top_chat_match(state);
+            true
+        }
+        UiFocus::Composer if state.emoji_picker.is_open => false,
+        UiFocus::Composer => {
+            state.input.paste(text);
+            true
+        }
+        UiFocus::Search => {
+            let line = text.replace(['\r', '\n'], " ");
+            state.message_view.search.query.paste(&line);
+            state.message_view.search.recompute_matches(&state.messages);
+            true
+        }
+        UiFocus::Chats | UiFocus::Messages => false,
+    }
+}
+
+/// Keys while the message detail view covers the screen: scrolling, and
+/// Esc, `q` or Enter to close it.
+fn handle_detail_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -> bool {
+    let Some(detail) = state.message_detail.as_mut() else {
+        return false;
+    };
+    match (key.code, style) {
+        (KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q'), _) => state.message_detail = None,
+        (KeyCode::Up, _) | (KeyCode::Char('k'), KeymapStyle::Vim) => {
+            detail.scroll = detail.scroll.saturating_sub(1);
+        }
+        (KeyCode::Down, _) | (KeyCode::Char('j'), KeymapStyle::Vim) => {
+            detail.scroll = detail.scroll.saturating_add(1);
+        }
+        (KeyCode::PageUp, _) => detail.scroll = detail.scroll.saturating_sub(10),
+        (KeyCode::PageDown, _) => detail.scroll = detail.scroll.saturating_add(10),
+        (KeyCode::Home, _) => detail.scroll = 0,
+        _ => return false,
+    }
+    true
+}
+
+/// Keys while the command palette is open: typing narrows the commands,
+/// Enter runs the highlighted one.
+fn handle_palette_key(state: &mut UiState, key: KeyEvent) -> bool {
+    let palette = &mut state.command_palette;
+    match key.code {
+        KeyCode::Esc => *palette = CommandPaletteState::default(),
+        KeyCode::Enter => {
+            let chosen = palette.items.get(palette.selected).cloned();
+            *palette = CommandPaletteState::default();
+            let command = PaletteCommand::ALL
+                .into_iter()
+                .find(|command| chosen.as_deref() == Some(command.label()));
+            if let Some(command) = command {
+                run_palette_command(state, command);
+            }
+        }
+        KeyCode::Up => palette.selected = palette.selected.saturating_sub(1),
+        KeyCode::Down => {
+            palette.selected = (palette.selected + 1).min(palette.items.len().saturating_sub(1));
+        }
+        KeyCode::Backspace => {
+            palette.query.pop();
+            filter_palette(state);
+        }
+        KeyCode::Char(c)
+            if !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            palette.query.push(c);
+            filter_palette(state);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Lists the commands fuzzily matching the palette query, best first.
+fn filter_palette(state: &mut UiState) {
+    let palette = &mut state.command_palette;
+    let query = palette.query.trim().to_lowercase();
+    let mut scored: Vec<(usize, usize, &str)> = PaletteCommand::ALL
+        .iter()
+        .enumerate()
+        .filter_map(|(index, command)| {
+            let score = fuzzy_score(&query, &command.label().to_lowercase())?;
+            Some((score, index, command.label()))
+        })
+        .collect();
+    if query.is_empty() {
+        scored.sort_by_key(|(_, index, _)| *index);
+    } else {
+        scored.sort();
+    }
+    palette.items = scored
+        .into_iter()
+        .map(|(_, _, label)| label.to_string())
+        .collect();
+    palette.selected = 0;
+}
+
+fn run_palette_command(state: &mut UiState, command: PaletteCommand) {
+    match command {
+        PaletteCommand::SearchAllChats => {
+            state.global_search = GlobalSearchState {
+                is_open: true,
+                ..Default::default()
+            };
+        }
+        PaletteCommand::FilterChats => {
+            state.focus = UiFocus::Chats;
+            state.chat_filter = ChatFilterState {
+                is_open: true,
+                ..Default::default()
+            };
+        }
+        PaletteCommand::CycleChatSort => state.chat_sort = state.chat_sort.next(),
+    }
+}
+
+/// Keys while the cross-chat search is open: typing searches, Up and Down
+/// pick a result and Enter shows it in its chat.
+fn handle_global_search_key(state: &mut UiState, key: KeyEvent) -> bool {
+    let search = &mut state.global_search;
+    match key.code {
+        KeyCode::Esc => *search = GlobalSearchState::default(),
+        KeyCode::Enter => {
+            let Some(result) = search.results.get(search.selected) else {
+                return false;
+            };
+            let (chat_id, message_id) = (result.chat_id, result.message_id);
+            *search = GlobalSearchState::default();
+            if let Some(index) = state.chats.iter().position(|chat| chat.id == chat_id) {
+                select_chat(&mut state.chats, index);
+            }
+            state.chat_filter = ChatFilterState::default();
+            state.focus = UiFocus::Messages;
+            state.actions.push(UiAction::JumpToMessage {
+                chat_id,
+                message_id,
+            });
+        }
+        KeyCode::Up => search.selected = search.selected.saturating_sub(1),
+        KeyCode::Down => {
+            search.selected = (search.selected + 1).min(search.results.len().saturating_sub(1));
+        }
+        _ => {
+            let before = search.query.text.clone();
+            if !handle_text_key(&mut search.query, key) {
+                return false;
+            }
+            if search.query.text != before {
+                request_global_search(state);
+            }
+        }
+    }
+    true
+}
+
+fn request_global_search(state: &mut UiState) {
+    let query = state.global_search.query.text.trim().to_string();
+    state.global_search.selected = 0;
+    state
+        .actions
+        .retain(|action| !matches!(action, UiAction::SearchAllChats(_)));
+    state.actions.push(UiAction::SearchAllChats(query));
+}
+
+/// `Alt` plus an arrow: left and right narrow and widen the chat list, up
+/// and down grow and shrink the composer.
+fn resize_panes(state: &mut UiState, key: KeyEvent) -> bool {
+    if key.modifiers != KeyModifiers::ALT {
+        return false;
+    }
+    match key.code {
+        KeyCode::Left => state.layout.resize_chat_list(-2),
+        KeyCode::Right => state.layout.resize_chat_list(2),
+        KeyCode::Up => state.layout.resize_composer(1),
+        KeyCode::Down => state.layout.resize_composer(-1),
+        _ => return false,
+    }
+    true
+}
+
+/// Selects the chat of the newest toast and dismisses that chat's toasts.
+fn jump_to_toast(state: &mut UiState) -> bool {
+    let Some(chat_id) = state.toasts.last().map(|toast| toast.chat_id) else {
+        return false;
+    };
+    state.toasts.retain(|toast| toast.chat_id != chat_id);
+    let Some(index) = state.chats.iter().position(|chat| chat.id == chat_id) else {
+        return true;
+    };
+    select_chat(&mut state.chats, index);
+    state.chat_filter = ChatFilterState::default();
+    state.focus = UiFocus::Messages;
+    true
+}
+
 fn cycle_focus(state: &mut UiState) {
+    state.chat_filter = ChatFilterState::default();
+    state.forwarding = None;
     state.focus = match state.focus {
         UiFocus::Chats => UiFocus::Messages,
         UiFocus::Messages => UiFocus::Composer,
@@ -38,7 +323,53 @@ fn cycle_focus(state: &mut UiState) {
 }
 
 fn handle_chats_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -> bool {
+    if state.forwarding.is_some() && key.modifiers == KeyModifiers::NONE {
+        match key.code {
+            KeyCode::Enter => return forward_to_selected_chat(state),
+            KeyCode::Esc if !state.chat_filter.is_open => {
+                state.forwarding = None;
+                state.focus = UiFocus::Messages;
+                return true;
+            }
+            _ => {}
+        }
+    }
+    if state.chat_filter.is_open {
+        return handle_chat_filter_key(state, key);
+    }
     match (key.code, style) {
+        (KeyCode::Char('/'), _) if key.modifiers == KeyModifiers::NONE => {
+            state.chat_filter = ChatFilterState {
+                is_open: true,
+                ..Default::default()
+            };
+            true
+        }
+        // The app re-sorts the list on its next refresh.
+        (KeyCode::Char('s'), _) if key.modifiers == KeyModifiers::NONE => {
+            state.chat_sort = state.chat_sort.next();
+            true
+        }
+        (KeyCode::Char('m'), _) if key.modifiers == KeyModifiers::NONE => {
+            let Some(chat) = state.chats.iter().find(|chat| chat.is_selected) else {
+                return false;
+            };
+            state.actions.push(UiAction::ToggleMute {
+                chat_id: chat.id,
+                muted: !chat.muted,
+            });
+            true
+        }
+        (KeyCode::Char('p'), _) if key.modifiers == KeyModifiers::NONE => {
+            let Some(chat) = state.chats.iter().find(|chat| chat.is_selected) else {
+                return false;
+            };
+            state.actions.push(UiAction::TogglePin {
+                chat_id: chat.id,
+                pinned: !chat.pinned,
+            });
+            true
+        }
         (KeyCode::Up, _) => {
             move_chat_selection(&mut state.chats, -1);
             true
@@ -68,6 +399,18 @@ fn handle_chats_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -> b
 }
 
 fn handle_messages_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -> bool {
+    // Anything but a second `d` cancels a pending delete.
+    if std::mem::take(&mut state.confirm_delete) {
+        if key.code == KeyCode::Char('d') && key.modifiers == KeyModifiers::NONE {
+            let message_ids = take_selection(state);
+            state.actions.push(UiAction::DeleteMessages(message_ids));
+            return true;
+        }
+        if key.code == KeyCode::Esc {
+            return true;
+        }
+    }
+    let has_selection = !state.message_view.selected_ids.is_empty();
     match key {
         KeyEvent {
             code: KeyCode::Char('i'),
@@ -92,6 +435,44 @@ fn handle_messages_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -
             modifiers: KeyModifiers::NONE,
             ..
         } if style == KeymapStyle::Vim => jump_search_match(state, false),
+        KeyEvent {
+            code: KeyCode::Char('v' | 'V'),
+            modifiers,
+            ..
+        } if style == KeymapStyle::Vim
+            && !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            toggle_visual_mode(state)
+        }
+        KeyEvent {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+            ..
+        } if state.message_view.visual.is_some() => {
+            state.message_view.end_visual(true);
+            true
+        }
+        KeyEvent {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+            ..
+        } if has_selection => {
+            state.message_view.selected_ids.clear();
+            true
+        }
+        KeyEvent {
+            code: KeyCode::Char('f'),
+            modifiers: KeyModifiers::NONE,
+            ..
+        } if has_selection => start_forwarding(state),
+        KeyEvent {
+            code: KeyCode::Char('d'),
+            modifiers: KeyModifiers::NONE,
+            ..
+        } if has_selection => {
+            state.confirm_delete = true;
+            true
+        }
         KeyEvent {
             code: KeyCode::Char('j'),
             modifiers: KeyModifiers::NONE,
@@ -117,6 +498,62 @@ fn handle_messages_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -
             modifiers: KeyModifiers::NONE,
             ..
         } => toggle_message_selection(state),
+        KeyEvent {
+            code: KeyCode::Char('y'),
+            modifiers: KeyModifiers::NONE,
+            ..
+        } => copy_messages(state),
+        KeyEvent {
+            code: KeyCode::Char('r'),
+            modifiers: KeyModifiers::NONE,
+            ..
+        } => reply_to_cursor_message(state),
+        KeyEvent {
+            code: KeyCode::Char('x'),
+            modifiers: KeyModifiers::NONE,
+            ..
+        } => state.message_view.toggle_spoilers(&state.messages),
+        KeyEvent {
+            code: KeyCode::Char('u'),
+            modifiers: KeyModifiers::NONE,
+            ..
+        } => jump_to_replied_message(state),
+        KeyEvent {
+            code: KeyCode::Char(c @ ('s' | 'o' | 't')),
+            modifiers: KeyModifiers::NONE,
+            ..
+        } => {
+            let Some(message) = state
+                .message_view
+                .cursor
+                .and_then(|index| state.messages.get(index))
+                .filter(|message| message.media.is_some())
+            else {
+                return false;
+            };
+            state.actions.push(match c {
+                's' => UiAction::SaveMedia(message.id),
+                'o' => UiAction::OpenMedia(message.id),
+                _ => UiAction::TranscribeVoice(message.id),
+            });
+            true
+        }
+        KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+            ..
+        } => match state.message_view.cursor_message_id(&state.messages) {
+            Some(message_id) => {
+                state.actions.push(UiAction::OpenMessageDetail(message_id));
+                true
+            }
+            None => false,
+        },
+        KeyEvent {
+            code: KeyCode::Char('c'),
+            modifiers,
+            ..
+        } if modifiers.contains(KeyModifiers::CONTROL) => copy_messages(state),
         KeyEvent {
             code: KeyCode::Up,
             modifiers: KeyModifiers::NONE,
@@ -162,6 +599,20 @@ fn handle_messages_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -
             let forward = !modifiers.contains(KeyModifiers::SHIFT);
             jump_search_match(state, forward)
         }
+        KeyEvent {
+            code: KeyCode::Char('r'),
+            modifiers,
+            ..
+        } if modifiers.contains(KeyModifiers::CONTROL) => {
+            request_send_action(state, UiAction::RetrySend)
+        }
+        KeyEvent {
+            code: KeyCode::Char('d'),
+            modifiers,
+            ..
+        } if modifiers.contains(KeyModifiers::CONTROL) => {
+            request_send_action(state, UiAction::DiscardSend)
+        }
         KeyEvent {
             code: KeyCode::Char('b'),
             modifiers,
@@ -181,7 +632,18 @@ fn handle_messages_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -
 }
 
 fn handle_composer_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -> bool {
+    if state.emoji_picker.is_open {
+        return handle_emoji_key(state, key);
+    }
     match key {
+        KeyEvent {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+            ..
+        } if state.reply_to.is_some() => {
+            state.reply_to = None;
+            true
+        }
         KeyEvent {
             code: KeyCode::Esc,
             modifiers: KeyModifiers::NONE,
@@ -198,10 +660,147 @@ fn handle_composer_key(state: &mut UiState, key: KeyEvent, style: KeymapStyle) -
             state.focus = UiFocus::Messages;
             true
         }
+        KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+            ..
+        } => {
+            if state.input.text.trim().is_empty() {
+                return false;
+            }
+            state.actions.push(UiAction::SendComposer);
+            true
+        }
+        KeyEvent {
+            code: KeyCode::Enter,
+            modifiers,
+            ..
+        } if modifiers.intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) => {
+            state.input.insert_newline();
+            true
+        }
+        KeyEvent {
+            code: KeyCode::Char(' '),
+            modifiers,
+            ..
+        } if modifiers.contains(KeyModifiers::CONTROL) => {
+            state.emoji_picker.open(false);
+            true
+        }
+        KeyEvent {
+            code: KeyCode::Char('o'),
+            modifiers,
+            ..
+        } if modifiers.contains(KeyModifiers::CONTROL) => {
+            state.attach_picker.open();
+            true
+        }
+        KeyEvent {
+            code: KeyCode::Char(':'),
+            modifiers,
+            ..
+        } if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+            && state.input.at_word_start() =>
+        {
+            state.emoji_picker.open(true);
+            true
+        }
         _ => handle_text_key(&mut state.input, key),
     }
 }
 
+/// Keys while the emoji picker is open. Typing anything that cannot be part
+/// of an emoji name gives up on the picker and keeps what was typed, so `:)`
+/// and `a : b` still reach the draft.
+fn handle_emoji_key(state: &mut UiState, key: KeyEvent) -> bool {
+    let picker = &mut state.emoji_picker;
+    match key.code {
+        KeyCode::Enter => {
+            let Some(emoji) = picker.selected_emoji() else {
+                dismiss_emoji_picker(state, None);
+                return true;
+            };
+            picker.remember(emoji);
+            picker.close();
+            state.input.insert_str(emoji.glyph);
+            true
+        }
+        KeyCode::Esc => {
+            dismiss_emoji_picker(state, None);
+            true
+        }
+        KeyCode::Up => {
+            picker.move_selection(-1);
+            true
+        }
+        KeyCode::Down => {
+            picker.move_selection(1);
+            true
+        }
+        KeyCode::Backspace if picker.query.text.is_empty() => {
+            picker.close();
+            true
+        }
+        KeyCode::Char(c)
+            if !key.modifiers.contains(KeyModifiers::CONTROL) && !is_emoji_name_char(c) =>
+        {
+            dismiss_emoji_picker(state, Some(c));
+            true
+        }
+        _ => {
+            let handled = handle_text_key(&mut picker.query, key);
+            if handled {
+                picker.recompute_matches();
+            }
+            handled
+        }
+    }
+}
+
+/// Keys while the attachment picker is open. Enter sends the file once the
+/// path names one.
+fn handle_attach_key(state: &mut UiState, key: KeyEvent) -> bool {
+    let picker = &mut state.attach_picker;
+    match key.code {
+        KeyCode::Esc => picker.close(),
+        KeyCode::Tab => picker.complete(),
+        KeyCode::Enter => {
+            let Some(path) = picker.selected_file() else {
+                return false;
+            };
+            picker.close();
+            state.actions.push(UiAction::SendAttachment(path));
+        }
+        _ => {
+            let handled = handle_text_key(&mut picker.path, key);
+            if handled {
+                picker.completions.clear();
+            }
+            return handled;
+        }
+    }
+    true
+}
+
+/// Characters that can appear in an emoji name, like `+1` or `sweat_smile`.
+fn is_emoji_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '+' | '-')
+}
+
+/// Closes the picker, returning the `:` and query typed so far to the draft,
+/// followed by `next` when a character ended the search.
+fn dismiss_emoji_picker(state: &mut UiState, next: Option<char>) {
+    let picker = &mut state.emoji_picker;
+    let mut typed = String::new();
+    if picker.opened_by_colon {
+        typed.push(':');
+        typed.push_str(&picker.query.text);
+    }
+    typed.extend(next);
+    picker.close();
+    state.input.insert_str(&typed);
+}
+
 fn handle_search_key(state: &mut UiState, key: KeyEvent) -> bool {
     match key {
         KeyEvent {
@@ -221,7 +820,7 @@ fn handle_search_key(state: &mut UiState, key: KeyEvent) -> bool {
             let selected = state.message_view.search.selected_match();
             if let Some(match_index) = selected {
                 state.message_view.cursor = Some(match_index);
-                state.message_view.scroll_offset = match_index;
+                state.message_view.scroll_to(match_index);
                 return true;
             }
             false
@@ -258,7 +857,7 @@ fn jump_search_match(state: &mut UiState, forward: bool) -> bool {
     let match_index = state.message_view.search.advance(forward);
     if let Some(index) = match_index {
         state.message_view.cursor = Some(index);
-        state.message_view.scroll_offset = index;
+        state.message_view.scroll_to(index);
         return true;
     }
     false
@@ -274,9 +873,13 @@ fn move_message_cursor(state: &mut UiState, delta: i32) -> bool {
         .cursor
         .map(|index| index as i32)
         .unwrap_or(max_index);
+    // Pushing past the oldest loaded message asks for the page before it.
+    if current == 0 && delta < 0 && !state.actions.contains(&UiAction::LoadOlderMessages) {
+        state.actions.push(UiAction::LoadOlderMessages);
+    }
     let next = (current + delta).clamp(0, max_index) as usize;
     state.message_view.cursor = Some(next);
-    ensure_cursor_visible(state);
+    follow_cursor(state);
     true
 }
 
@@ -287,7 +890,7 @@ fn jump_message_cursor(state: &mut UiState, index: usize) -> bool {
     let max_index = state.messages.len().saturating_sub(1);
     let next = index.min(max_index);
     state.message_view.cursor = Some(next);
-    ensure_cursor_visible(state);
+    follow_cursor(state);
     true
 }
 
@@ -297,20 +900,34 @@ fn jump_message_cursor_to_end(state: &mut UiState) -> bool {
     }
     let max_index = state.messages.len().saturating_sub(1);
     state.message_view.cursor = Some(max_index);
-    ensure_cursor_visible(state);
+    follow_cursor(state);
     true
 }
 
+/// Keeps the cursor on screen and any visual range following it.
+fn follow_cursor(state: &mut UiState) {
+    ensure_cursor_visible(state);
+    state.message_view.extend_visual(&state.messages);
+}
+
+/// Scrolls just enough for the whole cursor message to show, or its start
+/// when it is taller than the pane.
 fn ensure_cursor_visible(state: &mut UiState) {
     let Some(cursor) = state.message_view.cursor else {
         return;
     };
+    let heights = message_heights(state, state.message_view.page_width);
+    let Some(height) = heights.get(cursor) else {
+        return;
+    };
     let page_size = state.message_view.page_size.max(1);
-    let scroll = state.message_view.scroll_offset;
-    if cursor < scroll {
-        state.message_view.scroll_offset = cursor;
-    } else if cursor >= scroll + page_size {
-        state.message_view.scroll_offset = cursor + 1 - page_size;
+    let top = top_row(state, &heights);
+    let cursor_top: usize = heights[..cursor].iter().sum();
+    let cursor_bottom = cursor_top + height;
+    if cursor_top < top {
+        set_top_row(state, &heights, cursor_top);
+    } else if cursor_bottom > top + page_size {
+        set_top_row(state, &heights, cursor_top.min(cursor_bottom - page_size));
     }
 }
 
@@ -319,25 +936,279 @@ fn scroll_page(state: &mut UiState, direction: i32) -> bool {
     scroll_by(state, direction * page)
 }
 
+/// Scrolls by `delta` wrapped rows, stopping once the last row is at the
+/// bottom of the pane.
 fn scroll_by(state: &mut UiState, delta: i32) -> bool {
     if state.messages.is_empty() {
         return false;
     }
-    let max_offset = state.messages.len() as i32 - 1;
-    let current = state.message_view.scroll_offset as i32;
-    let next = (current + delta).clamp(0, max_offset) as usize;
-    state.message_view.scroll_offset = next;
+    let heights = message_heights(state, state.message_view.page_width);
+    let total: usize = heights.iter().sum();
+    let max_top = total.saturating_sub(state.message_view.page_size.max(1));
+    let current = top_row(state, &heights) as i64;
+    let next = (current + i64::from(delta)).clamp(0, max_top as i64) as usize;
+    set_top_row(state, &heights, next);
     true
 }
 
-fn toggle_message_selection(state: &mut UiState) -> bool {
-    let Some(message_id) = state.message_view.cursor_message_id(&state.messages) else {
-        return false;
-    };
-    state.message_view.toggle_selection(message_id);
+/// Wrapped rows above the top of the pane.
+fn top_row(state: &UiState, heights: &[usize]) -> usize {
+    let view = &state.message_view;
+    let top = view.scroll_offset.min(heights.len().saturating_sub(1));
+    let rows_into_top = heights
+        .get(top)
+        .map_or(0, |height| view.scroll_rows.min(height - 1));
+    heights[..top].iter().sum::<usize>() + rows_into_top
+}
+
+/// Scrolls so `row` of the wrapped messages is at the top of the pane.
+fn set_top_row(state: &mut UiState, heights: &[usize], row: usize) {
+    let mut above = 0;
+    for (index, height) in heights.iter().enumerate() {
+        if row < above + height {
+            state.message_view.scroll_offset = index;
+            state.message_view.scroll_rows = row - above;
+            return;
+        }
+        above += height;
+    }
+    state
+        .message_view
+        .scroll_to(heights.len().saturating_sub(1));
+}
+
+/// Replies to the cursor message from the composer. Local echoes have no
+/// Telegram id yet and cannot be replied to.
+fn reply_to_cursor_message(state: &mut UiState) -> bool {
+    let message = state
+        .message_view
+        .cursor
+        .and_then(|index| state.messages.get(index))
+        .filter(|message| message.id > 0);
+    let Some(message) = message else {
+        return false;
+    };
+    state.reply_to = Some(ReplyTarget::for_message(message));
+    state.focus = UiFocus::Composer;
+    true
+}
+
+/// Ends visual mode and empties the selection, returning its ids oldest
+/// first.
+fn take_selection(state: &mut UiState) -> Vec<i64> {
+    state.message_view.end_visual(false);
+    std::mem::take(&mut state.message_view.selected_ids)
+        .into_iter()
+        .collect()
+}
+
+/// Takes the selection and moves to the chat list to pick where it goes.
+fn start_forwarding(state: &mut UiState) -> bool {
+    let Some(from_chat_id) = state
+        .chats
+        .iter()
+        .find(|chat| chat.is_selected)
+        .map(|chat| chat.id)
+    else {
+        return false;
+    };
+    let message_ids = take_selection(state);
+    state.forwarding = Some(ForwardState {
+        from_chat_id,
+        message_ids,
+    });
+    state.chat_filter = ChatFilterState::default();
+    state.focus = UiFocus::Chats;
+    true
+}
+
+/// Moves the cursor to the message the cursor message replies to; the app
+/// loads older history first when the original is not in the list.
+fn jump_to_replied_message(state: &mut UiState) -> bool {
+    let Some(message_id) = state
+        .message_view
+        .cursor
+        .and_then(|index| state.messages.get(index))
+        .and_then(|message| message.reply_to.as_ref())
+        .map(|reply| reply.message_id)
+    else {
+        return false;
+    };
+    if let Some(index) = state
+        .messages
+        .iter()
+        .position(|message| message.id == message_id)
+    {
+        state.message_view.cursor = Some(index);
+        state.message_view.scroll_to(index);
+        return true;
+    }
+    let Some(chat_id) = state
+        .chats
+        .iter()
+        .find(|chat| chat.is_selected)
+        .map(|chat| chat.id)
+    else {
+        return false;
+    };
+    state.actions.push(UiAction::JumpToMessage {
+        chat_id,
+        message_id,
+    });
+    true
+}
+
+fn forward_to_selected_chat(state: &mut UiState) -> bool {
+    let Some(forwarding) = state.forwarding.take() else {
+        return false;
+    };
+    if let Some(to_chat_id) = state
+        .chats
+        .iter()
+        .find(|chat| chat.is_selected)
+        .map(|chat| chat.id)
+    {
+        state.actions.push(UiAction::ForwardMessages {
+            from_chat_id: forwarding.from_chat_id,
+            to_chat_id,
+            message_ids: forwarding.message_ids,
+        });
+    }
+    state.chat_filter = ChatFilterState::default();
+    state.focus = UiFocus::Messages;
+    true
+}
+
+fn toggle_visual_mode(state: &mut UiState) -> bool {
+    if state.message_view.visual.is_some() {
+        state.message_view.end_visual(false);
+        return true;
+    }
+    state.message_view.start_visual(&state.messages)
+}
+
+fn toggle_message_selection(state: &mut UiState) -> bool {
+    let Some(message_id) = state.message_view.cursor_message_id(&state.messages) else {
+        return false;
+    };
+    state.message_view.toggle_selection(message_id);
+    true
+}
+
+/// Copies the selected messages, or the cursor message when nothing is
+/// selected, one `[time] author: body` line each.
+fn copy_messages(state: &mut UiState) -> bool {
+    let view = &state.message_view;
+    let lines: Vec<String> = if view.selected_ids.is_empty() {
+        view.cursor
+            .and_then(|index| state.messages.get(index))
+            .map(format_for_clipboard)
+            .into_iter()
+            .collect()
+    } else {
+        state
+            .messages
+            .iter()
+            .filter(|message| view.selected_ids.contains(&message.id))
+            .map(format_for_clipboard)
+            .collect()
+    };
+    if lines.is_empty() {
+        return false;
+    }
+    state.actions.push(UiAction::CopyText(lines.join("\n")));
+    state.message_view.end_visual(false);
     true
 }
 
+fn format_for_clipboard(message: &MessageItem) -> String {
+    if message.timestamp.is_empty() {
+        format!("{}: {}", message.author, message.body)
+    } else {
+        format!(
+            "[{}] {}: {}",
+            message.timestamp, message.author, message.body
+        )
+    }
+}
+
+/// Local echoes of sends carry the negated send id until Telegram assigns one.
+/// Retries or discards the local echo under the cursor, or else the newest
+/// failed send the banner shows.
+fn request_send_action(state: &mut UiState, action: fn(u64) -> UiAction) -> bool {
+    let message_id = state
+        .message_view
+        .cursor_message_id(&state.messages)
+        .filter(|message_id| *message_id < 0)
+        .or_else(|| state.failed_sends.last().map(|failed| failed.message_id));
+    match message_id {
+        Some(message_id) => {
+            state.actions.push(action(message_id.unsigned_abs()));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Keys while the chat filter is open: typing narrows the list, Up/Down move
+/// through the matches, Enter picks the highlighted one and Esc drops the
+/// filter.
+fn handle_chat_filter_key(state: &mut UiState, key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Esc => {
+            state.chat_filter = ChatFilterState::default();
+            true
+        }
+        // Typing already highlighted the top match.
+        KeyCode::Enter => {
+            if !state.chat_filter.matches(&state.chats).is_empty() {
+                state.focus = UiFocus::Messages;
+            }
+            state.chat_filter = ChatFilterState::default();
+            true
+        }
+        KeyCode::Up | KeyCode::Down => {
+            let matches = state.chat_filter.matches(&state.chats);
+            let Some(last) = matches.len().checked_sub(1) else {
+                return true;
+            };
+            let current = matches
+                .iter()
+                .position(|idx| state.chats[*idx].is_selected)
+                .unwrap_or(0);
+            let next = if key.code == KeyCode::Up {
+                current.saturating_sub(1)
+            } else {
+                (current + 1).min(last)
+            };
+            select_chat(&mut state.chats, matches[next]);
+            true
+        }
+        _ => {
+            let handled = handle_text_key(&mut state.chat_filter.query, key);
+            if handled {
+                select_top_chat_match(state);
+            }
+            handled
+        }
+    }
+}
+
+/// Highlights the best match of the chat filter, if there is one.
+fn select_top_chat_match(state: &mut UiState) -> bool {
+    let Some(top) = state.chat_filter.matches(&state.chats).first().copied() else {
+        return false;
+    };
+    select_chat(&mut state.chats, top);
+    true
+}
+
+fn select_chat(chats: &mut [ChatListItem], index: usize) {
+    for (idx, chat) in chats.iter_mut().enumerate() {
+        chat.is_selected = idx == index;
+    }
+}
+
 fn move_chat_selection(chats: &mut [ChatListItem], delta: i32) {
     if chats.is_empty() {
         return;
@@ -345,15 +1216,16 @@ fn move_chat_selection(chats: &mut [ChatListItem], delta: i32) {
     let current = chats.iter().position(|chat| chat.is_selected).unwrap_or(0) as i32;
     let max_index = chats.len() as i32 - 1;
     let next = (current + delta).clamp(0, max_index) as usize;
-    for (idx, chat) in chats.iter_mut().enumerate() {
-        chat.is_selected = idx == next;
-    }
+    select_chat(chats, next);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::view::{ChatListItem, MessageItem};
+    use crate::view::{
+        ChatListItem, FailedSend, GlobalSearchResult, LayoutConfig, MediaItem, MessageDetail, Toast,
+    };
+    use std::collections::BTreeSet;
 
     fn sample_state() -> UiState {
         let mut state = UiState {
@@ -362,13 +1234,23 @@ mod tests {
                     id: 1,
                     author: "Ada".to_string(),
                     timestamp: "09:10".to_string(),
+                    sent_at: 33000,
                     body: "hello".to_string(),
+                    expires_in: None,
+                    media: None,
+                    reply_to: None,
+                    delivery: None,
                 },
                 MessageItem {
                     id: 2,
                     author: "You".to_string(),
                     timestamp: "09:11".to_string(),
+                    sent_at: 33060,
                     body: "reply".to_string(),
+                    expires_in: None,
+                    media: None,
+                    reply_to: None,
+                    delivery: None,
                 },
             ],
             ..Default::default()
@@ -377,6 +1259,142 @@ mod tests {
         state
     }
 
+    #[test]
+    fn yank_copies_the_cursor_message_or_the_selection() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Messages;
+        state.message_view.cursor = Some(0);
+
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        ));
+        state.message_view.toggle_selection(1);
+        state.message_view.toggle_selection(2);
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+            KeymapStyle::Vscode,
+        ));
+
+        assert_eq!(
+            state.actions,
+            vec![
+                UiAction::CopyText("[09:10] Ada: hello".to_string()),
+                UiAction::CopyText("[09:10] Ada: hello\n[09:11] You: reply".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn visual_mode_selects_the_range_to_the_cursor() {
+        let mut state = sample_state();
+        state.messages.push(MessageItem {
+            id: 3,
+            author: "Ada".to_string(),
+            timestamp: "09:12".to_string(),
+            sent_at: 33120,
+            body: "third".to_string(),
+            expires_in: None,
+            media: None,
+            reply_to: None,
+            delivery: None,
+        });
+        state.focus = UiFocus::Messages;
+        state.message_view.cursor = Some(2);
+        let press = |state: &mut UiState, code| {
+            handle_ui_key(
+                state,
+                KeyEvent::new(code, KeyModifiers::NONE),
+                KeymapStyle::Vim,
+            )
+        };
+
+        assert!(press(&mut state, KeyCode::Char('v')));
+        press(&mut state, KeyCode::Char('k'));
+        assert_eq!(state.message_view.selected_ids, BTreeSet::from([2, 3]));
+        press(&mut state, KeyCode::Char('k'));
+        press(&mut state, KeyCode::Char('j'));
+        assert_eq!(state.message_view.selected_ids, BTreeSet::from([2, 3]));
+
+        press(&mut state, KeyCode::Esc);
+        assert!(state.message_view.visual.is_none());
+        assert!(state.message_view.selected_ids.is_empty());
+
+        press(&mut state, KeyCode::Char('V'));
+        press(&mut state, KeyCode::Char('g'));
+        press(&mut state, KeyCode::Char('y'));
+        assert!(state.message_view.visual.is_none());
+        assert_eq!(state.message_view.selected_ids, BTreeSet::from([1, 2]));
+        assert_eq!(state.actions.len(), 1);
+    }
+
+    #[test]
+    fn enter_opens_message_detail_and_esc_closes_it() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Messages;
+        state.message_view.cursor = Some(1);
+
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        ));
+        assert_eq!(state.actions, vec![UiAction::OpenMessageDetail(2)]);
+
+        state.message_detail = Some(MessageDetail {
+            id: 2,
+            ..Default::default()
+        });
+        handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Down, KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        );
+        assert_eq!(
+            state.message_detail.as_ref().map(|detail| detail.scroll),
+            Some(1)
+        );
+        assert_eq!(state.message_view.cursor, Some(1));
+
+        handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        );
+        assert!(state.message_detail.is_none());
+    }
+
+    #[test]
+    fn r_sets_a_reply_target_that_esc_clears() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Messages;
+        state.message_view.cursor = Some(0);
+
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        ));
+        assert_eq!(state.focus, UiFocus::Composer);
+        assert_eq!(
+            state.reply_to,
+            Some(ReplyTarget {
+                message_id: 1,
+                author: "Ada".to_string(),
+                snippet: "hello".to_string(),
+            })
+        );
+
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        handle_ui_key(&mut state, esc, KeymapStyle::Vscode);
+        assert!(state.reply_to.is_none());
+        assert_eq!(state.focus, UiFocus::Composer);
+        handle_ui_key(&mut state, esc, KeymapStyle::Vscode);
+        assert_eq!(state.focus, UiFocus::Messages);
+    }
+
     #[test]
     fn vim_jk_moves_message_cursor() {
         let mut state = sample_state();
@@ -419,6 +1437,276 @@ mod tests {
         assert_eq!(state.message_view.cursor, Some(0));
     }
 
+    #[test]
+    fn scrolls_by_wrapped_rows_to_show_the_whole_cursor_message() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Messages;
+        state.messages[1].body = "word ".repeat(12);
+        state.message_view.page_width = 20;
+        let heights = message_heights(&state, 20);
+        assert!(heights[0] >= 2 && heights[1] >= 2);
+        state.message_view.page_size = heights[1] + 1;
+        state.message_view.cursor = Some(0);
+
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        handle_ui_key(&mut state, key(KeyCode::Down), KeymapStyle::Vscode);
+        assert_eq!(state.message_view.cursor, Some(1));
+        assert_eq!(state.message_view.scroll_offset, 0);
+        assert_eq!(state.message_view.scroll_rows, heights[0] - 1);
+
+        handle_ui_key(&mut state, key(KeyCode::PageUp), KeymapStyle::Vscode);
+        assert_eq!(
+            (
+                state.message_view.scroll_offset,
+                state.message_view.scroll_rows
+            ),
+            (0, 0)
+        );
+        handle_ui_key(&mut state, key(KeyCode::PageDown), KeymapStyle::Vscode);
+        handle_ui_key(&mut state, key(KeyCode::PageDown), KeymapStyle::Vscode);
+        assert_eq!(
+            (
+                state.message_view.scroll_offset,
+                state.message_view.scroll_rows
+            ),
+            (0, heights[0] - 1)
+        );
+    }
+
+    #[test]
+    fn x_reveals_and_hides_the_cursor_message_spoilers() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Messages;
+        state.message_view.cursor = Some(1);
+        let x = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+
+        assert!(handle_ui_key(&mut state, x, KeymapStyle::Vim));
+        assert!(state.message_view.revealed_spoilers.contains(&2));
+        handle_ui_key(&mut state, x, KeymapStyle::Vim);
+        assert!(state.message_view.revealed_spoilers.is_empty());
+    }
+
+    #[test]
+    fn media_keys_act_on_the_cursor_attachment() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Messages;
+        state.messages[1].media = Some(MediaItem {
+            label: "voice 0:42".to_string(),
+            has_preview: false,
+            transcript: None,
+        });
+        let press = |state: &mut UiState, c: char| {
+            handle_ui_key(
+                state,
+                KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+                KeymapStyle::Vim,
+            )
+        };
+
+        state.message_view.cursor = Some(0);
+        assert!(!press(&mut state, 's'));
+        state.message_view.cursor = Some(1);
+        for c in ['s', 'o', 't'] {
+            assert!(press(&mut state, c));
+        }
+
+        assert_eq!(
+            state.actions,
+            vec![
+                UiAction::SaveMedia(2),
+                UiAction::OpenMedia(2),
+                UiAction::TranscribeVoice(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn u_jumps_to_the_message_a_reply_quotes() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Messages;
+        state.chats = vec![ChatListItem {
+            id: 10,
+            title: "Chat".to_string(),
+            unread: 0,
+            is_selected: true,
+            section: String::new(),
+            typing: false,
+            muted: false,
+            pinned: false,
+        }];
+        state.messages[1].reply_to = Some(ReplyTarget::for_message(&state.messages[0]));
+        let u = KeyEvent::new(KeyCode::Char('u'), KeyModifiers::NONE);
+
+        state.message_view.cursor = Some(0);
+        assert!(!handle_ui_key(&mut state, u, KeymapStyle::Vim));
+
+        state.message_view.cursor = Some(1);
+        assert!(handle_ui_key(&mut state, u, KeymapStyle::Vim));
+        assert_eq!(state.message_view.cursor, Some(0));
+        assert!(state.actions.is_empty());
+
+        state.messages[1].reply_to = Some(ReplyTarget {
+            message_id: -5,
+            author: String::new(),
+            snippet: String::new(),
+        });
+        state.message_view.cursor = Some(1);
+        assert!(handle_ui_key(&mut state, u, KeymapStyle::Vim));
+        assert_eq!(
+            state.actions,
+            vec![UiAction::JumpToMessage {
+                chat_id: 10,
+                message_id: -5,
+            }]
+        );
+    }
+
+    #[test]
+    fn moving_past_the_top_asks_for_older_messages_and_keeps_the_cursor() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Messages;
+        state.message_view.cursor = Some(1);
+        let up = KeyEvent::new(KeyCode::Up, KeyModifiers::NONE);
+
+        handle_ui_key(&mut state, up, KeymapStyle::Vscode);
+        assert!(state.actions.is_empty());
+        handle_ui_key(&mut state, up, KeymapStyle::Vscode);
+        handle_ui_key(&mut state, up, KeymapStyle::Vscode);
+        assert_eq!(state.actions, vec![UiAction::LoadOlderMessages]);
+
+        let older = MessageItem {
+            id: -5,
+            ..state.messages[0].clone()
+        };
+        let previous = state.messages.clone();
+        state.messages.insert(0, older);
+        state.message_view.follow(&previous, &state.messages);
+        assert_eq!(state.message_view.cursor, Some(1));
+        assert_eq!(state.message_view.scroll_offset, 1);
+    }
+
+    #[test]
+    fn bulk_keys_act_on_the_selection() {
+        let mut state = sample_state();
+        state.chats = [10, 11]
+            .map(|id| ChatListItem {
+                id,
+                title: format!("Chat {id}"),
+                unread: 0,
+                is_selected: id == 10,
+                section: String::new(),
+                typing: false,
+                muted: false,
+                pinned: false,
+            })
+            .to_vec();
+        state.focus = UiFocus::Messages;
+        let press = |state: &mut UiState, code: KeyCode| {
+            handle_ui_key(
+                state,
+                KeyEvent::new(code, KeyModifiers::NONE),
+                KeymapStyle::Vscode,
+            )
+        };
+        let select_both = |state: &mut UiState| {
+            state.message_view.selected_ids.extend([1, 2]);
+        };
+
+        assert!(!press(&mut state, KeyCode::Char('d')));
+        select_both(&mut state);
+        press(&mut state, KeyCode::Char('d'));
+        assert!(state.confirm_delete);
+        press(&mut state, KeyCode::Esc);
+        assert!(!state.confirm_delete);
+        assert_eq!(state.message_view.selected_ids.len(), 2);
+        press(&mut state, KeyCode::Char('d'));
+        press(&mut state, KeyCode::Char('d'));
+        assert_eq!(state.actions, vec![UiAction::DeleteMessages(vec![1, 2])]);
+        assert!(state.message_view.selected_ids.is_empty());
+        state.actions.clear();
+
+        select_both(&mut state);
+        press(&mut state, KeyCode::Char('f'));
+        assert_eq!(state.focus, UiFocus::Chats);
+        assert!(state.message_view.selected_ids.is_empty());
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Enter);
+        assert_eq!(
+            state.actions,
+            vec![UiAction::ForwardMessages {
+                from_chat_id: 10,
+                to_chat_id: 11,
+                message_ids: vec![1, 2],
+            }]
+        );
+        assert_eq!(state.focus, UiFocus::Messages);
+        assert!(state.forwarding.is_none());
+
+        select_both(&mut state);
+        press(&mut state, KeyCode::Esc);
+        assert!(state.message_view.selected_ids.is_empty());
+    }
+
+    #[test]
+    fn palette_opens_global_search_and_enter_jumps_to_a_result() {
+        let mut state = sample_state();
+        state.chats = [10, 11]
+            .map(|id| ChatListItem {
+                id,
+                title: format!("Chat {id}"),
+                unread: 0,
+                is_selected: id == 10,
+                section: String::new(),
+                typing: false,
+                muted: false,
+                pinned: false,
+            })
+            .to_vec();
+        let press = |state: &mut UiState, code: KeyCode, modifiers: KeyModifiers| {
+            handle_ui_key(state, KeyEvent::new(code, modifiers), KeymapStyle::Vscode)
+        };
+
+        press(&mut state, KeyCode::Char('p'), KeyModifiers::CONTROL);
+        assert_eq!(state.command_palette.items.len(), PaletteCommand::ALL.len());
+        for c in "search".chars() {
+            press(&mut state, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        assert_eq!(state.command_palette.items, ["Search all chats"]);
+        press(&mut state, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(!state.command_palette.is_open);
+        assert!(state.global_search.is_open);
+
+        press(&mut state, KeyCode::Char('h'), KeyModifiers::NONE);
+        press(&mut state, KeyCode::Char('i'), KeyModifiers::NONE);
+        assert_eq!(
+            state.actions,
+            vec![UiAction::SearchAllChats("hi".to_string())]
+        );
+        state.actions.clear();
+
+        state.global_search.results = [(10, 3), (11, 7)]
+            .map(|(chat_id, message_id)| GlobalSearchResult {
+                chat_id,
+                message_id,
+                chat_title: format!("Chat {chat_id}"),
+                author: "Ada".to_string(),
+                snippet: "[hi] there".to_string(),
+            })
+            .to_vec();
+        press(&mut state, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut state, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(!state.global_search.is_open);
+        assert!(state.chats[1].is_selected);
+        assert_eq!(state.focus, UiFocus::Messages);
+        assert_eq!(
+            state.actions,
+            vec![UiAction::JumpToMessage {
+                chat_id: 11,
+                message_id: 7
+            }]
+        );
+    }
+
     #[test]
     fn toggles_message_selection() {
         let mut state = sample_state();
@@ -472,12 +1760,20 @@ mod tests {
                     title: "General".to_string(),
                     unread: 0,
                     is_selected: true,
+                    section: String::new(),
+                    typing: false,
+                    muted: false,
+                    pinned: false,
                 },
                 ChatListItem {
                     id: 11,
                     title: "Design".to_string(),
                     unread: 1,
                     is_selected: false,
+                    section: String::new(),
+                    typing: false,
+                    muted: false,
+                    pinned: false,
                 },
             ],
             ..Default::default()
@@ -492,4 +1788,368 @@ mod tests {
         assert!(state.chats[1].is_selected);
         assert!(!state.chats[0].is_selected);
     }
+
+    #[test]
+    fn m_toggles_mute_of_the_selected_chat() {
+        let mut state = UiState {
+            focus: UiFocus::Chats,
+            chats: [10, 11]
+                .map(|id| ChatListItem {
+                    id,
+                    title: format!("Chat {id}"),
+                    unread: 0,
+                    is_selected: id == 10,
+                    section: String::new(),
+                    typing: false,
+                    muted: id == 11,
+                    pinned: false,
+                })
+                .to_vec(),
+            ..Default::default()
+        };
+        let press = |state: &mut UiState, code: KeyCode| {
+            handle_ui_key(
+                state,
+                KeyEvent::new(code, KeyModifiers::NONE),
+                KeymapStyle::Vim,
+            )
+        };
+
+        assert!(press(&mut state, KeyCode::Char('m')));
+        press(&mut state, KeyCode::Char('j'));
+        assert!(press(&mut state, KeyCode::Char('m')));
+
+        assert_eq!(
+            state.actions,
+            vec![
+                UiAction::ToggleMute {
+                    chat_id: 10,
+                    muted: true
+                },
+                UiAction::ToggleMute {
+                    chat_id: 11,
+                    muted: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn p_toggles_pin_of_the_selected_chat() {
+        let mut state = UiState {
+            focus: UiFocus::Chats,
+            chats: [10, 11]
+                .map(|id| ChatListItem {
+                    id,
+                    title: format!("Chat {id}"),
+                    unread: 0,
+                    is_selected: id == 11,
+                    section: String::new(),
+                    typing: false,
+                    muted: false,
+                    pinned: id == 11,
+                })
+                .to_vec(),
+            ..Default::default()
+        };
+
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('p'), KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        ));
+
+        assert_eq!(
+            state.actions,
+            vec![UiAction::TogglePin {
+                chat_id: 11,
+                pinned: false
+            }]
+        );
+    }
+
+    #[test]
+    fn chat_filter_narrows_the_list_and_enter_picks_the_top_match() {
+        let chat = |id: i64, title: &str| ChatListItem {
+            id,
+            title: title.to_string(),
+            unread: 0,
+            is_selected: id == 10,
+            section: String::new(),
+            typing: false,
+            muted: false,
+            pinned: false,
+        };
+        let mut state = UiState {
+            focus: UiFocus::Chats,
+            chats: vec![chat(10, "General"), chat(11, "Design"), chat(12, "Dev ops")],
+            ..Default::default()
+        };
+        let press = |state: &mut UiState, code: KeyCode| {
+            handle_ui_key(
+                state,
+                KeyEvent::new(code, KeyModifiers::NONE),
+                KeymapStyle::Vscode,
+            )
+        };
+
+        press(&mut state, KeyCode::Char('/'));
+        for c in "de".chars() {
+            press(&mut state, KeyCode::Char(c));
+        }
+        assert_eq!(state.chat_filter.matches(&state.chats), vec![1, 2]);
+        assert!(state.chats[1].is_selected);
+
+        press(&mut state, KeyCode::Down);
+        assert!(state.chats[2].is_selected);
+        press(&mut state, KeyCode::Char('v'));
+        press(&mut state, KeyCode::Enter);
+        assert!(state.chats[2].is_selected);
+        assert!(!state.chat_filter.is_open);
+        assert_eq!(state.focus, UiFocus::Messages);
+    }
+
+    #[test]
+    fn ctrl_g_jumps_to_the_chat_of_the_newest_toast() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Composer;
+        state.chats = [10, 11]
+            .map(|id| ChatListItem {
+                id,
+                title: format!("Chat {id}"),
+                unread: 0,
+                is_selected: id == 10,
+                section: String::new(),
+                typing: false,
+                muted: false,
+                pinned: false,
+            })
+            .to_vec();
+        let toast = |chat_id: i64| Toast {
+            chat_id,
+            chat_title: format!("Chat {chat_id}"),
+            text: "Ada: hi".to_string(),
+            expires_at: 0,
+        };
+        state.toasts = vec![toast(11), toast(10), toast(11)];
+        let ctrl_g = KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL);
+
+        assert!(handle_ui_key(&mut state, ctrl_g, KeymapStyle::Vscode));
+        assert!(state.chats[1].is_selected && !state.chats[0].is_selected);
+        assert_eq!(state.focus, UiFocus::Messages);
+        assert_eq!(state.toasts, vec![toast(10)]);
+
+        assert!(handle_ui_key(&mut state, ctrl_g, KeymapStyle::Vscode));
+        assert!(state.chats[0].is_selected);
+        assert!(state.toasts.is_empty());
+    }
+
+    #[test]
+    fn alt_arrows_resize_the_chat_list_and_composer() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Composer;
+        let alt = |code| KeyEvent::new(code, KeyModifiers::ALT);
+
+        assert!(handle_ui_key(
+            &mut state,
+            alt(KeyCode::Right),
+            KeymapStyle::Vscode
+        ));
+        assert_eq!(state.layout.chat_width, 26);
+        for _ in 0..20 {
+            handle_ui_key(&mut state, alt(KeyCode::Left), KeymapStyle::Vscode);
+        }
+        assert_eq!(state.layout.chat_width, LayoutConfig::MIN_CHAT_WIDTH);
+
+        for _ in 0..6 {
+            handle_ui_key(&mut state, alt(KeyCode::Up), KeymapStyle::Vscode);
+        }
+        assert_eq!(state.layout.composer_min_lines, 7);
+        assert_eq!(state.layout.composer_max_lines, 7);
+        handle_ui_key(&mut state, alt(KeyCode::Down), KeymapStyle::Vscode);
+        assert_eq!(state.layout.composer_min_lines, 6);
+        assert!(state.input.text.is_empty());
+    }
+
+    #[test]
+    fn enter_in_composer_requests_a_send() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Composer;
+
+        assert!(!handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        ));
+        state.input.text = "hi".to_string();
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        ));
+        assert_eq!(state.actions, vec![UiAction::SendComposer]);
+    }
+
+    #[test]
+    fn colon_opens_the_emoji_picker() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Composer;
+        let type_text = |state: &mut UiState, text: &str| {
+            for c in text.chars() {
+                handle_ui_key(
+                    state,
+                    KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+                    KeymapStyle::Vscode,
+                );
+            }
+        };
+
+        type_text(&mut state, "hot :fir");
+        assert!(state.emoji_picker.is_open);
+        assert_eq!(state.emoji_picker.query.text, "fir");
+        handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            KeymapStyle::Vscode,
+        );
+        assert!(!state.emoji_picker.is_open);
+        assert_eq!(state.input.text, "hot 🔥");
+        assert_eq!(state.emoji_picker.recents[0].name, "fire");
+        assert!(state.actions.is_empty());
+
+        type_text(&mut state, " :) a:b");
+        assert!(!state.emoji_picker.is_open);
+        assert_eq!(state.input.text, "hot 🔥 :) a:b");
+    }
+
+    #[test]
+    fn ctrl_o_picks_a_file_to_send() {
+        let dir = std::env::temp_dir().join(format!("attach-keys-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("report.pdf"), b"pdf").unwrap();
+        let mut state = sample_state();
+        state.focus = UiFocus::Composer;
+        let press = |state: &mut UiState, code: KeyCode, modifiers: KeyModifiers| {
+            handle_ui_key(state, KeyEvent::new(code, modifiers), KeymapStyle::Vscode)
+        };
+
+        press(&mut state, KeyCode::Char('o'), KeyModifiers::CONTROL);
+        assert!(state.attach_picker.is_open);
+        assert!(handle_ui_paste(
+            &mut state,
+            &format!("{}/rep", dir.display())
+        ));
+        assert!(!press(&mut state, KeyCode::Enter, KeyModifiers::NONE));
+
+        press(&mut state, KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(state.focus, UiFocus::Composer);
+        assert!(press(&mut state, KeyCode::Enter, KeyModifiers::NONE));
+
+        assert!(!state.attach_picker.is_open);
+        assert_eq!(
+            state.actions,
+            vec![UiAction::SendAttachment(dir.join("report.pdf"))]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn paste_lands_in_the_composer_as_one_edit() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Composer;
+        state.input.paste("draft: ");
+
+        assert!(handle_ui_paste(&mut state, "line one\r\nline two\n"));
+        assert_eq!(state.input.text, "draft: line one\nline two\n");
+        assert!(state.actions.is_empty());
+
+        handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL),
+            KeymapStyle::Vscode,
+        );
+        assert_eq!(state.input.text, "draft: ");
+
+        state.focus = UiFocus::Messages;
+        assert!(!handle_ui_paste(&mut state, "ignored"));
+    }
+
+    #[test]
+    fn shift_or_alt_enter_starts_a_new_line() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Composer;
+        state.input.text = "hi".to_string();
+        state.input.cursor = 2;
+
+        for modifiers in [KeyModifiers::SHIFT, KeyModifiers::ALT] {
+            assert!(handle_ui_key(
+                &mut state,
+                KeyEvent::new(KeyCode::Enter, modifiers),
+                KeymapStyle::Vscode,
+            ));
+        }
+
+        assert_eq!(state.input.text, "hi\n\n");
+        assert_eq!(state.input.cursor_position(), (2, 0));
+        assert!(state.actions.is_empty());
+    }
+
+    #[test]
+    fn requests_retry_and_discard_for_local_echoes() {
+        let mut state = sample_state();
+        state.messages.push(MessageItem {
+            id: -7,
+            author: "You".to_string(),
+            timestamp: "09:12".to_string(),
+            sent_at: 33120,
+            body: "failed".to_string(),
+            expires_in: None,
+            media: None,
+            reply_to: None,
+            delivery: None,
+        });
+        state.focus = UiFocus::Messages;
+        state.message_view.cursor = Some(2);
+
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            KeymapStyle::Vim,
+        ));
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
+            KeymapStyle::Vscode,
+        ));
+        assert_eq!(
+            state.actions,
+            vec![UiAction::RetrySend(7), UiAction::DiscardSend(7)]
+        );
+
+        state.message_view.cursor = Some(0);
+        assert!(!handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            KeymapStyle::Vim,
+        ));
+
+        // Away from any echo, the keys act on the newest failed send.
+        state.actions.clear();
+        state.failed_sends = vec![
+            FailedSend {
+                message_id: -4,
+                error: None,
+            },
+            FailedSend {
+                message_id: -7,
+                error: Some("FLOOD_WAIT".to_string()),
+            },
+        ];
+        assert!(handle_ui_key(
+            &mut state,
+            KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            KeymapStyle::Vim,
+        ));
+        assert_eq!(state.actions, vec![UiAction::RetrySend(7)]);
+    }
 }
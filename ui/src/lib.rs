@@ -1,6 +1,12 @@
 //! TUI components and test harness.
 
+pub mod attach;
+pub mod bidi;
+pub mod emoji;
+pub mod fuzzy;
 pub mod input;
 pub mod interaction;
+pub mod markup;
 pub mod test_harness;
+pub mod theme;
 pub mod view;
@@ -0,0 +1,340 @@
+//! Telegram-style markdown in message bodies: `**bold**`, `__italic__`,
+//! `~~strikethrough~~`, `||spoilers||`, `` `inline code` `` and fenced code
+//! blocks, which are syntax highlighted by their declared language when the
+//! `syntax-highlighting` feature is on.
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use unicode_width::UnicodeWidthStr;
+
+/// Leading spaces in code become no-break spaces so wrapping with trimming
+/// keeps the indentation.
+const NBSP: char = '\u{a0}';
+
+const SPOILER_MARKER: &str = "||";
+/// Stands in for each character of a spoiler until it is revealed.
+const SPOILER_MASK: char = '▒';
+
+const INLINE_MARKERS: [(&str, Modifier); 3] = [
+    ("**", Modifier::BOLD),
+    ("__", Modifier::ITALIC),
+    ("~~", Modifier::CROSSED_OUT),
+];
+
+pub fn code_style() -> Style {
+    Style::default().bg(Color::DarkGray)
+}
+
+fn spoiler_style() -> Style {
+    Style::default().fg(Color::DarkGray)
+}
+
+/// Styled lines for a message body, with spoilers masked unless
+/// `reveal_spoilers` is set.
+pub fn body_lines(body: &str, reveal_spoilers: bool) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    // Language and lines of the fenced block being read, if any.
+    let mut code_block: Option<(String, Vec<&str>)> = None;
+    for line in body.split('\n') {
+        if let Some(fence) = line.trim_start().strip_prefix("```") {
+            match code_block.take() {
+                Some((language, code)) => lines.extend(code_lines(&language, &code)),
+                None => code_block = Some((fence.trim().to_string(), Vec::new())),
+            }
+            continue;
+        }
+        match code_block.as_mut() {
+            Some((_, code)) => code.push(line),
+            None => lines.push(Line::from(inline_spans(line, reveal_spoilers))),
+        }
+    }
+    // An unclosed fence still renders as code.
+    if let Some((language, code)) = code_block {
+        lines.extend(code_lines(&language, &code));
+    }
+    if lines.is_empty() {
+        lines.push(Line::default());
+    }
+    lines
+}
+
+/// Code block lines, highlighted for `language` when it is known and
+/// monochrome otherwise.
+fn code_lines(language: &str, code: &[&str]) -> Vec<Line<'static>> {
+    #[cfg(feature = "syntax-highlighting")]
+    if let Some(lines) = highlight::highlight(language, code) {
+        return lines;
+    }
+    #[cfg(not(feature = "syntax-highlighting"))]
+    let _ = language;
+    code.iter()
+        .map(|line| Line::from(Span::styled(preserve_indent(line), code_style())))
+        .collect()
+}
+
+fn preserve_indent(line: &str) -> String {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let mut preserved: String = std::iter::repeat_n(NBSP, indent).collect();
+    preserved.push_str(&line[indent..]);
+    preserved
+}
+
+#[cfg(feature = "syntax-highlighting")]
+mod highlight {
+    use std::sync::OnceLock;
+
+    use ratatui::{
+        style::Color,
+        text::{Line, Span},
+    };
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::{Theme, ThemeSet};
+    use syntect::parsing::SyntaxSet;
+
+    use super::{code_style, preserve_indent};
+
+    const THEME: &str = "base16-ocean.dark";
+
+    fn syntaxes() -> &'static SyntaxSet {
+        static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+        SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+    }
+
+    fn theme() -> &'static Theme {
+        static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+        &THEME_SET.get_or_init(ThemeSet::load_defaults).themes[THEME]
+    }
+
+    /// Highlighted lines, or `None` when the language is missing or unknown.
+    pub(super) fn highlight(language: &str, code: &[&str]) -> Option<Vec<Line<'static>>> {
+        if language.is_empty() {
+            return None;
+        }
+        let syntaxes = syntaxes();
+        let syntax = syntaxes.find_syntax_by_token(language)?;
+        let mut highlighter = HighlightLines::new(syntax, theme());
+        let mut lines = Vec::with_capacity(code.len());
+        for line in code {
+            let source = format!("{line}\n");
+            let regions = highlighter.highlight_line(&source, syntaxes).ok()?;
+            let mut spans = Vec::with_capacity(regions.len());
+            let mut leading = true;
+            for (style, text) in regions {
+                let text = text.trim_end_matches('\n');
+                if text.is_empty() {
+                    continue;
+                }
+                let text = if leading {
+                    preserve_indent(text)
+                } else {
+                    text.to_string()
+                };
+                leading = leading && text.chars().all(|c| c == super::NBSP);
+                let color = style.foreground;
+                let span_style = code_style().fg(Color::Rgb(color.r, color.g, color.b));
+                spans.push(Span::styled(text, span_style));
+            }
+            lines.push(Line::from(spans));
+        }
+        Some(lines)
+    }
+}
+
+/// Splits one line into styled spans. A marker only opens when its closing
+/// marker follows on the same line, so stray `**` or `__` stay literal.
+fn inline_spans(line: &str, reveal_spoilers: bool) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut active = Modifier::empty();
+    // Inside a spoiler that is still masked.
+    let mut masked = false;
+    let mut in_spoiler = false;
+    let mut rest = line;
+
+    'scan: while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            if let Some(end) = rest[1..].find('`').filter(|end| *end > 0) {
+                flush(&mut spans, &mut plain, active, masked);
+                let code = &rest[1..=end];
+                spans.push(if masked {
+                    Span::styled(mask(code), spoiler_style())
+                } else {
+                    Span::styled(code.to_string(), code_style())
+                });
+                rest = &rest[end + 2..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix(SPOILER_MARKER) {
+            if in_spoiler || after.find(SPOILER_MARKER).is_some_and(|end| end > 0) {
+                flush(&mut spans, &mut plain, active, masked);
+                in_spoiler = !in_spoiler;
+                masked = in_spoiler && !reveal_spoilers;
+                rest = after;
+                continue;
+            }
+        }
+        for (marker, modifier) in INLINE_MARKERS {
+            let Some(after) = rest.strip_prefix(marker) else {
+                continue;
+            };
+            if active.contains(modifier) {
+                flush(&mut spans, &mut plain, active, masked);
+                active.remove(modifier);
+                rest = after;
+                continue 'scan;
+            }
+            if after.find(marker).is_some_and(|end| end > 0) {
+                flush(&mut spans, &mut plain, active, masked);
+                active.insert(modifier);
+                rest = after;
+                continue 'scan;
+            }
+        }
+        plain.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    flush(&mut spans, &mut plain, active, masked);
+    spans
+}
+
+fn flush(spans: &mut Vec<Span<'static>>, plain: &mut String, active: Modifier, masked: bool) {
+    if plain.is_empty() {
+        return;
+    }
+    let text = std::mem::take(plain);
+    if masked {
+        spans.push(Span::styled(mask(&text), spoiler_style()));
+    } else {
+        let style = Style::default().add_modifier(active);
+        spans.push(Span::styled(text, style));
+    }
+}
+
+/// One mask character per column, so wide characters stay as wide masked.
+fn mask(text: &str) -> String {
+    std::iter::repeat_n(SPOILER_MASK, text.width()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled(line: &Line) -> Vec<(String, Style)> {
+        line.spans
+            .iter()
+            .map(|span| (span.content.to_string(), span.style))
+            .collect()
+    }
+
+    #[test]
+    fn styles_inline_markup() {
+        let lines = body_lines("a **bold** and __it ~~both~~__ `x**y`", false);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let italic = Style::default().add_modifier(Modifier::ITALIC);
+        let struck = Style::default().add_modifier(Modifier::ITALIC | Modifier::CROSSED_OUT);
+
+        assert_eq!(
+            styled(&lines[0]),
+            vec![
+                ("a ".to_string(), Style::default()),
+                ("bold".to_string(), bold),
+                (" and ".to_string(), Style::default()),
+                ("it ".to_string(), italic),
+                ("both".to_string(), struck),
+                (" ".to_string(), Style::default()),
+                ("x**y".to_string(), code_style()),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_unmatched_markers_alone() {
+        let lines = body_lines("snake__case and 2 ** 3", false);
+        assert_eq!(
+            styled(&lines[0]),
+            vec![("snake__case and 2 ** 3".to_string(), Style::default())]
+        );
+    }
+
+    #[test]
+    fn masks_spoilers_until_revealed() {
+        let hidden = body_lines("plot: ||he **was** `dead`|| all along", false);
+        let shown = body_lines("plot: ||he **was** `dead`|| all along", true);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+
+        assert_eq!(
+            styled(&hidden[0]),
+            vec![
+                ("plot: ".to_string(), Style::default()),
+                ("▒▒▒".to_string(), spoiler_style()),
+                ("▒▒▒".to_string(), spoiler_style()),
+                ("▒".to_string(), spoiler_style()),
+                ("▒▒▒▒".to_string(), spoiler_style()),
+                (" all along".to_string(), Style::default()),
+            ]
+        );
+        assert_eq!(
+            styled(&shown[0]),
+            vec![
+                ("plot: ".to_string(), Style::default()),
+                ("he ".to_string(), Style::default()),
+                ("was".to_string(), bold),
+                (" ".to_string(), Style::default()),
+                ("dead".to_string(), code_style()),
+                (" all along".to_string(), Style::default()),
+            ]
+        );
+        assert_eq!(
+            styled(&body_lines("||日本||", false)[0]),
+            vec![("▒▒▒▒".to_string(), spoiler_style())]
+        );
+        assert_eq!(
+            styled(&body_lines("a || b", false)[0]),
+            vec![("a || b".to_string(), Style::default())]
+        );
+    }
+
+    #[test]
+    fn fenced_code_keeps_indentation() {
+        let lines = body_lines("look:\n```\nfn main() {\n    run();\n}\n```\ndone", false);
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            styled(&lines[2]),
+            vec![("\u{a0}\u{a0}\u{a0}\u{a0}run();".to_string(), code_style())]
+        );
+        assert_eq!(styled(&lines[4])[0].0, "done");
+    }
+
+    #[test]
+    fn unknown_languages_fall_back_to_plain_code() {
+        let lines = body_lines("```klingon\nqapla'\n```", false);
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            styled(&lines[0]),
+            vec![("qapla'".to_string(), code_style())]
+        );
+    }
+
+    #[cfg(feature = "syntax-highlighting")]
+    #[test]
+    fn highlights_known_languages() {
+        let lines = body_lines("```rust\n    let x = 1;\n```", false);
+        let text: String = lines[0]
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+
+        assert_eq!(text, "\u{a0}\u{a0}\u{a0}\u{a0}let x = 1;");
+        assert!(lines[0].spans.len() > 1);
+        assert!(lines[0]
+            .spans
+            .iter()
+            .all(|span| span.style.bg == code_style().bg && span.style.fg.is_some()));
+    }
+}
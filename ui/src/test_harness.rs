@@ -42,8 +42,13 @@ pub fn render_to_string(state: &UiState, size: (u16, u16)) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::view::{ChatListItem, CommandPaletteState, DraftModalState, MessageItem};
+    use crate::view::{
+        preview_placements, ChatListItem, ChatSort, CommandPaletteState, DeliveryMark,
+        DraftModalState, FailedSend, MediaItem, MessageItem, Notice, ReplyTarget, Toast, UiFocus,
+    };
     use insta::assert_snapshot;
+    use ratatui::layout::Rect;
+    use ratatui::style::Color;
 
     fn sample_state() -> UiState {
         let mut state = UiState::default();
@@ -54,18 +59,30 @@ mod tests {
                 title: "General".to_string(),
                 unread: 0,
                 is_selected: true,
+                section: String::new(),
+                typing: false,
+                muted: false,
+                pinned: false,
             },
             ChatListItem {
                 id: 2,
                 title: "Product".to_string(),
                 unread: 3,
                 is_selected: false,
+                section: String::new(),
+                typing: false,
+                muted: false,
+                pinned: false,
             },
             ChatListItem {
                 id: 3,
                 title: "Design".to_string(),
                 unread: 1,
                 is_selected: false,
+                section: String::new(),
+                typing: false,
+                muted: false,
+                pinned: false,
             },
         ];
         state.messages = vec![
@@ -73,19 +90,34 @@ mod tests {
                 id: 100,
                 author: "Ada".to_string(),
                 timestamp: "09:12".to_string(),
+                sent_at: 33120,
                 body: "Morning team".to_string(),
+                expires_in: None,
+                media: None,
+                reply_to: None,
+                delivery: None,
             },
             MessageItem {
                 id: 101,
                 author: "You".to_string(),
                 timestamp: "09:13".to_string(),
+                sent_at: 33180,
                 body: "Morning, syncing on layout".to_string(),
+                expires_in: None,
+                media: None,
+                reply_to: None,
+                delivery: None,
             },
             MessageItem {
                 id: 102,
                 author: "Ada".to_string(),
                 timestamp: "09:15".to_string(),
+                sent_at: 33300,
                 body: "Need the LLM draft soon".to_string(),
+                expires_in: None,
+                media: None,
+                reply_to: None,
+                delivery: None,
             },
         ];
         state.message_view.cursor = Some(1);
@@ -103,6 +135,357 @@ mod tests {
         assert_snapshot!(rendered);
     }
 
+    #[test]
+    fn renders_expiring_message_badge() {
+        let mut state = sample_state();
+        state.messages[2].expires_in = Some(125);
+
+        let rendered = render_to_string(&state, (80, 20));
+
+        assert_snapshot!(rendered);
+    }
+
+    #[test]
+    fn groups_consecutive_messages_from_one_author() {
+        let mut state = sample_state();
+        let follow_up = MessageItem {
+            id: 103,
+            timestamp: "09:16".to_string(),
+            sent_at: state.messages[2].sent_at + 60,
+            body: "By noon ideally".to_string(),
+            ..state.messages[2].clone()
+        };
+        state.messages.push(follow_up);
+        state.message_view.search.query.text.clear();
+        state.message_view.search.recompute_matches(&state.messages);
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        let indent = " ".repeat("[09:15] Ada: ".len());
+        assert!(lines[3].contains("│[  ] [09:15] Ada: Need the LLM draft soon "));
+        assert!(lines[4].contains(&format!("│[  ] {indent}By noon ideally ")));
+    }
+
+    #[test]
+    fn shows_media_placeholders_and_reserves_preview_rows() {
+        let mut state = sample_state();
+        state.layout.preview_rows = 3;
+        state.messages[0].body.clear();
+        state.messages[0].media = Some(MediaItem {
+            label: "photo 1.2MB".to_string(),
+            has_preview: true,
+            transcript: None,
+        });
+        state.messages[2].media = Some(MediaItem {
+            label: "file 40KB".to_string(),
+            has_preview: false,
+            transcript: None,
+        });
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[1].contains("[09:12] Ada: [photo 1.2MB] "));
+        assert!(lines[5].contains("[09:13] You: Morning, syncing on layout "));
+        assert!(lines[7].contains("│[file 40KB] "));
+
+        let placements = preview_placements(Rect::new(0, 0, 80, 20), &state);
+        assert_eq!(placements.len(), 1);
+        assert_eq!(placements[0].message_id, 100);
+        assert_eq!(placements[0].area, Rect::new(25, 2, 54, 3));
+
+        state.command_palette.is_open = true;
+        assert!(preview_placements(Rect::new(0, 0, 80, 20), &state).is_empty());
+    }
+
+    #[test]
+    fn draws_chat_sections_once_chats_span_several() {
+        let mut state = sample_state();
+        state.chats[0].section = "Pinned".to_string();
+        for chat in &mut state.chats[1..] {
+            chat.section = "Chats".to_string();
+        }
+        state.chat_sort = ChatSort::UnreadFirst;
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[0].starts_with("┌Chats (unread first)"));
+        assert!(lines[1].starts_with("│Pinned "));
+        assert!(lines[2].starts_with("│General "));
+        assert!(lines[3].starts_with("│Chats "));
+        assert!(lines[4].starts_with("│Product (3) "));
+    }
+
+    #[test]
+    fn highlights_every_search_match_in_message_text() {
+        let mut state = sample_state();
+        state.messages[2].body = "Draft the LLM draft soon".to_string();
+        state.message_view.search.recompute_matches(&state.messages);
+
+        let buffer = render_to_buffer(&state, (80, 20));
+        let row: String = (0..80)
+            .map(|x| buffer.get(x, 3).symbol().to_string())
+            .collect();
+        let byte_start = row.find("Draft the").expect("match row");
+        let body_start = row[..byte_start].chars().count() as u16;
+        let style_at = |offset: u16| buffer.get(body_start + offset, 3).style();
+
+        assert!(row.contains("[ *] [09:15] Ada: Draft the LLM draft soon"));
+        assert_eq!(style_at(0).bg, Some(Color::Yellow));
+        assert_eq!(style_at(4).bg, Some(Color::Yellow));
+        assert_ne!(style_at(5).bg, Some(Color::Yellow));
+        assert_eq!(style_at(14).bg, Some(Color::Yellow));
+        assert_ne!(style_at(19).bg, Some(Color::Yellow));
+    }
+
+    #[test]
+    fn shows_who_is_typing_over_the_chat_status() {
+        let mut state = sample_state();
+        state.message_view.search.query.text.clear();
+        state.typing = vec!["Ada".to_string()];
+        state.chats[2].typing = true;
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[0].contains("┌Messages · Ada is typing…"));
+        assert!(lines[3].starts_with("│Design (1) … "));
+
+        state.typing.push("Grace".to_string());
+        state.chat_status = Some("12 members".to_string());
+        let rendered = render_to_string(&state, (80, 20));
+        assert!(rendered.contains("Messages · Ada and Grace are typing…"));
+
+        state.typing.clear();
+        let rendered = render_to_string(&state, (80, 20));
+        assert!(rendered.contains("┌Messages · 12 members"));
+    }
+
+    #[test]
+    fn marks_muted_and_pinned_chats_in_the_list() {
+        let mut state = sample_state();
+        state.chats[1].muted = true;
+        state.chats[2].pinned = true;
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[1].starts_with("│General "));
+        assert!(lines[2].starts_with("│Product (3) 🔕"));
+        assert!(lines[3].starts_with("│Design (1) 📌"));
+    }
+
+    #[test]
+    fn draws_the_attachment_picker_over_the_composer() {
+        let mut state = sample_state();
+        state.focus = UiFocus::Composer;
+        state.attach_picker.open();
+        state.attach_picker.path.insert_str("/no/such/file.png");
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[13].contains("┌Attach (Tab completes, Enter sends, Esc cancels)"));
+        assert!(lines[14].contains("│/no/such/file.png"));
+        assert!(lines[15].contains("│No such file"));
+    }
+
+    #[test]
+    fn draws_voice_transcripts_under_the_placeholder() {
+        let mut state = sample_state();
+        state.messages[0].body.clear();
+        state.messages[0].media = Some(MediaItem {
+            label: "voice 0:42 38KB · transcribed".to_string(),
+            has_preview: false,
+            transcript: Some("See you at noon".to_string()),
+        });
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[1].contains("Ada: [voice 0:42 38KB · transcribed] "));
+        assert!(lines[2].contains("│See you at noon "));
+    }
+
+    #[test]
+    fn quotes_replies_above_their_body() {
+        let mut state = sample_state();
+        state.messages[1].reply_to = Some(ReplyTarget {
+            message_id: 7,
+            author: String::new(),
+            snippet: String::new(),
+        });
+        state.messages[2].reply_to = Some(ReplyTarget::for_message(&state.messages[0]));
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[2].contains("You: ↪ earlier message "));
+        assert!(lines[3].contains("│Morning, syncing on layout "));
+        assert!(lines[4].contains("Ada: ↪ Ada: Morning team "));
+        assert!(lines[5].contains("│Need the LLM draft soon "));
+    }
+
+    #[test]
+    fn marks_how_far_outgoing_messages_got() {
+        let mut state = sample_state();
+        state.messages[1].delivery = Some(DeliveryMark::Read);
+        state.messages.push(MessageItem {
+            id: -3,
+            author: "You".to_string(),
+            timestamp: "09:16".to_string(),
+            sent_at: 33360,
+            body: "On it".to_string(),
+            expires_in: None,
+            media: None,
+            reply_to: None,
+            delivery: Some(DeliveryMark::Failed),
+        });
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[1].contains("Ada: Morning team "));
+        assert!(lines[2].contains("You: ✓✓ Morning, syncing on layout "));
+        assert!(lines[4].contains("You: ✗ On it "));
+    }
+
+    #[test]
+    fn shows_notices_in_a_status_bar_under_the_composer() {
+        let mut state = sample_state();
+        state.notice = Some(Notice {
+            text: "Reloaded app.toml: theme".to_string(),
+            is_error: true,
+            expires_at: 0,
+        });
+
+        let buffer = render_to_buffer(&state, (80, 20));
+        let rendered = buffer_to_string(&buffer);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[16].starts_with("┌Composer"));
+        assert!(lines[18].starts_with("└"));
+        assert!(lines[19].starts_with(" Reloaded app.toml: theme"));
+        assert_eq!(buffer.get(1, 19).bg, Color::Red);
+    }
+
+    #[test]
+    fn shows_a_banner_for_failed_sends_above_the_composer() {
+        let mut state = sample_state();
+        state.messages[1].delivery = Some(DeliveryMark::Failed);
+        state.messages[1].id = -4;
+        state.message_view.selected_ids.clear();
+        state.failed_sends = vec![FailedSend {
+            message_id: -4,
+            error: Some("FLOOD_WAIT".to_string()),
+        }];
+
+        let buffer = render_to_buffer(&state, (80, 20));
+        let rendered = buffer_to_string(&buffer);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[16]
+            .starts_with(" ✗ Message not sent (FLOOD_WAIT): Ctrl+R retry · Ctrl+D discard"));
+        assert!(lines[17].starts_with("┌Composer"));
+        let body = lines[2]
+            .find("Morning, syncing")
+            .expect("failed message body");
+        let column = lines[2][..body].chars().count() as u16;
+        assert_eq!(buffer.get(column, 2).fg, Color::Red);
+    }
+
+    #[test]
+    fn lays_out_panes_at_the_configured_sizes() {
+        let mut state = sample_state();
+        state.layout.chat_width = 30;
+        state.layout.composer_min_lines = 3;
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[0].starts_with(&format!("┌Chats{}┐┌Messages", "─".repeat(23))));
+        assert!(lines[15].starts_with("┌Composer"));
+        assert!(lines[19].starts_with("└"));
+    }
+
+    #[test]
+    fn stacks_toasts_in_the_top_right_corner() {
+        let mut state = sample_state();
+        state.toasts = ["Product", "Design"]
+            .map(|chat_title| Toast {
+                chat_id: 2,
+                chat_title: chat_title.to_string(),
+                text: "Ada: lunch?".to_string(),
+                expires_at: 0,
+            })
+            .to_vec();
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[1].ends_with("┌Design────────────────────────────────┐"));
+        assert!(lines[2].ends_with("│Ada: lunch?                           │"));
+        assert!(lines[4].ends_with("┌Product───────────────────────────────┐"));
+    }
+
+    #[test]
+    fn truncates_wide_text_by_columns() {
+        let mut state = sample_state();
+        state.toasts = vec![Toast {
+            chat_id: 2,
+            chat_title: "開発".to_string(),
+            text: "日本語".repeat(20),
+            expires_at: 0,
+        }];
+        state.messages[2].body = "漢字".repeat(30);
+
+        let buffer = render_to_buffer(&state, (80, 20));
+        let row = |y: u16| -> Vec<String> {
+            (0..80)
+                .map(|x| buffer.get(x, y).symbol().to_string())
+                .collect()
+        };
+
+        let toast = row(2);
+        assert_eq!(toast[79], "│");
+        assert_eq!(toast[77], "…");
+        assert_eq!(toast[75], "語");
+
+        let reply = ReplyTarget::for_message(&state.messages[2]);
+        assert_eq!(reply.snippet, format!("{}…", "漢字".repeat(11) + "漢"));
+    }
+
+    #[test]
+    fn highlights_the_focused_pane() {
+        let mut state = sample_state();
+        let theme = state.theme;
+        let corner_fg = |state: &UiState, x: u16| render_to_buffer(state, (80, 20)).get(x, 0).fg;
+
+        state.focus = UiFocus::Chats;
+        assert_eq!(corner_fg(&state, 0), theme.focused_border);
+        assert_eq!(corner_fg(&state, 24), theme.unfocused_border);
+
+        state.focus = UiFocus::Search;
+        assert_eq!(corner_fg(&state, 0), theme.unfocused_border);
+        assert_eq!(corner_fg(&state, 24), theme.focused_border);
+    }
+
+    #[test]
+    fn shows_the_bulk_action_bar_over_the_composer() {
+        let mut state = sample_state();
+        state.message_view.selected_ids.insert(102);
+
+        let rendered = render_to_string(&state, (80, 20));
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[16].starts_with(" 2 selected: y copy · f forward · d delete"));
+
+        state.confirm_delete = true;
+        let rendered = render_to_string(&state, (80, 20));
+        assert!(rendered.contains(" Delete 2 messages? d to confirm"));
+    }
+
     #[test]
     fn renders_command_palette() {
         let mut state = sample_state();
@@ -0,0 +1,42 @@
+//! Colors of the pane chrome, set from the `[theme]` config section.
+
+use ratatui::style::{Color, Modifier, Style};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Border and title of the pane that has focus.
+    pub focused_border: Color,
+    /// Borders of the other panes.
+    pub unfocused_border: Color,
+}
+
+impl Theme {
+    pub fn border_style(&self, focused: bool) -> Style {
+        let color = if focused {
+            self.focused_border
+        } else {
+            self.unfocused_border
+        };
+        Style::default().fg(color)
+    }
+
+    /// Focused titles are bold as well, for terminals with few colors.
+    pub fn title_style(&self, focused: bool) -> Style {
+        if focused {
+            Style::default()
+                .fg(self.focused_border)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            focused_border: Color::Cyan,
+            unfocused_border: Color::DarkGray,
+        }
+    }
+}
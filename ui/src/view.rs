@@ -1,13 +1,34 @@
 use std::collections::BTreeSet;
+use std::path::PathBuf;
 
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Modifier, Style},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
     widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::attach::AttachPickerState;
+use crate::bidi::reorder_line;
+use crate::emoji::EmojiPickerState;
+use crate::fuzzy::fuzzy_score;
 use crate::input::InputState;
+use crate::markup::body_lines;
+use crate::theme::Theme;
+
+/// Consecutive messages from one author this close together share the
+/// author line.
+const AUTHOR_GROUP_WINDOW_SECS: i64 = 5 * 60;
+/// Trails the title of a muted chat.
+const MUTED_GLYPH: &str = " 🔕";
+/// Trails the title of a pinned chat.
+const PINNED_GLYPH: &str = " 📌";
+/// Starts the quoted snippet of a reply.
+const QUOTE_MARKER: &str = "↪ ";
 
 #[derive(Debug, Clone)]
 pub struct ChatListItem {
@@ -15,6 +36,48 @@ pub struct ChatListItem {
     pub title: String,
     pub unread: u32,
     pub is_selected: bool,
+    /// Heading of the chat-list section the chat is listed under, such as
+    /// `Pinned`. Headings are drawn once chats fall into several sections.
+    pub section: String,
+    /// Someone in the chat is typing.
+    pub typing: bool,
+    /// Notifications from the chat are muted.
+    pub muted: bool,
+    /// The chat is pinned to the top of the list.
+    pub pinned: bool,
+}
+
+/// How the chat list orders chats below the pinned section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChatSort {
+    /// Latest message first.
+    #[default]
+    Recent,
+    /// Chats with unread messages first, then by recency.
+    UnreadFirst,
+    /// One section per folder, the main list first and the archive last.
+    Folders,
+    Alphabetical,
+}
+
+impl ChatSort {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Recent => Self::UnreadFirst,
+            Self::UnreadFirst => Self::Folders,
+            Self::Folders => Self::Alphabetical,
+            Self::Alphabetical => Self::Recent,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Recent => "recent",
+            Self::UnreadFirst => "unread first",
+            Self::Folders => "folders",
+            Self::Alphabetical => "a-z",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -22,7 +85,103 @@ pub struct MessageItem {
     pub id: i64,
     pub author: String,
     pub timestamp: String,
+    /// Unix time the message was sent.
+    pub sent_at: i64,
     pub body: String,
+    /// Seconds until Telegram auto-deletes the message, if it has a TTL.
+    pub expires_in: Option<i64>,
+    pub media: Option<MediaItem>,
+    /// Message this one replies to, quoted above its body.
+    pub reply_to: Option<ReplyTarget>,
+    /// Set for outgoing messages only.
+    pub delivery: Option<DeliveryMark>,
+}
+
+/// Local echo of a message the send pipeline gave up on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedSend {
+    /// Placeholder id of the echo; its magnitude is the send id.
+    pub message_id: i64,
+    /// Why the last attempt failed, when known this session.
+    pub error: Option<String>,
+}
+
+/// How far an outgoing message got, marked after its author.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryMark {
+    /// Waiting in the send queue.
+    Queued,
+    /// Being sent or uploaded.
+    Sending,
+    Sent,
+    /// The peer has read it.
+    Read,
+    /// Gave up after retries; `Ctrl+R` retries it.
+    Failed,
+}
+
+impl DeliveryMark {
+    pub fn glyph(self) -> &'static str {
+        match self {
+            DeliveryMark::Queued => "🕓",
+            DeliveryMark::Sending => "↑",
+            DeliveryMark::Sent => "✓",
+            DeliveryMark::Read => "✓✓",
+            DeliveryMark::Failed => "✗",
+        }
+    }
+
+    fn style(self) -> Style {
+        match self {
+            DeliveryMark::Queued | DeliveryMark::Sending | DeliveryMark::Sent => {
+                Style::default().add_modifier(Modifier::DIM)
+            }
+            DeliveryMark::Read => Style::default().fg(Color::Cyan),
+            DeliveryMark::Failed => Style::default().fg(Color::Red),
+        }
+    }
+}
+
+/// Attachment drawn under a message body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaItem {
+    /// Placeholder text such as `photo 1.2MB`.
+    pub label: String,
+    /// A downloaded preview is ready, so rows are reserved for the app to
+    /// draw it into; see [`preview_placements`].
+    pub has_preview: bool,
+    /// Speech-to-text of a voice message, drawn under the placeholder.
+    pub transcript: Option<String>,
+}
+
+/// Screen area reserved for the inline preview of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreviewPlacement {
+    pub message_id: i64,
+    pub area: Rect,
+}
+
+/// Notice of a new message in a chat other than the selected one, stacked in
+/// the top right corner. `Ctrl+G` jumps to the chat of the newest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toast {
+    pub chat_id: i64,
+    pub chat_title: String,
+    /// Author and first line of the message.
+    pub text: String,
+    /// Unix time the app dismisses the toast at.
+    pub expires_at: i64,
+}
+
+/// Line in the status bar under the composer, such as the outcome of
+/// reloading the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notice {
+    pub text: String,
+    /// Drawn in red.
+    pub is_error: bool,
+    /// Unix time the app clears the notice at.
+    pub expires_at: i64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -34,6 +193,33 @@ pub enum UiFocus {
     Search,
 }
 
+/// Narrows the chat list to titles fuzzily matching the query.
+#[derive(Debug, Clone, Default)]
+pub struct ChatFilterState {
+    pub is_open: bool,
+    pub query: InputState,
+}
+
+impl ChatFilterState {
+    /// Indices of the chats to list, best match first. Without a query every
+    /// chat is listed in its original order.
+    pub fn matches(&self, chats: &[ChatListItem]) -> Vec<usize> {
+        let query = self.query.text.trim().to_lowercase();
+        if query.is_empty() {
+            return (0..chats.len()).collect();
+        }
+        let mut scored: Vec<(usize, usize)> = chats
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, chat)| {
+                fuzzy_score(&query, &chat.title.to_lowercase()).map(|score| (score, idx))
+            })
+            .collect();
+        scored.sort();
+        scored.into_iter().map(|(_, idx)| idx).collect()
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct MessageSearchState {
     pub is_open: bool,
@@ -87,23 +273,54 @@ impl MessageSearchState {
     }
 }
 
+/// A vim visual-mode range, from the anchor message to the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisualSelection {
+    pub anchor_id: i64,
+    /// Selection from before visual mode, which the range adds to.
+    pub base: BTreeSet<i64>,
+}
+
+/// Where the message view stood in one chat, kept while another is shown.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageViewMemory {
+    pub scroll_offset: usize,
+    /// Id rather than index, so messages arriving meanwhile don't move it.
+    pub cursor_id: Option<i64>,
+    pub selected_ids: BTreeSet<i64>,
+}
+
 #[derive(Debug, Clone)]
 pub struct MessageViewState {
+    /// Index of the message at the top of the pane.
     pub scroll_offset: usize,
+    /// Wrapped rows of the top message scrolled out of view above the pane.
+    pub scroll_rows: usize,
     pub cursor: Option<usize>,
     pub selected_ids: BTreeSet<i64>,
     pub search: MessageSearchState,
+    /// Rows the message pane shows, kept up to date by [`fit_message_view`].
     pub page_size: usize,
+    /// Columns messages wrap at; 0 until the pane is first measured, which
+    /// counts every message line as one row.
+    pub page_width: u16,
+    pub visual: Option<VisualSelection>,
+    /// Messages whose spoilers are shown.
+    pub revealed_spoilers: BTreeSet<i64>,
 }
 
 impl Default for MessageViewState {
     fn default() -> Self {
         Self {
             scroll_offset: 0,
+            scroll_rows: 0,
             cursor: None,
             selected_ids: BTreeSet::new(),
             search: MessageSearchState::default(),
             page_size: 8,
+            page_width: 0,
+            visual: None,
+            revealed_spoilers: BTreeSet::new(),
         }
     }
 }
@@ -112,17 +329,107 @@ impl MessageViewState {
     pub fn reconcile(&mut self, messages: &[MessageItem]) {
         let existing_ids: BTreeSet<i64> = messages.iter().map(|message| message.id).collect();
         self.selected_ids.retain(|id| existing_ids.contains(id));
+        self.revealed_spoilers
+            .retain(|id| existing_ids.contains(id));
 
         if messages.is_empty() {
             self.cursor = None;
-            self.scroll_offset = 0;
+            self.scroll_to(0);
         } else {
             let max_index = messages.len().saturating_sub(1);
             self.cursor = Some(self.cursor.unwrap_or(max_index).min(max_index));
-            self.scroll_offset = self.scroll_offset.min(max_index);
+            if self.scroll_offset > max_index {
+                self.scroll_to(max_index);
+            }
         }
 
         self.search.recompute_matches(messages);
+        self.extend_visual(messages);
+    }
+
+    /// Keeps the cursor and scroll on the same messages when `messages`
+    /// replaces `previous`, as when older history is prepended.
+    pub fn follow(&mut self, previous: &[MessageItem], messages: &[MessageItem]) {
+        let moved = |index: usize| {
+            let id = previous.get(index)?.id;
+            messages.iter().position(|message| message.id == id)
+        };
+        if let Some(cursor) = self.cursor.and_then(moved) {
+            self.cursor = Some(cursor);
+        }
+        if let Some(scroll_offset) = moved(self.scroll_offset) {
+            self.scroll_offset = scroll_offset;
+        }
+        self.reconcile(messages);
+    }
+
+    /// Puts the start of the message at `index` at the top of the pane.
+    pub fn scroll_to(&mut self, index: usize) {
+        self.scroll_offset = index;
+        self.scroll_rows = 0;
+    }
+
+    pub fn memory(&self, messages: &[MessageItem]) -> MessageViewMemory {
+        MessageViewMemory {
+            scroll_offset: self.scroll_offset,
+            cursor_id: self.cursor_message_id(messages),
+            selected_ids: self.selected_ids.clone(),
+        }
+    }
+
+    /// Returns to where `memory` left off in `messages`, or without one to
+    /// the latest message. Visual mode ends either way.
+    pub fn restore(&mut self, memory: Option<MessageViewMemory>, messages: &[MessageItem]) {
+        let memory = memory.unwrap_or_default();
+        self.visual = None;
+        self.scroll_to(memory.scroll_offset);
+        self.cursor = memory
+            .cursor_id
+            .and_then(|id| messages.iter().position(|message| message.id == id));
+        self.selected_ids = memory.selected_ids;
+        self.reconcile(messages);
+    }
+
+    /// Starts visual mode anchored at the cursor message.
+    pub fn start_visual(&mut self, messages: &[MessageItem]) -> bool {
+        let Some(anchor_id) = self.cursor_message_id(messages) else {
+            return false;
+        };
+        self.visual = Some(VisualSelection {
+            anchor_id,
+            base: self.selected_ids.clone(),
+        });
+        self.extend_visual(messages);
+        true
+    }
+
+    /// Leaves visual mode, keeping the range selected or, when `cancel` is
+    /// set, restoring the selection from before it started.
+    pub fn end_visual(&mut self, cancel: bool) {
+        if let Some(visual) = self.visual.take() {
+            if cancel {
+                self.selected_ids = visual.base;
+            }
+        }
+    }
+
+    /// Selects everything between the visual anchor and the cursor. Visual
+    /// mode ends if the anchor message is gone.
+    pub fn extend_visual(&mut self, messages: &[MessageItem]) {
+        let Some(visual) = &self.visual else {
+            return;
+        };
+        let anchor = messages
+            .iter()
+            .position(|message| message.id == visual.anchor_id);
+        let (Some(anchor), Some(cursor)) = (anchor, self.cursor) else {
+            self.visual = None;
+            return;
+        };
+        let range = anchor.min(cursor)..=anchor.max(cursor);
+        let mut selected = visual.base.clone();
+        selected.extend(messages[range].iter().map(|message| message.id));
+        self.selected_ids = selected;
     }
 
     pub fn toggle_selection(&mut self, message_id: i64) {
@@ -131,6 +438,17 @@ impl MessageViewState {
         }
     }
 
+    /// Shows or masks the spoilers of the cursor message.
+    pub fn toggle_spoilers(&mut self, messages: &[MessageItem]) -> bool {
+        let Some(message_id) = self.cursor_message_id(messages) else {
+            return false;
+        };
+        if !self.revealed_spoilers.insert(message_id) {
+            self.revealed_spoilers.remove(&message_id);
+        }
+        true
+    }
+
     pub fn cursor_message_id(&self, messages: &[MessageItem]) -> Option<i64> {
         self.cursor
             .and_then(|index| messages.get(index).map(|message| message.id))
@@ -154,6 +472,101 @@ impl Default for DraftModalState {
     }
 }
 
+/// Messages picked for forwarding, from the chat they were selected in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardState {
+    pub from_chat_id: i64,
+    pub message_ids: Vec<i64>,
+}
+
+/// Message the next send from the composer replies to, or that a message
+/// in the list quotes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyTarget {
+    pub message_id: i64,
+    /// Empty when the original is not loaded.
+    pub author: String,
+    /// First line of the message, shortened for the reply strip.
+    pub snippet: String,
+}
+
+impl ReplyTarget {
+    const SNIPPET_WIDTH: usize = 48;
+
+    pub fn for_message(message: &MessageItem) -> Self {
+        let first_line = message.body.lines().next().unwrap_or_default();
+        let mut snippet = truncate_to_width(first_line, Self::SNIPPET_WIDTH);
+        if snippet == first_line && first_line.len() < message.body.len() {
+            snippet.push('…');
+        }
+        Self {
+            message_id: message.id,
+            author: message.author.clone(),
+            snippet,
+        }
+    }
+}
+
+/// The start of `text` that fits in `max_width` columns, ending in `…` when
+/// anything was cut. Wide characters take two columns and are never split.
+fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if width + grapheme_width + 1 > max_width {
+            break;
+        }
+        width += grapheme_width;
+        truncated.push_str(grapheme);
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// Everything known about one message, shown full screen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageDetail {
+    pub id: i64,
+    pub author: String,
+    pub body: String,
+    pub sent_at: String,
+    pub edited_at: Option<String>,
+    pub expires_at: Option<String>,
+    /// Delivery state of outgoing messages.
+    pub status: Option<String>,
+    /// Snippet of the message this one replies to.
+    pub reply_to: Option<String>,
+    pub reactions: Vec<String>,
+    pub attachment: Option<String>,
+    pub scroll: u16,
+}
+
+/// One hit of a search across all chats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalSearchResult {
+    pub chat_id: i64,
+    pub message_id: i64,
+    pub chat_title: String,
+    pub author: String,
+    /// Excerpt around the match.
+    pub snippet: String,
+}
+
+/// Search across every cached chat, opened from the command palette and
+/// drawn over the main screen.
+#[derive(Debug, Clone, Default)]
+pub struct GlobalSearchState {
+    pub is_open: bool,
+    pub query: InputState,
+    /// Filled by the app for the query, best match first.
+    pub results: Vec<GlobalSearchResult>,
+    pub selected: usize,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct CommandPaletteState {
     pub is_open: bool,
@@ -162,79 +575,420 @@ pub struct CommandPaletteState {
     pub selected: usize,
 }
 
+/// Requests raised by key handling for the app to carry out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UiAction {
+    /// Queue the failed send with this id again.
+    RetrySend(u64),
+    /// Drop the failed send with this id and its local echo.
+    DiscardSend(u64),
+    /// Send the composer text to the selected chat.
+    SendComposer,
+    /// Put this text on the system clipboard.
+    CopyText(String),
+    /// Fill [`UiState::message_detail`] for the message with this id.
+    OpenMessageDetail(i64),
+    /// The cursor was moved up past the oldest loaded message; load a page
+    /// before it.
+    LoadOlderMessages,
+    /// Fill [`GlobalSearchState::results`] for this query.
+    SearchAllChats(String),
+    /// Show this message in its chat, loading older history if needed.
+    JumpToMessage { chat_id: i64, message_id: i64 },
+    /// Forward these messages of one chat into another.
+    ForwardMessages {
+        from_chat_id: i64,
+        to_chat_id: i64,
+        message_ids: Vec<i64>,
+    },
+    /// Delete these messages of the selected chat.
+    DeleteMessages(Vec<i64>),
+    /// Mute or unmute notifications from this chat.
+    ToggleMute { chat_id: i64, muted: bool },
+    /// Pin this chat to the top of the list or unpin it.
+    TogglePin { chat_id: i64, pinned: bool },
+    /// Send this file to the selected chat, captioned with the composer text.
+    SendAttachment(PathBuf),
+    /// Download the attachment of the message with this id.
+    SaveMedia(i64),
+    /// Download the attachment of the message with this id and open it in
+    /// the external player.
+    OpenMedia(i64),
+    /// Ask Telegram for the speech-to-text of this voice message.
+    TranscribeVoice(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutConfig {
+    /// Columns of the chat list, borders included.
+    pub chat_width: u16,
+    /// Text lines the composer shows even when the draft is shorter.
+    pub composer_min_lines: u16,
+    /// Text lines the composer grows to before it scrolls.
+    pub composer_max_lines: u16,
+    /// Rows reserved under a message for its inline image preview.
+    pub preview_rows: u16,
+}
+
+impl LayoutConfig {
+    pub const MIN_CHAT_WIDTH: u16 = 12;
+    pub const MAX_CHAT_WIDTH: u16 = 80;
+    pub const MAX_COMPOSER_LINES: u16 = 20;
+
+    /// Widens (or with a negative `delta`, narrows) the chat list.
+    pub fn resize_chat_list(&mut self, delta: i16) {
+        self.chat_width = self
+            .chat_width
+            .saturating_add_signed(delta)
+            .clamp(Self::MIN_CHAT_WIDTH, Self::MAX_CHAT_WIDTH);
+    }
+
+    /// Grows (or with a negative `delta`, shrinks) the composer, raising its
+    /// maximum along with it.
+    pub fn resize_composer(&mut self, delta: i16) {
+        self.composer_min_lines = self
+            .composer_min_lines
+            .saturating_add_signed(delta)
+            .clamp(1, Self::MAX_COMPOSER_LINES);
+        self.composer_max_lines = self.composer_max_lines.max(self.composer_min_lines);
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            chat_width: 24,
+            composer_min_lines: 1,
+            composer_max_lines: 5,
+            preview_rows: 8,
+        }
+    }
+}
+
+/// Where each pane of the main screen goes.
+struct PaneAreas {
+    chats: Rect,
+    messages: Rect,
+    failed: Rect,
+    bulk: Rect,
+    reply: Rect,
+    composer: Rect,
+    status: Rect,
+}
+
+fn pane_areas(area: Rect, state: &UiState) -> PaneAreas {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(u16::from(!state.failed_sends.is_empty())),
+            Constraint::Length(u16::from(bulk_bar_text(state).is_some())),
+            Constraint::Length(u16::from(state.reply_to.is_some())),
+            Constraint::Length(composer_lines(state) + 2),
+            Constraint::Length(u16::from(state.notice.is_some())),
+        ])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Length(state.layout.chat_width),
+            Constraint::Min(1),
+        ])
+        .split(rows[0]);
+    PaneAreas {
+        chats: columns[0],
+        messages: columns[1],
+        failed: rows[1],
+        bulk: rows[2],
+        reply: rows[3],
+        composer: rows[4],
+        status: rows[5],
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct UiState {
     pub focus: UiFocus,
+    pub layout: LayoutConfig,
+    pub theme: Theme,
     pub input: InputState,
+    pub reply_to: Option<ReplyTarget>,
+    /// Selected messages waiting for the chat to forward them to.
+    pub forwarding: Option<ForwardState>,
+    /// `d` was pressed on a selection; a second `d` deletes it.
+    pub confirm_delete: bool,
     pub chats: Vec<ChatListItem>,
+    pub chat_sort: ChatSort,
+    pub chat_filter: ChatFilterState,
     pub messages: Vec<MessageItem>,
+    /// Names of the people typing in the selected chat.
+    pub typing: Vec<String>,
+    /// Messages of the selected chat that could not be sent, oldest first;
+    /// a banner offers to retry or discard them.
+    pub failed_sends: Vec<FailedSend>,
+    /// Presence of the selected chat's peer, such as `online`, or its member
+    /// count.
+    pub chat_status: Option<String>,
     pub message_view: MessageViewState,
     pub draft_modal: DraftModalState,
     pub command_palette: CommandPaletteState,
+    pub global_search: GlobalSearchState,
+    pub emoji_picker: EmojiPickerState,
+    pub attach_picker: AttachPickerState,
+    /// Oldest first; the app adds and expires them.
+    pub toasts: Vec<Toast>,
+    /// Status bar line; the app sets and expires it.
+    pub notice: Option<Notice>,
+    /// Open detail view, drawn over everything else.
+    pub message_detail: Option<MessageDetail>,
+    /// Drained by the app after each key.
+    pub actions: Vec<UiAction>,
 }
 
 pub fn draw(frame: &mut Frame, state: &UiState) {
     let area = frame.size();
-    let rows = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Min(1), Constraint::Length(3)])
-        .split(area);
-
-    let columns = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Length(24), Constraint::Min(1)])
-        .split(rows[0]);
-
-    let chat_items: Vec<ListItem> = if state.chats.is_empty() {
-        vec![ListItem::new("No chats")]
-    } else {
-        state
-            .chats
-            .iter()
-            .map(|chat| {
-                let unread = if chat.unread > 0 {
-                    format!(" ({})", chat.unread)
-                } else {
-                    String::new()
-                };
-                ListItem::new(format!("{}{}", chat.title, unread))
-            })
-            .collect()
-    };
+    let composer_lines = composer_lines(state);
+    let panes = pane_areas(area, state);
+    let composer_area = panes.composer;
 
+    let (chat_items, selected_chat) = chat_list_items(state);
     let mut chat_state = ListState::default();
-    let selected_chat = state.chats.iter().position(|chat| chat.is_selected);
     chat_state.select(selected_chat);
 
+    let chat_title = if let Some(forwarding) = &state.forwarding {
+        format!("Forward {} to…", forwarding.message_ids.len())
+    } else if state.chat_filter.is_open {
+        format!("Chats /{}", state.chat_filter.query.text)
+    } else if state.chat_sort != ChatSort::Recent {
+        format!("Chats ({})", state.chat_sort.label())
+    } else {
+        "Chats".to_string()
+    };
     let chat_list = List::new(chat_items)
-        .block(Block::default().title("Chats").borders(Borders::ALL))
+        .block(pane_block(state, UiFocus::Chats).title(chat_title))
         .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
 
-    let (message_text, scroll_offset) = build_message_text(state);
+    let message_width = Block::default()
+        .borders(Borders::ALL)
+        .inner(panes.messages)
+        .width;
+    let (message_text, scroll_offset) = build_message_text(state, message_width);
     let message_title = message_view_title(state);
 
     let message_view = Paragraph::new(message_text)
         .wrap(Wrap { trim: true })
         .scroll((scroll_offset, 0))
-        .block(Block::default().title(message_title).borders(Borders::ALL));
+        .block(pane_block(state, UiFocus::Messages).title(message_title));
 
+    let (cursor_row, cursor_column) = state.input.cursor_position();
+    let composer_scroll = (cursor_row as u16).saturating_sub(composer_lines - 1);
     let composer = Paragraph::new(state.input.text.as_str())
-        .block(Block::default().title("Composer").borders(Borders::ALL));
+        .scroll((composer_scroll, 0))
+        .block(pane_block(state, UiFocus::Composer).title("Composer"));
+
+    frame.render_stateful_widget(chat_list, panes.chats, &mut chat_state);
+    frame.render_widget(message_view, panes.messages);
+    frame.render_widget(composer, composer_area);
 
-    frame.render_stateful_widget(chat_list, columns[0], &mut chat_state);
-    frame.render_widget(message_view, columns[1]);
-    frame.render_widget(composer, rows[1]);
+    if let Some(text) = failed_banner_text(state) {
+        let banner = Paragraph::new(text).style(Style::default().fg(Color::White).bg(Color::Red));
+        frame.render_widget(banner, panes.failed);
+    }
+
+    if let Some(text) = bulk_bar_text(state) {
+        let bar = Paragraph::new(text).style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_widget(bar, panes.bulk);
+    }
+
+    if let Some(reply) = &state.reply_to {
+        let strip = Paragraph::new(format!(
+            " Replying to {}: {} (Esc to cancel)",
+            reply.author, reply.snippet
+        ))
+        .style(Style::default().add_modifier(Modifier::ITALIC));
+        frame.render_widget(strip, panes.reply);
+    }
+
+    if let Some(notice) = &state.notice {
+        let style = if notice.is_error {
+            Style::default().fg(Color::White).bg(Color::Red)
+        } else {
+            Style::default().add_modifier(Modifier::REVERSED)
+        };
+        let bar = Paragraph::new(format!(" {}", notice.text)).style(style);
+        frame.render_widget(bar, panes.status);
+    }
+
+    if state.focus == UiFocus::Composer {
+        let inner_width = composer_area.width.saturating_sub(2);
+        let column = (cursor_column as u16).min(inner_width.saturating_sub(1));
+        frame.set_cursor(
+            composer_area.x + 1 + column,
+            composer_area.y + 1 + cursor_row as u16 - composer_scroll,
+        );
+    }
+
+    draw_toasts(frame, &state.toasts, area);
+
+    if state.emoji_picker.is_open {
+        draw_emoji_picker(frame, state, composer_area);
+    }
+
+    if state.attach_picker.is_open {
+        draw_attach_picker(frame, &state.attach_picker, composer_area);
+    }
 
     if state.draft_modal.is_open {
         draw_draft_modal(frame, state, area);
     }
 
+    if state.global_search.is_open {
+        draw_global_search(frame, &state.global_search, area);
+    }
+
     if state.command_palette.is_open {
         draw_command_palette(frame, state, area);
     }
+
+    if let Some(detail) = &state.message_detail {
+        draw_message_detail(frame, detail, area);
+    }
+}
+
+/// Actions for the selected messages, or the pending delete confirmation.
+/// Why the newest failed send failed, and how to retry or drop it; the keys
+/// act on the failed message under the cursor, or else the newest.
+fn failed_banner_text(state: &UiState) -> Option<String> {
+    let newest = state.failed_sends.last()?;
+    let what = match state.failed_sends.len() {
+        1 => "Message not sent".to_string(),
+        count => format!("{count} messages not sent"),
+    };
+    let why = newest
+        .error
+        .as_deref()
+        .map(|error| format!(" ({error})"))
+        .unwrap_or_default();
+    Some(format!(" ✗ {what}{why}: Ctrl+R retry · Ctrl+D discard"))
+}
+
+fn bulk_bar_text(state: &UiState) -> Option<String> {
+    let count = state.message_view.selected_ids.len();
+    if count == 0 {
+        return None;
+    }
+    let noun = if count == 1 { "message" } else { "messages" };
+    if state.confirm_delete {
+        return Some(format!(
+            " Delete {count} {noun}? d to confirm, any other key to cancel"
+        ));
+    }
+    Some(format!(
+        " {count} selected: y copy · f forward · d delete · Esc clear"
+    ))
+}
+
+/// Bordered block of the pane `pane`, highlighted while it has focus. The
+/// message search counts as the message pane.
+fn pane_block(state: &UiState, pane: UiFocus) -> Block<'static> {
+    let focus = match state.focus {
+        UiFocus::Search => UiFocus::Messages,
+        focus => focus,
+    };
+    let focused = focus == pane;
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(state.theme.border_style(focused))
+        .title_style(state.theme.title_style(focused))
+}
+
+/// Chat-list rows with section headings, and the row of the selected chat.
+/// Filtered lists are ranked by match, so they go without headings.
+fn chat_list_items(state: &UiState) -> (Vec<ListItem<'static>>, Option<usize>) {
+    if state.chats.is_empty() {
+        return (vec![ListItem::new("No chats")], None);
+    }
+    let listed = state.chat_filter.matches(&state.chats);
+    if listed.is_empty() {
+        return (vec![ListItem::new("No matches")], None);
+    }
+    let first_section = &state.chats[listed[0]].section;
+    let sectioned = state.chat_filter.query.text.trim().is_empty()
+        && listed
+            .iter()
+            .any(|idx| state.chats[*idx].section != *first_section);
+
+    let mut items = Vec::new();
+    let mut selected = None;
+    let mut section: Option<&str> = None;
+    for chat in listed.iter().map(|idx| &state.chats[*idx]) {
+        if sectioned && section != Some(chat.section.as_str()) {
+            section = Some(chat.section.as_str());
+            items.push(
+                ListItem::new(chat.section.clone())
+                    .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)),
+            );
+        }
+        if chat.is_selected {
+            selected = Some(items.len());
+        }
+        let unread = if chat.unread > 0 {
+            format!(" ({})", chat.unread)
+        } else {
+            String::new()
+        };
+        let mut line = Line::from(format!("{}{}", chat.title, unread));
+        if chat.pinned {
+            line.spans.push(Span::raw(PINNED_GLYPH));
+        }
+        if chat.muted {
+            line.spans.push(Span::styled(
+                MUTED_GLYPH,
+                Style::default().add_modifier(Modifier::DIM),
+            ));
+        }
+        if chat.typing {
+            line.spans.push(Span::styled(
+                " …",
+                Style::default().add_modifier(Modifier::DIM),
+            ));
+        }
+        items.push(ListItem::new(line));
+    }
+    (items, selected)
+}
+
+/// Text rows the composer shows: one per draft line, between the configured
+/// minimum and maximum.
+fn composer_lines(state: &UiState) -> u16 {
+    let min_lines = state.layout.composer_min_lines.max(1);
+    let max_lines = state.layout.composer_max_lines.max(min_lines);
+    (state.input.line_count().min(max_lines as usize) as u16).max(min_lines)
 }
 
 fn message_view_title(state: &UiState) -> String {
+    let title = message_mode_title(state);
+    match typing_label(&state.typing).or_else(|| state.chat_status.clone()) {
+        Some(status) => format!("{title} · {status}"),
+        None => title,
+    }
+}
+
+/// `Ada is typing…`, naming up to two people.
+fn typing_label(typing: &[String]) -> Option<String> {
+    match typing {
+        [] => None,
+        [name] => Some(format!("{name} is typing…")),
+        [first, second] => Some(format!("{first} and {second} are typing…")),
+        names => Some(format!("{} people are typing…", names.len())),
+    }
+}
+
+fn message_mode_title(state: &UiState) -> String {
+    if state.message_view.visual.is_some() {
+        let count = state.message_view.selected_ids.len();
+        return format!("Messages (visual: {count} selected)");
+    }
     if state.message_view.search.is_open || !state.message_view.search.query.text.is_empty() {
         if state.message_view.search.query.text.is_empty() {
             "Messages (search)".to_string()
@@ -249,56 +1003,319 @@ fn message_view_title(state: &UiState) -> String {
     }
 }
 
-fn build_message_text(state: &UiState) -> (String, u16) {
+fn build_message_text(state: &UiState, width: u16) -> (Text<'static>, u16) {
     if state.messages.is_empty() {
-        return ("No messages".to_string(), 0);
+        return (Text::from("No messages"), 0);
+    }
+
+    let mut lines: Vec<Line<'static>> = Vec::new();
+    for (idx, message) in state.messages.iter().enumerate() {
+        lines.extend(message_lines(state, idx, message));
     }
 
-    let search_matches = &state.message_view.search.matches;
-    let lines: Vec<String> = state
+    (Text::from(lines), message_scroll(state, width))
+}
+
+/// Rows scrolled past at the top of a message pane `width` columns wide.
+fn message_scroll(state: &UiState, width: u16) -> u16 {
+    let heights = message_heights(state, width);
+    let top = state
+        .message_view
+        .scroll_offset
+        .min(heights.len().saturating_sub(1));
+    let rows_into_top = heights
+        .get(top)
+        .map_or(0, |height| state.message_view.scroll_rows.min(height - 1));
+    let scroll: usize = heights[..top].iter().sum::<usize>() + rows_into_top;
+    scroll.min(u16::MAX as usize) as u16
+}
+
+/// Rows each message takes once wrapped at `width` columns, or its line
+/// count when the width is not known yet.
+pub(crate) fn message_heights(state: &UiState, width: u16) -> Vec<usize> {
+    state
         .messages
         .iter()
         .enumerate()
         .map(|(idx, message)| {
-            let cursor_marker = if state.message_view.cursor == Some(idx) {
-                ">"
-            } else {
-                " "
-            };
-            let selected_marker = if state.message_view.selected_ids.contains(&message.id) {
-                "x"
-            } else {
-                " "
-            };
-            let match_marker = if search_matches.contains(&idx) {
-                "*"
-            } else {
-                " "
-            };
-            let timestamp = if message.timestamp.is_empty() {
-                String::new()
-            } else {
-                format!("[{}] ", message.timestamp)
-            };
-            format!(
-                "{} [{}{}] {}{}: {}",
-                cursor_marker,
-                selected_marker,
-                match_marker,
-                timestamp,
-                message.author,
-                message.body
-            )
+            let lines = message_lines(state, idx, message);
+            if width == 0 {
+                return lines.len();
+            }
+            lines
+                .into_iter()
+                .map(|line| {
+                    Paragraph::new(line)
+                        .wrap(Wrap { trim: true })
+                        .line_count(width)
+                })
+                .sum::<usize>()
+                .max(1)
         })
-        .collect();
+        .collect()
+}
 
-    let scroll_offset = state
-        .message_view
-        .scroll_offset
-        .min(lines.len().saturating_sub(1))
-        .min(u16::MAX as usize) as u16;
+/// Records the size of the message pane on a screen of `area`, which
+/// cursor movement and paging measure in wrapped rows.
+pub fn fit_message_view(area: Rect, state: &mut UiState) {
+    let inner = Block::default()
+        .borders(Borders::ALL)
+        .inner(pane_areas(area, state).messages);
+    state.message_view.page_size = usize::from(inner.height);
+    state.message_view.page_width = inner.width;
+}
+
+/// Areas of the message pane where the previews of `state` land on a screen
+/// of `area`, in the layout [`draw`] uses. Only fully visible previews are
+/// listed, and none while an overlay covers the pane.
+pub fn preview_placements(area: Rect, state: &UiState) -> Vec<PreviewPlacement> {
+    let preview_rows = state.layout.preview_rows;
+    let covered = state.message_detail.is_some()
+        || !state.toasts.is_empty()
+        || state.draft_modal.is_open
+        || state.command_palette.is_open
+        || state.global_search.is_open
+        || state.emoji_picker.is_open
+        || state.attach_picker.is_open;
+    if covered || preview_rows == 0 {
+        return Vec::new();
+    }
+    let inner = Block::default()
+        .borders(Borders::ALL)
+        .inner(pane_areas(area, state).messages);
+    let scroll = i32::from(message_scroll(state, inner.width));
+
+    let mut placements = Vec::new();
+    let mut row = 0i32;
+    for (idx, message) in state.messages.iter().enumerate() {
+        let lines = message_lines(state, idx, message);
+        let preview_start = message
+            .media
+            .as_ref()
+            .filter(|media| media.has_preview)
+            .map(|_| lines.len() - usize::from(preview_rows));
+        for (line_idx, line) in lines.into_iter().enumerate() {
+            let top = row - scroll;
+            if preview_start == Some(line_idx)
+                && top >= 0
+                && top + i32::from(preview_rows) <= i32::from(inner.height)
+            {
+                placements.push(PreviewPlacement {
+                    message_id: message.id,
+                    area: Rect::new(inner.x, inner.y + top as u16, inner.width, preview_rows),
+                });
+            }
+            let rows = Paragraph::new(line)
+                .wrap(Wrap { trim: true })
+                .line_count(inner.width);
+            row += rows as i32;
+        }
+    }
+    placements
+}
+
+/// One message as styled lines, its markers and author on the first.
+fn message_lines(state: &UiState, idx: usize, message: &MessageItem) -> Vec<Line<'static>> {
+    let cursor_marker = if state.message_view.cursor == Some(idx) {
+        ">"
+    } else {
+        " "
+    };
+    let selected_marker = if state.message_view.selected_ids.contains(&message.id) {
+        "x"
+    } else {
+        " "
+    };
+    let match_marker = if state.message_view.search.matches.contains(&idx) {
+        "*"
+    } else {
+        " "
+    };
+    let timestamp = if message.timestamp.is_empty() {
+        String::new()
+    } else {
+        format!("[{}] ", message.timestamp)
+    };
+    let mut header = format!("{}{}: ", timestamp, message.author);
+    let continues_group = idx
+        .checked_sub(1)
+        .and_then(|previous| state.messages.get(previous))
+        .is_some_and(|previous| continues_author_group(previous, message));
+    if continues_group {
+        header = " ".repeat(header.width());
+    }
+    let ttl = message
+        .expires_in
+        .map(|seconds| format!("[ttl {}] ", ttl_badge(seconds)))
+        .unwrap_or_default();
+    let prefix = format!(
+        "{} [{}{}] {}{}",
+        cursor_marker, selected_marker, match_marker, header, ttl
+    );
+    let mut body = Vec::new();
+    if let Some(reply) = &message.reply_to {
+        body.push(quote_line(reply));
+    }
+    match &message.media {
+        Some(_) if message.body.is_empty() => {}
+        _ => body.extend(body_lines(
+            &message.body,
+            state.message_view.revealed_spoilers.contains(&message.id),
+        )),
+    }
+    if let Some(media) = &message.media {
+        let placeholder = format!("[{}]", media.label);
+        body.push(Line::from(Span::styled(
+            placeholder,
+            Style::default().add_modifier(Modifier::DIM),
+        )));
+        let transcript = media.transcript.as_deref().unwrap_or_default();
+        body.extend(transcript.lines().map(|line| {
+            Line::from(Span::styled(
+                line.to_string(),
+                Style::default().add_modifier(Modifier::ITALIC),
+            ))
+        }));
+        if media.has_preview {
+            let rows = state.layout.preview_rows;
+            body.extend((0..rows).map(|_| Line::default()));
+        }
+    }
+    if state.message_view.search.matches.contains(&idx) {
+        let needle = state.message_view.search.query.text.trim().to_lowercase();
+        for line in &mut body {
+            highlight_matches(line, &needle);
+        }
+    }
+    if message.delivery == Some(DeliveryMark::Failed) {
+        let failed = DeliveryMark::Failed.style();
+        for span in body.iter_mut().flat_map(|line| line.spans.iter_mut()) {
+            span.style = span.style.patch(failed);
+        }
+    }
+    let mut body: Vec<Line<'static>> = body.into_iter().map(reorder_line).collect();
+    let mut lead = vec![Span::raw(prefix)];
+    if let Some(delivery) = message.delivery {
+        lead.push(Span::styled(
+            format!("{} ", delivery.glyph()),
+            delivery.style(),
+        ));
+    }
+    body[0].spans.splice(0..0, lead);
+    body
+}
+
+/// The quoted snippet a reply starts with; `u` jumps to the original.
+fn quote_line(reply: &ReplyTarget) -> Line<'static> {
+    let quote = if reply.author.is_empty() {
+        format!("{QUOTE_MARKER}earlier message")
+    } else {
+        format!("{QUOTE_MARKER}{}: {}", reply.author, reply.snippet)
+    };
+    Line::from(Span::styled(
+        quote,
+        Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
+    ))
+}
+
+fn match_style() -> Style {
+    Style::default().fg(Color::Black).bg(Color::Yellow)
+}
+
+/// Styles every case-insensitive occurrence of `needle`, which is already
+/// lowercase, as a search match. Occurrences spanning differently styled
+/// spans are left alone.
+fn highlight_matches(line: &mut Line<'static>, needle: &str) {
+    if needle.is_empty() {
+        return;
+    }
+    let mut spans = Vec::with_capacity(line.spans.len());
+    for span in line.spans.drain(..) {
+        let text = span.content.as_ref();
+        let lower = text.to_lowercase();
+        // Offsets in `lower` only carry over when lowercasing kept every
+        // character's length.
+        let ranges: Vec<(usize, usize)> = lower
+            .match_indices(needle)
+            .map(|(start, found)| (start, start + found.len()))
+            .collect();
+        let aligned = lower.len() == text.len()
+            && ranges
+                .iter()
+                .all(|(start, end)| text.is_char_boundary(*start) && text.is_char_boundary(*end));
+        if ranges.is_empty() || !aligned {
+            spans.push(span);
+            continue;
+        }
+        let mut rest = 0;
+        for (start, end) in ranges {
+            if start > rest {
+                spans.push(Span::styled(text[rest..start].to_string(), span.style));
+            }
+            spans.push(Span::styled(
+                text[start..end].to_string(),
+                span.style.patch(match_style()),
+            ));
+            rest = end;
+        }
+        if rest < text.len() {
+            spans.push(Span::styled(text[rest..].to_string(), span.style));
+        }
+    }
+    line.spans = spans;
+}
+
+/// Whether `message` follows `previous` closely enough from the same author
+/// to be drawn under the same author line.
+fn continues_author_group(previous: &MessageItem, message: &MessageItem) -> bool {
+    previous.author == message.author
+        && (0..=AUTHOR_GROUP_WINDOW_SECS).contains(&(message.sent_at - previous.sent_at))
+}
+
+fn ttl_badge(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    if seconds < 60 {
+        format!("{seconds}s")
+    } else if seconds < 3600 {
+        format!("{}m", seconds / 60)
+    } else if seconds < 86_400 {
+        format!("{}h", seconds / 3600)
+    } else {
+        format!("{}d", seconds / 86_400)
+    }
+}
+
+fn draw_message_detail(frame: &mut Frame, detail: &MessageDetail, area: Rect) {
+    frame.render_widget(Clear, area);
+
+    let mut lines = vec![
+        format!("From:      {}", detail.author),
+        format!("Sent:      {}", detail.sent_at),
+    ];
+    let optional = [
+        ("Edited:", &detail.edited_at),
+        ("Expires:", &detail.expires_at),
+        ("Status:", &detail.status),
+        ("Reply to:", &detail.reply_to),
+        ("Attachment:", &detail.attachment),
+    ];
+    for (label, value) in optional {
+        if let Some(value) = value {
+            lines.push(format!("{label:<10} {value}"));
+        }
+    }
+    if !detail.reactions.is_empty() {
+        lines.push(format!("Reactions: {}", detail.reactions.join("  ")));
+    }
+    lines.push(String::new());
+    lines.push(detail.body.clone());
 
-    (lines.join("\n"), scroll_offset)
+    let title = format!("Message {} (Esc to close)", detail.id);
+    let view = Paragraph::new(lines.join("\n"))
+        .wrap(Wrap { trim: false })
+        .scroll((detail.scroll, 0))
+        .block(Block::default().title(title).borders(Borders::ALL));
+    frame.render_widget(view, area);
 }
 
 fn draw_draft_modal(frame: &mut Frame, state: &UiState, area: Rect) {
@@ -316,6 +1333,144 @@ fn draw_draft_modal(frame: &mut Frame, state: &UiState, area: Rect) {
     frame.render_widget(draft, modal_area);
 }
 
+/// Stacks toasts down the top right corner, newest first, as many as fit.
+fn draw_toasts(frame: &mut Frame, toasts: &[Toast], area: Rect) {
+    const TOAST_WIDTH: u16 = 40;
+    const TOAST_HEIGHT: u16 = 3;
+    let width = TOAST_WIDTH.min(area.width);
+    let mut y = area.y + 1;
+    for toast in toasts.iter().rev() {
+        if y + TOAST_HEIGHT > area.bottom() {
+            break;
+        }
+        let toast_area = Rect::new(area.right() - width, y, width, TOAST_HEIGHT);
+        frame.render_widget(Clear, toast_area);
+        let inner_width = usize::from(width.saturating_sub(2));
+        let notice = Paragraph::new(truncate_to_width(&toast.text, inner_width)).block(
+            Block::default()
+                .title(truncate_to_width(&toast.chat_title, inner_width))
+                .borders(Borders::ALL),
+        );
+        frame.render_widget(notice, toast_area);
+        y += TOAST_HEIGHT;
+    }
+}
+
+/// Draws the picker just above the composer, over the message pane.
+fn draw_emoji_picker(frame: &mut Frame, state: &UiState, composer_area: Rect) {
+    const MAX_VISIBLE: usize = 8;
+    let picker = &state.emoji_picker;
+    let visible = picker.matches.len().clamp(1, MAX_VISIBLE) as u16;
+    let height = (visible + 2).min(composer_area.y);
+    let width = 32.min(composer_area.width);
+    if height < 3 {
+        return;
+    }
+    let picker_area = Rect::new(composer_area.x, composer_area.y - height, width, height);
+    frame.render_widget(Clear, picker_area);
+
+    let items: Vec<ListItem> = if picker.matches.is_empty() {
+        vec![ListItem::new("No matches")]
+    } else {
+        picker
+            .matches
+            .iter()
+            .map(|emoji| ListItem::new(format!("{} {}", emoji.glyph, emoji.name)))
+            .collect()
+    };
+    let mut list_state = ListState::default();
+    if !picker.matches.is_empty() {
+        list_state.select(Some(picker.selected.min(picker.matches.len() - 1)));
+    }
+    let title = format!("Emoji :{}", picker.query.text);
+    let list = List::new(items)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    frame.render_stateful_widget(list, picker_area, &mut list_state);
+}
+
+/// Draws the path input and its preview line just above the composer.
+fn draw_attach_picker(frame: &mut Frame, picker: &AttachPickerState, composer_area: Rect) {
+    const HEIGHT: u16 = 4;
+    if composer_area.y < HEIGHT {
+        return;
+    }
+    let picker_area = Rect::new(
+        composer_area.x,
+        composer_area.y - HEIGHT,
+        composer_area.width,
+        HEIGHT,
+    );
+    frame.render_widget(Clear, picker_area);
+
+    let inner_width = usize::from(picker_area.width.saturating_sub(2));
+    let preview = Line::styled(
+        truncate_to_width(&picker.preview(), inner_width),
+        Style::default().add_modifier(Modifier::DIM),
+    );
+    let text = Text::from(vec![Line::from(picker.path.text.clone()), preview]);
+    let block = Block::default()
+        .title("Attach (Tab completes, Enter sends, Esc cancels)")
+        .borders(Borders::ALL);
+    frame.render_widget(Paragraph::new(text).block(block), picker_area);
+
+    let (_, cursor_column) = picker.path.cursor_position();
+    let column = (cursor_column as u16).min(picker_area.width.saturating_sub(3));
+    frame.set_cursor(picker_area.x + 1 + column, picker_area.y + 1);
+}
+
+fn draw_global_search(frame: &mut Frame, search: &GlobalSearchState, area: Rect) {
+    let search_area = centered_rect(area, 80, 70);
+    frame.render_widget(Clear, search_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1)])
+        .split(search_area);
+
+    let query = Paragraph::new(search.query.text.as_str()).block(
+        Block::default()
+            .title("Search all chats (Enter to open, Esc to close)")
+            .borders(Borders::ALL),
+    );
+    frame.render_widget(query, chunks[0]);
+    let (_, cursor_column) = search.query.cursor_position();
+    let column = (cursor_column as u16).min(chunks[0].width.saturating_sub(3));
+    frame.set_cursor(chunks[0].x + 1 + column, chunks[0].y + 1);
+
+    let items: Vec<ListItem> = if search.results.is_empty() {
+        let empty = if search.query.text.trim().is_empty() {
+            "Type to search"
+        } else {
+            "No matches"
+        };
+        vec![ListItem::new(empty)]
+    } else {
+        search
+            .results
+            .iter()
+            .map(|result| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{} ", result.chat_title),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(format!("{}: {}", result.author, result.snippet)),
+                ]))
+            })
+            .collect()
+    };
+    let mut list_state = ListState::default();
+    if !search.results.is_empty() {
+        list_state.select(Some(search.selected.min(search.results.len() - 1)));
+    }
+    let results = List::new(items)
+        .block(Block::default().title("Results").borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(results, chunks[1], &mut list_state);
+}
+
 fn draw_command_palette(frame: &mut Frame, state: &UiState, area: Rect) {
     let palette_area = centered_rect(area, 60, 35);
     frame.render_widget(Clear, palette_area);